@@ -0,0 +1,221 @@
+use lopdf::{dictionary, Dictionary, Object, StringFormat};
+
+use crate::encoding::{decode_text_string, encode_text_string};
+use crate::utils::get_text_string;
+use crate::{FieldKey, Form};
+
+/// Format in which a form is submitted by `ButtonAction::SubmitForm`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubmitFormat {
+    /// Forms Data Format, the format of Acrobat
+    #[default]
+    Fdf,
+    /// An HTML form submission, as `application/x-www-form-urlencoded`
+    Html,
+    /// The XML version of FDF
+    Xfdf,
+    /// The whole PDF document
+    Pdf,
+}
+
+impl SubmitFormat {
+    /// The bits of the submit form action flags selecting this format
+    fn flags(self) -> i64 {
+        match self {
+            SubmitFormat::Fdf => 0,
+            SubmitFormat::Html => 1 << 2,
+            SubmitFormat::Xfdf => 1 << 5,
+            SubmitFormat::Pdf => 1 << 8,
+        }
+    }
+}
+
+/// The action performed when a push button is clicked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ButtonAction {
+    /// Sends the values of the fields to a URL
+    SubmitForm { url: String, format: SubmitFormat },
+    /// Resets every field to its default value
+    ResetForm,
+    /// Opens a URI
+    Uri(String),
+    /// Runs a JavaScript script
+    JavaScript(String),
+}
+
+impl ButtonAction {
+    /// Builds the action dictionary (`/A`) of this action
+    pub(crate) fn to_dictionary(&self) -> Dictionary {
+        match self {
+            ButtonAction::SubmitForm { url, format } => dictionary! {
+                "S" => "SubmitForm",
+                "F" => dictionary! {
+                    "FS" => "URL",
+                    "F" => Object::String(url.as_bytes().to_vec(), StringFormat::Literal),
+                },
+                "Flags" => format.flags(),
+            },
+            ButtonAction::ResetForm => dictionary! {
+                "S" => "ResetForm",
+            },
+            ButtonAction::Uri(uri) => dictionary! {
+                "S" => "URI",
+                "URI" => Object::String(uri.as_bytes().to_vec(), StringFormat::Literal),
+            },
+            ButtonAction::JavaScript(script) => dictionary! {
+                "S" => "JavaScript",
+                "JS" => Object::string_literal(encode_text_string(script)),
+            },
+        }
+    }
+}
+
+/// The events that trigger the additional actions (`/AA`) of a field or of its widgets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionTrigger {
+    /// The user types in the field or changes its selection (`K`), usually a script that
+    /// accepts or refuses the keystroke
+    Keystroke,
+    /// The value is about to be displayed (`F`), usually a script formatting it as a number,
+    /// a date...
+    Format,
+    /// The value changed (`V`), usually a script checking it
+    Validate,
+    /// Another field changed (`C`), usually a script computing the value of this one
+    Calculate,
+    /// The cursor enters the widget (`E`)
+    CursorEnter,
+    /// The cursor leaves the widget (`X`)
+    CursorExit,
+    /// The mouse button is pressed in the widget (`D`)
+    MouseDown,
+    /// The mouse button is released in the widget (`U`)
+    MouseUp,
+    /// The widget receives the focus (`Fo`)
+    Focus,
+    /// The widget loses the focus (`Bl`)
+    Blur,
+    /// The page of the widget is opened (`PO`)
+    PageOpen,
+    /// The page of the widget is closed (`PC`)
+    PageClose,
+    /// The page of the widget becomes visible (`PV`)
+    PageVisible,
+    /// The page of the widget is no longer visible (`PI`)
+    PageInvisible,
+}
+
+impl ActionTrigger {
+    /// Gets the trigger named by a key of an additional-actions dictionary
+    fn from_key(key: &[u8]) -> Option<Self> {
+        Some(match key {
+            b"K" => ActionTrigger::Keystroke,
+            b"F" => ActionTrigger::Format,
+            b"V" => ActionTrigger::Validate,
+            b"C" => ActionTrigger::Calculate,
+            b"E" => ActionTrigger::CursorEnter,
+            b"X" => ActionTrigger::CursorExit,
+            b"D" => ActionTrigger::MouseDown,
+            b"U" => ActionTrigger::MouseUp,
+            b"Fo" => ActionTrigger::Focus,
+            b"Bl" => ActionTrigger::Blur,
+            b"PO" => ActionTrigger::PageOpen,
+            b"PC" => ActionTrigger::PageClose,
+            b"PV" => ActionTrigger::PageVisible,
+            b"PI" => ActionTrigger::PageInvisible,
+            _ => return None,
+        })
+    }
+}
+
+/// An additional action of a field or of one of its widgets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdditionalAction {
+    /// The event running the action
+    pub trigger: ActionTrigger,
+    /// The type of the action (`/S`), e.g. `JavaScript`, `SubmitForm` or `ResetForm`
+    pub action_type: String,
+    /// The script of a JavaScript action
+    pub script: Option<String>,
+}
+
+impl Form {
+    /// Gets the additional actions (`/AA`) of the field at index `n` and of its widgets, in the
+    /// order of their triggers. The format, keystroke and validate scripts tell what a field
+    /// expects, e.g. a number or a date, and the calculate script how its value is computed.
+    ///
+    /// Returns an empty list if n is larger than the number of fields or the field is not part of
+    /// the form.
+    pub fn get_additional_actions(&self, n: impl FieldKey) -> Vec<AdditionalAction> {
+        let n = match self.try_resolve(n) {
+            Ok(n) => n,
+            Err(_) => return Vec::new(),
+        };
+        let oid = self.form_ids[n];
+
+        let mut objects = vec![oid];
+        objects.extend(
+            self.widgets_of(oid)
+                .into_iter()
+                .filter(|&widget| widget != oid),
+        );
+
+        let mut actions = Vec::new();
+        for id in objects {
+            let additional = self
+                .get_dictionary(id)
+                .and_then(|dict| dict.get(b"AA"))
+                .and_then(|aa| self.dereference(aa));
+            let additional = match additional {
+                Ok((_, Object::Dictionary(additional))) => additional,
+                _ => continue,
+            };
+            for (key, action) in additional.iter() {
+                let trigger = match ActionTrigger::from_key(key) {
+                    Some(trigger) => trigger,
+                    None => continue,
+                };
+                if let Some(action) = self.additional_action(trigger, action) {
+                    actions.push(action);
+                }
+            }
+        }
+
+        actions
+    }
+
+    /// Gets the additional action run by `trigger` from its action dictionary
+    fn additional_action(
+        &self,
+        trigger: ActionTrigger,
+        action: &Object,
+    ) -> Option<AdditionalAction> {
+        let (_, action) = self.dereference(action).ok()?;
+        let action = action.as_dict().ok()?;
+        let action_type = action
+            .get(b"S")
+            .and_then(Object::as_name_str)
+            .ok()?
+            .to_owned();
+
+        Some(AdditionalAction {
+            trigger,
+            action_type,
+            script: self.action_script(action),
+        })
+    }
+
+    /// Gets the script (`/JS`) of a JavaScript action, held in a text string or a stream
+    pub(crate) fn action_script(&self, action: &Dictionary) -> Option<String> {
+        match action.get(b"JS").map(|js| self.dereference(js)) {
+            Ok(Ok((_, Object::Stream(stream)))) => {
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                Some(decode_text_string(&content))
+            }
+            Ok(Ok((_, js))) => get_text_string(js),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,1110 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream};
+
+use crate::bidi::visual_order;
+use crate::choice::display_text;
+use crate::cjk;
+use crate::encoding::{decode_text_string, encode_win_ansi};
+use crate::geometry::AppearanceFrame;
+use crate::metrics::{measure_text, StandardFont};
+use crate::style::ARTIFACT_TAG;
+use crate::utils::{get_choice_selection, get_field_flags, DictionaryExt};
+use crate::{AppearanceOverride, FieldType, Form, TextAppearanceOptions, TextFieldFlags};
+
+/// Font resource used when the default appearance of a field doesn't name one
+pub(crate) const DEFAULT_FONT: &str = "Helv";
+
+/// Names commonly given to the standard 14 fonts in default appearances, with their base font
+const STANDARD_FONTS: &[(&str, &str)] = &[
+    ("Helv", "Helvetica"),
+    ("HeBo", "Helvetica-Bold"),
+    ("HeOb", "Helvetica-Oblique"),
+    ("HeBO", "Helvetica-BoldOblique"),
+    ("Cour", "Courier"),
+    ("CoBo", "Courier-Bold"),
+    ("CoOb", "Courier-Oblique"),
+    ("CoBO", "Courier-BoldOblique"),
+    ("TiRo", "Times-Roman"),
+    ("TiBo", "Times-Bold"),
+    ("TiIt", "Times-Italic"),
+    ("TiBI", "Times-BoldItalic"),
+    ("Symb", "Symbol"),
+    ("ZaDb", "ZapfDingbats"),
+];
+
+/// Gap between the edges of the widget and the text, in unscaled text space units
+const PADDING: f32 = 2.0;
+
+/// Distance between the baselines of consecutive lines, as a multiple of the font size
+const LEADING: f32 = 1.15;
+
+/// Bounds of the size chosen for automatically sized text, in points
+const AUTO_FONT_SIZE_MIN: f32 = 4.0;
+const AUTO_FONT_SIZE_MAX: f32 = 12.0;
+
+/// Step by which the automatic font size is decreased until the text fits
+const AUTO_FONT_SIZE_STEP: f32 = 0.5;
+
+/// ZapfDingbats glyph drawn in checked boxes whose widget doesn't name one (a check mark)
+const CHECK_GLYPH: &str = "4";
+
+/// Size of a ZapfDingbats glyph relative to the font size, and the part of the widget it covers
+const CHECK_GLYPH_WIDTH: f32 = 0.85;
+const CHECK_GLYPH_HEIGHT: f32 = 0.7;
+const CHECK_GLYPH_SCALE: f32 = 0.8;
+
+/// Color of the highlight behind the selected items of a list box
+const HIGHLIGHT_COLOR: Color = Color::Rgb(0.6, 0.75, 0.85);
+
+/// Depth of the baseline under the bottom of the glyphs, as a multiple of the font size
+const DESCENT: f32 = 0.2;
+
+/// Operators of a previous text block that are dropped before the text is drawn again
+const TEXT_OPERATORS: &[&str] = &[
+    "bt", "tc", "tw", "tz", "g", "rg", "k", "tm", "tr", "tf", "tj", "et", "q", "bmc", "emc",
+];
+
+/// A color in one of the device color spaces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    /// Gray level between 0.0 (black) and 1.0 (white)
+    Gray(f32),
+    /// Red, green and blue components between 0.0 and 1.0
+    Rgb(f32, f32, f32),
+    /// Cyan, magenta, yellow and black components between 0.0 and 1.0
+    Cmyk(f32, f32, f32, f32),
+}
+
+impl Color {
+    /// The operation selecting this color for filling, which is how text is painted
+    pub(crate) fn fill_operation(self) -> Operation {
+        match self {
+            Color::Gray(g) => Operation::new("g", vec![g.into()]),
+            Color::Rgb(r, g, b) => Operation::new("rg", vec![r.into(), g.into(), b.into()]),
+            Color::Cmyk(c, m, y, k) => {
+                Operation::new("k", vec![c.into(), m.into(), y.into(), k.into()])
+            }
+        }
+    }
+
+    /// Reads a color from its components, as in the `/MK` entries of widgets: one for gray,
+    /// three for RGB and four for CMYK. No components means a transparent color.
+    pub(crate) fn from_components(components: &[f32]) -> Option<Self> {
+        match *components {
+            [g] => Some(Color::Gray(g)),
+            [r, g, b] => Some(Color::Rgb(r, g, b)),
+            [c, m, y, k] => Some(Color::Cmyk(c, m, y, k)),
+            _ => None,
+        }
+    }
+
+    /// Gets the components of the color, in the order of its color space
+    pub(crate) fn components(self) -> Vec<f32> {
+        match self {
+            Color::Gray(g) => vec![g],
+            Color::Rgb(r, g, b) => vec![r, g, b],
+            Color::Cmyk(c, m, y, k) => vec![c, m, y, k],
+        }
+    }
+
+    /// The operation selecting this color for stroking, which is how borders are painted
+    pub(crate) fn stroke_operation(self) -> Operation {
+        match self {
+            Color::Gray(g) => Operation::new("G", vec![g.into()]),
+            Color::Rgb(r, g, b) => Operation::new("RG", vec![r.into(), g.into(), b.into()]),
+            Color::Cmyk(c, m, y, k) => {
+                Operation::new("K", vec![c.into(), m.into(), y.into(), k.into()])
+            }
+        }
+    }
+}
+
+/// Horizontal alignment of the text of a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlignment {
+    /// Converts a quadding value (`/Q`), anything unknown being left aligned
+    fn from_quadding(q: i64) -> Self {
+        match q {
+            1 => TextAlignment::Center,
+            2 => TextAlignment::Right,
+            _ => TextAlignment::Left,
+        }
+    }
+
+    /// The quadding value (`/Q`) of this alignment
+    pub(crate) fn quadding(self) -> i64 {
+        match self {
+            TextAlignment::Left => 0,
+            TextAlignment::Center => 1,
+            TextAlignment::Right => 2,
+        }
+    }
+}
+
+/// The font and color set by a default appearance string (`/DA`)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DefaultAppearance {
+    /// Name of the font resource, without the leading slash
+    pub font_name: String,
+    /// Font size, 0 meaning the text is sized to fit the widget
+    pub font_size: f32,
+    pub color: Color,
+}
+
+impl DefaultAppearance {
+    /// Parses the `Tf` and color operators of a default appearance string. Missing parts fall back
+    /// to black Helvetica with an automatic size.
+    pub fn parse(da: &str) -> Self {
+        let mut res = DefaultAppearance {
+            font_name: DEFAULT_FONT.to_owned(),
+            font_size: 0.0,
+            color: Color::Gray(0.0),
+        };
+
+        let mut operands: Vec<&str> = Vec::new();
+        for token in da.split_whitespace() {
+            let numbers = |count: usize| -> Option<Vec<f32>> {
+                let start = operands.len().checked_sub(count)?;
+                operands[start..].iter().map(|o| o.parse().ok()).collect()
+            };
+
+            match token {
+                "Tf" => {
+                    if let [.., name, size] = operands.as_slice() {
+                        res.font_name = name.trim_start_matches('/').to_owned();
+                        res.font_size = size.parse().unwrap_or(0.0);
+                    }
+                }
+                "g" => {
+                    if let Some(c) = numbers(1) {
+                        res.color = Color::Gray(c[0]);
+                    }
+                }
+                "rg" => {
+                    if let Some(c) = numbers(3) {
+                        res.color = Color::Rgb(c[0], c[1], c[2]);
+                    }
+                }
+                "k" => {
+                    if let Some(c) = numbers(4) {
+                        res.color = Color::Cmyk(c[0], c[1], c[2], c[3]);
+                    }
+                }
+                _ if token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '\'') => {}
+                _ => {
+                    operands.push(token);
+                    continue;
+                }
+            }
+            operands.clear();
+        }
+
+        res
+    }
+
+    /// Writes the default appearance string selecting this font and color
+    pub fn encode(&self) -> String {
+        let color = match self.color {
+            Color::Gray(g) => format!("{} g", g),
+            Color::Rgb(r, g, b) => format!("{} {} {} rg", r, g, b),
+            Color::Cmyk(c, m, y, k) => format!("{} {} {} {} k", c, m, y, k),
+        };
+        format!("/{} {} Tf {}", self.font_name, self.font_size, color)
+    }
+}
+
+/// What the normal appearance stream of a field shows
+#[derive(Debug, Clone)]
+pub(crate) enum AppearanceContent {
+    /// Text on a single line or flowing over several, like the value of a text field
+    Text(String, TextAppearanceOptions),
+    /// The items of a list box, one per row, with the selected ones highlighted
+    ListBox {
+        items: Vec<String>,
+        selected: Vec<String>,
+    },
+}
+
+impl AppearanceContent {
+    /// Gets all the text shown, which tells whether a CJK font is needed
+    fn text(&self) -> String {
+        match self {
+            AppearanceContent::Text(text, _) => text.clone(),
+            AppearanceContent::ListBox { items, .. } => items.concat(),
+        }
+    }
+
+    /// Gets the name of the font resource asked for instead of the one of the default appearance
+    fn font_name(&self) -> Option<&str> {
+        match self {
+            AppearanceContent::Text(_, options) => options.font_name.as_deref(),
+            AppearanceContent::ListBox { .. } => None,
+        }
+    }
+}
+
+/// The text state that affects the width of a line
+struct TextMetrics {
+    font: StandardFont,
+    /// Whether the text is drawn with a CJK font instead of `font`
+    cjk: bool,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scaling: f32,
+}
+
+impl TextMetrics {
+    /// Measures the width of a line of text, in unscaled text space units
+    fn width(&self, text: &str) -> f32 {
+        let chars = text.chars().count() as f32;
+        let spaces = text.chars().filter(|&c| c == ' ').count() as f32;
+
+        let glyphs = if self.cjk {
+            let width = text
+                .chars()
+                .map(|c| u32::from(cjk::glyph_width(c)))
+                .sum::<u32>();
+            width as f32 * self.font_size / 1000.0
+        } else {
+            measure_text(self.font, self.font_size, text)
+        };
+
+        (glyphs + chars * self.char_spacing + spaces * self.word_spacing) * self.horizontal_scaling
+            / 100.0
+    }
+
+    /// Lays the text out in a frame of the given width: one line, or as many as needed to fit
+    /// the width when `multiline` is set
+    fn layout(&self, text: &str, width: f32, multiline: bool) -> Vec<String> {
+        if multiline {
+            self.wrap(text, width - 2.0 * PADDING)
+        } else {
+            vec![text.to_owned()]
+        }
+    }
+
+    /// Whether the text fits inside a frame of the given size at the current font size
+    fn fits(&self, text: &str, width: f32, height: f32, multiline: bool) -> bool {
+        let lines = self.layout(text, width, multiline);
+        let text_height = if multiline {
+            lines.len() as f32 * self.font_size * LEADING
+        } else {
+            self.font_size
+        };
+
+        text_height <= height - 2.0 * PADDING
+            && lines
+                .iter()
+                .all(|line| self.width(line) <= width - 2.0 * PADDING)
+    }
+
+    /// Sets the largest font size between `min` and `max` at which the text fits the frame,
+    /// or `min` if it doesn't fit at all
+    fn fit(&mut self, text: &str, width: f32, height: f32, multiline: bool, min: f32, max: f32) {
+        self.font_size = max;
+        while self.font_size > min && !self.fits(text, width, height, multiline) {
+            self.font_size -= AUTO_FONT_SIZE_STEP;
+        }
+        self.font_size = self.font_size.max(min);
+    }
+
+    /// Encodes text for the font it is drawn with
+    fn encode(&self, text: &str) -> Vec<u8> {
+        if self.cjk {
+            cjk::encode_ucs2(text)
+        } else {
+            encode_win_ansi(text)
+        }
+    }
+
+    /// Splits text into lines at line breaks and wherever a word would overflow `max_width`
+    fn wrap(&self, text: &str, max_width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for paragraph in text.split("\r\n").flat_map(|p| p.split(['\r', '\n'])) {
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                // Lines may also break between CJK characters, which aren't separated by spaces
+                for (i, piece) in cjk::split_at_cjk(word).into_iter().enumerate() {
+                    let separator = if i == 0 { " " } else { "" };
+                    let candidate = if line.is_empty() {
+                        piece.to_owned()
+                    } else {
+                        format!("{}{}{}", line, separator, piece)
+                    };
+
+                    if !line.is_empty() && self.width(&candidate) > max_width {
+                        lines.push(std::mem::replace(&mut line, piece.to_owned()));
+                    } else {
+                        line = candidate;
+                    }
+                }
+            }
+            lines.push(line);
+        }
+
+        lines
+    }
+}
+
+impl Form {
+    /// Gets an entry that controls variable text (`/DA`, `/Q` or `/DR`): from the field or its
+    /// ancestors, and otherwise from the document-wide default in the AcroForm dictionary
+    pub(crate) fn variable_text_entry(&self, oid: ObjectId, key: &[u8]) -> Option<&Object> {
+        self.inherited_field_entry(oid, key).or_else(|| {
+            let value = self.acroform()?.get(key).ok()?;
+            self.dereference(value).ok().map(|(_, value)| value)
+        })
+    }
+
+    /// Gets the default appearance of a field, inherited or from the AcroForm dictionary
+    pub(crate) fn default_appearance(&self, oid: ObjectId) -> DefaultAppearance {
+        match self.variable_text_entry(oid, b"DA") {
+            Some(Object::String(bytes, _)) => {
+                DefaultAppearance::parse(&String::from_utf8_lossy(bytes))
+            }
+            _ => DefaultAppearance::parse(""),
+        }
+    }
+
+    /// Finds the font resource named `font_name` in the default resources (`/DR`) of a field or
+    /// of the AcroForm dictionary. A standard font is created for the usual abbreviations, like
+    /// `Helv`, missing from the resources, and Helvetica replaces any other unknown font.
+    ///
+    /// Returns the name under which the font is used and the font object.
+    fn font_resource(&self, oid: ObjectId, font_name: &str) -> (String, Object) {
+        let font = self
+            .variable_text_entry(oid, b"DR")
+            .and_then(|dr| dr.as_dict().ok())
+            .and_then(|dr| dr.get(b"Font").ok())
+            .and_then(|fonts| self.dereference(fonts).ok())
+            .and_then(|(_, fonts)| fonts.as_dict().ok())
+            .and_then(|fonts| fonts.get(font_name.as_bytes()).ok());
+        if let Some(font) = font {
+            return (font_name.to_owned(), font.clone());
+        }
+
+        let (font_name, font) = standard_font_dictionary(font_name);
+        (font_name, Object::Dictionary(font))
+    }
+
+    /// Gets the standard font whose metrics are used to lay text out in `font`, Helvetica if the
+    /// font isn't one of them
+    fn standard_font(&self, font: &Object) -> StandardFont {
+        self.dereference(font)
+            .and_then(|(_, font)| font.as_dict())
+            .and_then(|font| font.get(b"BaseFont"))
+            .and_then(Object::as_name_str)
+            .ok()
+            .and_then(StandardFont::from_base_font)
+            .unwrap_or(StandardFont::Helvetica)
+    }
+
+    /// Copies a dictionary that may be given by reference, an empty one if there is none
+    fn dereferenced_dict(&self, object: Option<&Object>) -> Dictionary {
+        object
+            .and_then(|object| self.dereference(object).ok())
+            .and_then(|(_, object)| object.as_dict().ok())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Gets the alignment of the text of a field from its quadding (`/Q`)
+    pub(crate) fn text_alignment(&self, oid: ObjectId) -> TextAlignment {
+        self.variable_text_entry(oid, b"Q")
+            .and_then(|q| q.as_i64().ok())
+            .map_or(TextAlignment::Left, TextAlignment::from_quadding)
+    }
+
+    /// Regenerates the appearance stream (`/AP /N`) of the text field at index `n` from its value,
+    /// creating the stream if the field has none.
+    ///
+    /// The font, size and color come from the default appearance of the field unless `options`
+    /// overrides them. Without `options.font_size`, a size of 0 in the default appearance is
+    /// replaced by the largest size at which the text fits the widget.
+    pub(crate) fn regenerate_text_appearance(
+        &mut self,
+        n: usize,
+        options: &TextAppearanceOptions,
+    ) -> Result<(), lopdf::Error> {
+        let text = self.text_appearance_value(n)?;
+        let (text, options) = self.displayed_text(n, text, options);
+        self.draw_appearance(n, &AppearanceContent::Text(text, options))
+    }
+
+    /// Gets the value of the text field at index `n` as shown in its appearance
+    pub(crate) fn text_appearance_value(&self, n: usize) -> Result<String, lopdf::Error> {
+        Ok(match self.get_dictionary(self.form_ids[n])?.get(b"V") {
+            Ok(Object::String(bytes, _)) => decode_text_string(bytes),
+            _ => String::new(),
+        })
+    }
+
+    /// Gets what the appearance stream of the field at index `n` shows, for the fields whose
+    /// appearance is drawn from their value: text fields, with the options they were last set
+    /// with if their appearance is pending or else the default ones, and choice fields
+    pub(crate) fn appearance_content(&self, n: usize) -> Option<AppearanceContent> {
+        match self.try_get_type(n).ok()? {
+            FieldType::Text => {
+                let text = self.text_appearance_value(n).ok()?;
+                let options = self.pending_text_options(n).cloned().unwrap_or_default();
+                let (text, options) = self.displayed_text(n, text, &options);
+                Some(AppearanceContent::Text(text, options))
+            }
+            FieldType::ComboBox | FieldType::ListBox => self.choice_appearance_content(n).ok(),
+            _ => None,
+        }
+    }
+
+    /// Regenerates the appearance stream of the choice field at index `n` from its selection.
+    ///
+    /// A combo box shows its selected item like a single line text field. A list box shows its
+    /// items one per row, starting at its top index (`/TI`) or further down if needed for the
+    /// first selected item to be visible, with the selected rows highlighted.
+    pub(crate) fn regenerate_choice_appearance(&mut self, n: usize) -> Result<(), lopdf::Error> {
+        let content = self.choice_appearance_content(n)?;
+        self.draw_appearance(n, &content)
+    }
+
+    /// Gets what the appearance stream of the choice field at index `n` shows
+    pub(crate) fn choice_appearance_content(
+        &self,
+        n: usize,
+    ) -> Result<AppearanceContent, lopdf::Error> {
+        let kind = self.try_get_type(n).unwrap_or(FieldType::Unknown);
+        if kind != FieldType::ComboBox && kind != FieldType::ListBox {
+            return Err(lopdf::Error::Type);
+        }
+        let selected = get_choice_selection(self.get_dictionary(self.form_ids[n])?);
+        let options = self.choice_options(n);
+
+        Ok(match kind {
+            FieldType::ComboBox => {
+                let text = selected
+                    .first()
+                    .map(|export| display_text(&options, export))
+                    .unwrap_or_default();
+                let options = TextAppearanceOptions {
+                    multiline: Some(false),
+                    ..TextAppearanceOptions::default()
+                };
+                AppearanceContent::Text(text, options)
+            }
+            _ => {
+                let items = options
+                    .iter()
+                    .map(|option| option.display().to_owned())
+                    .collect::<Vec<_>>();
+                let selected = selected
+                    .iter()
+                    .map(|export| display_text(&options, export))
+                    .collect::<Vec<_>>();
+                AppearanceContent::ListBox { items, selected }
+            }
+        })
+    }
+
+    /// Draws `content` in the appearance stream of the field at index `n`
+    pub(crate) fn draw_appearance(
+        &mut self,
+        n: usize,
+        content: &AppearanceContent,
+    ) -> Result<(), lopdf::Error> {
+        let target = self.prepare_appearance(n, content)?;
+        let stream = self.build_appearance(n, &target, content)?;
+        self.insert_object(target.0, Object::Stream(stream));
+        Ok(())
+    }
+
+    /// Gets the normal appearance stream of the field at index `n` ready for `content` to be
+    /// drawn in it: the stream is created if the field has none, and the font is picked, CJK
+    /// fonts being added to the default resources. Returns the id of the stream and the font.
+    pub(crate) fn prepare_appearance(
+        &mut self,
+        n: usize,
+        content: &AppearanceContent,
+    ) -> Result<(ObjectId, (String, Object)), lopdf::Error> {
+        let oid = self.form_ids[n];
+        if self.appearance_frame(oid).is_none() {
+            return Err(lopdf::Error::DictKey);
+        }
+
+        // CJK text is drawn with one of the fonts viewers provide for it
+        let font = match self.cjk_font_for(&content.text()) {
+            Some(cjk_font) => self.register_cjk_font(cjk_font)?,
+            None => {
+                let da_font = self.default_appearance(oid).font_name;
+                self.font_resource(oid, content.font_name().unwrap_or(&da_font))
+            }
+        };
+
+        Ok((self.normal_appearance_id(n)?, font))
+    }
+
+    /// Builds the normal appearance stream of the field at index `n` showing `content`, from the
+    /// stream and font given by `prepare_appearance`. The document is left untouched, so the
+    /// streams of several fields can be built at the same time.
+    pub(crate) fn build_appearance(
+        &self,
+        n: usize,
+        (id, font): &(ObjectId, (String, Object)),
+        content: &AppearanceContent,
+    ) -> Result<Stream, lopdf::Error> {
+        // The frame of the appearance, which takes the rotation of the page into account
+        let frame = self
+            .appearance_frame(self.form_ids[n])
+            .ok_or(lopdf::Error::DictKey)?;
+
+        let operations = match content {
+            AppearanceContent::Text(text, options) => {
+                self.text_operations(n, text, options, &frame, font)?
+            }
+            AppearanceContent::ListBox { items, selected } => {
+                self.list_box_operations(n, items, selected, &frame, font)?
+            }
+        };
+        self.appearance_stream(n, *id, &frame, font.clone(), operations)
+    }
+
+    /// Builds the operations drawing `text` in the appearance stream of the field at index `n`
+    fn text_operations(
+        &self,
+        n: usize,
+        text: &str,
+        options: &TextAppearanceOptions,
+        frame: &AppearanceFrame,
+        (font_name, font): &(String, Object),
+    ) -> Result<Vec<Operation>, lopdf::Error> {
+        let cjk_font = self.cjk_font_for(text);
+        let field = self.get_dictionary(self.form_ids[n])?;
+        let da = self.default_appearance(self.form_ids[n]);
+        let multiline = options.multiline.unwrap_or_else(|| {
+            TextFieldFlags::from_bits_truncate(get_field_flags(field))
+                .contains(TextFieldFlags::MULTILINE)
+        });
+
+        let color = options.color.unwrap_or(da.color);
+        let alignment = options
+            .alignment
+            .unwrap_or_else(|| self.text_alignment(self.form_ids[n]));
+
+        // Sizes given by the caller are in points, convert them to the user space of the page
+        let mut metrics = TextMetrics {
+            font: self.standard_font(font),
+            cjk: cjk_font.is_some(),
+            font_size: da.font_size,
+            char_spacing: options.char_spacing.unwrap_or(0.0) / frame.user_unit,
+            word_spacing: options.word_spacing.unwrap_or(0.0) / frame.user_unit,
+            horizontal_scaling: options.horizontal_scaling.unwrap_or(100.0),
+        };
+        match options.font_size {
+            Some(font_size) => metrics.font_size = font_size / frame.user_unit,
+            None if metrics.font_size <= 0.0 => metrics.fit(
+                text,
+                frame.width,
+                frame.height,
+                multiline,
+                options.min_font_size.unwrap_or(AUTO_FONT_SIZE_MIN) / frame.user_unit,
+                options.max_font_size.unwrap_or(AUTO_FONT_SIZE_MAX) / frame.user_unit,
+            ),
+            None => {}
+        }
+        let font_size = metrics.font_size;
+        let lines = metrics.layout(text, frame.width, multiline);
+
+        let mut operations = vec![
+            Operation::new("BMC", vec!["Tx".into()]),
+            Operation::new("q", vec![]),
+            Operation::new("BT", vec![]),
+            Operation::new(
+                "Tf",
+                vec![
+                    Object::Name(font_name.clone().into_bytes()),
+                    font_size.into(),
+                ],
+            ),
+            color.fill_operation(),
+        ];
+
+        // Character spacing, word spacing and horizontal scaling, when requested
+        if let Some(char_spacing) = options.char_spacing {
+            operations.push(Operation::new(
+                "Tc",
+                vec![(char_spacing / frame.user_unit).into()],
+            ));
+        }
+        if let Some(word_spacing) = options.word_spacing {
+            operations.push(Operation::new(
+                "Tw",
+                vec![(word_spacing / frame.user_unit).into()],
+            ));
+        }
+        if let Some(horizontal_scaling) = options.horizontal_scaling {
+            operations.push(Operation::new("Tz", vec![horizontal_scaling.into()]));
+        }
+
+        // Single lines are centered vertically (formula picked up from Poppler), multiple lines
+        // flow down from the top of the widget
+        let top = if multiline {
+            frame.height - PADDING - font_size
+        } else if frame.height > 0.0 {
+            0.5 * frame.height - 0.4 * font_size
+        } else {
+            0.5 * font_size
+        };
+
+        for (i, line) in lines.into_iter().enumerate() {
+            let width = metrics.width(&line);
+            let x = match alignment {
+                TextAlignment::Left => PADDING,
+                TextAlignment::Center => 0.5 * (frame.width - width),
+                TextAlignment::Right => frame.width - PADDING - width,
+            };
+            let y = top - i as f32 * font_size * LEADING;
+
+            operations.extend(vec![
+                Operation::new(
+                    "Tm",
+                    vec![1.into(), 0.into(), 0.into(), 1.into(), x.into(), y.into()],
+                ),
+                Operation::new(
+                    "Tj",
+                    vec![Object::string_literal(metrics.encode(&visual_order(&line)))],
+                ),
+            ]);
+        }
+
+        operations.extend(vec![
+            Operation::new("ET", vec![]),
+            Operation::new("Q", vec![]),
+            Operation::new("EMC", vec![]),
+        ]);
+
+        Ok(operations)
+    }
+
+    /// Draws the caption of the push button at index `n` in its appearance stream, centered on
+    /// a single line
+    pub(crate) fn draw_button_caption(
+        &mut self,
+        n: usize,
+        caption: &str,
+    ) -> Result<(), lopdf::Error> {
+        let options = TextAppearanceOptions {
+            alignment: Some(TextAlignment::Center),
+            multiline: Some(false),
+            ..TextAppearanceOptions::default()
+        };
+        self.draw_appearance(n, &AppearanceContent::Text(caption.to_owned(), options))
+    }
+
+    /// Gets the number of items a list box shows at once
+    pub(crate) fn list_box_visible_rows(&self, oid: ObjectId) -> Option<usize> {
+        let frame = self.appearance_frame(oid)?;
+        let font_size = list_box_font_size(&self.default_appearance(oid), &frame);
+        Some(list_box_rows(&frame, font_size))
+    }
+
+    /// Builds the operations drawing the rows of a list box in the appearance stream of the field
+    /// at index `n`, highlighting the `selected` items
+    fn list_box_operations(
+        &self,
+        n: usize,
+        items: &[String],
+        selected: &[String],
+        frame: &AppearanceFrame,
+        (font_name, font): &(String, Object),
+    ) -> Result<Vec<Operation>, lopdf::Error> {
+        let oid = self.form_ids[n];
+
+        let da = self.default_appearance(oid);
+        let alignment = self.text_alignment(oid);
+        let cjk_font = self.cjk_font_for(&items.concat());
+        let field = self.get_dictionary(oid)?;
+
+        let metrics = TextMetrics {
+            font: self.standard_font(font),
+            cjk: cjk_font.is_some(),
+            font_size: list_box_font_size(&da, frame),
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 100.0,
+        };
+        let font_size = metrics.font_size;
+        let row_height = font_size * LEADING;
+        let visible_rows = list_box_rows(frame, font_size);
+
+        // Scroll down from the top index when the first selected item would be hidden
+        let mut top_index = field
+            .get(b"TI")
+            .and_then(Object::as_i64)
+            .map_or(0, |ti| ti.max(0) as usize);
+        if let Some(first) = items.iter().position(|item| selected.contains(item)) {
+            if first < top_index || first >= top_index + visible_rows {
+                top_index = first;
+            }
+        }
+        let rows = items
+            .iter()
+            .skip(top_index)
+            .take(visible_rows)
+            .enumerate()
+            .map(|(row, item)| {
+                let bottom = frame.height - PADDING - (row + 1) as f32 * row_height;
+                (bottom, item)
+            })
+            .collect::<Vec<_>>();
+
+        // Clip to the inside of the border, then paint the highlights under the text
+        let mut operations = vec![
+            Operation::new("BMC", vec!["Tx".into()]),
+            Operation::new("q", vec![]),
+            Operation::new(
+                "re",
+                vec![
+                    1.into(),
+                    1.into(),
+                    (frame.width - 2.0).into(),
+                    (frame.height - 2.0).into(),
+                ],
+            ),
+            Operation::new("W", vec![]),
+            Operation::new("n", vec![]),
+            HIGHLIGHT_COLOR.fill_operation(),
+        ];
+        for (bottom, _) in rows.iter().filter(|(_, item)| selected.contains(item)) {
+            operations.extend(vec![
+                Operation::new(
+                    "re",
+                    vec![
+                        1.into(),
+                        (*bottom).into(),
+                        (frame.width - 2.0).into(),
+                        row_height.into(),
+                    ],
+                ),
+                Operation::new("f", vec![]),
+            ]);
+        }
+
+        operations.extend(vec![
+            Operation::new("BT", vec![]),
+            Operation::new(
+                "Tf",
+                vec![
+                    Object::Name(font_name.clone().into_bytes()),
+                    font_size.into(),
+                ],
+            ),
+            da.color.fill_operation(),
+        ]);
+        for (bottom, item) in rows {
+            let width = metrics.width(item);
+            let x = match alignment {
+                TextAlignment::Left => PADDING,
+                TextAlignment::Center => 0.5 * (frame.width - width),
+                TextAlignment::Right => frame.width - PADDING - width,
+            };
+            let y = bottom + 0.5 * (row_height - font_size) + DESCENT * font_size;
+
+            operations.extend(vec![
+                Operation::new(
+                    "Tm",
+                    vec![1.into(), 0.into(), 0.into(), 1.into(), x.into(), y.into()],
+                ),
+                Operation::new(
+                    "Tj",
+                    vec![Object::string_literal(metrics.encode(&visual_order(item)))],
+                ),
+            ]);
+        }
+        operations.extend(vec![
+            Operation::new("ET", vec![]),
+            Operation::new("Q", vec![]),
+            Operation::new("EMC", vec![]),
+        ]);
+
+        Ok(operations)
+    }
+
+    /// Gets the id of the normal appearance stream of the field at index `n`, creating the
+    /// stream if the field has none
+    fn normal_appearance_id(&mut self, n: usize) -> Result<ObjectId, lopdf::Error> {
+        let field = self.get_dictionary(self.form_ids[n])?;
+
+        // Reuse the normal appearance stream, or create one if the field has none
+        let ap = field.get(b"AP").and_then(|ap| self.dereference(ap));
+        let object_id = match ap {
+            Ok((_, Object::Dictionary(ap_dict))) => Some(ap_dict.get(b"N")?.as_reference()?),
+            _ => None,
+        };
+        let object_id = match object_id {
+            Some(id) => id,
+            None => {
+                let new_id = self
+                    .document
+                    .add_object(Stream::new(Dictionary::new(), Vec::new()));
+
+                let mut ap_dict = Dictionary::new();
+                ap_dict.set("N", new_id);
+                self.get_object_mut(self.form_ids[n])?
+                    .as_dict_mut()?
+                    .set_in_place("AP", ap_dict);
+
+                new_id
+            }
+        };
+        Ok(object_id)
+    }
+
+    /// Gets the appearance override registered for the field at index `n`, under its fully
+    /// qualified name or under a partial name that designates it, as in `get_index_by_name`
+    fn appearance_override(&self, n: usize) -> Option<&AppearanceOverride> {
+        let qualified = self.get_qualified_name(n);
+        let partial = self.get_name(n);
+        qualified.into_iter().chain(partial).find_map(|name| {
+            if self.get_index_by_name(&name) == Some(n) {
+                self.appearance_overrides.get(&name)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Builds the normal appearance stream `object_id` of the field at index `n` with its
+    /// variable text replaced by `operations`. Whatever else the stream draws, like borders and
+    /// backgrounds, is kept.
+    fn appearance_stream(
+        &self,
+        n: usize,
+        object_id: ObjectId,
+        frame: &AppearanceFrame,
+        (font_name, font): (String, Object),
+        operations: Vec<Operation>,
+    ) -> Result<Stream, lopdf::Error> {
+        let mut stream = self.get_object(object_id)?.as_stream()?.clone();
+
+        // Make the font available to the stream, without touching resources shared with others
+        let mut resources = self.dereferenced_dict(stream.dict.get(b"Resources").ok());
+        let mut fonts = self.dereferenced_dict(resources.get(b"Font").ok());
+        fonts.set(font_name, font);
+        resources.set("Font", fonts);
+
+        frame.apply(&mut stream.dict);
+        stream.dict.set_in_place("Resources", resources);
+
+        // Decode and get the content, even if is compressed
+        let mut content = match stream.decompressed_content() {
+            Ok(content) => Content::decode(&content)?,
+            Err(_) => Content::decode(&stream.content)?,
+        };
+
+        // Drop the marked content holding the previous text, and the text operators of streams
+        // that don't mark it. Backgrounds and borders, marked as artifacts, are kept.
+        let mut depth = 0;
+        let mut artifact_depth = 0;
+        content.operations.retain(|operation| {
+            let tag = match operation.operands.first() {
+                Some(Object::Name(name)) => name.as_slice(),
+                _ => b"",
+            };
+            match operation.operator.as_str() {
+                "BMC" | "BDC" if artifact_depth > 0 || (depth == 0 && tag == ARTIFACT_TAG) => {
+                    artifact_depth += 1;
+                    return true;
+                }
+                "EMC" if artifact_depth > 0 => {
+                    artifact_depth -= 1;
+                    return true;
+                }
+                _ if artifact_depth > 0 => return true,
+                "BMC" | "BDC" if depth > 0 || tag == b"Tx" => depth += 1,
+                "EMC" if depth > 0 => depth -= 1,
+                _ if depth > 0 => {}
+                operator => return !TEXT_OPERATORS.contains(&operator.to_lowercase().as_str()),
+            }
+            false
+        });
+
+        content.operations.extend(operations);
+
+        // Let a registered override post-process or replace the generated operations
+        if let Some(appearance_override) = self.appearance_override(n) {
+            appearance_override(&mut content.operations);
+        }
+
+        // Set the new content to the original stream and compress it
+        if let Ok(encoded_content) = content.encode() {
+            stream.set_plain_content(encoded_content);
+            let _ = stream.compress();
+        }
+
+        Ok(stream)
+    }
+
+    /// Gets the name of the on state of a checkbox widget: the first state of its normal
+    /// appearance other than `Off`, `Yes` if it has none
+    pub(crate) fn on_state(&self, oid: ObjectId) -> String {
+        self.appearance_on_state(oid)
+            .unwrap_or_else(|| "Yes".to_owned())
+    }
+
+    /// Gets the on state of a checkbox field: the on state of its first widget
+    pub(crate) fn field_on_state(&self, oid: ObjectId) -> String {
+        self.on_state(self.widget_of(oid).unwrap_or(oid))
+    }
+
+    /// Gets the first state of the normal appearance of a widget other than `Off`, if any
+    pub(crate) fn appearance_on_state(&self, oid: ObjectId) -> Option<String> {
+        let normal = self
+            .get_dictionary(oid)
+            .and_then(|widget| widget.get(b"AP"))
+            .and_then(|ap| self.dereference(ap))
+            .and_then(|(_, ap)| ap.as_dict())
+            .and_then(|ap| ap.get(b"N"))
+            .and_then(|normal| self.dereference(normal))
+            .and_then(|(_, normal)| normal.as_dict())
+            .ok()?;
+
+        normal
+            .iter()
+            .map(|(name, _)| name)
+            .find(|name| name.as_slice() != b"Off")
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+    }
+
+    /// Makes sure the widget `oid` of a checkbox has a normal appearance for its `on` state and
+    /// for `Off`, creating the missing ones. The on appearance draws the caption of the widget
+    /// (`/MK /CA`, a check mark by default) in ZapfDingbats, scaled to the widget.
+    pub(crate) fn ensure_check_box_appearance(
+        &mut self,
+        oid: ObjectId,
+        on: &str,
+    ) -> Result<(), lopdf::Error> {
+        let frame = self.appearance_frame(oid).ok_or(lopdf::Error::DictKey)?;
+        let widget = self.get_dictionary(oid)?;
+
+        let mut ap = match widget.get(b"AP") {
+            Ok(ap) => self.dereference(ap)?.1.as_dict()?.clone(),
+            Err(_) => Dictionary::new(),
+        };
+        let mut normal = match ap.get(b"N") {
+            Ok(normal) => match self.dereference(normal)?.1 {
+                Object::Dictionary(normal) => normal.clone(),
+                _ => Dictionary::new(),
+            },
+            Err(_) => Dictionary::new(),
+        };
+        if normal.has(on.as_bytes()) && normal.has(b"Off") {
+            return Ok(());
+        }
+
+        let caption = match widget
+            .get(b"MK")
+            .and_then(|mk| self.dereference(mk))
+            .and_then(|(_, mk)| mk.as_dict())
+            .and_then(|mk| mk.get(b"CA"))
+        {
+            Ok(Object::String(bytes, _)) if !bytes.is_empty() => bytes.clone(),
+            _ => CHECK_GLYPH.as_bytes().to_vec(),
+        };
+        let color = self.default_appearance(oid).color;
+
+        if !normal.has(on.as_bytes()) {
+            let size = CHECK_GLYPH_SCALE
+                * (frame.width / CHECK_GLYPH_WIDTH).min(frame.height / CHECK_GLYPH_HEIGHT);
+            let x = 0.5 * (frame.width - CHECK_GLYPH_WIDTH * size);
+            let y = 0.5 * (frame.height - CHECK_GLYPH_HEIGHT * size);
+
+            let content = Content {
+                operations: vec![
+                    Operation::new("q", vec![]),
+                    Operation::new("BT", vec![]),
+                    color.fill_operation(),
+                    Operation::new("Tf", vec!["ZaDb".into(), size.into()]),
+                    Operation::new("Td", vec![x.into(), y.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(caption)]),
+                    Operation::new("ET", vec![]),
+                    Operation::new("Q", vec![]),
+                ],
+            };
+
+            let mut dict = dictionary! {
+                "Resources" => dictionary! {
+                    "Font" => dictionary! {
+                        "ZaDb" => dictionary! {
+                            "Type" => "Font",
+                            "Subtype" => "Type1",
+                            "BaseFont" => "ZapfDingbats",
+                        },
+                    },
+                },
+            };
+            frame.apply(&mut dict);
+            let id = self
+                .document
+                .add_object(Stream::new(dict, content.encode()?));
+            normal.set(on, id);
+        }
+        if !normal.has(b"Off") {
+            let mut dict = Dictionary::new();
+            frame.apply(&mut dict);
+            let id = self.document.add_object(Stream::new(dict, Vec::new()));
+            normal.set("Off", id);
+        }
+
+        ap.set("N", normal);
+        self.get_object_mut(oid)?
+            .as_dict_mut()?
+            .set_in_place("AP", ap);
+
+        Ok(())
+    }
+}
+
+/// Gets the font size of the items of a list box, the largest automatic size when the default
+/// appearance asks for one
+fn list_box_font_size(da: &DefaultAppearance, frame: &AppearanceFrame) -> f32 {
+    if da.font_size > 0.0 {
+        da.font_size
+    } else {
+        AUTO_FONT_SIZE_MAX / frame.user_unit
+    }
+}
+
+/// Gets the number of rows of items of `font_size` that fit a list box
+fn list_box_rows(frame: &AppearanceFrame, font_size: f32) -> usize {
+    ((frame.height - 2.0 * PADDING) / (font_size * LEADING))
+        .floor()
+        .max(1.0) as usize
+}
+
+/// Creates the dictionary of the standard font abbreviated `font_name`, like `Helv` or `TiRo`.
+/// Helvetica replaces any unknown font.
+///
+/// Returns the name under which the font is used and the font dictionary.
+pub(crate) fn standard_font_dictionary(font_name: &str) -> (String, Dictionary) {
+    let (font_name, base_font) = STANDARD_FONTS
+        .iter()
+        .find(|(name, _)| *name == font_name)
+        .copied()
+        .unwrap_or((DEFAULT_FONT, "Helvetica"));
+    let mut font = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => base_font,
+    };
+    // The symbolic fonts have their own built-in encoding
+    if !matches!(base_font, "Symbol" | "ZapfDingbats") {
+        font.set("Encoding", "WinAnsiEncoding");
+    }
+
+    (font_name.to_owned(), font)
+}
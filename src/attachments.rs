@@ -0,0 +1,146 @@
+use lopdf::{dictionary, Dictionary, Object, Stream};
+
+use crate::encoding::{decode_text_string, encode_pdf_doc, encode_text_string};
+use crate::utils::pdf_date_now;
+use crate::{Form, ValueError};
+
+/// A file embedded in the document, listed in the catalog `/Names /EmbeddedFiles` tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedFile {
+    /// The file name from `/UF`, or `/F` if there is no Unicode name
+    pub name: String,
+    /// The MIME type of the file (`/Subtype` of the embedded stream), e.g. `application/pdf`
+    pub mime_type: Option<String>,
+    /// The size of the file in bytes, from the `/Params` of the embedded stream
+    pub size: Option<usize>,
+}
+
+impl Form {
+    /// Embeds the file `name` holding `bytes`, of MIME type `mime` (e.g. `application/pdf` or
+    /// `image/png`), in the document, so that a filled form carries its supporting documents.
+    /// Viewers list the file in their attachments panel. A file with the same name is
+    /// replaced.
+    ///
+    /// Fails with `ValueError::NotFound` if the document has no catalog.
+    pub fn attach_file(&mut self, name: &str, bytes: &[u8], mime: &str) -> Result<(), ValueError> {
+        let mut stream = Stream::new(
+            dictionary! {
+                "Type" => "EmbeddedFile",
+                "Subtype" => Object::Name(mime.as_bytes().to_vec()),
+                "Params" => dictionary! {
+                    "Size" => bytes.len() as i64,
+                    "ModDate" => Object::string_literal(pdf_date_now()),
+                },
+            },
+            bytes.to_vec(),
+        );
+        let _ = stream.compress();
+        let stream = self.document.add_object(stream);
+
+        // `/F` is limited to PDFDocEncoding, `/UF` holds the actual name
+        let file_name = encode_pdf_doc(name).unwrap_or_else(|| {
+            name.chars()
+                .map(|c| if c.is_ascii() { c as u8 } else { b'_' })
+                .collect()
+        });
+        let spec = self.document.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal(file_name),
+            "UF" => Object::string_literal(encode_text_string(name)),
+            "EF" => dictionary! { "F" => stream, "UF" => stream },
+        });
+
+        let (replaced, mut entries): (Vec<_>, Vec<_>) = self
+            .name_tree(b"EmbeddedFiles")
+            .into_iter()
+            .partition(|(existing, _)| decode_text_string(existing) == name);
+        // The replaced file is dropped rather than saved unreferenced
+        for (_, spec) in replaced {
+            if let Ok(id) = spec.as_reference() {
+                let streams = self
+                    .get_dictionary(id)
+                    .ok()
+                    .and_then(|spec| spec.get(b"EF").and_then(Object::as_dict).ok())
+                    .map(|files| {
+                        files
+                            .iter()
+                            .filter_map(|(_, file)| file.as_reference().ok())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                for id in streams.into_iter().chain(Some(id)) {
+                    self.remove_object(id);
+                }
+            }
+        }
+        entries.push((encode_text_string(name), spec.into()));
+        self.set_name_tree(b"EmbeddedFiles", entries)
+    }
+
+    /// Gets the files embedded in the document, in the order of the name tree
+    pub fn get_embedded_files(&self) -> Vec<EmbeddedFile> {
+        self.name_tree(b"EmbeddedFiles")
+            .into_iter()
+            .map(|(key, spec)| {
+                let spec = self
+                    .dereference(&spec)
+                    .and_then(|(_, spec)| spec.as_dict())
+                    .ok();
+                let stream = spec.and_then(|spec| self.embedded_stream(spec));
+                let params = stream
+                    .and_then(|stream| stream.dict.get(b"Params").ok())
+                    .and_then(|params| self.dereference(params).ok())
+                    .and_then(|(_, params)| params.as_dict().ok());
+
+                EmbeddedFile {
+                    name: spec
+                        .and_then(file_name)
+                        .unwrap_or_else(|| decode_text_string(&key)),
+                    mime_type: stream
+                        .and_then(|stream| stream.dict.get(b"Subtype").ok())
+                        .and_then(|subtype| subtype.as_name_str().ok())
+                        .map(str::to_owned),
+                    size: params
+                        .and_then(|params| params.get(b"Size").ok())
+                        .and_then(|size| size.as_i64().ok())
+                        .map(|size| size.max(0) as usize),
+                }
+            })
+            .collect()
+    }
+
+    /// Gets the content of the embedded file named `name`, or `None` if there is no such file
+    pub fn get_embedded_file(&self, name: &str) -> Option<Vec<u8>> {
+        self.name_tree(b"EmbeddedFiles")
+            .into_iter()
+            .find_map(|(key, spec)| {
+                let (_, spec) = self.dereference(&spec).ok()?;
+                let spec = spec.as_dict().ok()?;
+                if decode_text_string(&key) != name && file_name(spec).as_deref() != Some(name) {
+                    return None;
+                }
+                let stream = self.embedded_stream(spec)?;
+                Some(
+                    stream
+                        .decompressed_content()
+                        .unwrap_or_else(|_| stream.content.clone()),
+                )
+            })
+    }
+
+    /// Gets the embedded file stream of a file specification, preferring the Unicode one
+    fn embedded_stream<'a>(&'a self, spec: &'a Dictionary) -> Option<&'a Stream> {
+        let (_, files) = self.dereference(spec.get(b"EF").ok()?).ok()?;
+        let files = files.as_dict().ok()?;
+        let file = files.get(b"UF").or_else(|_| files.get(b"F")).ok()?;
+        self.dereference(file).ok()?.1.as_stream().ok()
+    }
+}
+
+/// Gets the file name of a file specification, from `/UF` or else `/F`
+fn file_name(spec: &Dictionary) -> Option<String> {
+    match spec.get(b"UF").or_else(|_| spec.get(b"F")) {
+        Ok(Object::String(bytes, _)) => Some(decode_text_string(bytes)),
+        _ => None,
+    }
+}
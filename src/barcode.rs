@@ -0,0 +1,716 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Object, Stream};
+
+use crate::encoding::encode_text_string;
+use crate::pdf417::{self, pdf417};
+use crate::utils::DictionaryExt;
+use crate::{Color, FieldKey, FieldType, Form, ValueError};
+
+/// Light modules around a QR code, required by the specification
+const QR_QUIET_ZONE: usize = 4;
+
+/// Light modules on both sides of a Code 128 barcode, required by the specification
+const CODE128_QUIET_ZONE: usize = 10;
+
+/// The symbology of a barcode drawn by `Form::fill_barcode_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbology {
+    /// A QR code, holding the data as bytes, with the given error correction level
+    QrCode(QrErrorCorrection),
+    /// A Code 128 linear barcode, holding printable ASCII
+    Code128,
+    /// A PDF417 stacked barcode, holding the data as bytes, with the error correction level
+    /// recommended for its length
+    Pdf417,
+}
+
+/// The error correction level of a QR code, the share of the symbol that can be damaged while
+/// it can still be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QrErrorCorrection {
+    /// About 7%
+    Low,
+    /// About 15%
+    #[default]
+    Medium,
+    /// About 25%
+    Quartile,
+    /// About 30%
+    High,
+}
+
+impl QrErrorCorrection {
+    fn index(self) -> usize {
+        match self {
+            QrErrorCorrection::Low => 0,
+            QrErrorCorrection::Medium => 1,
+            QrErrorCorrection::Quartile => 2,
+            QrErrorCorrection::High => 3,
+        }
+    }
+
+    /// The bits of the level in the format information
+    fn format_bits(self) -> u32 {
+        match self {
+            QrErrorCorrection::Low => 1,
+            QrErrorCorrection::Medium => 0,
+            QrErrorCorrection::Quartile => 3,
+            QrErrorCorrection::High => 2,
+        }
+    }
+}
+
+impl Form {
+    /// Fills in the text field at index `n` with `data` and draws it as a QR code in the normal
+    /// appearance of its widgets, centered and as large as fits, so that a printed form can be
+    /// scanned. See `fill_barcode_with` for other symbologies.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn fill_barcode(&mut self, n: impl FieldKey, data: &str) -> Result<(), ValueError> {
+        self.fill_barcode_with(n, data, Symbology::QrCode(QrErrorCorrection::default()))
+    }
+
+    /// Fills in the text field at index `n` with `data` and draws it as a barcode of
+    /// `symbology` in the normal appearance of its widgets. Code 128 barcodes fill the widget,
+    /// QR codes and PDF417 symbols are centered and as large as fits.
+    ///
+    /// Viewers regenerate the appearance of text fields when the AcroForm asks for it
+    /// (`/NeedAppearances`), which replaces the barcode with the text.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field is not a text field,
+    /// `ValueError::TooLong` if the data doesn't fit the largest QR code or PDF417 symbol,
+    /// `ValueError::InvalidCharacter` if Code 128 cannot encode a character, or
+    /// `ValueError::NotFound` if the field has no widget with a rectangle.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn fill_barcode_with(
+        &mut self,
+        n: impl FieldKey,
+        data: &str,
+        symbology: Symbology,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_not_signature(n)?;
+        self.check_not_locked(n)?;
+        if self.try_get_type(n)? != FieldType::Text {
+            return Err(self.type_mismatch(n, &[FieldType::Text]));
+        }
+
+        let symbol = match symbology {
+            Symbology::QrCode(level) => Symbol::Matrix {
+                modules: qr_code(data.as_bytes(), level)?,
+                quiet_zone: QR_QUIET_ZONE,
+            },
+            Symbology::Code128 => Symbol::Linear(code128(data)?),
+            Symbology::Pdf417 => Symbol::Matrix {
+                modules: pdf417(data.as_bytes())?,
+                quiet_zone: pdf417::QUIET_ZONE,
+            },
+        };
+
+        let oid = self.form_ids[n];
+        for widget in self.widgets_of(oid) {
+            let frame = self.appearance_frame(widget).ok_or(ValueError::NotFound)?;
+            let content = Content {
+                operations: symbol.operations(frame.width, frame.height),
+            }
+            .encode()
+            .map_err(|_| ValueError::NotFound)?;
+            let mut dict = Dictionary::new();
+            frame.apply(&mut dict);
+            dict.set("Resources", Dictionary::new());
+            let stream = self.document.add_object(Stream::new(dict, content));
+            self.dictionary_mut(widget)?
+                .set_in_place("AP", dictionary! { "N" => stream });
+        }
+
+        self.dictionary_mut(oid)?
+            .set_in_place("V", Object::string_literal(encode_text_string(data)));
+        self.mark_modified(n);
+        Ok(())
+    }
+}
+
+/// The modules of an encoded barcode
+enum Symbol {
+    /// The dark modules of a 2D or stacked symbol, row by row from the top, with the light
+    /// modules required around it
+    Matrix {
+        modules: Vec<Vec<bool>>,
+        quiet_zone: usize,
+    },
+    /// The widths of the bars and spaces of a linear symbol, starting with a bar
+    Linear(Vec<usize>),
+}
+
+impl Symbol {
+    /// Builds the operations drawing the symbol in black over a white background of
+    /// `width` by `height`
+    fn operations(&self, width: f32, height: f32) -> Vec<Operation> {
+        let mut operations = vec![
+            Color::Gray(1.0).fill_operation(),
+            rectangle(0.0, 0.0, width, height),
+            Operation::new("f", vec![]),
+            Color::Gray(0.0).fill_operation(),
+        ];
+
+        match self {
+            Symbol::Matrix {
+                modules,
+                quiet_zone,
+            } => {
+                let columns = modules.first().map_or(0, Vec::len);
+                let module = (width / (columns + 2 * quiet_zone) as f32)
+                    .min(height / (modules.len() + 2 * quiet_zone) as f32);
+                let left = 0.5 * (width - module * columns as f32);
+                let top = 0.5 * (height + module * modules.len() as f32);
+                for (y, row) in modules.iter().enumerate() {
+                    // Consecutive dark modules of a row are drawn as one rectangle
+                    let mut x = 0;
+                    while x < row.len() {
+                        if !row[x] {
+                            x += 1;
+                            continue;
+                        }
+                        let start = x;
+                        while x < row.len() && row[x] {
+                            x += 1;
+                        }
+                        operations.push(rectangle(
+                            left + start as f32 * module,
+                            top - (y + 1) as f32 * module,
+                            (x - start) as f32 * module,
+                            module,
+                        ));
+                    }
+                }
+            }
+            Symbol::Linear(widths) => {
+                let count = widths.iter().sum::<usize>() + 2 * CODE128_QUIET_ZONE;
+                let module = width / count as f32;
+                let mut x = CODE128_QUIET_ZONE;
+                for (i, &bar) in widths.iter().enumerate() {
+                    if i % 2 == 0 {
+                        operations.push(rectangle(
+                            x as f32 * module,
+                            0.0,
+                            bar as f32 * module,
+                            height,
+                        ));
+                    }
+                    x += bar;
+                }
+            }
+        }
+
+        operations.push(Operation::new("f", vec![]));
+        operations
+    }
+}
+
+fn rectangle(x: f32, y: f32, width: f32, height: f32) -> Operation {
+    Operation::new("re", vec![x.into(), y.into(), width.into(), height.into()])
+}
+
+/// Widths of the bars and spaces of the Code 128 symbols, by value. 103 to 105 are the start
+/// symbols of the code sets A, B and C.
+const CODE128_PATTERNS: [&[u8; 6]; 106] = [
+    b"212222", b"222122", b"222221", b"121223", b"121322", b"131222", b"122213", b"122312",
+    b"132212", b"221213", b"221312", b"231212", b"112232", b"122132", b"122231", b"113222",
+    b"123122", b"123221", b"223211", b"221132", b"221231", b"213212", b"223112", b"312131",
+    b"311222", b"321122", b"321221", b"312212", b"322112", b"322211", b"212123", b"212321",
+    b"232121", b"111323", b"131123", b"131321", b"112313", b"132113", b"132311", b"211313",
+    b"231113", b"231311", b"112133", b"112331", b"132131", b"113123", b"113321", b"133121",
+    b"313121", b"211331", b"231131", b"213113", b"213311", b"213131", b"311123", b"311321",
+    b"331121", b"312113", b"312311", b"332111", b"314111", b"221411", b"431111", b"111224",
+    b"111422", b"121124", b"121421", b"141122", b"141221", b"112214", b"112412", b"122114",
+    b"122411", b"142112", b"142211", b"241211", b"221114", b"413111", b"241112", b"134111",
+    b"111242", b"121142", b"121241", b"114212", b"124112", b"124211", b"411212", b"421112",
+    b"421211", b"212141", b"214121", b"412121", b"111143", b"111341", b"131141", b"114113",
+    b"114311", b"411113", b"411311", b"113141", b"114131", b"311141", b"411131", b"211412",
+    b"211214", b"211232",
+];
+
+/// Widths of the bars and spaces of the Code 128 stop symbol
+const CODE128_STOP: &[u8; 7] = b"2331112";
+
+/// Encodes printable ASCII as a Code 128 barcode, with code set C when the data is an even
+/// number of digits and code set B otherwise
+fn code128(data: &str) -> Result<Vec<usize>, ValueError> {
+    if let Some(c) = data.chars().find(|c| !(' '..='~').contains(c)) {
+        return Err(ValueError::InvalidCharacter(c));
+    }
+
+    let digits = data.bytes().all(|b| b.is_ascii_digit());
+    let mut values = if digits && !data.is_empty() && data.len().is_multiple_of(2) {
+        let mut values = vec![105];
+        for pair in data.as_bytes().chunks(2) {
+            values.push(usize::from((pair[0] - b'0') * 10 + pair[1] - b'0'));
+        }
+        values
+    } else {
+        let mut values = vec![104];
+        values.extend(data.bytes().map(|b| usize::from(b - b' ')));
+        values
+    };
+    let checksum = values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| i.max(1) * value)
+        .sum::<usize>()
+        % 103;
+    values.push(checksum);
+
+    Ok(values
+        .iter()
+        .flat_map(|&value| CODE128_PATTERNS[value].iter())
+        .chain(CODE128_STOP.iter())
+        .map(|&width| usize::from(width - b'0'))
+        .collect())
+}
+
+/// Error correction codewords per block of each QR code version (index 1 to 40), by level
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [
+        0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28,
+        30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ],
+    [
+        0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30,
+        30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+];
+
+/// Error correction blocks of each QR code version (index 1 to 40), by level
+const ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [
+        0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13,
+        14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21,
+        23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29,
+        34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32,
+        35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+
+/// Encodes bytes as a QR code of the smallest version that holds them, in byte mode
+fn qr_code(data: &[u8], level: QrErrorCorrection) -> Result<Vec<Vec<bool>>, ValueError> {
+    let version = (1..=40)
+        .find(|&version| {
+            4 + count_bits(version) + 8 * data.len() <= 8 * data_codewords(version, level)
+        })
+        .ok_or_else(|| ValueError::TooLong {
+            max: (8 * data_codewords(40, level) - 4 - count_bits(40)) / 8,
+            got: data.len(),
+        })?;
+
+    // Mode indicator, character count and data, then the terminator and the padding
+    let mut bits = Vec::new();
+    let mut push = |value: usize, len: usize| {
+        bits.extend((0..len).rev().map(|i| (value >> i) & 1 == 1));
+    };
+    push(0b0100, 4);
+    push(data.len(), count_bits(version));
+    for &byte in data {
+        push(usize::from(byte), 8);
+    }
+    let capacity = 8 * data_codewords(version, level);
+    bits.extend(std::iter::repeat_n(false, (capacity - bits.len()).min(4)));
+    bits.extend(std::iter::repeat_n(false, (8 - bits.len() % 8) % 8));
+    let mut codewords = bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, &bit| acc << 1 | u8::from(bit)))
+        .collect::<Vec<_>>();
+    for pad in [0xEC, 0x11].iter().cycle() {
+        if codewords.len() == capacity / 8 {
+            break;
+        }
+        codewords.push(*pad);
+    }
+
+    let mut qr = QrMatrix::new(version);
+    qr.draw_function_patterns(level);
+    qr.draw_codewords(&add_error_correction(&codewords, version, level));
+
+    // The mask with the lowest penalty makes the symbol easiest to read
+    let mask = (0..8)
+        .min_by_key(|&mask| {
+            qr.apply_mask(mask);
+            qr.draw_format_bits(level, mask);
+            let penalty = qr.penalty();
+            qr.apply_mask(mask);
+            penalty
+        })
+        .unwrap_or(0);
+    qr.apply_mask(mask);
+    qr.draw_format_bits(level, mask);
+
+    Ok(qr.modules)
+}
+
+/// Bits of the character count in byte mode
+fn count_bits(version: usize) -> usize {
+    if version <= 9 {
+        8
+    } else {
+        16
+    }
+}
+
+/// Modules of a QR code of `version` available for codewords
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+/// Data codewords of a QR code of `version`
+fn data_codewords(version: usize, level: QrErrorCorrection) -> usize {
+    raw_data_modules(version) / 8
+        - usize::from(ECC_CODEWORDS_PER_BLOCK[level.index()][version])
+            * usize::from(ERROR_CORRECTION_BLOCKS[level.index()][version])
+}
+
+/// Splits the data codewords into blocks, appends the error correction codewords of each block
+/// and interleaves the blocks
+fn add_error_correction(data: &[u8], version: usize, level: QrErrorCorrection) -> Vec<u8> {
+    let blocks = usize::from(ERROR_CORRECTION_BLOCKS[level.index()][version]);
+    let ecc_len = usize::from(ECC_CODEWORDS_PER_BLOCK[level.index()][version]);
+    let raw_codewords = raw_data_modules(version) / 8;
+    let short_blocks = blocks - raw_codewords % blocks;
+    let short_len = raw_codewords / blocks;
+    let divisor = reed_solomon_divisor(ecc_len);
+
+    let mut all = Vec::with_capacity(blocks);
+    let mut start = 0;
+    for i in 0..blocks {
+        let len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let mut block = data[start..start + len].to_vec();
+        start += len;
+        let ecc = reed_solomon_remainder(&block, &divisor);
+        if i < short_blocks {
+            // Placeholder keeping the codewords of all blocks aligned, skipped below
+            block.push(0);
+        }
+        block.extend(ecc);
+        all.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..all[0].len() {
+        for (j, block) in all.iter().enumerate() {
+            if i != short_len - ecc_len || j >= short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+/// Computes the generator polynomial of a Reed-Solomon code of `degree` over GF(2^8), without
+/// its leading term
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    result[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    result
+}
+
+/// Computes the Reed-Solomon error correction codewords of `data`
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (x, &y) in result.iter_mut().zip(divisor) {
+            *x ^= gf_multiply(y, factor);
+        }
+    }
+    result
+}
+
+/// Multiplies two elements of GF(2^8) modulo the QR code polynomial `x^8 + x^4 + x^3 + x^2 + 1`
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((u32::from(y) >> i) & 1) * u32::from(x);
+    }
+    z as u8
+}
+
+/// The modules of a QR code being built
+struct QrMatrix {
+    version: usize,
+    size: usize,
+    modules: Vec<Vec<bool>>,
+    /// Modules of the function patterns, which hold no data and are not masked
+    function: Vec<Vec<bool>>,
+}
+
+impl QrMatrix {
+    fn new(version: usize) -> Self {
+        let size = 4 * version + 17;
+        QrMatrix {
+            version,
+            size,
+            modules: vec![vec![false; size]; size],
+            function: vec![vec![false; size]; size],
+        }
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y][x] = dark;
+        self.function[y][x] = true;
+    }
+
+    /// Draws the finder, timing and alignment patterns and reserves the format and version
+    /// information
+    fn draw_function_patterns(&mut self, level: QrErrorCorrection) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4i64..=4 {
+                for dx in -4i64..=4 {
+                    let (xx, yy) = (x as i64 + dx, y as i64 + dy);
+                    if (0..size as i64).contains(&xx) && (0..size as i64).contains(&yy) {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(xx as usize, yy as usize, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+
+        let positions = self.alignment_positions();
+        let count = positions.len();
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // The corners with finder patterns have no alignment pattern
+                if (i == 0 && (j == 0 || j == count - 1)) || (i == count - 1 && j == 0) {
+                    continue;
+                }
+                for dy in -2i64..=2 {
+                    for dx in -2i64..=2 {
+                        self.set_function(
+                            (x as i64 + dx) as usize,
+                            (y as i64 + dy) as usize,
+                            dx.abs().max(dy.abs()) != 1,
+                        );
+                    }
+                }
+            }
+        }
+
+        self.draw_format_bits(level, 0);
+        self.draw_version();
+    }
+
+    /// Gets the coordinates of the centers of the alignment patterns along each axis
+    fn alignment_positions(&self) -> Vec<usize> {
+        if self.version == 1 {
+            return Vec::new();
+        }
+        let count = self.version / 7 + 2;
+        let step = (self.version * 8 + count * 3 + 5) / (count * 4 - 4) * 2;
+        let mut positions = (0..count - 1)
+            .map(|i| self.size - 7 - i * step)
+            .collect::<Vec<_>>();
+        positions.push(6);
+        positions.reverse();
+        positions
+    }
+
+    /// Draws both copies of the format information of `level` and `mask`
+    fn draw_format_bits(&mut self, level: QrErrorCorrection, mask: u32) {
+        let data = level.format_bits() << 3 | mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        let size = self.size;
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        // Always dark
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Draws both copies of the version information, present from version 7
+    fn draw_version(&mut self) {
+        if self.version < 7 {
+            return;
+        }
+        let mut remainder = self.version as u32;
+        for _ in 0..12 {
+            remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+        }
+        let bits = (self.version as u32) << 12 | remainder;
+        for i in 0..18 {
+            let dark = (bits >> i) & 1 == 1;
+            let (a, b) = (self.size - 11 + i % 3, i / 3);
+            self.set_function(a, b, dark);
+            self.set_function(b, a, dark);
+        }
+    }
+
+    /// Places the codewords in the zigzag order, two columns at a time from the bottom right
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let mut i = 0;
+        let mut right = self.size as i64 - 1;
+        while right >= 1 {
+            // The vertical timing pattern is skipped
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..self.size {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        self.size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.function[y][x] && i < codewords.len() * 8 {
+                        self.modules[y][x] = (codewords[i / 8] >> (7 - i % 8)) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            right -= 2;
+        }
+    }
+
+    /// Inverts the data modules selected by `mask`, which undoes a previous call
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if invert && !self.function[y][x] {
+                    self.modules[y][x] = !self.modules[y][x];
+                }
+            }
+        }
+    }
+
+    /// Computes the penalty score of the symbol, which the chosen mask minimizes
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+
+        let columns = (0..size)
+            .map(|x| (0..size).map(|y| self.modules[y][x]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let finder_like: [&[bool]; 2] = [
+            &[
+                true, false, true, true, true, false, true, false, false, false, false,
+            ],
+            &[
+                false, false, false, false, true, false, true, true, true, false, true,
+            ],
+        ];
+        for line in self.modules.iter().chain(columns.iter()) {
+            // Runs of five or more modules of the same color
+            let mut run = 1;
+            for i in 1..=size {
+                if i < size && line[i] == line[i - 1] {
+                    run += 1;
+                } else {
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+            }
+            // Patterns looking like a finder pattern
+            penalty += 40
+                * line
+                    .windows(11)
+                    .filter(|window| finder_like.contains(window))
+                    .count();
+        }
+
+        // Blocks of 2 by 2 modules of the same color
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = self.modules[y][x];
+                if self.modules[y][x + 1] == color
+                    && self.modules[y + 1][x] == color
+                    && self.modules[y + 1][x + 1] == color
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        // Balance of dark and light modules
+        let total = size * size;
+        let dark = self.modules.iter().flatten().filter(|&&dark| dark).count();
+        let deviation = (dark * 20).abs_diff(total * 10);
+        penalty += 10 * deviation.div_ceil(total).saturating_sub(1);
+
+        penalty
+    }
+}
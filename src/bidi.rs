@@ -0,0 +1,122 @@
+#[cfg(feature = "bidi")]
+use unicode_bidi::ParagraphBidiInfo;
+
+/// Arabic letters with the code point of their isolated presentation form. The final, initial and
+/// medial forms follow it, letters that only join on their right side have no initial and medial
+/// forms. Hamza, which never joins, keeps its only form.
+#[cfg(feature = "bidi")]
+#[rustfmt::skip]
+const ARABIC_FORMS: &[(char, u32, bool)] = &[
+    // (letter, isolated form, joins on both sides)
+    ('\u{622}', 0xFE81, false), ('\u{623}', 0xFE83, false), ('\u{624}', 0xFE85, false),
+    ('\u{625}', 0xFE87, false), ('\u{626}', 0xFE89, true), ('\u{627}', 0xFE8D, false),
+    ('\u{628}', 0xFE8F, true), ('\u{629}', 0xFE93, false), ('\u{62A}', 0xFE95, true),
+    ('\u{62B}', 0xFE99, true), ('\u{62C}', 0xFE9D, true), ('\u{62D}', 0xFEA1, true),
+    ('\u{62E}', 0xFEA5, true), ('\u{62F}', 0xFEA9, false), ('\u{630}', 0xFEAB, false),
+    ('\u{631}', 0xFEAD, false), ('\u{632}', 0xFEAF, false), ('\u{633}', 0xFEB1, true),
+    ('\u{634}', 0xFEB5, true), ('\u{635}', 0xFEB9, true), ('\u{636}', 0xFEBD, true),
+    ('\u{637}', 0xFEC1, true), ('\u{638}', 0xFEC5, true), ('\u{639}', 0xFEC9, true),
+    ('\u{63A}', 0xFECD, true), ('\u{641}', 0xFED1, true), ('\u{642}', 0xFED5, true),
+    ('\u{643}', 0xFED9, true), ('\u{644}', 0xFEDD, true), ('\u{645}', 0xFEE1, true),
+    ('\u{646}', 0xFEE5, true), ('\u{647}', 0xFEE9, true), ('\u{648}', 0xFEED, false),
+    ('\u{649}', 0xFEEF, false), ('\u{64A}', 0xFEF1, true),
+];
+
+/// Ligatures of lam with the variants of alef, with the code point of their isolated form. The
+/// final form follows it.
+#[cfg(feature = "bidi")]
+const LAM_ALEF: &[(char, u32)] = &[
+    ('\u{622}', 0xFEF5),
+    ('\u{623}', 0xFEF7),
+    ('\u{625}', 0xFEF9),
+    ('\u{627}', 0xFEFB),
+];
+
+#[cfg(feature = "bidi")]
+const LAM: char = '\u{644}';
+
+/// Tatweel, which joins on both sides without changing shape
+#[cfg(feature = "bidi")]
+const TATWEEL: char = '\u{640}';
+
+/// Converts a line of text from logical order, as it is typed and stored in the value of a
+/// field, to the order in which it is drawn from left to right. Arabic letters are replaced by
+/// the presentation form matching their position in the word first.
+///
+/// Without the `bidi` feature, the line is returned as is.
+pub(crate) fn visual_order(line: &str) -> String {
+    #[cfg(feature = "bidi")]
+    {
+        let shaped = shape_arabic(line);
+        let info = ParagraphBidiInfo::new(&shaped, None);
+        info.reorder_line(0..shaped.len()).into_owned()
+    }
+
+    #[cfg(not(feature = "bidi"))]
+    line.to_owned()
+}
+
+/// Whether a character is a combining mark that doesn't take part in joining
+#[cfg(feature = "bidi")]
+fn is_transparent(c: char) -> bool {
+    matches!(c, '\u{610}'..='\u{61A}' | '\u{64B}'..='\u{65F}' | '\u{670}')
+}
+
+/// Whether a character joins the character after it
+#[cfg(feature = "bidi")]
+fn joins_next(c: char) -> bool {
+    c == TATWEEL
+        || ARABIC_FORMS
+            .iter()
+            .any(|&(letter, _, dual)| letter == c && dual)
+}
+
+/// Whether a character joins the character before it
+#[cfg(feature = "bidi")]
+fn joins_previous(c: char) -> bool {
+    c == TATWEEL || ARABIC_FORMS.iter().any(|&(letter, _, _)| letter == c)
+}
+
+/// Replaces Arabic letters by their isolated, final, initial or medial presentation form, and
+/// lam followed by alef by their ligature
+#[cfg(feature = "bidi")]
+fn shape_arabic(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    // The neighbours of a letter, skipping combining marks
+    let previous = |i: usize| chars[..i].iter().rev().find(|&&c| !is_transparent(c));
+    let next = |i: usize| chars[i + 1..].iter().find(|&&c| !is_transparent(c));
+
+    let mut res = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let joined_before = previous(i).is_some_and(|&p| joins_next(p));
+
+        let ligature = chars
+            .get(i + 1)
+            .and_then(|&alef| LAM_ALEF.iter().find(|&&(variant, _)| variant == alef));
+        if let (LAM, Some(&(_, isolated))) = (c, ligature) {
+            let form = isolated + u32::from(joined_before);
+            res.extend(char::from_u32(form));
+            i += 2;
+            continue;
+        }
+
+        match ARABIC_FORMS.iter().find(|&&(letter, _, _)| letter == c) {
+            Some(&(_, isolated, dual)) => {
+                let joined_after = dual && next(i).is_some_and(|&n| joins_previous(n));
+                let form = match (joined_before, joined_after) {
+                    (false, false) => isolated,
+                    (true, false) => isolated + 1,
+                    (false, true) => isolated + 2,
+                    (true, true) => isolated + 3,
+                };
+                res.extend(char::from_u32(form));
+            }
+            None => res.push(c),
+        }
+        i += 1;
+    }
+
+    res
+}
@@ -0,0 +1,333 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::appearance::DEFAULT_FONT;
+use crate::encoding::encode_win_ansi;
+use crate::{ButtonAction, ChoiceFieldOptions, Form, TextFieldOptions, ValueError};
+
+/// Version of the documents created by `FormBuilder`
+const PDF_VERSION: &str = "1.7";
+
+/// Authors a fillable PDF from scratch
+///
+/// The builder starts with one blank page of the given size. Labels and fields are placed on the
+/// last page added, at coordinates in points from its lower left corner, and the document is only
+/// created by `build`.
+///
+/// ```no_run
+/// use pdf_forms2::{FormBuilder, TextFieldOptions};
+///
+/// let mut form = FormBuilder::new([612.0, 792.0])
+///     .label("Name", [72.0, 700.0], 12.0)
+///     .text_field("name", [150.0, 695.0, 400.0, 715.0], TextFieldOptions::default())
+///     .label("Subscribe", [72.0, 660.0], 12.0)
+///     .checkbox("subscribe", [150.0, 655.0, 165.0, 670.0], "Yes")
+///     .build()
+///     .unwrap();
+/// form.save("path/to/pdf").unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FormBuilder {
+    page_size: [f32; 2],
+    pages: usize,
+    items: Vec<(usize, Item)>,
+}
+
+/// Something placed on a page of a `FormBuilder`
+#[derive(Debug, Clone)]
+enum Item {
+    Label {
+        text: String,
+        position: [f32; 2],
+        font_size: f32,
+    },
+    Text {
+        name: String,
+        rect: [f32; 4],
+        options: TextFieldOptions,
+    },
+    CheckBox {
+        name: String,
+        rect: [f32; 4],
+        export_value: String,
+    },
+    Radio {
+        name: String,
+        buttons: Vec<([f32; 4], String)>,
+    },
+    ComboBox {
+        name: String,
+        rect: [f32; 4],
+        options: ChoiceFieldOptions,
+    },
+    ListBox {
+        name: String,
+        rect: [f32; 4],
+        options: ChoiceFieldOptions,
+    },
+    Button {
+        name: String,
+        rect: [f32; 4],
+        caption: String,
+        action: ButtonAction,
+    },
+}
+
+impl FormBuilder {
+    /// Starts a document with one blank page of `page_size` (`[width, height]`, in points)
+    pub fn new(page_size: [f32; 2]) -> Self {
+        FormBuilder {
+            page_size,
+            pages: 1,
+            items: Vec::new(),
+        }
+    }
+
+    /// Adds a blank page of the same size, on which the next labels and fields are placed
+    pub fn page(mut self) -> Self {
+        self.pages += 1;
+        self
+    }
+
+    /// Writes `text` in Helvetica at `font_size`, with its baseline starting at `position`
+    pub fn label(mut self, text: &str, position: [f32; 2], font_size: f32) -> Self {
+        self.push(Item::Label {
+            text: text.to_owned(),
+            position,
+            font_size,
+        });
+        self
+    }
+
+    /// Places a text field, see `Form::add_text_field`
+    pub fn text_field(mut self, name: &str, rect: [f32; 4], options: TextFieldOptions) -> Self {
+        self.push(Item::Text {
+            name: name.to_owned(),
+            rect,
+            options,
+        });
+        self
+    }
+
+    /// Places a checkbox, see `Form::add_checkbox`
+    pub fn checkbox(mut self, name: &str, rect: [f32; 4], export_value: &str) -> Self {
+        self.push(Item::CheckBox {
+            name: name.to_owned(),
+            rect,
+            export_value: export_value.to_owned(),
+        });
+        self
+    }
+
+    /// Places a radio group with one button per rectangle and export value, see
+    /// `Form::add_radio_group`
+    pub fn radio_group(mut self, name: &str, buttons: Vec<([f32; 4], String)>) -> Self {
+        self.push(Item::Radio {
+            name: name.to_owned(),
+            buttons,
+        });
+        self
+    }
+
+    /// Places a combo box, see `Form::add_combo_box`
+    pub fn combo_box(mut self, name: &str, rect: [f32; 4], options: ChoiceFieldOptions) -> Self {
+        self.push(Item::ComboBox {
+            name: name.to_owned(),
+            rect,
+            options,
+        });
+        self
+    }
+
+    /// Places a list box, see `Form::add_list_box`
+    pub fn list_box(mut self, name: &str, rect: [f32; 4], options: ChoiceFieldOptions) -> Self {
+        self.push(Item::ListBox {
+            name: name.to_owned(),
+            rect,
+            options,
+        });
+        self
+    }
+
+    /// Places a push button, see `Form::add_push_button`
+    pub fn push_button(
+        mut self,
+        name: &str,
+        rect: [f32; 4],
+        caption: &str,
+        action: ButtonAction,
+    ) -> Self {
+        self.push(Item::Button {
+            name: name.to_owned(),
+            rect,
+            caption: caption.to_owned(),
+            action,
+        });
+        self
+    }
+
+    /// Creates the document and its form
+    ///
+    /// Fails with the error of the first field that cannot be created, e.g.
+    /// `ValueError::NameTaken` if two top level fields have the same name.
+    pub fn build(self) -> Result<Form, ValueError> {
+        let mut form = Form::from_parts(self.blank_document(), Vec::new());
+        let font_name = form.register_standard_font(DEFAULT_FONT)?;
+        let font = form
+            .default_font(&font_name)
+            .cloned()
+            .ok_or(ValueError::NotFound)?;
+
+        let mut labels = vec![Vec::new(); self.pages];
+        for (page, item) in self.items {
+            match item {
+                Item::Label {
+                    text,
+                    position,
+                    font_size,
+                } => labels[page].extend(label_operations(&font_name, &text, position, font_size)),
+                Item::Text {
+                    name,
+                    rect,
+                    options,
+                } => {
+                    form.add_text_field(page, rect, &name, options)?;
+                }
+                Item::CheckBox {
+                    name,
+                    rect,
+                    export_value,
+                } => {
+                    form.add_checkbox(page, rect, &name, &export_value)?;
+                }
+                Item::Radio { name, buttons } => {
+                    let buttons = buttons
+                        .into_iter()
+                        .map(|(rect, export_value)| (page, rect, export_value))
+                        .collect();
+                    form.add_radio_group(&name, buttons)?;
+                }
+                Item::ComboBox {
+                    name,
+                    rect,
+                    options,
+                } => {
+                    form.add_combo_box(page, rect, &name, options)?;
+                }
+                Item::ListBox {
+                    name,
+                    rect,
+                    options,
+                } => {
+                    form.add_list_box(page, rect, &name, options)?;
+                }
+                Item::Button {
+                    name,
+                    rect,
+                    caption,
+                    action,
+                } => {
+                    form.add_push_button(page, rect, &name, &caption, action)?;
+                }
+            }
+        }
+
+        for (page, operations) in labels.into_iter().enumerate() {
+            if !operations.is_empty() {
+                form.write_page_content(page, &font_name, font.clone(), operations)
+                    .map_err(|_| ValueError::NotFound)?;
+            }
+        }
+        Ok(form)
+    }
+
+    fn push(&mut self, item: Item) {
+        self.items.push((self.pages - 1, item));
+    }
+
+    /// Creates a document with the blank pages of the builder
+    fn blank_document(&self) -> Document {
+        let mut document = Document::with_version(PDF_VERSION);
+        let pages_id = document.new_object_id();
+
+        let kids = (0..self.pages)
+            .map(|_| {
+                Object::Reference(document.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                }))
+            })
+            .collect::<Vec<_>>();
+        let [width, height] = self.page_size;
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => self.pages as i64,
+                "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            }),
+        );
+
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+}
+
+impl Form {
+    /// Sets the content of a blank page to `operations`, drawing with `font` named `font_name`
+    fn write_page_content(
+        &mut self,
+        page: usize,
+        font_name: &str,
+        font: Object,
+        operations: Vec<Operation>,
+    ) -> Result<(), lopdf::Error> {
+        let page_id: ObjectId = self
+            .page_id(page)
+            .ok_or(lopdf::Error::PageNumberNotFound(page as u32))?;
+        let content = Content { operations }.encode()?;
+        let content_id = self
+            .document
+            .add_object(Stream::new(dictionary! {}, content));
+
+        let page = self.get_object_mut(page_id)?.as_dict_mut()?;
+        page.set("Contents", content_id);
+        page.set(
+            "Resources",
+            dictionary! {
+                "Font" => dictionary! { font_name => font },
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Builds the operations writing a line of text with its baseline starting at `position`
+fn label_operations(
+    font_name: &str,
+    text: &str,
+    position: [f32; 2],
+    font_size: f32,
+) -> Vec<Operation> {
+    vec![
+        Operation::new("BT", vec![]),
+        Operation::new(
+            "Tf",
+            vec![
+                Object::Name(font_name.as_bytes().to_vec()),
+                font_size.into(),
+            ],
+        ),
+        Operation::new("Td", vec![position[0].into(), position[1].into()]),
+        Operation::new(
+            "Tj",
+            vec![Object::String(encode_win_ansi(text), StringFormat::Literal)],
+        ),
+        Operation::new("ET", vec![]),
+    ]
+}
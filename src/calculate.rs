@@ -0,0 +1,194 @@
+use lopdf::Object;
+
+use crate::format::{parse_number, FormatScript};
+use crate::{ActionTrigger, FieldValue, Form, TextAppearanceOptions};
+
+/// The operations of the `AFSimple_Calculate` script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Sum,
+    Average,
+    Product,
+    Min,
+    Max,
+}
+
+/// A calculate script (`/AA /C`) of the form `AFSimple_Calculate("SUM", ["a", "b"])`
+#[derive(Debug, Clone, PartialEq)]
+struct SimpleCalculation {
+    operation: Operation,
+    /// Names of the fields the value is computed from
+    fields: Vec<String>,
+}
+
+impl SimpleCalculation {
+    /// Recognizes an `AFSimple_Calculate` call. The fields are given either as an array of
+    /// names or as a single string of names separated by commas.
+    fn parse(script: &str) -> Option<Self> {
+        let start = script.find("AFSimple_Calculate")?;
+        let mut strings = quoted_strings(&script[start..]).into_iter();
+
+        let operation = match strings.next()?.as_str() {
+            "SUM" => Operation::Sum,
+            "AVG" => Operation::Average,
+            "PRD" => Operation::Product,
+            "MIN" => Operation::Min,
+            "MAX" => Operation::Max,
+            _ => return None,
+        };
+        let mut fields = strings.collect::<Vec<_>>();
+        if fields.len() == 1 {
+            fields = fields[0]
+                .split(',')
+                .map(|name| name.trim().to_owned())
+                .filter(|name| !name.is_empty())
+                .collect();
+        }
+
+        Some(SimpleCalculation { operation, fields })
+    }
+
+    /// Computes the result from the values of the fields
+    fn apply(&self, values: &[f64]) -> f64 {
+        match self.operation {
+            Operation::Sum => values.iter().sum(),
+            Operation::Average if values.is_empty() => 0.0,
+            Operation::Average => values.iter().sum::<f64>() / values.len() as f64,
+            Operation::Product => values.iter().product(),
+            Operation::Min => values.iter().copied().reduce(f64::min).unwrap_or(0.0),
+            Operation::Max => values.iter().copied().reduce(f64::max).unwrap_or(0.0),
+        }
+    }
+}
+
+impl Form {
+    /// Updates the value and the appearance of the calculated fields, whose calculate script
+    /// (`/AA /C`) is the standard `AFSimple_Calculate` script of Acrobat (sum, average,
+    /// product, minimum or maximum of other fields). Call it after filling the fields the
+    /// calculations depend on. Returns the indices of the fields that were updated.
+    ///
+    /// The fields are calculated in the calculation order of the AcroForm (`/CO`), or in the
+    /// order of the fields when it has none, so a calculated field can use the result of
+    /// another one. A name given to the script that is the name of a parent field stands for
+    /// all the fields under it. Values that are not numbers count as 0, and the result is shown
+    /// as formatted by the format script of the field.
+    pub fn recalculate(&mut self) -> Vec<usize> {
+        let mut updated = Vec::new();
+
+        for n in self.calculation_order() {
+            let calculation = match self
+                .get_additional_actions(n)
+                .into_iter()
+                .filter(|action| action.trigger == ActionTrigger::Calculate)
+                .find_map(|action| SimpleCalculation::parse(action.script.as_deref()?))
+            {
+                Some(calculation) => calculation,
+                None => continue,
+            };
+
+            let values = calculation
+                .fields
+                .iter()
+                .flat_map(|name| self.operand_values(name))
+                .collect::<Vec<_>>();
+            let result = calculation.apply(&values);
+            let value = if result.is_finite() {
+                result.to_string()
+            } else {
+                String::new()
+            };
+
+            if self.get_value(n) == Some(FieldValue::Text(value.clone())) {
+                continue;
+            }
+            let options = TextAppearanceOptions {
+                apply_format: true,
+                ..Default::default()
+            };
+            match self.set_text_with(n, value, &options) {
+                Ok(()) => updated.push(n),
+                Err(e) => warn!("Calculation of field {n} failed: {e}"),
+            }
+        }
+
+        updated
+    }
+
+    /// Gets the indices of the fields in the calculation order (`/CO`) of the AcroForm, or all
+    /// the fields when there is none
+    fn calculation_order(&self) -> Vec<usize> {
+        let order = self
+            .acroform()
+            .and_then(|acroform| acroform.get(b"CO").ok())
+            .and_then(|order| self.dereference(order).ok());
+
+        match order {
+            Some((_, Object::Array(order))) => order
+                .iter()
+                .filter_map(|field| field.as_reference().ok())
+                .filter_map(|oid| self.index_of(oid))
+                .collect(),
+            _ => (0..self.len()).collect(),
+        }
+    }
+
+    /// Gets the numeric values of the field named `name`, or of every field under it
+    fn operand_values(&self, name: &str) -> Vec<f64> {
+        let prefix = format!("{}.", name);
+        (0..self.len())
+            .filter(|&n| {
+                self.get_qualified_name(n)
+                    .is_some_and(|qualified| qualified == name || qualified.starts_with(&prefix))
+            })
+            .map(|n| self.numeric_value(n))
+            .collect()
+    }
+
+    /// Reads the value of a field as a number, with the decimal separator of its number format,
+    /// 0 when it isn't a number
+    fn numeric_value(&self, n: usize) -> f64 {
+        let text = match self.get_value(n) {
+            Some(FieldValue::Text(text))
+            | Some(FieldValue::Radio(text))
+            | Some(FieldValue::Choice(text)) => text,
+            Some(FieldValue::Choices(choices)) => choices.into_iter().next().unwrap_or_default(),
+            Some(FieldValue::Checked(true)) => self.check_box_on_state(n),
+            Some(FieldValue::Checked(false)) | None => String::new(),
+        };
+        let separators = match self.format_script(n) {
+            Some(FormatScript::Number { separators, .. })
+            | Some(FormatScript::Percent { separators, .. }) => separators,
+            _ => 0,
+        };
+        parse_number(&text, separators).unwrap_or(0.0)
+    }
+}
+
+/// Gets the strings quoted in the first call of a script, up to its closing parenthesis
+fn quoted_strings(script: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current: Option<(char, String)> = None;
+    let mut depth = 0;
+    let mut chars = script.chars();
+
+    while let Some(c) = chars.next() {
+        match (&mut current, c) {
+            (Some((quote, _)), c) if c == *quote => {
+                strings.extend(current.take().map(|(_, string)| string))
+            }
+            (Some((_, string)), '\\') => string.extend(chars.next()),
+            (Some((_, string)), c) => string.push(c),
+            (None, '"') | (None, '\'') => current = Some((c, String::new())),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            (None, _) => {}
+        }
+    }
+
+    strings
+}
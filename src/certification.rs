@@ -0,0 +1,140 @@
+use lopdf::{dictionary, Object};
+
+use crate::utils::{pdf_date_now, DictionaryExt};
+use crate::{FieldKey, Form, ValueError};
+
+/// The changes a certification signature allows after the document is certified, the `/P` of
+/// its DocMDP transform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MdpPermissions {
+    /// Any change invalidates the certification
+    NoChanges,
+    /// Filling in forms, instantiating page templates and signing
+    FormFilling,
+    /// Form filling, and creating, editing and deleting annotations
+    Annotations,
+}
+
+impl MdpPermissions {
+    fn to_p(self) -> i64 {
+        match self {
+            MdpPermissions::NoChanges => 1,
+            MdpPermissions::FormFilling => 2,
+            MdpPermissions::Annotations => 3,
+        }
+    }
+
+    fn from_p(p: i64) -> Self {
+        match p {
+            1 => MdpPermissions::NoChanges,
+            3 => MdpPermissions::Annotations,
+            // 2 is the default of the spec
+            _ => MdpPermissions::FormFilling,
+        }
+    }
+}
+
+impl Form {
+    /// Prepares the signature field at index `n` for a certification (author) signature, like
+    /// `prepare_signature`: the signature dictionary gets a DocMDP transform allowing the
+    /// changes of `permissions` after the document is certified, and is set as the `/DocMDP`
+    /// of the `/Perms` of the catalog.
+    ///
+    /// A document has a single certification signature, which must be its first signature.
+    /// Fails with `ValueError::TypeMismatch` if the field is not a signature field, or
+    /// `ValueError::Readonly` if the field or the document is already signed or certified.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn prepare_certification(
+        &mut self,
+        n: impl FieldKey,
+        reserved_len: usize,
+        permissions: MdpPermissions,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        if self.is_signed() || self.get_certification().is_some() {
+            return Err(ValueError::Readonly {
+                field: self.field_context(n),
+            });
+        }
+
+        let reference = dictionary! {
+            "Type" => "SigRef",
+            "TransformMethod" => "DocMDP",
+            "TransformParams" => dictionary! {
+                "Type" => "TransformParams",
+                "P" => permissions.to_p(),
+                "V" => "1.2",
+            },
+        };
+        let value = self.prepare_signature_value(
+            n,
+            reserved_len,
+            dictionary! {
+                "Type" => "Sig",
+                "Filter" => "Adobe.PPKLite",
+                "SubFilter" => "adbe.pkcs7.detached",
+                "M" => Object::string_literal(pdf_date_now()),
+                "Reference" => vec![reference.into()],
+            },
+        )?;
+
+        let root = self
+            .document
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|_| ValueError::NotFound)?;
+        let perms = match self.dictionary_mut(root)?.get(b"Perms") {
+            Ok(Object::Reference(id)) => Some(*id),
+            _ => None,
+        };
+        let perms = match perms {
+            Some(id) => self.dictionary_mut(id)?,
+            None => {
+                let catalog = self.dictionary_mut(root)?;
+                if !matches!(catalog.get(b"Perms"), Ok(Object::Dictionary(_))) {
+                    catalog.set("Perms", dictionary! {});
+                }
+                match catalog.get_mut(b"Perms") {
+                    Ok(Object::Dictionary(perms)) => perms,
+                    _ => return Err(ValueError::NotFound),
+                }
+            }
+        };
+        perms.set_in_place("DocMDP", value);
+        Ok(())
+    }
+
+    /// Gets the changes the certification signature of the document allows, or `None` if the
+    /// document is not certified
+    pub fn get_certification(&self) -> Option<MdpPermissions> {
+        let catalog = self.catalog().ok()?;
+        let (_, perms) = self.dereference(catalog.get(b"Perms").ok()?).ok()?;
+        let (_, signature) = self
+            .dereference(perms.as_dict().ok()?.get(b"DocMDP").ok()?)
+            .ok()?;
+        let references = signature.as_dict().ok()?.get(b"Reference").ok();
+
+        let p = references
+            .and_then(|references| self.dereference(references).ok())
+            .and_then(|(_, references)| references.as_array().ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|reference| self.dereference(reference).ok())
+            .filter_map(|(_, reference)| reference.as_dict().ok())
+            .find(|reference| {
+                reference
+                    .get(b"TransformMethod")
+                    .and_then(Object::as_name)
+                    .is_ok_and(|method| method == b"DocMDP")
+            })
+            .and_then(|reference| reference.get(b"TransformParams").ok())
+            .and_then(|params| self.dereference(params).ok())
+            .and_then(|(_, params)| params.as_dict().ok())
+            .and_then(|params| params.get(b"P").and_then(Object::as_i64).ok())
+            .unwrap_or(2);
+        Some(MdpPermissions::from_p(p))
+    }
+}
@@ -0,0 +1,327 @@
+use lopdf::Object;
+
+use crate::encoding::encode_text_string;
+use crate::utils::{get_choice_selection, get_text_string, selection_items, DictionaryExt};
+use crate::{ChoiceFieldFlags, FieldKey, FieldType, Form, ValueError};
+
+/// An option of a list box or combo box
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChoiceOption {
+    /// The value of the field when the option is selected
+    pub export: String,
+    /// The text shown for the option, the export value when `None`
+    pub display: Option<String>,
+}
+
+impl ChoiceOption {
+    /// Creates an option whose export value is also the text shown
+    pub fn new<S: Into<String>>(value: S) -> Self {
+        ChoiceOption {
+            export: value.into(),
+            display: None,
+        }
+    }
+
+    /// Creates an option with distinct export value and text shown
+    pub fn with_display<E: Into<String>, D: Into<String>>(export: E, display: D) -> Self {
+        ChoiceOption {
+            export: export.into(),
+            display: Some(display.into()),
+        }
+    }
+
+    /// The text shown for the option
+    pub fn display(&self) -> &str {
+        self.display.as_deref().unwrap_or(&self.export)
+    }
+
+    /// Reads an entry of `/Opt`: a text string, or a pair of the export value and of the text
+    /// shown
+    pub(crate) fn from_object(object: &Object) -> Option<Self> {
+        match object {
+            Object::Array(pair) => {
+                let export = pair.first().and_then(get_text_string)?;
+                let display = pair.get(1).and_then(get_text_string);
+                Some(ChoiceOption { export, display })
+            }
+            _ => get_text_string(object).map(ChoiceOption::new),
+        }
+    }
+
+    /// Converts the option to an entry of `/Opt`: a text string, or a pair of the export value
+    /// and of the text shown when they differ
+    pub(crate) fn to_object(&self) -> Object {
+        let export = Object::string_literal(encode_text_string(&self.export));
+        match &self.display {
+            Some(display) if *display != self.export => Object::Array(vec![
+                export,
+                Object::string_literal(encode_text_string(display)),
+            ]),
+            _ => export,
+        }
+    }
+}
+
+impl From<&str> for ChoiceOption {
+    fn from(value: &str) -> Self {
+        ChoiceOption::new(value)
+    }
+}
+
+impl From<String> for ChoiceOption {
+    fn from(value: String) -> Self {
+        ChoiceOption::new(value)
+    }
+}
+
+/// Sorts options alphabetically by the text shown, as fields with the sort flag require
+pub(crate) fn sort_options(options: &mut [ChoiceOption]) {
+    options.sort_by(|a, b| a.display().cmp(b.display()));
+}
+
+/// Gets the export value of the option designated by `value`, either its export value or the
+/// text shown for it. Export values take precedence over the texts of other options.
+pub(crate) fn export_value<'a>(options: &'a [ChoiceOption], value: &str) -> Option<&'a str> {
+    options
+        .iter()
+        .find(|option| option.export == value)
+        .or_else(|| options.iter().find(|option| option.display() == value))
+        .map(|option| option.export.as_str())
+}
+
+/// Gets the text shown for a selected export value, the value itself when it isn't an option,
+/// as in the text typed in an editable combo box
+pub(crate) fn display_text(options: &[ChoiceOption], export: &str) -> String {
+    options
+        .iter()
+        .find(|option| option.export == export)
+        .map_or(export, ChoiceOption::display)
+        .to_owned()
+}
+
+/// Converts a selection to a value (`/V` or `/DV`): a text string for a single item, an array
+/// otherwise
+pub(crate) fn selection_object(selected: &[String]) -> Object {
+    match selected {
+        [single] => Object::string_literal(encode_text_string(single)),
+        _ => Object::Array(
+            selected
+                .iter()
+                .map(|item| Object::string_literal(encode_text_string(item)))
+                .collect(),
+        ),
+    }
+}
+
+impl Form {
+    /// Gets the options of the list box or combo box at index `n`, with their export value and
+    /// displayed text. Other fields have no options.
+    ///
+    /// Returns an empty list if n is larger than the number of fields or the field is not part of
+    /// the form.
+    pub fn get_options(&self, n: impl FieldKey) -> Vec<ChoiceOption> {
+        let n = match self.try_resolve(n) {
+            Ok(n) => n,
+            Err(_) => return Vec::new(),
+        };
+        if let Some(info) = self.field_info(n) {
+            return info.options.clone();
+        }
+        match self.inherited_field_entry(self.form_ids[n], b"Opt") {
+            Some(Object::Array(options)) => options
+                .iter()
+                .filter_map(ChoiceOption::from_object)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Adds an option at the end of the list box or combo box at index `n`, or at its place in
+    /// alphabetical order if the field has the sort flag.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field isn't a choice field, or
+    /// `ValueError::InvalidSelection` if it already has an option with the same export value.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn add_option(
+        &mut self,
+        n: impl FieldKey,
+        option: impl Into<ChoiceOption>,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let option = option.into();
+        let mut options = self.choice_options_of(n)?;
+        if options.iter().any(|other| other.export == option.export) {
+            return Err(ValueError::InvalidSelection);
+        }
+
+        options.push(option);
+        self.write_options(n, options)
+    }
+
+    /// Removes the option with the export value `export` from the list box or combo box at
+    /// index `n`. It is deselected if it was selected, also in the default selection.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field isn't a choice field, or
+    /// `ValueError::InvalidSelection` if it has no such option.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn remove_option(&mut self, n: impl FieldKey, export: &str) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let mut options = self.choice_options_of(n)?;
+        let len = options.len();
+        options.retain(|option| option.export != export);
+        if options.len() == len {
+            return Err(ValueError::InvalidSelection);
+        }
+
+        self.write_options(n, options)
+    }
+
+    /// Replaces the options of the list box or combo box at index `n`, sorting them if the field
+    /// has the sort flag. Selected items that are no longer options are deselected, also in the
+    /// default selection.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field isn't a choice field, or
+    /// `ValueError::InvalidSelection` if two options have the same export value.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_options(
+        &mut self,
+        n: impl FieldKey,
+        options: Vec<ChoiceOption>,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.choice_options_of(n)?;
+        for (i, option) in options.iter().enumerate() {
+            if options[..i]
+                .iter()
+                .any(|other| other.export == option.export)
+            {
+                return Err(ValueError::InvalidSelection);
+            }
+        }
+
+        self.write_options(n, options)
+    }
+
+    /// Gets the options of a choice field as reported in its state, without the blank ones
+    pub(crate) fn choice_options(&self, n: usize) -> Vec<ChoiceOption> {
+        let mut options = self.get_options(n);
+        options.retain(|option| !option.display().is_empty());
+        options
+    }
+
+    /// Updates the indices of the selected items of a list box (`/I`) from its value, and its
+    /// top index (`/TI`) so the first selected item is visible. Both are removed when nothing
+    /// is selected, and from combo boxes, which have no use for them.
+    pub(crate) fn update_selection_indices(&mut self, n: usize) -> Result<(), ValueError> {
+        let oid = self.form_ids[n];
+        let is_list_box = self.try_get_type(n)? == FieldType::ListBox;
+        let options = self.get_options(n);
+        let visible_rows = self.list_box_visible_rows(oid).unwrap_or(1);
+
+        let field = self
+            .get_object_mut(oid)
+            .and_then(Object::as_dict_mut)
+            .map_err(|_| ValueError::NotFound)?;
+        let selected = get_choice_selection(field);
+        let indices = options
+            .iter()
+            .enumerate()
+            .filter(|(_, option)| selected.contains(&option.export))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        match indices.first() {
+            Some(&first) if is_list_box => {
+                let top_index = field
+                    .get(b"TI")
+                    .and_then(Object::as_i64)
+                    .map_or(0, |ti| ti.max(0) as usize);
+                if first < top_index || first >= top_index + visible_rows {
+                    field.set_in_place("TI", first as i64);
+                }
+                field.set_in_place(
+                    "I",
+                    indices
+                        .into_iter()
+                        .map(|i| Object::Integer(i as i64))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            _ => {
+                field.remove(b"I");
+                field.remove(b"TI");
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the options of a choice field, failing if the field isn't one
+    fn choice_options_of(&self, n: usize) -> Result<Vec<ChoiceOption>, ValueError> {
+        match self.try_get_type(n)? {
+            FieldType::ListBox | FieldType::ComboBox => Ok(self.get_options(n)),
+            _ => Err(self.type_mismatch(n, &[FieldType::ListBox, FieldType::ComboBox])),
+        }
+    }
+
+    /// Writes the options of a choice field, dropping the selected items that are no longer
+    /// options, and redraws its appearance
+    fn write_options(
+        &mut self,
+        n: usize,
+        mut options: Vec<ChoiceOption>,
+    ) -> Result<(), ValueError> {
+        let flags = self.get_choice_field_flags(n)?;
+        if flags.contains(ChoiceFieldFlags::SORT) {
+            sort_options(&mut options);
+        }
+        // The value of an editable combo box may be any text
+        let keeps_any_value = flags.contains(ChoiceFieldFlags::COMBO | ChoiceFieldFlags::EDIT);
+
+        let field = self
+            .get_object_mut(self.form_ids[n])
+            .and_then(Object::as_dict_mut)
+            .map_err(|_| ValueError::NotFound)?;
+        for key in ["V", "DV"] {
+            let selected = field
+                .get(key.as_bytes())
+                .map(selection_items)
+                .unwrap_or_default();
+            let kept = selected
+                .iter()
+                .filter(|item| keeps_any_value || options.iter().any(|o| o.export == **item))
+                .cloned()
+                .collect::<Vec<_>>();
+            if kept.len() == selected.len() {
+                continue;
+            }
+            if kept.is_empty() {
+                field.remove(key.as_bytes());
+            } else {
+                field.set_in_place(key, selection_object(&kept));
+            }
+        }
+        field.set_in_place(
+            "Opt",
+            options
+                .iter()
+                .map(ChoiceOption::to_object)
+                .collect::<Vec<_>>(),
+        );
+        // The indices of the selected items, and the first visible one, are stale
+        field.remove(b"TI");
+        // They, and the appearance, are computed from the cached options
+        self.refresh_field_info(n);
+        self.update_selection_indices(n)?;
+
+        self.update_choice_appearance(n)
+            .map_err(|_| ValueError::NotFound)?;
+        self.mark_modified(n);
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use std::convert::TryFrom;
+
+use lopdf::{dictionary, Dictionary, Object, ObjectId, StringFormat};
+
+use crate::Form;
+
+/// One of the CID-keyed fonts for Chinese, Japanese and Korean that PDF readers provide without
+/// them being embedded (Adobe's Asian font packs), used to draw CJK text in appearances
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CjkFont {
+    /// STSong-Light, for Simplified Chinese
+    #[default]
+    SimplifiedChinese,
+    /// MSung-Light, for Traditional Chinese
+    TraditionalChinese,
+    /// HeiseiMin-W3, for Japanese
+    Japanese,
+    /// HYSMyeongJo-Medium, for Korean
+    Korean,
+}
+
+impl CjkFont {
+    /// Picks the font for a text from its script: Hangul is Korean and kana Japanese. Text
+    /// written with Han characters only uses `default`, since they are shared by the languages.
+    pub(crate) fn for_text(text: &str, default: CjkFont) -> Option<CjkFont> {
+        if text.chars().any(is_hangul) {
+            Some(CjkFont::Korean)
+        } else if text.chars().any(is_kana) {
+            Some(CjkFont::Japanese)
+        } else if text.chars().any(is_cjk) {
+            Some(default)
+        } else {
+            None
+        }
+    }
+
+    /// Name under which the font is registered in the default resources
+    pub(crate) fn resource_name(self) -> &'static str {
+        match self {
+            CjkFont::SimplifiedChinese => "STSong",
+            CjkFont::TraditionalChinese => "MSung",
+            CjkFont::Japanese => "HeiMin",
+            CjkFont::Korean => "HYSMyeongJo",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            CjkFont::SimplifiedChinese => "STSong-Light",
+            CjkFont::TraditionalChinese => "MSung-Light",
+            CjkFont::Japanese => "HeiseiMin-W3",
+            CjkFont::Korean => "HYSMyeongJo-Medium",
+        }
+    }
+
+    /// The predefined CMap mapping UCS-2 codes to the CIDs of the font
+    fn cmap(self) -> &'static str {
+        match self {
+            CjkFont::SimplifiedChinese => "UniGB-UCS2-H",
+            CjkFont::TraditionalChinese => "UniCNS-UCS2-H",
+            CjkFont::Japanese => "UniJIS-UCS2-H",
+            CjkFont::Korean => "UniKS-UCS2-H",
+        }
+    }
+
+    /// Ordering and supplement of the Adobe character collection of the font
+    fn character_collection(self) -> (&'static str, i64) {
+        match self {
+            CjkFont::SimplifiedChinese => ("GB1", 2),
+            CjkFont::TraditionalChinese => ("CNS1", 1),
+            CjkFont::Japanese => ("Japan1", 2),
+            CjkFont::Korean => ("Korea1", 1),
+        }
+    }
+
+    /// Font bounding box, ascent, descent and cap height from the metrics of the font
+    fn metrics(self) -> ([i64; 4], i64, i64, i64) {
+        match self {
+            CjkFont::SimplifiedChinese => ([-25, -254, 1000, 880], 880, -120, 880),
+            CjkFont::TraditionalChinese => ([-160, -249, 1015, 1071], 880, -120, 880),
+            CjkFont::Japanese => ([-123, -257, 1001, 910], 857, -143, 709),
+            CjkFont::Korean => ([0, -148, 1001, 880], 880, -120, 880),
+        }
+    }
+}
+
+/// Encodes text for a CJK font: two bytes per character, in UCS-2. Characters outside of the
+/// basic multilingual plane, which UCS-2 lacks, are replaced by a question mark.
+pub(crate) fn encode_ucs2(text: &str) -> Vec<u8> {
+    text.chars()
+        .flat_map(|c| {
+            u16::try_from(u32::from(c))
+                .unwrap_or(u16::from(b'?'))
+                .to_be_bytes()
+        })
+        .collect()
+}
+
+/// Gets the width of a character of a CJK font in thousandths of the font size: half-width for
+/// ASCII, full-width for everything else
+pub(crate) fn glyph_width(c: char) -> u16 {
+    if c.is_ascii() {
+        500
+    } else {
+        1000
+    }
+}
+
+/// Whether a character is written with a CJK font: Han, kana, Hangul or CJK punctuation
+pub(crate) fn is_cjk(c: char) -> bool {
+    is_hangul(c)
+        || is_kana(c)
+        || matches!(c,
+            '\u{2E80}'..='\u{2FDF}'
+            | '\u{3000}'..='\u{303F}'
+            | '\u{3190}'..='\u{31FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FFEF}'
+        )
+}
+
+/// Splits a word before and after each of its CJK characters, where lines may break
+pub(crate) fn split_at_cjk(word: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, c) in word.char_indices() {
+        if is_cjk(c) {
+            if start < i {
+                pieces.push(&word[start..i]);
+            }
+            pieces.push(&word[i..i + c.len_utf8()]);
+            start = i + c.len_utf8();
+        }
+    }
+    if start < word.len() || pieces.is_empty() {
+        pieces.push(&word[start..]);
+    }
+    pieces
+}
+
+fn is_hangul(c: char) -> bool {
+    matches!(c, '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}')
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}')
+}
+
+impl Form {
+    /// Chooses the font used for text written with Han characters only, which could be Chinese
+    /// or Japanese. Simplified Chinese by default.
+    pub fn set_cjk_font(&mut self, font: CjkFont) {
+        self.cjk_font = font;
+    }
+
+    /// Gets the font used for text written with Han characters only
+    pub fn cjk_font(&self) -> CjkFont {
+        self.cjk_font
+    }
+
+    /// Gets the CJK font to draw `text` with, if it has CJK characters
+    pub(crate) fn cjk_font_for(&self, text: &str) -> Option<CjkFont> {
+        CjkFont::for_text(text, self.cjk_font)
+    }
+
+    /// Adds a CJK font to the default resources (`/DR`) of the AcroForm dictionary unless it is
+    /// there already, and returns its resource name and a reference to it
+    pub(crate) fn register_cjk_font(
+        &mut self,
+        font: CjkFont,
+    ) -> Result<(String, Object), lopdf::Error> {
+        let name = font.resource_name();
+
+        if let Some(existing) = self.default_font(name).cloned() {
+            return Ok((name.to_owned(), existing));
+        }
+
+        let font_id = self.add_cjk_font(font);
+        self.default_fonts_mut()?.set(name, font_id);
+        Ok((name.to_owned(), font_id.into()))
+    }
+
+    /// Adds the objects of a Type0 font using a CJK font as descendant
+    fn add_cjk_font(&mut self, font: CjkFont) -> ObjectId {
+        let (bbox, ascent, descent, cap_height) = font.metrics();
+        let (ordering, supplement) = font.character_collection();
+
+        let descriptor_id = self.document.add_object(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => font.base_font(),
+            "Flags" => 6,
+            "FontBBox" => bbox.iter().map(|&v| Object::Integer(v)).collect::<Vec<_>>(),
+            "ItalicAngle" => 0,
+            "Ascent" => ascent,
+            "Descent" => descent,
+            "CapHeight" => cap_height,
+            "StemV" => 93,
+        });
+
+        self.document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type0",
+            "BaseFont" => format!("{}-{}", font.base_font(), font.cmap()),
+            "Encoding" => font.cmap(),
+            "DescendantFonts" => vec![Object::Dictionary(dictionary! {
+                "Type" => "Font",
+                "Subtype" => "CIDFontType0",
+                "BaseFont" => font.base_font(),
+                "CIDSystemInfo" => dictionary! {
+                    "Registry" => Object::String(b"Adobe".to_vec(), StringFormat::Literal),
+                    "Ordering" => Object::String(ordering.as_bytes().to_vec(), StringFormat::Literal),
+                    "Supplement" => supplement,
+                },
+                "FontDescriptor" => descriptor_id,
+                "DW" => 1000,
+                // The half-width Latin characters
+                "W" => vec![1.into(), 95.into(), 500.into()],
+            })],
+        })
+    }
+
+    /// Gets the font named `name` in the default resources (`/DR`) of the AcroForm
+    pub(crate) fn default_font(&self, name: &str) -> Option<&Object> {
+        self.acroform()
+            .and_then(|acroform| acroform.get(b"DR").ok())
+            .and_then(|dr| self.dereference(dr).ok())
+            .and_then(|(_, dr)| dr.as_dict().ok())
+            .and_then(|dr| dr.get(b"Font").ok())
+            .and_then(|fonts| self.dereference(fonts).ok())
+            .and_then(|(_, fonts)| fonts.as_dict().ok())
+            .and_then(|fonts| fonts.get(name.as_bytes()).ok())
+    }
+
+    /// Gets the `/Font` dictionary of the default resources of the AcroForm, creating the
+    /// missing dictionaries
+    pub(crate) fn default_fonts_mut(&mut self) -> Result<&mut Dictionary, lopdf::Error> {
+        let acroform = self.acroform_mut().ok_or(lopdf::Error::DictKey)?;
+        if !acroform.has(b"DR") {
+            acroform.set("DR", Dictionary::new());
+        }
+        let dr_id = acroform.get(b"DR")?.as_reference().ok();
+
+        let dr = self.default_resources_mut(dr_id)?;
+        if !dr.has(b"Font") {
+            dr.set("Font", Dictionary::new());
+        }
+        match dr.get(b"Font")?.as_reference() {
+            Ok(fonts_id) => self.get_object_mut(fonts_id)?.as_dict_mut(),
+            Err(_) => self
+                .default_resources_mut(dr_id)?
+                .get_mut(b"Font")?
+                .as_dict_mut(),
+        }
+    }
+
+    /// Gets the default resources of the AcroForm, which are an object of their own if `dr_id`
+    /// is set
+    fn default_resources_mut(
+        &mut self,
+        dr_id: Option<ObjectId>,
+    ) -> Result<&mut Dictionary, lopdf::Error> {
+        match dr_id {
+            Some(id) => self.get_object_mut(id)?.as_dict_mut(),
+            None => self
+                .acroform_mut()
+                .ok_or(lopdf::Error::DictKey)?
+                .get_mut(b"DR")?
+                .as_dict_mut(),
+        }
+    }
+}
@@ -0,0 +1,526 @@
+use lopdf::content::Content;
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream};
+
+use crate::appearance::{standard_font_dictionary, DefaultAppearance, DEFAULT_FONT};
+use crate::choice::{selection_object, sort_options};
+use crate::encoding::encode_text_string;
+use crate::style::background_operations;
+use crate::{
+    AnnotationFlags, ButtonAction, ButtonFieldFlags, ChoiceFieldFlags, ChoiceOption, Color, Form,
+    TextAlignment, TextFieldFlags, ValueError, WidgetStyle,
+};
+
+/// Font of the check marks and radio bullets, ZapfDingbats
+const SYMBOL_FONT: &str = "ZaDb";
+
+/// Caption of checkboxes, a check mark in ZapfDingbats
+const CHECK_CAPTION: &str = "4";
+
+/// Caption of radio buttons, a bullet in ZapfDingbats
+const RADIO_CAPTION: &str = "l";
+
+/// Gray level of the background of push buttons
+const BUTTON_BACKGROUND: f32 = 0.75;
+
+/// Options of a text field created with `Form::add_text_field`
+#[derive(Debug, Clone, Default)]
+pub struct TextFieldOptions {
+    /// Initial value of the field
+    pub value: Option<String>,
+    /// Name of the standard font the text is drawn with, e.g. `Helv` (the default), `Cour` or
+    /// `TiRo`
+    pub font_name: Option<String>,
+    /// Font size, 0 (the default) for a size that fits the text in the widget
+    pub font_size: Option<f32>,
+    /// Color of the text, black by default
+    pub color: Option<Color>,
+    /// Horizontal alignment of the text, left by default
+    pub alignment: Option<TextAlignment>,
+    /// Maximum number of characters of the value (`/MaxLen`)
+    pub max_len: Option<usize>,
+    /// Field flags, e.g. multiline or password
+    pub flags: TextFieldFlags,
+    /// Text shown by viewers when the pointer hovers the field (`/TU`)
+    pub tooltip: Option<String>,
+}
+
+/// Options of a list box or combo box created with `Form::add_list_box` or
+/// `Form::add_combo_box`
+#[derive(Debug, Clone, Default)]
+pub struct ChoiceFieldOptions {
+    /// The items of the field, sorted by their displayed text if `flags` has `SORT`
+    pub options: Vec<ChoiceOption>,
+    /// Export values of the items selected initially, which are also the default selection
+    pub selected: Vec<String>,
+    /// Field flags, e.g. editable or multiselect. The combo flag is set by the function creating
+    /// the field.
+    pub flags: ChoiceFieldFlags,
+    /// Name of the standard font the items are drawn with, e.g. `Helv` (the default), `Cour` or
+    /// `TiRo`
+    pub font_name: Option<String>,
+    /// Font size, 0 (the default) for an automatic size
+    pub font_size: Option<f32>,
+    /// Color of the text, black by default
+    pub color: Option<Color>,
+    /// Text shown by viewers when the pointer hovers the field (`/TU`)
+    pub tooltip: Option<String>,
+}
+
+impl Form {
+    /// Creates a text field named `name` on the page at index `page` (starting at 0), within
+    /// `rect` (`[llx, lly, urx, ury]`, in page coordinates). The field is added to the AcroForm
+    /// and to the annotations of the page, and its font to the default resources of the form.
+    ///
+    /// Returns the index of the new field. Fails with `ValueError::NotFound` if the page doesn't
+    /// exist, or `ValueError::NameTaken` if a top level field already has the name.
+    pub fn add_text_field(
+        &mut self,
+        page: usize,
+        rect: [f32; 4],
+        name: &str,
+        options: TextFieldOptions,
+    ) -> Result<usize, ValueError> {
+        let page = self.page_id(page).ok_or(ValueError::NotFound)?;
+        self.check_new_name(None, name, None)?;
+
+        let font_name = options.font_name.as_deref().unwrap_or(DEFAULT_FONT);
+        let da = DefaultAppearance {
+            font_name: self.register_standard_font(font_name)?,
+            font_size: options.font_size.unwrap_or(0.0),
+            color: options.color.unwrap_or(Color::Gray(0.0)),
+        };
+
+        let mut field = self.widget_dictionary(page, rect);
+        field.set("FT", "Tx");
+        field.set("T", Object::string_literal(encode_text_string(name)));
+        if !options.flags.is_empty() {
+            field.set("Ff", i64::from(options.flags.bits()));
+        }
+        field.set("DA", Object::string_literal(da.encode()));
+        if let Some(alignment) = options.alignment {
+            field.set("Q", alignment.quadding());
+        }
+        if let Some(max_len) = options.max_len {
+            field.set("MaxLen", max_len as i64);
+        }
+        if let Some(tooltip) = &options.tooltip {
+            field.set("TU", Object::string_literal(encode_text_string(tooltip)));
+        }
+
+        let n = self.add_field(page, field)?;
+        if let Some(value) = options.value {
+            self.set_text(n, value)?;
+        }
+        Ok(n)
+    }
+
+    /// Creates an unchecked checkbox named `name` on the page at index `page` (starting at 0),
+    /// within `rect` (`[llx, lly, urx, ury]`, in page coordinates). `export_value` is the name of
+    /// its on state, the value of the field when it is checked. Appearance streams are created
+    /// for the on and off states.
+    ///
+    /// Returns the index of the new field. Fails with `ValueError::NotFound` if the page doesn't
+    /// exist, `ValueError::NameTaken` if a top level field already has the name or
+    /// `ValueError::InvalidSelection` if the export value is empty or `Off`.
+    pub fn add_checkbox(
+        &mut self,
+        page: usize,
+        rect: [f32; 4],
+        name: &str,
+        export_value: &str,
+    ) -> Result<usize, ValueError> {
+        let page = self.page_id(page).ok_or(ValueError::NotFound)?;
+        self.check_new_name(None, name, None)?;
+        check_export_value(export_value)?;
+
+        let mut field = self.widget_dictionary(page, rect);
+        field.set("FT", "Btn");
+        field.set("T", Object::string_literal(encode_text_string(name)));
+        field.set("DA", Object::string_literal(self.symbol_appearance()?));
+        field.set(
+            "MK",
+            dictionary! { "CA" => Object::string_literal(CHECK_CAPTION) },
+        );
+        field.set("V", "Off");
+        field.set("AS", "Off");
+
+        let n = self.add_field(page, field)?;
+        self.ensure_check_box_appearance(self.form_ids[n], export_value)
+            .map_err(|_| ValueError::NotFound)?;
+        Ok(n)
+    }
+
+    /// Creates a radio button group named `name` with one button per `(page, rect, export
+    /// value)` entry of `buttons`. Pages are indices starting at 0 and rectangles are given as
+    /// `[llx, lly, urx, ury]` in page coordinates. No button is selected, and appearance streams
+    /// are created for the on and off states of each button.
+    ///
+    /// Returns the index of the new field. Fails with `ValueError::NotFound` if a page doesn't
+    /// exist, `ValueError::NameTaken` if a top level field already has the name or
+    /// `ValueError::InvalidSelection` if there are no buttons or an export value is empty or
+    /// `Off`.
+    pub fn add_radio_group(
+        &mut self,
+        name: &str,
+        buttons: Vec<(usize, [f32; 4], String)>,
+    ) -> Result<usize, ValueError> {
+        self.check_new_name(None, name, None)?;
+        if buttons.is_empty() {
+            return Err(ValueError::InvalidSelection);
+        }
+        let buttons = buttons
+            .into_iter()
+            .map(|(page, rect, export_value)| {
+                check_export_value(&export_value)?;
+                let page = self.page_id(page).ok_or(ValueError::NotFound)?;
+                Ok((page, rect, export_value))
+            })
+            .collect::<Result<Vec<_>, ValueError>>()?;
+
+        let flags = ButtonFieldFlags::RADIO | ButtonFieldFlags::NO_TOGGLE_TO_OFF;
+        let da = self.symbol_appearance()?;
+        let parent = self.document.add_object(dictionary! {
+            "FT" => "Btn",
+            "T" => Object::string_literal(encode_text_string(name)),
+            "Ff" => i64::from(flags.bits()),
+            "DA" => Object::string_literal(da),
+            "V" => "Off",
+        });
+
+        let mut kids = Vec::with_capacity(buttons.len());
+        for (page, rect, export_value) in buttons {
+            let mut widget = self.widget_dictionary(page, rect);
+            widget.set("Parent", parent);
+            widget.set(
+                "MK",
+                dictionary! { "CA" => Object::string_literal(RADIO_CAPTION) },
+            );
+            widget.set("AS", "Off");
+            let kid = self.document.add_object(widget);
+            self.add_annotation(page, kid)?;
+            self.ensure_check_box_appearance(kid, &export_value)
+                .map_err(|_| ValueError::NotFound)?;
+            kids.push(Object::Reference(kid));
+        }
+        self.get_object_mut(parent)
+            .and_then(Object::as_dict_mut)
+            .map_err(|_| ValueError::NotFound)?
+            .set("Kids", kids);
+
+        self.add_top_level_field(parent)
+    }
+
+    /// Creates a combo box named `name` on the page at index `page` (starting at 0), within
+    /// `rect` (`[llx, lly, urx, ury]`, in page coordinates)
+    ///
+    /// Returns the index of the new field. Fails with `ValueError::NotFound` if the page doesn't
+    /// exist, `ValueError::NameTaken` if a top level field already has the name, and
+    /// `ValueError::InvalidSelection` or `ValueError::TooManySelected` if the selection doesn't
+    /// match the options.
+    pub fn add_combo_box(
+        &mut self,
+        page: usize,
+        rect: [f32; 4],
+        name: &str,
+        mut options: ChoiceFieldOptions,
+    ) -> Result<usize, ValueError> {
+        options.flags.insert(ChoiceFieldFlags::COMBO);
+        self.add_choice_field(page, rect, name, options)
+    }
+
+    /// Creates a list box named `name` on the page at index `page` (starting at 0), within
+    /// `rect` (`[llx, lly, urx, ury]`, in page coordinates)
+    ///
+    /// Returns the index of the new field. Fails with `ValueError::NotFound` if the page doesn't
+    /// exist, `ValueError::NameTaken` if a top level field already has the name, and
+    /// `ValueError::InvalidSelection` or `ValueError::TooManySelected` if the selection doesn't
+    /// match the options.
+    pub fn add_list_box(
+        &mut self,
+        page: usize,
+        rect: [f32; 4],
+        name: &str,
+        mut options: ChoiceFieldOptions,
+    ) -> Result<usize, ValueError> {
+        options
+            .flags
+            .remove(ChoiceFieldFlags::COMBO | ChoiceFieldFlags::EDIT);
+        self.add_choice_field(page, rect, name, options)
+    }
+
+    fn add_choice_field(
+        &mut self,
+        page: usize,
+        rect: [f32; 4],
+        name: &str,
+        mut options: ChoiceFieldOptions,
+    ) -> Result<usize, ValueError> {
+        let page = self.page_id(page).ok_or(ValueError::NotFound)?;
+        self.check_new_name(None, name, None)?;
+
+        let flags = options.flags;
+        let editable = flags.contains(ChoiceFieldFlags::COMBO | ChoiceFieldFlags::EDIT);
+        let known = |item: &String| options.options.iter().any(|option| option.export == *item);
+        if !editable && !options.selected.iter().all(known) {
+            return Err(ValueError::InvalidSelection);
+        }
+        if !flags.contains(ChoiceFieldFlags::MULTISELECT) && options.selected.len() > 1 {
+            return Err(ValueError::TooManySelected);
+        }
+        if flags.contains(ChoiceFieldFlags::SORT) {
+            sort_options(&mut options.options);
+        }
+
+        let font_name = options.font_name.as_deref().unwrap_or(DEFAULT_FONT);
+        let da = DefaultAppearance {
+            font_name: self.register_standard_font(font_name)?,
+            font_size: options.font_size.unwrap_or(0.0),
+            color: options.color.unwrap_or(Color::Gray(0.0)),
+        };
+
+        let mut field = self.widget_dictionary(page, rect);
+        field.set("FT", "Ch");
+        field.set("T", Object::string_literal(encode_text_string(name)));
+        if !flags.is_empty() {
+            field.set("Ff", i64::from(flags.bits()));
+        }
+        field.set("DA", Object::string_literal(da.encode()));
+        field.set(
+            "Opt",
+            options
+                .options
+                .iter()
+                .map(ChoiceOption::to_object)
+                .collect::<Vec<_>>(),
+        );
+        if !options.selected.is_empty() {
+            field.set("V", selection_object(&options.selected));
+            field.set("DV", selection_object(&options.selected));
+        }
+        if let Some(tooltip) = &options.tooltip {
+            field.set("TU", Object::string_literal(encode_text_string(tooltip)));
+        }
+
+        let n = self.add_field(page, field)?;
+        self.regenerate_choice_appearance(n)
+            .map_err(|_| ValueError::NotFound)?;
+        Ok(n)
+    }
+
+    /// Creates a push button named `name` on the page at index `page` (starting at 0), within
+    /// `rect` (`[llx, lly, urx, ury]`, in page coordinates). The button shows `caption` over a
+    /// gray background and performs `action` when clicked.
+    ///
+    /// Returns the index of the new field. Fails with `ValueError::NotFound` if the page doesn't
+    /// exist, or `ValueError::NameTaken` if a top level field already has the name.
+    pub fn add_push_button(
+        &mut self,
+        page: usize,
+        rect: [f32; 4],
+        name: &str,
+        caption: &str,
+        action: ButtonAction,
+    ) -> Result<usize, ValueError> {
+        let page = self.page_id(page).ok_or(ValueError::NotFound)?;
+        self.check_new_name(None, name, None)?;
+
+        let da = DefaultAppearance {
+            font_name: self.register_standard_font(DEFAULT_FONT)?,
+            font_size: 0.0,
+            color: Color::Gray(0.0),
+        };
+
+        let mut field = self.widget_dictionary(page, rect);
+        field.set("FT", "Btn");
+        field.set("T", Object::string_literal(encode_text_string(name)));
+        field.set("Ff", i64::from(ButtonFieldFlags::PUSHBUTTON.bits()));
+        field.set("DA", Object::string_literal(da.encode()));
+        field.set(
+            "MK",
+            dictionary! {
+                "CA" => Object::string_literal(encode_text_string(caption)),
+                "BG" => vec![BUTTON_BACKGROUND.into()],
+                "BC" => vec![0.into()],
+            },
+        );
+        // Pushed look while the button is clicked
+        field.set("H", "P");
+        field.set("A", action.to_dictionary());
+
+        let n = self.add_field(page, field)?;
+        self.draw_button_background(n)
+            .and_then(|_| self.draw_button_caption(n, caption))
+            .map_err(|_| ValueError::NotFound)?;
+        Ok(n)
+    }
+
+    /// Creates the normal appearance of a push button with its background and border, on which
+    /// the caption is drawn next
+    fn draw_button_background(&mut self, n: usize) -> Result<(), lopdf::Error> {
+        let oid = self.form_ids[n];
+        let frame = self.appearance_frame(oid).ok_or(lopdf::Error::DictKey)?;
+
+        let style = WidgetStyle {
+            border_color: Some(Color::Gray(0.0)),
+            background_color: Some(Color::Gray(BUTTON_BACKGROUND)),
+            ..WidgetStyle::default()
+        };
+        let content = Content {
+            operations: background_operations(&frame, &style),
+        };
+        let mut dict = Dictionary::new();
+        frame.apply(&mut dict);
+        let stream = self
+            .document
+            .add_object(Stream::new(dict, content.encode()?));
+
+        self.get_object_mut(oid)?
+            .as_dict_mut()?
+            .set("AP", dictionary! { "N" => stream });
+        Ok(())
+    }
+
+    /// Registers ZapfDingbats in the default resources and returns the default appearance of
+    /// buttons drawn with it
+    fn symbol_appearance(&mut self) -> Result<String, ValueError> {
+        let da = DefaultAppearance {
+            font_name: self.register_standard_font(SYMBOL_FONT)?,
+            font_size: 0.0,
+            color: Color::Gray(0.0),
+        };
+        Ok(da.encode())
+    }
+
+    /// Creates the entries of a printable widget annotation placed within `rect` on a page
+    pub(crate) fn widget_dictionary(&self, page: ObjectId, rect: [f32; 4]) -> Dictionary {
+        let rect = self.page_transform(page).rect_to_user_space(rect);
+        let rect = [
+            rect[0].min(rect[2]),
+            rect[1].min(rect[3]),
+            rect[0].max(rect[2]),
+            rect[1].max(rect[3]),
+        ];
+
+        dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "Rect" => rect.iter().map(|&v| Object::Real(v.into())).collect::<Vec<_>>(),
+            "P" => page,
+            "F" => i64::from(AnnotationFlags::PRINT.bits()),
+        }
+    }
+
+    /// Adds a top level field to the document, to the AcroForm `/Fields` and, as it is merged
+    /// with its widget, to the `/Annots` of its page. Returns the index of the field.
+    pub(crate) fn add_field(
+        &mut self,
+        page: ObjectId,
+        field: Dictionary,
+    ) -> Result<usize, ValueError> {
+        self.ensure_acroform()?;
+        let oid = self.document.add_object(field);
+        self.add_annotation(page, oid)?;
+        self.add_top_level_field(oid)
+    }
+
+    /// Appends a field to the AcroForm `/Fields` and returns its index
+    fn add_top_level_field(&mut self, oid: ObjectId) -> Result<usize, ValueError> {
+        if self.fields_array_mut().is_none() {
+            if let Some(acroform) = self.acroform_mut() {
+                acroform.set("Fields", Vec::<Object>::new());
+            }
+        }
+        self.fields_array_mut()
+            .ok_or(ValueError::NotFound)?
+            .push(oid.into());
+
+        self.reindex();
+        self.index_of(oid).ok_or(ValueError::NotFound)
+    }
+
+    /// Appends an annotation to the `/Annots` of a page, creating the array if needed
+    pub(crate) fn add_annotation(
+        &mut self,
+        page: ObjectId,
+        annot: ObjectId,
+    ) -> Result<(), ValueError> {
+        let annots_id = match self
+            .get_dictionary(page)
+            .and_then(|page| page.get(b"Annots"))
+        {
+            Ok(Object::Reference(id)) => Some(*id),
+            _ => None,
+        };
+
+        match annots_id {
+            Some(id) => self
+                .get_object_mut(id)
+                .and_then(Object::as_array_mut)
+                .map_err(|_| ValueError::NotFound)?
+                .push(annot.into()),
+            None => {
+                let page = self
+                    .get_object_mut(page)
+                    .and_then(Object::as_dict_mut)
+                    .map_err(|_| ValueError::NotFound)?;
+                match page.get_mut(b"Annots") {
+                    Ok(Object::Array(annots)) => annots.push(annot.into()),
+                    _ => page.set("Annots", vec![annot.into()]),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the standard font abbreviated `font_name`, like `Helv`, to the default resources of
+    /// the AcroForm unless it is there already. Returns the name under which it is registered,
+    /// `Helv` for unknown fonts.
+    pub(crate) fn register_standard_font(&mut self, font_name: &str) -> Result<String, ValueError> {
+        self.ensure_acroform()?;
+        if self.default_font(font_name).is_some() {
+            return Ok(font_name.to_owned());
+        }
+
+        let (font_name, font) = standard_font_dictionary(font_name);
+        if self.default_font(&font_name).is_none() {
+            let font_id = self.document.add_object(font);
+            self.default_fonts_mut()
+                .map_err(|_| ValueError::NotFound)?
+                .set(font_name.as_str(), font_id);
+        }
+        Ok(font_name)
+    }
+
+    /// Creates an empty AcroForm dictionary if the document has none
+    pub(crate) fn ensure_acroform(&mut self) -> Result<(), ValueError> {
+        if self.acroform().is_some() {
+            return Ok(());
+        }
+
+        let acroform = self.document.add_object(dictionary! {
+            "Fields" => Vec::<Object>::new(),
+        });
+        let root = self
+            .document
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|_| ValueError::NotFound)?;
+        self.get_object_mut(root)
+            .and_then(Object::as_dict_mut)
+            .map_err(|_| ValueError::NotFound)?
+            .set("AcroForm", acroform);
+
+        Ok(())
+    }
+}
+
+/// Checks that an export value can name the on state of a button
+fn check_export_value(export_value: &str) -> Result<(), ValueError> {
+    if export_value.is_empty() || export_value == "Off" {
+        Err(ValueError::InvalidSelection)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::format::{format_date, parse_date, DateTime};
+use crate::{FieldKey, FieldType, Form, ValueError};
+
+/// Pattern of the dates of text fields without a date format
+const ISO_DATE: &str = "yyyy-mm-dd";
+
+impl Form {
+    /// Fills the text field at index `n` with a date, written in the date format of the field
+    /// (see `get_date_format`), or as `yyyy-mm-dd` when it has none
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_date(&mut self, n: impl FieldKey, date: NaiveDate) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let pattern = self
+            .get_date_format(n)
+            .unwrap_or_else(|| ISO_DATE.to_owned());
+        self.set_date_with(n, date, &pattern)
+    }
+
+    /// Fills the text field at index `n` with a date written with an Acrobat date pattern, e.g.
+    /// `dd/mm/yyyy` or `mmmm d, yyyy`
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_date_with(
+        &mut self,
+        n: impl FieldKey,
+        date: NaiveDate,
+        pattern: &str,
+    ) -> Result<(), ValueError> {
+        let date = DateTime {
+            year: date.year(),
+            month: date.month(),
+            day: date.day(),
+            ..Default::default()
+        };
+        self.set_text(n, format_date(&date, pattern))
+    }
+
+    /// Reads the value of the text field at index `n` as a date, written in the date format of
+    /// the field (see `get_date_format`) or as `yyyy-mm-dd`. `None` if the field is empty, isn't
+    /// a text field or doesn't hold a valid date.
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_date(&self, n: impl FieldKey) -> Option<NaiveDate> {
+        let n = self.try_resolve(n).ok()?;
+        let pattern = self
+            .get_date_format(n)
+            .unwrap_or_else(|| ISO_DATE.to_owned());
+        self.get_date_with(n, &pattern)
+    }
+
+    /// Reads the value of the text field at index `n` as a date written with an Acrobat date
+    /// pattern, or as `yyyy-mm-dd`
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_date_with(&self, n: impl FieldKey, pattern: &str) -> Option<NaiveDate> {
+        let n = self.try_resolve(n).ok()?;
+        if self.try_get_type(n).ok()? != FieldType::Text {
+            return None;
+        }
+        let text = self.text_appearance_value(n).ok()?;
+        let date = parse_date(&text, pattern)?;
+        NaiveDate::from_ymd_opt(date.year, date.month, date.day)
+    }
+}
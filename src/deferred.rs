@@ -0,0 +1,89 @@
+use crate::{FieldType, Form, TextAppearanceOptions};
+
+impl Form {
+    /// Chooses whether the setters regenerate the appearance of a field right away or only
+    /// remember that it is out of date. In deferred mode the appearances are produced once, when
+    /// the form is saved or `regenerate_all_appearances` is called, however many times a field
+    /// was set. Leaving deferred mode regenerates the pending appearances.
+    pub fn set_deferred_appearances(&mut self, enabled: bool) {
+        self.deferred_appearances = enabled;
+        if !enabled {
+            self.regenerate_pending_appearances();
+        }
+    }
+
+    /// Returns true if the setters defer the regeneration of appearances
+    pub fn deferred_appearances(&self) -> bool {
+        self.deferred_appearances
+    }
+
+    /// Regenerates the appearance streams of every text field and choice field from their
+    /// values, including the ones left out of date in deferred mode. Text fields set with
+    /// `set_text_with` keep the options they were set with.
+    ///
+    /// Fields whose appearance cannot be generated, e.g. without a widget, are reported and left
+    /// untouched.
+    pub fn regenerate_all_appearances(&mut self) {
+        for n in 0..self.len() {
+            self.regenerate_appearance(n);
+        }
+        self.pending_appearances.clear();
+    }
+
+    /// Regenerates the appearances left out of date in deferred mode
+    pub(crate) fn regenerate_pending_appearances(&mut self) {
+        if self.pending_appearances.is_empty() {
+            return;
+        }
+        for n in 0..self.len() {
+            if self.pending_appearances.contains_key(&self.form_ids[n]) {
+                self.regenerate_appearance(n);
+            }
+        }
+        self.pending_appearances.clear();
+    }
+
+    /// Regenerates the appearance of the text field at index `n` from its value or, in deferred
+    /// mode, remembers to do it later with the same options
+    pub(crate) fn update_text_appearance(
+        &mut self,
+        n: usize,
+        options: &TextAppearanceOptions,
+    ) -> Result<(), lopdf::Error> {
+        if self.deferred_appearances {
+            self.pending_appearances
+                .insert(self.form_ids[n], options.clone());
+            Ok(())
+        } else {
+            self.regenerate_text_appearance(n, options)
+        }
+    }
+
+    /// Regenerates the appearance of the choice field at index `n` from its selection or, in
+    /// deferred mode, remembers to do it later
+    pub(crate) fn update_choice_appearance(&mut self, n: usize) -> Result<(), lopdf::Error> {
+        if self.deferred_appearances {
+            self.pending_appearances
+                .insert(self.form_ids[n], TextAppearanceOptions::default());
+            Ok(())
+        } else {
+            self.regenerate_choice_appearance(n)
+        }
+    }
+
+    /// Gets the options a text field was last set with, if its appearance is pending
+    pub(crate) fn pending_text_options(&self, n: usize) -> Option<&TextAppearanceOptions> {
+        match self.try_get_type(n).ok()? {
+            FieldType::Text => self.pending_appearances.get(&self.form_ids[n]),
+            _ => None,
+        }
+    }
+
+    fn regenerate_appearance(&mut self, n: usize) {
+        if let Some(content) = self.appearance_content(n) {
+            if let Err(e) = self.draw_appearance(n, &content) {
+                warn!("Appearance regeneration failed: {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{FieldType, FieldValue, Form};
+
+/// A difference between the fields of two forms, as reported by `Form::diff`. Fields are named
+/// by their fully qualified name.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FieldDiff {
+    /// A field only found in the other form
+    Added { name: String },
+    /// A field only found in this form
+    Removed { name: String },
+    /// A field that has another name in the other form, found by its type and the position of
+    /// its widget
+    Renamed { from: String, to: String },
+    /// A field whose type differs in the other form
+    TypeChanged {
+        name: String,
+        from: FieldType,
+        to: FieldType,
+    },
+    /// A field whose value differs in the other form. `name` is its name in the other form.
+    ValueChanged {
+        name: String,
+        from: Option<FieldValue>,
+        to: Option<FieldValue>,
+    },
+}
+
+impl Form {
+    /// Compares the fields of this form with those of `other`, e.g. a later revision of the same
+    /// template or a filled copy of it. Fields are matched by fully qualified name. A field
+    /// missing from one of the forms is matched to a field missing from the other one with the
+    /// same type, on the same page and at the same position, which is reported as a rename.
+    ///
+    /// The differences are listed in the order of the fields of this form, followed by the fields
+    /// added in `other`. Fields without a name are ignored.
+    pub fn diff(&self, other: &Form) -> Vec<FieldDiff> {
+        let names = named_fields(self);
+        let other_names = named_fields(other);
+        let other_indices = other_names
+            .iter()
+            .map(|(name, n)| (name.as_str(), *n))
+            .collect::<HashMap<_, _>>();
+        let own_names = names
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<HashSet<_>>();
+
+        // Fields only found in the other form, which may be renamed fields of this one
+        let mut added = other_names
+            .iter()
+            .filter(|(name, _)| !own_names.contains(name.as_str()))
+            .map(|(name, m)| (name.as_str(), *m))
+            .collect::<Vec<_>>();
+
+        let mut diffs = Vec::new();
+        for (name, n) in &names {
+            let m = match other_indices.get(name.as_str()) {
+                Some(&m) => m,
+                None => {
+                    let key = self.widget_key(*n);
+                    let renamed = added
+                        .iter()
+                        .position(|&(_, m)| key.is_some() && other.widget_key(m) == key);
+                    match renamed {
+                        Some(i) => {
+                            let (to, m) = added.remove(i);
+                            diffs.push(FieldDiff::Renamed {
+                                from: name.clone(),
+                                to: to.to_owned(),
+                            });
+                            self.push_changes(*n, other, m, to, &mut diffs);
+                        }
+                        None => diffs.push(FieldDiff::Removed { name: name.clone() }),
+                    }
+                    continue;
+                }
+            };
+            self.push_changes(*n, other, m, name, &mut diffs);
+        }
+
+        diffs.extend(added.into_iter().map(|(name, _)| FieldDiff::Added {
+            name: name.to_owned(),
+        }));
+        diffs
+    }
+
+    /// Records the type or value change between the field at index `n` and the field at index
+    /// `m` of `other`, named `name`
+    fn push_changes(
+        &self,
+        n: usize,
+        other: &Form,
+        m: usize,
+        name: &str,
+        diffs: &mut Vec<FieldDiff>,
+    ) {
+        let (from, to) = (
+            self.try_get_type(n).unwrap_or(FieldType::Unknown),
+            other.try_get_type(m).unwrap_or(FieldType::Unknown),
+        );
+        if from != to {
+            diffs.push(FieldDiff::TypeChanged {
+                name: name.to_owned(),
+                from,
+                to,
+            });
+            return;
+        }
+
+        let (from, to) = (self.get_value(n), other.get_value(m));
+        if from != to {
+            diffs.push(FieldDiff::ValueChanged {
+                name: name.to_owned(),
+                from,
+                to,
+            });
+        }
+    }
+
+    /// Identifies a field by its type, its page and the rectangle of its widget, rounded to the
+    /// point
+    fn widget_key(&self, n: usize) -> Option<(FieldType, usize, [i64; 4])> {
+        let rect = self.get_rect(n)?;
+        Some((
+            self.try_get_type(n).ok()?,
+            self.get_page(n)?,
+            [
+                rect[0].round() as i64,
+                rect[1].round() as i64,
+                rect[2].round() as i64,
+                rect[3].round() as i64,
+            ],
+        ))
+    }
+}
+
+/// Lists the fields of a form that have a name, with their index, keeping the first field of
+/// every name
+fn named_fields(form: &Form) -> Vec<(String, usize)> {
+    let mut seen = HashSet::new();
+    (0..form.len())
+        .filter_map(|n| form.get_qualified_name(n).map(|name| (name, n)))
+        .filter(|(name, _)| seen.insert(name.clone()))
+        .collect()
+}
@@ -0,0 +1,211 @@
+use lopdf::{Dictionary, Object, Stream};
+
+use crate::utils::DictionaryExt;
+use crate::{Form, ValueError};
+
+impl Form {
+    /// Embeds validation data in the Document Security Store (`/DSS`) of the catalog, so the
+    /// signatures of the document can still be verified once the certificates have expired or
+    /// the revocation services are gone (long-term validation). `certs` are DER encoded
+    /// certificates, `crls` DER encoded revocation lists and `ocsp` DER encoded OCSP responses,
+    /// usually those of the chains of the signatures.
+    ///
+    /// The data is added to the `/Certs`, `/CRLs` and `/OCSPs` of the store, skipping what it
+    /// already holds, and every signature of the document gets a `/VRI` entry, keyed by the
+    /// SHA-1 digest of its `/Contents`, listing the data. Save the document incrementally, e.g.
+    /// with the `IncrementalOnly` save policy, to keep the signatures valid.
+    ///
+    /// Fails with `ValueError::NotFound` if the document has no catalog.
+    pub fn add_validation_data(
+        &mut self,
+        certs: &[Vec<u8>],
+        crls: &[Vec<u8>],
+        ocsp: &[Vec<u8>],
+    ) -> Result<(), ValueError> {
+        let mut dss = self
+            .catalog()
+            .and_then(|catalog| catalog.get(b"DSS"))
+            .and_then(|dss| self.dereference(dss))
+            .and_then(|(_, dss)| dss.as_dict())
+            .cloned()
+            .unwrap_or_default();
+
+        let entries = [
+            (&b"Certs"[..], &b"Cert"[..], certs),
+            (b"CRLs", b"CRL", crls),
+            (b"OCSPs", b"OCSP", ocsp),
+        ];
+        let mut vri = Dictionary::new();
+        for (key, vri_key, data) in entries {
+            let streams = self.add_validation_streams(&mut dss, key, data);
+            if !streams.is_empty() {
+                vri.set(vri_key.to_vec(), streams);
+            }
+        }
+
+        if !vri.is_empty() {
+            let mut vris = match dss.get(b"VRI").and_then(|vri| self.dereference(vri)) {
+                Ok((_, Object::Dictionary(vris))) => vris.clone(),
+                _ => Dictionary::new(),
+            };
+            for contents in self.signature_contents() {
+                let key = sha1(&contents)
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<String>();
+                // Data added for the signature before is kept
+                let mut entry = match vris.get(key.as_bytes()) {
+                    Ok(Object::Dictionary(entry)) => entry.clone(),
+                    _ => Dictionary::new(),
+                };
+                for (vri_key, streams) in vri.iter() {
+                    let mut merged = entry
+                        .get(vri_key)
+                        .and_then(Object::as_array)
+                        .cloned()
+                        .unwrap_or_default();
+                    for stream in streams.as_array().into_iter().flatten() {
+                        if !merged.iter().any(|existing| {
+                            existing.as_reference().ok() == stream.as_reference().ok()
+                        }) {
+                            merged.push(stream.clone());
+                        }
+                    }
+                    entry.set(vri_key.clone(), merged);
+                }
+                vris.set(key.into_bytes(), entry);
+            }
+            dss.set_in_place("VRI", vris);
+        }
+
+        let root = self
+            .document
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|_| ValueError::NotFound)?;
+        match self.dictionary_mut(root)?.get(b"DSS") {
+            Ok(Object::Reference(id)) => {
+                let id = *id;
+                self.insert_object(id, Object::Dictionary(dss));
+            }
+            _ => {
+                let dss = self.document.add_object(dss);
+                self.dictionary_mut(root)?.set_in_place("DSS", dss);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the validation data missing from the array `key` of the store, and returns the
+    /// streams of all the given data
+    fn add_validation_streams(
+        &mut self,
+        dss: &mut Dictionary,
+        key: &[u8],
+        data: &[Vec<u8>],
+    ) -> Vec<Object> {
+        let mut array = match dss.get(key).and_then(|array| self.dereference(array)) {
+            Ok((_, Object::Array(array))) => array.clone(),
+            _ => Vec::new(),
+        };
+
+        let mut streams = Vec::new();
+        for bytes in data {
+            let existing = array.iter().find(|stream| {
+                self.dereference(stream)
+                    .and_then(|(_, stream)| stream.as_stream())
+                    .is_ok_and(|stream| {
+                        stream
+                            .decompressed_content()
+                            .unwrap_or_else(|_| stream.content.clone())
+                            == *bytes
+                    })
+            });
+            let stream = match existing {
+                Some(stream) => stream.clone(),
+                None => {
+                    let mut stream = Stream::new(Dictionary::new(), bytes.clone());
+                    let _ = stream.compress();
+                    let stream = Object::Reference(self.document.add_object(stream));
+                    array.push(stream.clone());
+                    stream
+                }
+            };
+            streams.push(stream);
+        }
+
+        if !array.is_empty() {
+            dss.set_in_place(key.to_vec(), array);
+        }
+        streams
+    }
+
+    /// Gets the `/Contents` of every signature of the document
+    fn signature_contents(&self) -> Vec<Vec<u8>> {
+        self.signature_byte_ranges()
+            .into_iter()
+            .filter_map(|(oid, _)| {
+                let field = self.get_dictionary(oid).ok()?;
+                let value = self.signature_value(field)?;
+                match value.get(b"Contents") {
+                    Ok(Object::String(contents, _)) => Some(contents.clone()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Computes the SHA-1 digest of `data`, which names the `/VRI` entries
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (i, h) in h.iter().enumerate() {
+        digest[4 * i..4 * i + 4].copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
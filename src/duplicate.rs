@@ -0,0 +1,354 @@
+use std::collections::{HashMap, HashSet};
+
+use lopdf::{Dictionary, Object, ObjectId};
+
+use crate::encoding::encode_text_string;
+use crate::field_id::MAX_FIELD_DEPTH;
+use crate::geometry::MAX_PAGE_TREE_DEPTH;
+use crate::hierarchy::push_kid;
+use crate::utils::DictionaryExt;
+use crate::{Form, ValueError};
+
+/// A field with widgets on a duplicated page
+struct PageField {
+    oid: ObjectId,
+    /// The widgets of the field placed on the page
+    widgets: Vec<ObjectId>,
+    /// The fully qualified name of each copy of the field
+    names: Vec<String>,
+}
+
+impl Form {
+    /// Inserts `count` copies of the page at index `page` (starting at 0) right after it, with a
+    /// copy of every field placed on the page, e.g. to fill the rows of an invoice or a roster
+    /// from a single-row template. `rename` gets the fully qualified name of a field and the
+    /// number of the copy, counting from 1, and returns the fully qualified name of the copy of
+    /// the field, e.g. `item.1.qty` for `qty`. The parent fields of a new name are created when
+    /// they don't exist yet.
+    ///
+    /// The copies keep the value, flags and appearances of their field, with the entries they
+    /// inherited from their ancestors copied onto them. Only the widgets placed on the page are
+    /// copied. The content and resources of the page are shared by the copies, its other
+    /// annotations are copied.
+    ///
+    /// Fails with `ValueError::NotFound` if the page doesn't exist, `ValueError::InvalidName`
+    /// if a new name has an empty partial name and `ValueError::NameTaken` if it is the name
+    /// of an existing field or of another copy. Nothing is changed on failure.
+    pub fn duplicate_page_with_fields<F>(
+        &mut self,
+        page: usize,
+        count: usize,
+        rename: F,
+    ) -> Result<(), ValueError>
+    where
+        F: Fn(&str, usize) -> String,
+    {
+        let page_id = self.page_id(page).ok_or(ValueError::NotFound)?;
+        let fields = self.page_fields_to_copy(page, page_id, count, &rename)?;
+
+        let mut previous = page_id;
+        for copy in 0..count {
+            let mut page = self
+                .get_dictionary(page_id)
+                .map_err(|_| ValueError::NotFound)?
+                .clone();
+            page.remove(b"Annots");
+            let new_page = self.document.add_object(page);
+
+            let mut copies = HashMap::new();
+            for field in &fields {
+                self.copy_field(field, &field.names[copy], new_page, &mut copies)?;
+            }
+            let annots = self.copy_annotations(page_id, new_page, &copies);
+            self.dictionary_mut(new_page)?.set("Annots", annots);
+
+            self.insert_page_after(previous, new_page)?;
+            previous = new_page;
+        }
+
+        self.reindex();
+        Ok(())
+    }
+
+    /// Lists the fields placed on a page with the names of their copies, checking that the
+    /// names are valid and free
+    fn page_fields_to_copy<F>(
+        &self,
+        page: usize,
+        page_id: ObjectId,
+        count: usize,
+        rename: &F,
+    ) -> Result<Vec<PageField>, ValueError>
+    where
+        F: Fn(&str, usize) -> String,
+    {
+        let existing = (0..self.len())
+            .filter_map(|n| self.get_qualified_name(n))
+            .collect::<HashSet<_>>();
+        let mut taken = HashSet::new();
+
+        let mut fields = Vec::new();
+        for n in self.fields_on_page(page) {
+            let oid = self.form_ids[n];
+            let name = self.get_qualified_name(n).unwrap_or_default();
+            let widgets = self
+                .widgets_of(oid)
+                .into_iter()
+                .filter(|&widget| self.widget_page(widget) == Some(page_id))
+                .collect();
+
+            let mut names = Vec::new();
+            for copy in 1..=count {
+                let new_name = rename(&name, copy);
+                if new_name.split('.').any(str::is_empty) {
+                    return Err(ValueError::InvalidName);
+                }
+                // Neither the field nor one of its new parents may be an existing field
+                let clashes = new_name
+                    .match_indices('.')
+                    .map(|(i, _)| &new_name[..i])
+                    .chain(std::iter::once(new_name.as_str()))
+                    .any(|prefix| existing.contains(prefix));
+                let parent_of_existing = existing.iter().any(|name| {
+                    name.strip_prefix(new_name.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+                });
+                if clashes || parent_of_existing || !taken.insert(new_name.clone()) {
+                    return Err(ValueError::NameTaken);
+                }
+                names.push(new_name);
+            }
+
+            fields.push(PageField {
+                oid,
+                widgets,
+                names,
+            });
+        }
+
+        Ok(fields)
+    }
+
+    /// Copies a field and its widgets placed on a page as a field named `name` with its widgets
+    /// on `new_page`, recording the copy of each widget in `copies`
+    fn copy_field(
+        &mut self,
+        field: &PageField,
+        name: &str,
+        new_page: ObjectId,
+        copies: &mut HashMap<ObjectId, ObjectId>,
+    ) -> Result<(), ValueError> {
+        let mut parts = name.split('.').collect::<Vec<_>>();
+        let partial_name = parts.pop().unwrap_or_default();
+        let parent = self.parent_field_for(&parts)?;
+
+        let mut dict = self
+            .get_dictionary(field.oid)
+            .map_err(|_| ValueError::NotFound)?
+            .clone();
+        for (key, value) in self.inherited_entries(field.oid) {
+            if !dict.has(key) {
+                dict.set(key.to_vec(), value);
+            }
+        }
+        dict.set_in_place(
+            "T",
+            Object::string_literal(encode_text_string(partial_name)),
+        );
+        match parent {
+            Some(parent) => dict.set_in_place("Parent", parent),
+            None => {
+                dict.remove(b"Parent");
+            }
+        }
+
+        let merged = field.widgets.contains(&field.oid);
+        if merged {
+            self.copy_widget_entries(&mut dict, new_page);
+        } else {
+            dict.remove(b"Kids");
+        }
+        let new_field = self.document.add_object(dict);
+        if merged {
+            copies.insert(field.oid, new_field);
+        } else {
+            for &widget in &field.widgets {
+                let mut dict = self
+                    .get_dictionary(widget)
+                    .map_err(|_| ValueError::NotFound)?
+                    .clone();
+                dict.set_in_place("Parent", new_field);
+                self.copy_widget_entries(&mut dict, new_page);
+                let new_widget = self.document.add_object(dict);
+                push_kid(self.dictionary_mut(new_field)?, new_widget);
+                copies.insert(widget, new_widget);
+            }
+        }
+
+        match parent {
+            Some(parent) => push_kid(self.dictionary_mut(parent)?, new_field),
+            None => self.push_top_level_field(new_field),
+        }
+        Ok(())
+    }
+
+    /// Points a copied widget to its new page and gives it its own appearance streams, so
+    /// regenerating the appearance of one copy doesn't change the others
+    fn copy_widget_entries(&mut self, widget: &mut Dictionary, new_page: ObjectId) {
+        widget.set_in_place("P", new_page);
+        if let Ok(appearances) = widget.get(b"AP") {
+            let appearances = self.copy_streams(&appearances.clone(), 0);
+            widget.set_in_place("AP", appearances);
+        }
+    }
+
+    /// Copies an appearance dictionary, with a new object for every stream it refers to
+    fn copy_streams(&mut self, object: &Object, depth: usize) -> Object {
+        match object {
+            Object::Reference(id) if depth <= 2 => match self.get_object(*id) {
+                Ok(Object::Stream(stream)) => {
+                    let stream = stream.clone();
+                    Object::Reference(self.document.add_object(stream))
+                }
+                Ok(Object::Dictionary(dict)) => {
+                    let dict = Object::Dictionary(dict.clone());
+                    self.copy_streams(&dict, depth)
+                }
+                _ => object.clone(),
+            },
+            Object::Dictionary(dict) if depth <= 2 => {
+                let mut copy = Dictionary::new();
+                for (key, value) in dict.iter() {
+                    copy.set(key.clone(), self.copy_streams(value, depth + 1));
+                }
+                Object::Dictionary(copy)
+            }
+            _ => object.clone(),
+        }
+    }
+
+    /// Builds the `/Annots` of a copy of a page: the copies of the widgets, and copies of the
+    /// other annotations placed on the new page
+    fn copy_annotations(
+        &mut self,
+        page: ObjectId,
+        new_page: ObjectId,
+        copies: &HashMap<ObjectId, ObjectId>,
+    ) -> Vec<Object> {
+        let annots = match self
+            .get_dictionary(page)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|annots| self.dereference(annots))
+        {
+            Ok((_, Object::Array(annots))) => annots.clone(),
+            _ => Vec::new(),
+        };
+
+        annots
+            .into_iter()
+            .filter_map(|annot| match annot {
+                Object::Reference(id) => {
+                    if let Some(&copy) = copies.get(&id) {
+                        return Some(copy.into());
+                    }
+                    // Widgets of fields that were not copied are left out
+                    let mut dict = self.get_dictionary(id).ok()?.clone();
+                    if dict.get(b"Subtype").and_then(Object::as_name_str).ok() == Some("Widget") {
+                        return None;
+                    }
+                    dict.set_in_place("P", new_page);
+                    Some(self.document.add_object(dict).into())
+                }
+                annot => Some(annot),
+            })
+            .collect()
+    }
+
+    /// Gets the field named by the partial names `names`, creating the missing fields, or
+    /// `None` for the top level of the form
+    fn parent_field_for(&mut self, names: &[&str]) -> Result<Option<ObjectId>, ValueError> {
+        if names.len() > MAX_FIELD_DEPTH {
+            return Err(ValueError::InvalidName);
+        }
+
+        let mut parent = None;
+        for &name in names {
+            let siblings = match parent {
+                Some(parent) => self.kids_of(parent),
+                None => self.top_level_fields(),
+            };
+            let existing = siblings
+                .into_iter()
+                .find(|&sibling| self.get_partial_name_of(sibling).as_deref() == Some(name));
+
+            parent = Some(match existing {
+                Some(field) => field,
+                None => {
+                    let mut field = Dictionary::new();
+                    field.set("T", Object::string_literal(encode_text_string(name)));
+                    field.set("Kids", Object::Array(Vec::new()));
+                    if let Some(parent) = parent {
+                        field.set("Parent", parent);
+                    }
+                    let field = self.document.add_object(field);
+                    match parent {
+                        Some(parent) => push_kid(self.dictionary_mut(parent)?, field),
+                        None => self.push_top_level_field(field),
+                    }
+                    field
+                }
+            });
+        }
+
+        Ok(parent)
+    }
+
+    /// Appends a field to the AcroForm `/Fields`, creating the array if needed
+    fn push_top_level_field(&mut self, oid: ObjectId) {
+        if self.fields_array_mut().is_none() {
+            if let Some(acroform) = self.acroform_mut() {
+                acroform.set("Fields", Vec::<Object>::new());
+            }
+        }
+        if let Some(fields) = self.fields_array_mut() {
+            fields.push(oid.into());
+        }
+    }
+
+    /// Inserts a page in the page tree right after `page`, under the same parent
+    fn insert_page_after(&mut self, page: ObjectId, new_page: ObjectId) -> Result<(), ValueError> {
+        let parent = self
+            .get_dictionary(page)
+            .and_then(|page| page.get(b"Parent"))
+            .and_then(Object::as_reference)
+            .map_err(|_| ValueError::NotFound)?;
+        self.dictionary_mut(new_page)?
+            .set_in_place("Parent", parent);
+
+        let kids = match self.dictionary_mut(parent)?.get_mut(b"Kids") {
+            Ok(Object::Array(kids)) => kids,
+            _ => return Err(ValueError::NotFound),
+        };
+        let position = kids
+            .iter()
+            .position(|kid| matches!(kid, Object::Reference(id) if *id == page))
+            .ok_or(ValueError::NotFound)?;
+        kids.insert(position + 1, new_page.into());
+
+        // Every node up to the root counts one more page
+        let mut current = Some(parent);
+        let mut depth = 0;
+        while let Some(id) = current {
+            let node = self.dictionary_mut(id)?;
+            let count = node.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+            node.set_in_place("Count", count + 1);
+            current = node.get(b"Parent").and_then(Object::as_reference).ok();
+            depth += 1;
+            if depth > MAX_PAGE_TREE_DEPTH {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
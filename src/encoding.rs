@@ -0,0 +1,127 @@
+/// Characters of PDFDocEncoding from 0x18 to 0x1F, where it departs from Latin-1
+const PDF_DOC_LOW: [char; 8] = ['˘', 'ˇ', 'ˆ', '˙', '˝', '˛', '˚', '˜'];
+
+/// Characters of PDFDocEncoding from 0x80 to 0xA0, where it departs from Latin-1. The undefined
+/// code 0x9F is mapped to the replacement character.
+#[rustfmt::skip]
+const PDF_DOC_HIGH: [char; 33] = [
+    '•', '†', '‡', '…', '—', '–', 'ƒ', '⁄', '‹', '›', '−', '‰', '„', '“', '”', '‘',
+    '’', '‚', '™', 'ﬁ', 'ﬂ', 'Ł', 'Œ', 'Š', 'Ÿ', 'Ž', 'ı', 'ł', 'œ', 'š', 'ž', '\u{FFFD}',
+    '€',
+];
+
+/// Characters of WinAnsiEncoding between 0x80 and 0x9F, where it departs from Latin-1
+const WIN_ANSI_HIGH: [(char, u8); 27] = [
+    ('€', 0x80),
+    ('‚', 0x82),
+    ('ƒ', 0x83),
+    ('„', 0x84),
+    ('…', 0x85),
+    ('†', 0x86),
+    ('‡', 0x87),
+    ('ˆ', 0x88),
+    ('‰', 0x89),
+    ('Š', 0x8A),
+    ('‹', 0x8B),
+    ('Œ', 0x8C),
+    ('Ž', 0x8E),
+    ('‘', 0x91),
+    ('’', 0x92),
+    ('“', 0x93),
+    ('”', 0x94),
+    ('•', 0x95),
+    ('–', 0x96),
+    ('—', 0x97),
+    ('˜', 0x98),
+    ('™', 0x99),
+    ('š', 0x9A),
+    ('›', 0x9B),
+    ('œ', 0x9C),
+    ('ž', 0x9E),
+    ('Ÿ', 0x9F),
+];
+
+/// Decodes a PDF text string. UTF-16BE (PDF 1.x) and UTF-8 (PDF 2.0) strings are recognized by
+/// their byte order mark, anything else is PDFDocEncoding. Strings that are valid UTF-8 with
+/// multi-byte characters but no byte order mark, as written by older versions of this crate, are
+/// read as UTF-8: such sequences are very unlikely in PDFDocEncoding.
+pub fn decode_text_string(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        let units = bytes[2..]
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+            .collect::<Vec<_>>();
+        String::from_utf16_lossy(&units)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        String::from_utf8_lossy(rest).into_owned()
+    } else if !bytes.is_ascii() && std::str::from_utf8(bytes).is_ok() {
+        String::from_utf8_lossy(bytes).into_owned()
+    } else {
+        decode_pdf_doc(bytes)
+    }
+}
+
+/// Encodes a string as a PDF text string: in PDFDocEncoding when it has every character and the
+/// result can't be mistaken for UTF-8, as UTF-16BE otherwise
+pub fn encode_text_string(text: &str) -> Vec<u8> {
+    match encode_pdf_doc(text) {
+        Some(bytes) if bytes.is_ascii() || std::str::from_utf8(&bytes).is_err() => bytes,
+        _ => encode_utf16be(text),
+    }
+}
+
+/// Decodes bytes in PDFDocEncoding. Undefined codes become the replacement character.
+pub fn decode_pdf_doc(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            0x18..=0x1F => PDF_DOC_LOW[usize::from(byte - 0x18)],
+            0x7F => '\u{FFFD}',
+            0x80..=0xA0 => PDF_DOC_HIGH[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        })
+        .collect()
+}
+
+/// Encodes a string in PDFDocEncoding, or returns `None` if it has a character the encoding
+/// lacks
+pub fn encode_pdf_doc(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|c| match c {
+            '\u{0}'..='\u{17}' | ' '..='~' | '¡'..='ÿ' => Some(c as u8),
+            _ => PDF_DOC_LOW
+                .iter()
+                .position(|&low| low == c)
+                .map(|i| 0x18 + i as u8)
+                .or_else(|| {
+                    PDF_DOC_HIGH
+                        .iter()
+                        .position(|&high| high == c && c != '\u{FFFD}')
+                        .map(|i| 0x80 + i as u8)
+                }),
+        })
+        .collect()
+}
+
+/// Encodes a string as UTF-16BE with a byte order mark
+pub fn encode_utf16be(text: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFE, 0xFF];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    bytes
+}
+
+/// Encodes text in WinAnsiEncoding, the encoding of the standard fonts in appearance streams.
+/// Characters the encoding lacks are replaced by a question mark.
+pub fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{A0}'..='ÿ' => c as u8,
+            _ => WIN_ANSI_HIGH
+                .iter()
+                .find(|(high, _)| *high == c)
+                .map_or(b'?', |&(_, byte)| byte),
+        })
+        .collect()
+}
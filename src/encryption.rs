@@ -0,0 +1,324 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use aes::Aes128;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockEncryptMut, KeyIvInit};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, StringFormat};
+
+use crate::Form;
+
+/// Padding string of the standard security handler (ISO 32000-1, 7.6.3.3)
+const PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Length of the file encryption key in bytes (128 bits)
+const KEY_LENGTH: usize = 16;
+
+bitflags! {
+    /// The operations a user who opens the document with the user password is allowed to do
+    pub struct Permissions: u32 {
+        const PRINT                 = 0x4;
+        const MODIFY                = 0x8;
+        const COPY                  = 0x10;
+        const ANNOTATE              = 0x20;
+        const FILL_FORMS            = 0x100;
+        const EXTRACT_ACCESSIBILITY = 0x200;
+        const ASSEMBLE              = 0x400;
+        const PRINT_HIGH_QUALITY    = 0x800;
+    }
+}
+
+impl Permissions {
+    /// Returns true if the form fields may be filled in, which annotating also allows
+    pub fn can_fill_forms(self) -> bool {
+        self.intersects(Permissions::FILL_FORMS | Permissions::ANNOTATE)
+    }
+
+    /// Returns true if the contents of the document may be modified
+    pub fn can_modify(self) -> bool {
+        self.contains(Permissions::MODIFY)
+    }
+
+    /// Returns true if the document may be printed
+    pub fn can_print(self) -> bool {
+        self.contains(Permissions::PRINT)
+    }
+}
+
+/// Passwords and permissions used by `Form::save_encrypted`
+///
+/// The document is encrypted with 128-bit AES by the standard security handler (revision 4).
+#[derive(Debug, Clone)]
+pub struct EncryptionOptions {
+    user_password: String,
+    owner_password: String,
+    permissions: Permissions,
+}
+
+impl EncryptionOptions {
+    /// Creates options with the given passwords, granting every permission. An empty user
+    /// password lets anyone open the document while the permissions still apply.
+    pub fn new(user_password: &str, owner_password: &str) -> Self {
+        EncryptionOptions {
+            user_password: user_password.to_owned(),
+            owner_password: owner_password.to_owned(),
+            permissions: Permissions::all(),
+        }
+    }
+
+    /// Sets the operations allowed to users who don't know the owner password
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+}
+
+impl Form {
+    /// Saves the form encrypted to the specified path
+    pub fn save_encrypted<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &EncryptionOptions,
+    ) -> Result<(), io::Error> {
+        let mut file = File::create(path)?;
+        self.save_encrypted_to(&mut file, options)
+    }
+
+    /// Saves the form encrypted to the specified target. The form itself is left unencrypted.
+    pub fn save_encrypted_to<W: Write>(
+        &mut self,
+        target: &mut W,
+        options: &EncryptionOptions,
+    ) -> Result<(), io::Error> {
+        self.prepare_full_save();
+
+        let mut document = self.full_document().into_owned();
+        let file_id = file_id(&mut document)?;
+
+        // Bits 7-8 and 13-32 are reserved and must be set
+        let permissions = (options.permissions.bits() | 0xFFFF_F0C0) as i32;
+        let owner_key = owner_key(&options.owner_password, &options.user_password);
+        let key = file_key(&options.user_password, &owner_key, permissions, &file_id);
+        let user_key = user_key(&key, &file_id);
+
+        for (&id, object) in document.objects.iter_mut() {
+            encrypt_object(&key, id, object)?;
+        }
+
+        let encrypt_id = document.add_object(dictionary! {
+            "Filter" => "Standard",
+            "V" => 4,
+            "R" => 4,
+            "Length" => (KEY_LENGTH * 8) as i64,
+            "CF" => dictionary! {
+                "StdCF" => dictionary! {
+                    "CFM" => "AESV2",
+                    "AuthEvent" => "DocOpen",
+                    "Length" => KEY_LENGTH as i64,
+                },
+            },
+            "StmF" => "StdCF",
+            "StrF" => "StdCF",
+            "O" => Object::String(owner_key.to_vec(), StringFormat::Hexadecimal),
+            "U" => Object::String(user_key.to_vec(), StringFormat::Hexadecimal),
+            "P" => i64::from(permissions),
+        });
+        document.trailer.set("Encrypt", encrypt_id);
+
+        document.save_to(target)
+    }
+
+    /// Gets the operations the document allows to users who don't know the owner password, the
+    /// `/P` of its `/Encrypt` dictionary, to refuse a form that readers won't let be filled
+    /// before producing it. A document that is not encrypted allows everything.
+    pub fn permissions(&self) -> Permissions {
+        let p = self
+            .document
+            .trailer
+            .get(b"Encrypt")
+            .and_then(|encrypt| self.dereference(encrypt))
+            .and_then(|(_, encrypt)| encrypt.as_dict())
+            .and_then(|encrypt| encrypt.get(b"P"))
+            .and_then(Object::as_i64);
+        match p {
+            // `/P` is a signed 32-bit integer
+            Ok(p) => Permissions::from_bits_truncate(p as u32),
+            Err(_) => Permissions::all(),
+        }
+    }
+}
+
+/// Gets the first element of the `/ID` of the document, creating the `/ID` if it is missing
+fn file_id(document: &mut Document) -> Result<Vec<u8>, io::Error> {
+    if let Ok(Object::Array(id)) = document.trailer.get(b"ID") {
+        if let Some(Object::String(first, _)) = id.first() {
+            return Ok(first.clone());
+        }
+    }
+
+    let id = random_bytes::<16>()?.to_vec();
+    document.trailer.set(
+        "ID",
+        vec![
+            Object::String(id.clone(), StringFormat::Hexadecimal),
+            Object::String(id.clone(), StringFormat::Hexadecimal),
+        ],
+    );
+    Ok(id)
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], io::Error> {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Pads or truncates a password to 32 bytes
+fn pad_password(password: &str) -> [u8; 32] {
+    let mut padded = [0; 32];
+    let password = password.as_bytes();
+    let len = password.len().min(32);
+    padded[..len].copy_from_slice(&password[..len]);
+    padded[len..].copy_from_slice(&PADDING[..32 - len]);
+    padded
+}
+
+/// Computes the `/O` entry (algorithm 3)
+fn owner_key(owner_password: &str, user_password: &str) -> [u8; 32] {
+    // Without an owner password, the user password gives full access
+    let owner_password = if owner_password.is_empty() {
+        user_password
+    } else {
+        owner_password
+    };
+
+    let mut digest = md5::compute(pad_password(owner_password)).0;
+    for _ in 0..50 {
+        digest = md5::compute(digest).0;
+    }
+
+    let mut owner_key = pad_password(user_password);
+    rc4_rounds(&digest, &mut owner_key);
+    owner_key
+}
+
+/// Computes the file encryption key (algorithm 2)
+fn file_key(user_password: &str, owner_key: &[u8], permissions: i32, file_id: &[u8]) -> [u8; 16] {
+    let mut context = md5::Context::new();
+    context.consume(pad_password(user_password));
+    context.consume(owner_key);
+    context.consume(permissions.to_le_bytes());
+    context.consume(file_id);
+
+    let mut key = context.compute().0;
+    for _ in 0..50 {
+        key = md5::compute(key).0;
+    }
+    key
+}
+
+/// Computes the `/U` entry (algorithm 5)
+fn user_key(key: &[u8; 16], file_id: &[u8]) -> [u8; 32] {
+    let mut context = md5::Context::new();
+    context.consume(PADDING);
+    context.consume(file_id);
+
+    let mut user_key = [0; 32];
+    user_key[..16].copy_from_slice(&context.compute().0);
+    rc4_rounds(key, &mut user_key[..16]);
+    // The remaining 16 bytes are arbitrary padding
+    user_key[16..].copy_from_slice(&PADDING[..16]);
+    user_key
+}
+
+/// Encrypts `data` 20 times with RC4, XOR-ing every byte of the key with the round number
+fn rc4_rounds(key: &[u8], data: &mut [u8]) {
+    for round in 0..20 {
+        let round_key = key.iter().map(|byte| byte ^ round).collect::<Vec<_>>();
+        rc4(&round_key, data);
+    }
+}
+
+fn rc4(key: &[u8], data: &mut [u8]) {
+    let mut state = [0u8; 256];
+    for (i, value) in state.iter_mut().enumerate() {
+        *value = i as u8;
+    }
+
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    for byte in data {
+        i = i.wrapping_add(1);
+        j = j.wrapping_add(state[i as usize]);
+        state.swap(i as usize, j as usize);
+        *byte ^= state[state[i as usize].wrapping_add(state[j as usize]) as usize];
+    }
+}
+
+/// Encrypts the strings and stream contents of an indirect object (algorithm 1)
+fn encrypt_object(key: &[u8; 16], id: ObjectId, object: &mut Object) -> Result<(), io::Error> {
+    let mut context = md5::Context::new();
+    context.consume(key);
+    context.consume(&id.0.to_le_bytes()[..3]);
+    context.consume(id.1.to_le_bytes());
+    context.consume(b"sAlT");
+    let object_key = context.compute().0;
+
+    encrypt_strings(&object_key, object)
+}
+
+fn encrypt_strings(key: &[u8; 16], object: &mut Object) -> Result<(), io::Error> {
+    match object {
+        Object::String(text, _) => *text = aes_encrypt(key, text)?,
+        Object::Array(array) => {
+            for item in array {
+                encrypt_strings(key, item)?;
+            }
+        }
+        Object::Dictionary(dict) => encrypt_dictionary(key, dict)?,
+        Object::Stream(stream) => {
+            if matches!(stream.dict.type_name(), Ok("XRef")) {
+                return Ok(());
+            }
+            encrypt_dictionary(key, &mut stream.dict)?;
+            let content = aes_encrypt(key, &stream.content)?;
+            stream.set_content(content);
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn encrypt_dictionary(key: &[u8; 16], dict: &mut Dictionary) -> Result<(), io::Error> {
+    // The contents of a signature are not encrypted, the signature covers the bytes of the file
+    let is_signature = dict.has(b"ByteRange");
+
+    for (name, value) in dict.iter_mut() {
+        if is_signature && name == b"Contents" {
+            continue;
+        }
+        encrypt_strings(key, value)?;
+    }
+
+    Ok(())
+}
+
+/// Encrypts data with AES-128 in CBC mode, the random initialization vector prepended
+fn aes_encrypt(key: &[u8; 16], data: &[u8]) -> Result<Vec<u8>, io::Error> {
+    let iv = random_bytes::<16>()?;
+    let encryptor = cbc::Encryptor::<Aes128>::new(key.into(), &iv.into());
+
+    let mut res = iv.to_vec();
+    res.extend(encryptor.encrypt_padded_vec_mut::<Pkcs7>(data));
+    Ok(res)
+}
@@ -0,0 +1,203 @@
+use std::ops::Range;
+
+use lopdf::ObjectId;
+
+use crate::{
+    FieldId, FieldKey, FieldState, FieldType, FieldValue, Form, TextAppearanceOptions, ValueError,
+};
+
+/// A read-only handle to a field of a `Form`, as yielded by `Form::fields`
+#[derive(Clone, Copy)]
+pub struct Field<'a> {
+    form: &'a Form,
+    index: usize,
+}
+
+impl<'a> Field<'a> {
+    /// The current index of the field in the form
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The stable id of the field
+    pub fn id(&self) -> FieldId {
+        self.form.field_id_of(self.form.form_ids[self.index])
+    }
+
+    /// The id of the field dictionary in the document
+    pub fn object_id(&self) -> ObjectId {
+        self.form.get_object_id(self.index)
+    }
+
+    /// The partial name of the field
+    pub fn name(&self) -> Option<String> {
+        self.form.get_name(self.index)
+    }
+
+    /// The fully qualified name of the field
+    pub fn qualified_name(&self) -> Option<String> {
+        self.form.get_qualified_name(self.index)
+    }
+
+    /// The type of the field
+    pub fn field_type(&self) -> FieldType {
+        self.form.get_type(self.index)
+    }
+
+    /// The current state of the field
+    pub fn state(&self) -> FieldState {
+        self.form.get_state(self.index)
+    }
+
+    /// The value of the field, see `Form::get_value`
+    pub fn value(&self) -> Option<FieldValue> {
+        self.form.get_value(self.index)
+    }
+}
+
+/// A handle to a field of a `Form` that can also modify it, as returned by `Form::field_mut`
+pub struct FieldMut<'a> {
+    form: &'a mut Form,
+    index: usize,
+}
+
+impl<'a> FieldMut<'a> {
+    /// Reborrows the handle as a read-only `Field`
+    pub fn as_field(&self) -> Field<'_> {
+        Field {
+            form: self.form,
+            index: self.index,
+        }
+    }
+
+    /// The current index of the field in the form
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The stable id of the field
+    pub fn id(&self) -> FieldId {
+        self.as_field().id()
+    }
+
+    /// The id of the field dictionary in the document
+    pub fn object_id(&self) -> ObjectId {
+        self.as_field().object_id()
+    }
+
+    /// The partial name of the field
+    pub fn name(&self) -> Option<String> {
+        self.as_field().name()
+    }
+
+    /// The fully qualified name of the field
+    pub fn qualified_name(&self) -> Option<String> {
+        self.as_field().qualified_name()
+    }
+
+    /// The type of the field
+    pub fn field_type(&self) -> FieldType {
+        self.as_field().field_type()
+    }
+
+    /// The current state of the field
+    pub fn state(&self) -> FieldState {
+        self.as_field().state()
+    }
+
+    /// The value of the field, see `Form::get_value`
+    pub fn value(&self) -> Option<FieldValue> {
+        self.as_field().value()
+    }
+
+    /// See `Form::set_value`
+    pub fn set_value(&mut self, value: FieldValue) -> Result<(), ValueError> {
+        self.form.set_value(self.index, value)
+    }
+
+    /// See `Form::set_text`
+    pub fn set_text(&mut self, s: String) -> Result<(), ValueError> {
+        self.form.set_text(self.index, s)
+    }
+
+    /// See `Form::set_text_with`
+    pub fn set_text_with(
+        &mut self,
+        s: String,
+        options: &TextAppearanceOptions,
+    ) -> Result<(), ValueError> {
+        self.form.set_text_with(self.index, s, options)
+    }
+
+    /// See `Form::set_check_box`
+    pub fn set_check_box(&mut self, is_checked: bool) -> Result<(), ValueError> {
+        self.form.set_check_box(self.index, is_checked)
+    }
+
+    /// See `Form::set_radio`
+    pub fn set_radio(&mut self, choice: String) -> Result<(), ValueError> {
+        self.form.set_radio(self.index, choice)
+    }
+
+    /// See `Form::set_list_box`
+    pub fn set_list_box(&mut self, choices: Vec<String>) -> Result<(), ValueError> {
+        self.form.set_list_box(self.index, choices)
+    }
+
+    /// See `Form::set_combo_box`
+    pub fn set_combo_box(&mut self, choice: String) -> Result<(), ValueError> {
+        self.form.set_combo_box(self.index, choice)
+    }
+}
+
+/// Iterator over the fields of a `Form`, created by `Form::fields`
+pub struct Fields<'a> {
+    form: &'a Form,
+    indices: Range<usize>,
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Field<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let form = self.form;
+        self.indices.next().map(|index| Field { form, index })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Fields<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let form = self.form;
+        self.indices.next_back().map(|index| Field { form, index })
+    }
+}
+
+impl<'a> ExactSizeIterator for Fields<'a> {}
+
+impl Form {
+    /// Iterates over handles to all of the fields of the form, in index order
+    pub fn fields(&self) -> Fields<'_> {
+        Fields {
+            form: self,
+            indices: 0..self.len(),
+        }
+    }
+
+    /// Gets a read-only handle to a field, `None` if the key doesn't identify a field of the form
+    pub fn field(&self, n: impl FieldKey) -> Option<Field<'_>> {
+        n.index_in(self).map(|index| Field { form: self, index })
+    }
+
+    /// Gets a handle to a field that can modify it, `None` if the key doesn't identify a field
+    /// of the form
+    ///
+    /// To modify fields in a loop, iterate over `field_ids` and look each one up with this method.
+    pub fn field_mut(&mut self, n: impl FieldKey) -> Option<FieldMut<'_>> {
+        n.index_in(self)
+            .map(move |index| FieldMut { form: self, index })
+    }
+}
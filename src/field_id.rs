@@ -0,0 +1,160 @@
+use lopdf::{Object, ObjectId};
+
+use crate::encoding::decode_text_string;
+use crate::{FieldContext, FieldError, FieldType, Form, ValueError};
+
+/// Guards against reference loops in malformed `/Parent` chains
+pub(crate) const MAX_FIELD_DEPTH: usize = 64;
+
+/// A stable handle to a field of a `Form`
+///
+/// Unlike positional indices, a `FieldId` keeps pointing to the same field when other fields are
+/// added or removed. It can be passed to every getter and setter of `Form` in place of an index,
+/// and is resolved through a map kept up to date as the fields change.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldId {
+    object_id: ObjectId,
+    qualified_name: Option<String>,
+}
+
+impl FieldId {
+    /// The id of the field dictionary in the document
+    pub fn object_id(&self) -> ObjectId {
+        self.object_id
+    }
+
+    /// The fully qualified name of the field, its partial name joined to the names of its
+    /// ancestors with dots
+    pub fn qualified_name(&self) -> Option<&str> {
+        self.qualified_name.as_deref()
+    }
+}
+
+/// Anything that identifies a field of a `Form`: a positional index or a `FieldId`
+pub trait FieldKey {
+    /// Gets the current index of the field in `form`, if it is still part of it
+    fn index_in(&self, form: &Form) -> Option<usize>;
+}
+
+impl FieldKey for usize {
+    fn index_in(&self, form: &Form) -> Option<usize> {
+        if *self < form.len() {
+            Some(*self)
+        } else {
+            None
+        }
+    }
+}
+
+impl FieldKey for FieldId {
+    fn index_in(&self, form: &Form) -> Option<usize> {
+        form.index_of(self.object_id)
+    }
+}
+
+impl FieldKey for &FieldId {
+    fn index_in(&self, form: &Form) -> Option<usize> {
+        (*self).index_in(form)
+    }
+}
+
+impl Form {
+    /// Gets the stable id of the field at index `n`
+    ///
+    /// Returns `None` if n is larger than the number of fields
+    pub fn field_id(&self, n: usize) -> Option<FieldId> {
+        self.form_ids.get(n).map(|&oid| self.field_id_of(oid))
+    }
+
+    /// Builds the map from field dictionaries to their indices, which resolves `FieldId`s
+    pub(crate) fn build_id_index(&mut self) {
+        self.field_indices = self
+            .form_ids
+            .iter()
+            .enumerate()
+            .map(|(n, &oid)| (oid, n))
+            .collect();
+    }
+
+    /// Gets the index of the field dictionary `oid`, if it is a field of the form
+    pub(crate) fn index_of(&self, oid: ObjectId) -> Option<usize> {
+        self.field_indices
+            .get(&oid)
+            .copied()
+            .filter(|&n| self.form_ids.get(n) == Some(&oid))
+    }
+
+    /// Builds the stable id of the field dictionary `object_id`
+    pub(crate) fn field_id_of(&self, object_id: ObjectId) -> FieldId {
+        FieldId {
+            object_id,
+            qualified_name: self.qualified_name(object_id),
+        }
+    }
+
+    /// Gets the stable ids of all of the fields in the form
+    pub fn field_ids(&self) -> Vec<FieldId> {
+        self.form_ids
+            .iter()
+            .map(|&oid| self.field_id_of(oid))
+            .collect()
+    }
+
+    /// Finds the field with the given fully qualified name
+    pub fn find_field(&self, qualified_name: &str) -> Option<FieldId> {
+        self.form_ids
+            .iter()
+            .find(|&&oid| self.qualified_name(oid).as_deref() == Some(qualified_name))
+            .map(|&oid| self.field_id_of(oid))
+    }
+
+    /// Gets the current index of a field
+    ///
+    /// Fails with `FieldError::NotFound` if the field is not part of the form.
+    pub(crate) fn try_resolve(&self, key: impl FieldKey) -> Result<usize, FieldError> {
+        key.index_in(self).ok_or(FieldError::NotFound)
+    }
+
+    /// Describes the field at index `n` for error messages
+    pub(crate) fn field_context(&self, n: usize) -> FieldContext {
+        FieldContext {
+            index: n,
+            name: self.qualified_name(self.form_ids[n]),
+        }
+    }
+
+    /// Builds the error for the field at index `n` when its type is none of `expected`
+    pub(crate) fn type_mismatch(&self, n: usize, expected: &[FieldType]) -> ValueError {
+        ValueError::TypeMismatch {
+            field: self.field_context(n),
+            expected: expected.to_vec(),
+            found: self.try_get_type(n).unwrap_or(FieldType::Unknown),
+        }
+    }
+
+    /// Builds the fully qualified name of a field by walking its `/Parent` chain
+    pub(crate) fn qualified_name(&self, oid: ObjectId) -> Option<String> {
+        let mut names = Vec::new();
+        let mut current = self.get_dictionary(oid).ok();
+
+        while let Some(dict) = current {
+            if names.len() > MAX_FIELD_DEPTH {
+                break;
+            }
+            if let Ok(Object::String(name, _)) = dict.get(b"T") {
+                names.push(decode_text_string(name));
+            }
+            current = match dict.get(b"Parent") {
+                Ok(Object::Reference(parent)) => self.get_dictionary(*parent).ok(),
+                _ => None,
+            };
+        }
+
+        if names.is_empty() {
+            None
+        } else {
+            names.reverse();
+            Some(names.join("."))
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use lopdf::Object;
+
+use crate::utils::{DictionaryExt, FieldFlags};
+use crate::{FieldKey, FieldType, Form, ValueError};
+
+bitflags! {
+    /// The field flags (`/Ff`) of a text field
+    #[derive(Default)]
+    pub struct TextFieldFlags: u32 {
+        const READONLY          = 0x1;
+        const REQUIRED          = 0x2;
+        const NO_EXPORT         = 0x4;
+        const MULTILINE         = 0x1000;
+        const PASSWORD          = 0x2000;
+        const FILE_SELECT       = 0x100000;
+        const DO_NOT_SPELLCHECK = 0x400000;
+        const DO_NOT_SCROLL     = 0x800000;
+        const COMB              = 0x1000000;
+        const RICH_TEXT         = 0x2000000;
+    }
+}
+
+bitflags! {
+    /// The field flags (`/Ff`) of a push button, checkbox or radio button field
+    #[derive(Default)]
+    pub struct ButtonFieldFlags: u32 {
+        const READONLY          = 0x1;
+        const REQUIRED          = 0x2;
+        const NO_EXPORT         = 0x4;
+        const NO_TOGGLE_TO_OFF  = 0x4000;
+        const RADIO             = 0x8000;
+        const PUSHBUTTON        = 0x10000;
+        const RADIO_IN_UNISON   = 0x2000000;
+    }
+}
+
+bitflags! {
+    /// The field flags (`/Ff`) of a list box or combo box field
+    #[derive(Default)]
+    pub struct ChoiceFieldFlags: u32 {
+        const READONLY          = 0x1;
+        const REQUIRED          = 0x2;
+        const NO_EXPORT         = 0x4;
+        const COMBO             = 0x20000;
+        const EDIT              = 0x40000;
+        const SORT              = 0x80000;
+        const MULTISELECT       = 0x200000;
+        const DO_NOT_SPELLCHECK = 0x400000;
+        const COMMIT_ON_CHANGE  = 0x4000000;
+    }
+}
+
+bitflags! {
+    /// The annotation flags (`/F`) of a widget, which control whether viewers display and print it
+    pub struct AnnotationFlags: u32 {
+        const INVISIBLE         = 0x1;
+        const HIDDEN            = 0x2;
+        const PRINT             = 0x4;
+        const NO_ZOOM           = 0x8;
+        const NO_ROTATE         = 0x10;
+        const NO_VIEW           = 0x20;
+        const READ_ONLY         = 0x40;
+        const LOCKED            = 0x80;
+        const TOGGLE_NO_VIEW    = 0x100;
+        const LOCKED_CONTENTS   = 0x200;
+    }
+}
+
+macro_rules! builder_methods {
+    ($flags:ident) => {
+        impl $flags {
+            /// Returns a copy of these flags with `other` set
+            pub fn with(mut self, other: $flags) -> Self {
+                self.insert(other);
+                self
+            }
+
+            /// Returns a copy of these flags with `other` cleared
+            pub fn without(mut self, other: $flags) -> Self {
+                self.remove(other);
+                self
+            }
+        }
+    };
+}
+
+builder_methods!(TextFieldFlags);
+builder_methods!(ButtonFieldFlags);
+builder_methods!(ChoiceFieldFlags);
+builder_methods!(AnnotationFlags);
+
+impl Form {
+    /// Gets the raw field flags (`/Ff`) of the field at index `n`
+    ///
+    /// Returns 0 if n is larger than the number of fields or the field is not part of the form.
+    pub fn get_field_flags_raw(&self, n: impl FieldKey) -> u32 {
+        let n = match self.try_resolve(n) {
+            Ok(n) => n,
+            Err(_) => return 0,
+        };
+        if let Some(info) = self.field_info(n) {
+            return info.flags;
+        }
+        self.field_flags(self.form_ids[n]).unwrap_or(0)
+    }
+
+    /// Overwrites the raw field flags (`/Ff`) of the field at index `n`
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_field_flags_raw(&mut self, n: impl FieldKey, flags: u32) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let field = self
+            .get_object_mut(self.form_ids[n])
+            .and_then(Object::as_dict_mut)
+            .map_err(|_| ValueError::NotFound)?;
+
+        field.set_in_place("Ff", Object::Integer(i64::from(flags)));
+        self.mark_modified(n);
+
+        Ok(())
+    }
+
+    /// Gets the flags of the text field at index `n`
+    /// If it is not a text field, returns ValueError
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn get_text_field_flags(&self, n: impl FieldKey) -> Result<TextFieldFlags, ValueError> {
+        let n = self.try_resolve(n)?;
+        match self.try_get_type(n)? {
+            FieldType::Text => Ok(TextFieldFlags::from_bits_truncate(
+                self.get_field_flags_raw(n),
+            )),
+            _ => Err(self.type_mismatch(n, &[FieldType::Text])),
+        }
+    }
+
+    /// Sets the flags of the text field at index `n`. Bits with no meaning for text fields are
+    /// kept as they are.
+    /// If it is not a text field, returns ValueError
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_text_field_flags(
+        &mut self,
+        n: impl FieldKey,
+        flags: TextFieldFlags,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        match self.try_get_type(n)? {
+            FieldType::Text => self.merge_flags(n, TextFieldFlags::all().bits(), flags.bits()),
+            _ => Err(self.type_mismatch(n, &[FieldType::Text])),
+        }
+    }
+
+    /// Gets the flags of the button field (push button, checkbox or radio) at index `n`
+    /// If it is not a button field, returns ValueError
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn get_button_field_flags(&self, n: impl FieldKey) -> Result<ButtonFieldFlags, ValueError> {
+        let n = self.try_resolve(n)?;
+        match self.try_get_type(n)? {
+            FieldType::Button | FieldType::CheckBox | FieldType::Radio => Ok(
+                ButtonFieldFlags::from_bits_truncate(self.get_field_flags_raw(n)),
+            ),
+            _ => Err(self.type_mismatch(
+                n,
+                &[FieldType::Button, FieldType::CheckBox, FieldType::Radio],
+            )),
+        }
+    }
+
+    /// Sets the flags of the button field at index `n`. Bits with no meaning for buttons are kept
+    /// as they are.
+    /// If it is not a button field, returns ValueError
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_button_field_flags(
+        &mut self,
+        n: impl FieldKey,
+        flags: ButtonFieldFlags,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        match self.try_get_type(n)? {
+            FieldType::Button | FieldType::CheckBox | FieldType::Radio => {
+                self.merge_flags(n, ButtonFieldFlags::all().bits(), flags.bits())
+            }
+            _ => Err(self.type_mismatch(
+                n,
+                &[FieldType::Button, FieldType::CheckBox, FieldType::Radio],
+            )),
+        }
+    }
+
+    /// Gets the flags of the choice field (list box or combo box) at index `n`
+    /// If it is not a choice field, returns ValueError
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn get_choice_field_flags(&self, n: impl FieldKey) -> Result<ChoiceFieldFlags, ValueError> {
+        let n = self.try_resolve(n)?;
+        match self.try_get_type(n)? {
+            FieldType::ListBox | FieldType::ComboBox => Ok(ChoiceFieldFlags::from_bits_truncate(
+                self.get_field_flags_raw(n),
+            )),
+            _ => Err(self.type_mismatch(n, &[FieldType::ListBox, FieldType::ComboBox])),
+        }
+    }
+
+    /// Sets the flags of the choice field at index `n`. Bits with no meaning for choice fields
+    /// are kept as they are.
+    /// If it is not a choice field, returns ValueError
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_choice_field_flags(
+        &mut self,
+        n: impl FieldKey,
+        flags: ChoiceFieldFlags,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        match self.try_get_type(n)? {
+            FieldType::ListBox | FieldType::ComboBox => {
+                self.merge_flags(n, ChoiceFieldFlags::all().bits(), flags.bits())
+            }
+            _ => Err(self.type_mismatch(n, &[FieldType::ListBox, FieldType::ComboBox])),
+        }
+    }
+
+    /// Makes the field at index `n`, of any type, read-only or editable in viewers
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_readonly(&mut self, n: impl FieldKey, readonly: bool) -> Result<(), ValueError> {
+        self.set_common_flag(n, FieldFlags::READONLY, readonly)
+    }
+
+    /// Makes the field at index `n`, of any type, required or optional when the form is submitted
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_required(&mut self, n: impl FieldKey, required: bool) -> Result<(), ValueError> {
+        self.set_common_flag(n, FieldFlags::REQUIRED, required)
+    }
+
+    /// Sets whether the field at index `n`, of any type, is left out when the form is submitted
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_no_export(&mut self, n: impl FieldKey, no_export: bool) -> Result<(), ValueError> {
+        self.set_common_flag(n, FieldFlags::NO_EXPORT, no_export)
+    }
+
+    /// Sets or clears one of the flags shared by every field type
+    fn set_common_flag(
+        &mut self,
+        n: impl FieldKey,
+        flag: FieldFlags,
+        value: bool,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let bits = if value { flag.bits() } else { 0 };
+        self.merge_flags(n, flag.bits(), bits)
+    }
+
+    /// Gets the annotation flags (`/F`) of the widget of the field at index `n`. For fields with
+    /// several widgets, these are the flags of the first one.
+    ///
+    /// Returns no flags if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_annotation_flags(&self, n: impl FieldKey) -> AnnotationFlags {
+        let n = match self.try_resolve(n) {
+            Ok(n) => n,
+            Err(_) => return AnnotationFlags::empty(),
+        };
+        let flags = self
+            .widget_of(self.form_ids[n])
+            .and_then(|widget| self.get_dictionary(widget).ok())
+            .and_then(|widget| widget.get(b"F").ok())
+            .and_then(|flags| flags.as_i64().ok())
+            .unwrap_or(0);
+
+        AnnotationFlags::from_bits_truncate(flags as u32)
+    }
+
+    /// Sets the annotation flags (`/F`) of every widget of the field at index `n`
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_annotation_flags(
+        &mut self,
+        n: impl FieldKey,
+        flags: AnnotationFlags,
+    ) -> Result<(), ValueError> {
+        self.update_annotation_flags(n, |_| flags)
+    }
+
+    /// Hides the field at index `n`: viewers neither display nor print it
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn hide_field(&mut self, n: impl FieldKey) -> Result<(), ValueError> {
+        self.update_annotation_flags(n, |flags| flags.with(AnnotationFlags::HIDDEN))
+    }
+
+    /// Shows the field at index `n`, which is then displayed and printed
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn show_field(&mut self, n: impl FieldKey) -> Result<(), ValueError> {
+        self.update_annotation_flags(n, |flags| {
+            flags
+                .without(AnnotationFlags::HIDDEN | AnnotationFlags::NO_VIEW)
+                .with(AnnotationFlags::PRINT)
+        })
+    }
+
+    /// Rewrites the annotation flags of every widget of a field with `f`
+    fn update_annotation_flags<F>(&mut self, n: impl FieldKey, f: F) -> Result<(), ValueError>
+    where
+        F: Fn(AnnotationFlags) -> AnnotationFlags,
+    {
+        let n = self.try_resolve(n)?;
+        let widgets = self.widgets_of(self.form_ids[n]);
+        if widgets.is_empty() {
+            return Err(ValueError::NotFound);
+        }
+
+        for widget in widgets {
+            let widget = self
+                .get_object_mut(widget)
+                .and_then(Object::as_dict_mut)
+                .map_err(|_| ValueError::NotFound)?;
+            let flags = widget
+                .get(b"F")
+                .and_then(Object::as_i64)
+                .map(|flags| AnnotationFlags::from_bits_truncate(flags as u32))
+                .unwrap_or_else(|_| AnnotationFlags::empty());
+            widget.set_in_place("F", Object::Integer(i64::from(f(flags).bits())));
+        }
+        self.mark_modified(n);
+
+        Ok(())
+    }
+
+    /// Replaces the bits covered by `mask` with `bits`, leaving the others untouched
+    fn merge_flags(&mut self, n: usize, mask: u32, bits: u32) -> Result<(), ValueError> {
+        let raw = self.get_field_flags_raw(n);
+        self.set_field_flags_raw(n, (raw & !mask) | (bits & mask))
+    }
+}
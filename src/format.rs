@@ -0,0 +1,540 @@
+use crate::{ActionTrigger, Color, FieldKey, FieldType, Form, TextAppearanceOptions, ValueError};
+
+/// Date formats of the legacy `AFDate_Format` script, by index
+const DATE_FORMATS: &[&str] = &[
+    "m/d",
+    "m/d/yy",
+    "mm/dd/yy",
+    "mm/yy",
+    "d-mmm",
+    "d-mmm-yy",
+    "dd-mmm-yy",
+    "yy-mm-dd",
+    "mmm-yy",
+    "mmmm-yy",
+    "mmm d, yyyy",
+    "mmmm d, yyyy",
+    "m/d/yy h:MM tt",
+    "m/d/yy HH:MM",
+];
+
+const MONTHS: &[&str] = &[
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: &[&str] = &[
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// One of the standard format scripts of Acrobat, found in the `/AA /F` entry of text fields
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum FormatScript {
+    /// `AFNumber_Format(decimals, separators, negative, _, currency, prepend)`
+    Number {
+        decimals: usize,
+        separators: usize,
+        negative: usize,
+        currency: String,
+        prepend: bool,
+    },
+    /// `AFPercent_Format(decimals, separators, prepend)`
+    Percent {
+        decimals: usize,
+        separators: usize,
+        prepend: bool,
+    },
+    /// `AFDate_FormatEx(pattern)`, or `AFDate_Format(index)` with one of the legacy patterns.
+    /// The `AFDate_KeystrokeEx(pattern)` keystroke script gives the pattern as well.
+    Date(String),
+}
+
+/// A value as displayed by a format script
+pub(crate) struct Formatted {
+    pub(crate) text: String,
+    /// Negative numbers shown in red by the format
+    pub(crate) red: bool,
+}
+
+impl FormatScript {
+    /// Recognizes the first standard format call of a script
+    pub(crate) fn parse(script: &str) -> Option<Self> {
+        let (name, args) = parse_call(script)?;
+        let number = |i: usize| {
+            args.get(i)
+                .and_then(|arg| arg.parse::<f64>().ok())
+                .map_or(0, |value| value.max(0.0) as usize)
+        };
+        let flag = |i: usize| args.get(i).is_some_and(|arg| arg == "true" || arg == "1");
+
+        match name.as_str() {
+            "AFNumber_Format" => Some(FormatScript::Number {
+                decimals: number(0),
+                separators: number(1),
+                negative: number(2),
+                currency: args.get(4).cloned().unwrap_or_default(),
+                prepend: flag(5),
+            }),
+            "AFPercent_Format" => Some(FormatScript::Percent {
+                decimals: number(0),
+                separators: number(1),
+                prepend: flag(2),
+            }),
+            "AFDate_FormatEx" | "AFDate_KeystrokeEx" => {
+                args.first().cloned().map(FormatScript::Date)
+            }
+            "AFDate_Format" => DATE_FORMATS
+                .get(number(0))
+                .map(|pattern| FormatScript::Date((*pattern).to_owned())),
+            _ => None,
+        }
+    }
+
+    /// Formats a raw value the way Acrobat displays it. Values that cannot be read as a number
+    /// or a date are shown as they are, and empty values stay empty.
+    pub(crate) fn apply(&self, raw: &str) -> Formatted {
+        let unchanged = Formatted {
+            text: raw.to_owned(),
+            red: false,
+        };
+        if raw.trim().is_empty() {
+            return unchanged;
+        }
+
+        match self {
+            FormatScript::Number {
+                decimals,
+                separators,
+                negative,
+                currency,
+                prepend,
+            } => {
+                let value = match parse_number(raw, *separators) {
+                    Some(value) => value,
+                    None => return unchanged,
+                };
+                let digits = group_digits(value.abs(), *decimals, *separators);
+                let amount = if *prepend {
+                    format!("{}{}", currency, digits)
+                } else {
+                    format!("{}{}", digits, currency)
+                };
+                let is_negative =
+                    value < 0.0 && digits.chars().any(|c| c.is_ascii_digit() && c != '0');
+                let text = match (is_negative, negative) {
+                    (false, _) => amount,
+                    (true, 1) => amount,
+                    (true, 2) | (true, 3) => format!("({})", amount),
+                    (true, _) => format!("-{}", amount),
+                };
+                Formatted {
+                    text,
+                    red: is_negative && (*negative == 1 || *negative == 3),
+                }
+            }
+            FormatScript::Percent {
+                decimals,
+                separators,
+                prepend,
+            } => {
+                let value = match parse_number(raw, *separators) {
+                    Some(value) => value * 100.0,
+                    None => return unchanged,
+                };
+                let digits = group_digits(value.abs(), *decimals, *separators);
+                let sign = if value < 0.0 { "-" } else { "" };
+                let text = if *prepend {
+                    format!("{}%{}", sign, digits)
+                } else {
+                    format!("{}{}%", sign, digits)
+                };
+                Formatted { text, red: false }
+            }
+            FormatScript::Date(pattern) => match parse_date(raw, pattern) {
+                Some(date) => Formatted {
+                    text: format_date(&date, pattern),
+                    red: false,
+                },
+                None => unchanged,
+            },
+        }
+    }
+}
+
+impl Form {
+    /// Gets the value of the text field at index `n` as displayed by its format script (`/AA
+    /// /F`), when it is one of the standard `AFNumber_Format`, `AFPercent_Format`,
+    /// `AFDate_FormatEx` or `AFDate_Format` scripts. `None` if the field isn't a text field or
+    /// has no such script.
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_formatted_text(&self, n: impl FieldKey) -> Option<String> {
+        let n = self.try_resolve(n).ok()?;
+        let raw = self.text_appearance_value(n).ok()?;
+        self.format_value(n, &raw).map(|formatted| formatted.text)
+    }
+
+    /// Fills the text field at index `n` with the raw value `raw`, e.g. `1234.5` or
+    /// `2024-03-01`, and shows it in the appearance as formatted by the format script of the
+    /// field, e.g. `$1,234.50` or `03/01/2024`, like Acrobat does. See `get_formatted_text`
+    /// for the recognized scripts, other values are shown as they are.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_text_formatted(&mut self, n: impl FieldKey, raw: String) -> Result<(), ValueError> {
+        let options = TextAppearanceOptions {
+            apply_format: true,
+            ..Default::default()
+        };
+        self.set_text_with(n, raw, &options)
+    }
+
+    /// Gets the text shown in the appearance of the text field at index `n` for its value `text`
+    /// and the color it is shown with, applying the format script of the field when `options`
+    /// ask for it
+    pub(crate) fn displayed_text(
+        &self,
+        n: usize,
+        text: String,
+        options: &TextAppearanceOptions,
+    ) -> (String, TextAppearanceOptions) {
+        if !options.apply_format {
+            return (text, options.clone());
+        }
+        match self.format_value(n, &text) {
+            Some(formatted) => {
+                let mut options = options.clone();
+                if formatted.red && options.color.is_none() {
+                    options.color = Some(Color::Rgb(1.0, 0.0, 0.0));
+                }
+                (formatted.text, options)
+            }
+            None => (text, options.clone()),
+        }
+    }
+
+    /// Formats a value with the standard format script of the text field at index `n`
+    fn format_value(&self, n: usize, raw: &str) -> Option<Formatted> {
+        if self.try_get_type(n).ok()? != FieldType::Text {
+            return None;
+        }
+        self.format_script(n).map(|script| script.apply(raw))
+    }
+
+    /// Gets the date pattern of the text field at index `n`, e.g. `mm/dd/yyyy`, from its
+    /// standard date format or keystroke script (`AFDate_FormatEx`, `AFDate_Format` or
+    /// `AFDate_KeystrokeEx`). `None` if it has no such script.
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_date_format(&self, n: impl FieldKey) -> Option<String> {
+        let n = self.try_resolve(n).ok()?;
+        self.get_additional_actions(n)
+            .into_iter()
+            .filter(|action| {
+                matches!(
+                    action.trigger,
+                    ActionTrigger::Format | ActionTrigger::Keystroke
+                )
+            })
+            .find_map(
+                |action| match FormatScript::parse(action.script.as_deref()?)? {
+                    FormatScript::Date(pattern) => Some(pattern),
+                    _ => None,
+                },
+            )
+    }
+
+    /// Gets the standard format script of the field at index `n`, if it has one
+    pub(crate) fn format_script(&self, n: usize) -> Option<FormatScript> {
+        self.get_additional_actions(n)
+            .into_iter()
+            .filter(|action| action.trigger == ActionTrigger::Format)
+            .find_map(|action| FormatScript::parse(action.script.as_deref()?))
+    }
+}
+
+/// Splits the first function call of a script into its name and its arguments, with the quotes
+/// of string arguments removed
+fn parse_call(script: &str) -> Option<(String, Vec<String>)> {
+    let start = script.find("AF")?;
+    let script = &script[start..];
+    let open = script.find('(')?;
+    let name = script[..open].trim().to_owned();
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = script[open + 1..].chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), '\\') => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, ',') => args.push(std::mem::take(&mut current)),
+            (None, ')') => {
+                args.push(current);
+                return Some((name, args));
+            }
+            // Spaces are only kept within quotes
+            (None, c) if c.is_whitespace() => {}
+            (None, c) => current.push(c),
+        }
+    }
+    None
+}
+
+/// Reads a number typed with either decimal separator, ignoring the thousands separators
+pub(crate) fn parse_number(raw: &str, separators: usize) -> Option<f64> {
+    let raw = raw
+        .trim()
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\'')
+        .collect::<String>();
+    // A comma is the decimal separator of the styles 2 and 3, and when there is no period
+    let decimal_comma = separators == 2 || separators == 3 || !raw.contains('.');
+    let raw = if decimal_comma && raw.matches(',').count() == 1 {
+        raw.replace('.', "").replace(',', ".")
+    } else {
+        raw.replace(',', "")
+    };
+    raw.parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+/// Writes a positive number with `decimals` decimals and the separators of a separator style:
+/// `1,234.56`, `1234.56`, `1.234,56`, `1234,56` or `1'234.56`
+fn group_digits(value: f64, decimals: usize, separators: usize) -> String {
+    let (thousands, decimal) = match separators {
+        1 => (None, '.'),
+        2 => (Some('.'), ','),
+        3 => (None, ','),
+        4 => (Some('\''), '.'),
+        _ => (Some(','), '.'),
+    };
+
+    let fixed = format!("{:.*}", decimals, value);
+    let (integer, fraction) = match fixed.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (fixed.as_str(), None),
+    };
+
+    let mut text = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            if let Some(thousands) = thousands {
+                text.push(thousands);
+            }
+        }
+        text.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        text.push(decimal);
+        text.push_str(fraction);
+    }
+    text
+}
+
+/// A date and time read from a raw value
+#[derive(Debug, Default)]
+pub(crate) struct DateTime {
+    pub(crate) year: i32,
+    pub(crate) month: u32,
+    pub(crate) day: u32,
+    pub(crate) hour: u32,
+    pub(crate) minute: u32,
+    pub(crate) second: u32,
+}
+
+/// Reads a date written as `yyyy-mm-dd`, optionally followed by `HH:MM[:SS]` after a space or
+/// a `T`, or with the fields of `pattern` in order: numbers for the numeric fields, and names
+/// for the months (`mmm`, `mmmm`), the days of the week and `am` or `pm`
+pub(crate) fn parse_date(raw: &str, pattern: &str) -> Option<DateTime> {
+    let words = split_words(raw);
+
+    let iso = raw.trim().len() >= 10 && raw.trim().as_bytes()[4] == b'-';
+    let date = if iso {
+        let numbers = words
+            .iter()
+            .filter_map(|word| word.parse::<u32>().ok())
+            .collect::<Vec<_>>();
+        DateTime {
+            year: *numbers.first()? as i32,
+            month: *numbers.get(1)?,
+            day: *numbers.get(2)?,
+            hour: numbers.get(3).copied().unwrap_or(0),
+            minute: numbers.get(4).copied().unwrap_or(0),
+            second: numbers.get(5).copied().unwrap_or(0),
+        }
+    } else {
+        let mut date = DateTime {
+            year: 2000,
+            month: 1,
+            day: 1,
+            ..Default::default()
+        };
+        let mut pm = None;
+        let mut words = words.into_iter();
+        for token in tokenize(pattern) {
+            let field = match token.as_str() {
+                "yyyy" | "yy" | "mm" | "m" | "dd" | "d" | "HH" | "H" | "hh" | "h" | "MM" | "M"
+                | "ss" | "s" => Some(words.next()?.parse::<u32>().ok()?),
+                "mmmm" | "mmm" => {
+                    let name = words.next()?.to_lowercase();
+                    let month = MONTHS.iter().position(|month| {
+                        name.len() >= 3 && month.to_lowercase().starts_with(&name)
+                    })?;
+                    date.month = month as u32 + 1;
+                    None
+                }
+                "dddd" | "ddd" => {
+                    words.next()?;
+                    None
+                }
+                "tt" | "t" => {
+                    pm = Some(words.next()?.to_lowercase().starts_with('p'));
+                    None
+                }
+                _ => None,
+            };
+            if let Some(value) = field {
+                match token.as_str() {
+                    "yyyy" => date.year = value as i32,
+                    "yy" => date.year = 2000 + value as i32,
+                    "mm" | "m" => date.month = value,
+                    "dd" | "d" => date.day = value,
+                    "HH" | "H" | "hh" | "h" => date.hour = value,
+                    "MM" | "M" => date.minute = value,
+                    _ => date.second = value,
+                }
+            }
+        }
+        match pm {
+            Some(true) if date.hour < 12 => date.hour += 12,
+            Some(false) if date.hour == 12 => date.hour = 0,
+            _ => {}
+        }
+        date
+    };
+
+    let valid = (1..=12).contains(&date.month)
+        && date.day >= 1
+        && date.day <= days_in_month(date.year, date.month)
+        && date.hour < 24
+        && date.minute < 60
+        && date.second < 60;
+    valid.then_some(date)
+}
+
+/// Splits a value into its runs of digits and its runs of letters
+fn split_words(raw: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut previous = None;
+    for c in raw.chars() {
+        let kind = if c.is_ascii_digit() {
+            Some(true)
+        } else if c.is_alphabetic() {
+            Some(false)
+        } else {
+            None
+        };
+        match words.last_mut() {
+            Some(word) if kind.is_some() && kind == previous => word.push(c),
+            _ if kind.is_some() => words.push(c.to_string()),
+            _ => {}
+        }
+        previous = kind;
+    }
+    words
+}
+
+/// Writes a date with the date fields of an Acrobat pattern (`yyyy`, `mmmm`, `dd`, `HH`...)
+pub(crate) fn format_date(date: &DateTime, pattern: &str) -> String {
+    let month = MONTHS[date.month as usize - 1];
+    let weekday = WEEKDAYS[weekday(date.year, date.month, date.day)];
+    let hour12 = match date.hour % 12 {
+        0 => 12,
+        hour => hour,
+    };
+    let am_pm = if date.hour < 12 { "am" } else { "pm" };
+
+    tokenize(pattern)
+        .into_iter()
+        .map(|token| match token.as_str() {
+            "yyyy" => format!("{:04}", date.year),
+            "yy" => format!("{:02}", date.year.rem_euclid(100)),
+            "mmmm" => month.to_owned(),
+            "mmm" => month[..3].to_owned(),
+            "mm" => format!("{:02}", date.month),
+            "m" => date.month.to_string(),
+            "dddd" => weekday.to_owned(),
+            "ddd" => weekday[..3].to_owned(),
+            "dd" => format!("{:02}", date.day),
+            "d" => date.day.to_string(),
+            "HH" => format!("{:02}", date.hour),
+            "H" => date.hour.to_string(),
+            "hh" => format!("{:02}", hour12),
+            "h" => hour12.to_string(),
+            "MM" => format!("{:02}", date.minute),
+            "M" => date.minute.to_string(),
+            "ss" => format!("{:02}", date.second),
+            "s" => date.second.to_string(),
+            "tt" => am_pm.to_owned(),
+            "t" => am_pm[..1].to_owned(),
+            _ => token,
+        })
+        .collect()
+}
+
+/// Splits a date pattern into runs of the same letter and the literal text between them
+fn tokenize(pattern: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for c in pattern.chars() {
+        match tokens.last_mut() {
+            Some(last) if last.starts_with(c) && c.is_ascii_alphabetic() => last.push(c),
+            _ => tokens.push(c.to_string()),
+        }
+    }
+    tokens
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Gets the day of the week of a date, 0 being Sunday
+fn weekday(year: i32, month: u32, day: u32) -> usize {
+    const OFFSETS: [i32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let year = if month < 3 { year - 1 } else { year };
+    let days = year + year.div_euclid(4) - year.div_euclid(100)
+        + year.div_euclid(400)
+        + OFFSETS[month as usize - 1]
+        + day as i32;
+    days.rem_euclid(7) as usize
+}
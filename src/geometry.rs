@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, HashMap};
+
+use lopdf::{Dictionary, Object, ObjectId};
+
+use crate::tab_order::row_order;
+use crate::{FieldKey, Form};
+
+/// Guards against reference loops in malformed page trees
+pub(crate) const MAX_PAGE_TREE_DEPTH: usize = 64;
+
+/// The frame in which the appearance stream of a widget is drawn
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AppearanceFrame {
+    /// Width of the form XObject bounding box, along the text direction
+    pub width: f32,
+    /// Height of the form XObject bounding box
+    pub height: f32,
+    /// Rotation of the content, in degrees counterclockwise (0, 90, 180 or 270)
+    pub rotation: i64,
+    /// Size of a user space unit of the page, in points
+    pub user_unit: f32,
+}
+
+/// The coordinate transform of a page
+///
+/// Page coordinates are expressed in points, with the origin at the lower-left corner of the
+/// page's `/MediaBox`. The default user space of a page, in which annotation rectangles are
+/// stored, may have its origin elsewhere and, with `/UserUnit`, use units larger than a point.
+/// All the rectangle and stamping APIs of this crate go through this transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageTransform {
+    /// Lower-left corner of the MediaBox, in user space units
+    pub origin: (f32, f32),
+    /// Size of a user space unit, in points
+    pub user_unit: f32,
+}
+
+impl Default for PageTransform {
+    fn default() -> Self {
+        PageTransform {
+            origin: (0.0, 0.0),
+            user_unit: 1.0,
+        }
+    }
+}
+
+impl PageTransform {
+    /// Converts a point from page coordinates to user space
+    pub fn point_to_user_space(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x / self.user_unit + self.origin.0,
+            y / self.user_unit + self.origin.1,
+        )
+    }
+
+    /// Converts a point from user space to page coordinates
+    pub fn point_from_user_space(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.origin.0) * self.user_unit,
+            (y - self.origin.1) * self.user_unit,
+        )
+    }
+
+    /// Converts a rectangle `[llx, lly, urx, ury]` from page coordinates to user space
+    pub fn rect_to_user_space(&self, rect: [f32; 4]) -> [f32; 4] {
+        let (x1, y1) = self.point_to_user_space(rect[0], rect[1]);
+        let (x2, y2) = self.point_to_user_space(rect[2], rect[3]);
+        [x1, y1, x2, y2]
+    }
+
+    /// Converts a rectangle `[llx, lly, urx, ury]` from user space to page coordinates
+    pub fn rect_from_user_space(&self, rect: [f32; 4]) -> [f32; 4] {
+        let (x1, y1) = self.point_from_user_space(rect[0], rect[1]);
+        let (x2, y2) = self.point_from_user_space(rect[2], rect[3]);
+        [x1, y1, x2, y2]
+    }
+}
+
+impl AppearanceFrame {
+    /// The matrix mapping the bounding box onto the widget rectangle
+    pub fn matrix(&self) -> [f32; 6] {
+        match self.rotation {
+            90 => [0.0, 1.0, -1.0, 0.0, self.height, 0.0],
+            180 => [-1.0, 0.0, 0.0, -1.0, self.width, self.height],
+            270 => [0.0, -1.0, 1.0, 0.0, 0.0, self.width],
+            _ => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        }
+    }
+
+    /// Writes the form XObject entries describing this frame into an appearance stream dictionary
+    pub fn apply(&self, dict: &mut Dictionary) {
+        dict.set("Type", Object::Name(b"XObject".to_vec()));
+        dict.set("Subtype", Object::Name(b"Form".to_vec()));
+        dict.set(
+            "BBox",
+            vec![0.into(), 0.into(), self.width.into(), self.height.into()],
+        );
+        if self.rotation != 0 || dict.has(b"Matrix") {
+            dict.set(
+                "Matrix",
+                self.matrix()
+                    .iter()
+                    .map(|&v| Object::Real(v.into()))
+                    .collect::<Vec<_>>(),
+            );
+        }
+    }
+}
+
+impl Form {
+    /// Gets the coordinate transform of the page at index `page` (starting at 0)
+    pub fn get_page_transform(&self, page: usize) -> Option<PageTransform> {
+        self.page_id(page).map(|page| self.page_transform(page))
+    }
+
+    /// Gets the rectangle `[llx, lly, urx, ury]` of the widget annotation of the field at index
+    /// `n`, in page coordinates. For fields with several widgets, such as radio buttons, this is
+    /// the rectangle of the first one.
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_rect(&self, n: impl FieldKey) -> Option<[f32; 4]> {
+        let n = self.try_resolve(n).ok()?;
+        if let Some(info) = self.field_info(n) {
+            return info.rect;
+        }
+        let widget = self.widget_of(self.form_ids[n])?;
+        let rect = self.widget_rect(widget)?;
+        let rect = [
+            rect[0].min(rect[2]),
+            rect[1].min(rect[3]),
+            rect[0].max(rect[2]),
+            rect[1].max(rect[3]),
+        ];
+
+        let transform = self
+            .widget_page(widget)
+            .map(|page| self.page_transform(page))
+            .unwrap_or_default();
+        Some(transform.rect_from_user_space(rect))
+    }
+
+    /// Gets the index (starting at 0) of the page the widget annotation of the field at index `n`
+    /// is placed on. For fields with several widgets, this is the page of the first one.
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_page(&self, n: impl FieldKey) -> Option<usize> {
+        let n = self.try_resolve(n).ok()?;
+        if let Some(info) = self.field_info(n) {
+            return info.page;
+        }
+        let widget = self.widget_of(self.form_ids[n])?;
+        let page = self.widget_page(widget)?;
+        self.page_iter().position(|id| id == page)
+    }
+
+    /// Gets the indices of the fields with a widget annotation on the page at index `page`
+    /// (starting at 0). Fields with widgets on several pages are listed on each of them.
+    pub fn fields_on_page(&self, page: usize) -> Vec<usize> {
+        self.page_fields.get(page).cloned().unwrap_or_default()
+    }
+
+    /// Sorts the fields by their position, so that iterating over the indices follows the
+    /// reading order: page by page, and on each page in rows from the top, from left to right
+    /// within a row. Fields are placed at their first widget; fields not placed on any page come
+    /// last, in their previous order. The order is kept when the fields are indexed again, e.g.
+    /// after adding a field.
+    ///
+    /// Indices taken before sorting refer to other fields afterwards, `FieldId`s don't.
+    pub fn sort_fields_by_position(&mut self) {
+        self.sort_by_position = true;
+        self.sort_form_ids();
+        self.build_id_index();
+        self.build_field_info();
+        self.build_name_index();
+        self.build_page_index();
+    }
+
+    /// Sorts `form_ids` by page and by rows within a page, from the widgets rather than the
+    /// cached field info, which may be stale while reindexing
+    pub(crate) fn sort_form_ids(&mut self) {
+        let pages = self
+            .page_iter()
+            .enumerate()
+            .map(|(i, page)| (page, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut placed = BTreeMap::new();
+        let mut unplaced = Vec::new();
+        for &oid in &self.form_ids {
+            let position = self.widget_of(oid).and_then(|widget| {
+                let page = pages.get(&self.widget_page(widget)?)?;
+                Some((*page, self.widget_rect(widget)?))
+            });
+            match position {
+                Some((page, rect)) => placed
+                    .entry(page)
+                    .or_insert_with(Vec::new)
+                    .push((oid, rect)),
+                None => unplaced.push(oid),
+            }
+        }
+
+        self.form_ids = placed
+            .into_values()
+            .flat_map(row_order)
+            .chain(unplaced)
+            .collect();
+    }
+
+    /// Builds the map from pages to the fields placed on them, scanning the `/Annots` of every
+    /// page once. Widgets missing from every `/Annots` are placed by their `/P` entry.
+    pub(crate) fn build_page_index(&mut self) {
+        let pages = self.page_iter().collect::<Vec<_>>();
+
+        let mut annot_pages = HashMap::new();
+        for (i, &page) in pages.iter().enumerate() {
+            let annots = self
+                .get_dictionary(page)
+                .and_then(|page| page.get(b"Annots"))
+                .and_then(|annots| self.dereference(annots));
+            if let Ok((_, Object::Array(annots))) = annots {
+                for annot in annots {
+                    if let Ok(id) = annot.as_reference() {
+                        annot_pages.entry(id).or_insert(i);
+                    }
+                }
+            }
+        }
+
+        let mut page_fields = vec![Vec::new(); pages.len()];
+        for (n, &oid) in self.form_ids.iter().enumerate() {
+            let widgets = std::iter::once(oid).chain(self.kids_of(oid));
+            for widget in widgets {
+                let page = annot_pages.get(&widget).copied().or_else(|| {
+                    let page = self
+                        .get_dictionary(widget)
+                        .and_then(|widget| widget.get(b"P"))
+                        .and_then(Object::as_reference)
+                        .ok()?;
+                    pages.iter().position(|&id| id == page)
+                });
+                if let Some(fields) = page.map(|page| &mut page_fields[page]) {
+                    if fields.last() != Some(&n) {
+                        fields.push(n);
+                    }
+                }
+            }
+        }
+
+        self.page_fields = page_fields;
+    }
+
+    /// Gets the widget annotation of a field: the field itself when they are merged, or else its
+    /// first kid with a `/Rect`
+    pub(crate) fn widget_of(&self, oid: ObjectId) -> Option<ObjectId> {
+        self.widgets_of(oid).into_iter().next()
+    }
+
+    /// Gets the widget annotations of a field: the field itself when they are merged, or else its
+    /// kids with a `/Rect`
+    pub(crate) fn widgets_of(&self, oid: ObjectId) -> Vec<ObjectId> {
+        let has_rect = |id: ObjectId| self.get_dictionary(id).is_ok_and(|dict| dict.has(b"Rect"));
+
+        if has_rect(oid) {
+            vec![oid]
+        } else {
+            self.kids_of(oid)
+                .into_iter()
+                .filter(|&kid| has_rect(kid))
+                .collect()
+        }
+    }
+
+    /// Reads the `/Rect` of a widget annotation, as stored in user space
+    pub(crate) fn widget_rect(&self, oid: ObjectId) -> Option<[f32; 4]> {
+        let rect = self
+            .get_dictionary(oid)
+            .ok()?
+            .get(b"Rect")
+            .and_then(|rect| self.dereference(rect))
+            .ok()?
+            .1
+            .as_array()
+            .ok()?;
+        match rect.as_slice() {
+            [llx, lly, urx, ury, ..] => Some([number(llx), number(lly), number(urx), number(ury)]),
+            _ => None,
+        }
+    }
+
+    /// Gets the id of the page at index `page` (starting at 0)
+    pub(crate) fn page_id(&self, page: usize) -> Option<ObjectId> {
+        self.page_iter().nth(page)
+    }
+
+    /// Gets the coordinate transform of a page from its `/MediaBox` and `/UserUnit`
+    pub(crate) fn page_transform(&self, page: ObjectId) -> PageTransform {
+        let origin = match self.inherited_page_entry(page, b"MediaBox") {
+            Some(Object::Array(media_box)) if media_box.len() == 4 => {
+                let values = media_box.iter().map(number).collect::<Vec<_>>();
+                (values[0].min(values[2]), values[1].min(values[3]))
+            }
+            _ => (0.0, 0.0),
+        };
+        let user_unit = self
+            .get_dictionary(page)
+            .and_then(|page| page.get(b"UserUnit"))
+            .map(number)
+            .ok()
+            .filter(|unit| *unit > 0.0)
+            .unwrap_or(1.0);
+
+        PageTransform { origin, user_unit }
+    }
+
+    /// Gets the page a widget annotation is placed on, from its `/P` entry or, when missing, by
+    /// scanning the `/Annots` of every page
+    pub(crate) fn widget_page(&self, oid: ObjectId) -> Option<ObjectId> {
+        if let Ok(Object::Reference(page)) = self.get_dictionary(oid).ok()?.get(b"P") {
+            return Some(*page);
+        }
+
+        self.page_iter().find(|&page| {
+            let annots = self
+                .get_dictionary(page)
+                .and_then(|page| page.get(b"Annots"))
+                .and_then(|annots| self.dereference(annots));
+            match annots {
+                Ok((_, Object::Array(annots))) => annots
+                    .iter()
+                    .any(|annot| matches!(annot, Object::Reference(id) if *id == oid)),
+                _ => false,
+            }
+        })
+    }
+
+    /// Gets an entry of a page, following the page tree up for inheritable entries
+    pub(crate) fn inherited_page_entry(&self, page: ObjectId, key: &[u8]) -> Option<&Object> {
+        let mut current = Some(page);
+        let mut depth = 0;
+
+        while let Some(id) = current {
+            let dict = self.get_dictionary(id).ok()?;
+            if let Ok(value) = dict.get(key) {
+                return self.dereference(value).ok().map(|(_, value)| value);
+            }
+            depth += 1;
+            if depth > MAX_PAGE_TREE_DEPTH {
+                return None;
+            }
+            current = dict.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+
+        None
+    }
+
+    /// Gets the `/Rotate` of a page, normalized to 0, 90, 180 or 270
+    pub(crate) fn page_rotation(&self, page: ObjectId) -> i64 {
+        let rotation = self
+            .inherited_page_entry(page, b"Rotate")
+            .and_then(|rotate| rotate.as_i64().ok())
+            .unwrap_or(0);
+
+        normalize_rotation(rotation)
+    }
+
+    /// Computes the frame of the appearance stream of a widget. The rotation comes from the
+    /// widget's `/MK /R` entry or, when missing, from the rotation of its page, so the text reads
+    /// upright once the page is displayed.
+    pub(crate) fn appearance_frame(&self, oid: ObjectId) -> Option<AppearanceFrame> {
+        let widget = self.get_dictionary(oid).ok()?;
+        let rect = widget
+            .get(b"Rect")
+            .and_then(|rect| self.dereference(rect))
+            .ok()?
+            .1
+            .as_array()
+            .ok()?
+            .iter()
+            .map(|n| {
+                n.as_f64()
+                    .or_else(|_| n.as_i64().map(|n| n as f64))
+                    .unwrap_or(0.0) as f32
+            })
+            .collect::<Vec<_>>();
+        if rect.len() < 4 {
+            return None;
+        }
+
+        let rect_width = (rect[2] - rect[0]).abs();
+        let rect_height = (rect[3] - rect[1]).abs();
+
+        let widget_rotation = widget
+            .get(b"MK")
+            .and_then(|mk| self.dereference(mk))
+            .and_then(|(_, mk)| mk.as_dict())
+            .and_then(|mk| mk.get(b"R"))
+            .and_then(Object::as_i64)
+            .ok();
+        let page = self.widget_page(oid);
+        let rotation = match widget_rotation {
+            Some(rotation) => normalize_rotation(rotation),
+            None => page.map(|page| self.page_rotation(page)).unwrap_or(0),
+        };
+        let user_unit = page
+            .map(|page| self.page_transform(page).user_unit)
+            .unwrap_or(1.0);
+
+        let (width, height) = if rotation == 90 || rotation == 270 {
+            (rect_height, rect_width)
+        } else {
+            (rect_width, rect_height)
+        };
+
+        Some(AppearanceFrame {
+            width,
+            height,
+            rotation,
+            user_unit,
+        })
+    }
+}
+
+/// Reads a number that may be stored as an integer or a real
+pub(crate) fn number(object: &Object) -> f32 {
+    match object {
+        Object::Integer(n) => *n as f32,
+        Object::Real(n) => *n as f32,
+        _ => 0.0,
+    }
+}
+
+fn normalize_rotation(rotation: i64) -> i64 {
+    let rotation = rotation.rem_euclid(360);
+    rotation - rotation % 90
+}
@@ -0,0 +1,526 @@
+use lopdf::{Dictionary, Object, ObjectId};
+
+use crate::encoding::{decode_text_string, encode_text_string};
+use crate::field_id::MAX_FIELD_DEPTH;
+use crate::utils::{get_mapping_name, get_tooltip, DictionaryExt};
+use crate::{FieldError, FieldId, FieldKey, Form, ValueError};
+
+/// Entries a field inherits from its ancestors, copied onto a kid when it becomes standalone
+const INHERITABLE_KEYS: &[&[u8]] = &[b"FT", b"Ff", b"V", b"DV", b"DA", b"Q", b"Opt", b"MaxLen"];
+
+/// A field of the field tree of a form, as returned by `Form::field_tree`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldNode {
+    /// The id of the field dictionary in the document
+    pub object_id: ObjectId,
+    /// The partial name (`/T`) of the field
+    pub partial_name: Option<String>,
+    /// The partial names of the field and its ancestors joined with dots
+    pub qualified_name: Option<String>,
+    /// The index of the field in the form for terminal fields, `None` for the fields that only
+    /// group other fields
+    pub index: Option<usize>,
+    /// The child fields. Widget annotations are not listed.
+    pub children: Vec<FieldNode>,
+}
+
+impl Form {
+    /// Gets the ids of the kids (child fields or widget annotations) of the field at index `n`
+    ///
+    /// Returns an empty list if n is larger than the number of fields or the field is not part of
+    /// the form.
+    pub fn get_kids(&self, n: impl FieldKey) -> Vec<ObjectId> {
+        let n = match self.try_resolve(n) {
+            Ok(n) => n,
+            Err(_) => return Vec::new(),
+        };
+        self.kids_of(self.form_ids[n])
+    }
+
+    /// Gets the fully qualified name of the field at index `n`: the partial names of the field
+    /// and its ancestors joined with dots, e.g. `applicant.address.street`
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_qualified_name(&self, n: impl FieldKey) -> Option<String> {
+        let n = self.try_resolve(n).ok()?;
+        self.qualified_name(self.form_ids[n])
+    }
+
+    /// Gets the tree of fields of the form, starting from the top level fields of the AcroForm
+    /// `/Fields` array
+    pub fn field_tree(&self) -> Vec<FieldNode> {
+        self.top_level_fields()
+            .into_iter()
+            .map(|oid| self.field_node(oid, 0))
+            .collect()
+    }
+
+    fn field_node(&self, oid: ObjectId, depth: usize) -> FieldNode {
+        let children = if depth < MAX_FIELD_DEPTH {
+            self.kids_of(oid)
+                .into_iter()
+                // Kids without a name that are not fields of their own are widgets
+                .filter(|&kid| {
+                    self.index_of(kid).is_some() || self.get_partial_name_of(kid).is_some()
+                })
+                .map(|kid| self.field_node(kid, depth + 1))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        FieldNode {
+            object_id: oid,
+            partial_name: self.get_partial_name_of(oid),
+            qualified_name: self.qualified_name(oid),
+            index: self.index_of(oid),
+            children,
+        }
+    }
+
+    /// Gets the partial name (`/T`) of any field or widget object, including kids that are not
+    /// fields of their own
+    pub fn get_partial_name_of(&self, oid: ObjectId) -> Option<String> {
+        match self.get_dictionary(oid).ok()?.get(b"T") {
+            Ok(Object::String(name, _)) => Some(decode_text_string(name)),
+            _ => None,
+        }
+    }
+
+    /// Sets or, with `None`, removes the partial name (`/T`) of a field or widget object
+    pub fn set_partial_name_of(
+        &mut self,
+        oid: ObjectId,
+        name: Option<&str>,
+    ) -> Result<(), ValueError> {
+        let dict = self.dictionary_mut(oid)?;
+        match name {
+            Some(name) => dict.set_in_place("T", Object::string_literal(encode_text_string(name))),
+            None => {
+                dict.remove(b"T");
+            }
+        }
+        if let Some(n) = self.index_of(oid) {
+            self.refresh_field_info(n);
+        }
+        self.build_name_index();
+        Ok(())
+    }
+
+    /// Renames the field at index `n` by changing its partial name (`/T`). Its kids, whether
+    /// widgets or child fields, are left untouched and follow the new qualified name. Fails with
+    /// `ValueError::NameTaken` if a sibling field already has the name, as the qualified names
+    /// would then clash.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn rename_field(&mut self, n: impl FieldKey, new_name: &str) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let oid = self.form_ids[n];
+        self.check_new_name(self.get_parent_of(oid), new_name, Some(oid))?;
+
+        self.set_partial_name_of(oid, Some(new_name))?;
+        self.mark_modified(n);
+        Ok(())
+    }
+
+    /// Gets the tooltip of the field at index `n`, stored as its alternate name (`/TU`)
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_tooltip(&self, n: impl FieldKey) -> Option<String> {
+        let n = self.try_resolve(n).ok()?;
+        get_tooltip(self.get_dictionary(self.form_ids[n]).ok()?)
+    }
+
+    /// Sets or, with `None`, removes the tooltip of the field at index `n`. Viewers show it when
+    /// the pointer hovers the field, and screen readers read it in place of the field name.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_tooltip(&mut self, n: impl FieldKey, text: Option<&str>) -> Result<(), ValueError> {
+        self.set_field_text_entry(n, "TU", text)
+    }
+
+    /// Gets the mapping name (`/TM`) of the field at index `n`
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_mapping_name(&self, n: impl FieldKey) -> Option<String> {
+        let n = self.try_resolve(n).ok()?;
+        get_mapping_name(self.get_dictionary(self.form_ids[n]).ok()?)
+    }
+
+    /// Sets or, with `None`, removes the mapping name (`/TM`) of the field at index `n`, the name
+    /// under which its value is exported in place of the qualified name
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_mapping_name(
+        &mut self,
+        n: impl FieldKey,
+        name: Option<&str>,
+    ) -> Result<(), ValueError> {
+        self.set_field_text_entry(n, "TM", name)
+    }
+
+    /// Sets or removes a text string entry of a field
+    fn set_field_text_entry(
+        &mut self,
+        n: impl FieldKey,
+        key: &str,
+        text: Option<&str>,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let dict = self.dictionary_mut(self.form_ids[n])?;
+        match text {
+            Some(text) => dict.set_in_place(key, Object::string_literal(encode_text_string(text))),
+            None => {
+                dict.remove(key.as_bytes());
+            }
+        }
+        self.mark_modified(n);
+        Ok(())
+    }
+
+    /// Gets an entry of a field or widget object, following the `/Parent` chain up for inherited
+    /// entries
+    pub(crate) fn inherited_field_entry(&self, oid: ObjectId, key: &[u8]) -> Option<&Object> {
+        let mut current = Some(oid);
+        let mut depth = 0;
+
+        while let Some(id) = current {
+            let dict = self.get_dictionary(id).ok()?;
+            if let Ok(value) = dict.get(key) {
+                return self.dereference(value).ok().map(|(_, value)| value);
+            }
+            depth += 1;
+            if depth > MAX_FIELD_DEPTH {
+                return None;
+            }
+            current = dict.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+
+        None
+    }
+
+    /// Gets the parent (`/Parent`) of a field or widget object
+    pub fn get_parent_of(&self, oid: ObjectId) -> Option<ObjectId> {
+        match self.get_dictionary(oid).ok()?.get(b"Parent") {
+            Ok(Object::Reference(parent)) => Some(*parent),
+            _ => None,
+        }
+    }
+
+    /// Moves a field or widget object under a new parent, or to the top level of the form with
+    /// `None`. Both the `/Parent` entry and the `/Kids` (or AcroForm `/Fields`) arrays are updated.
+    ///
+    /// Fails with `ValueError::CyclicHierarchy` if `parent` is the object itself or one of its
+    /// descendants, and with `ValueError::NameTaken` if one of the new siblings has the same
+    /// partial name. The document is left untouched on failure.
+    pub fn set_parent_of(
+        &mut self,
+        oid: ObjectId,
+        parent: Option<ObjectId>,
+    ) -> Result<(), ValueError> {
+        if let Some(parent) = parent {
+            self.dictionary_mut(parent)?;
+            if self.is_ancestor_or_self(oid, parent) {
+                return Err(ValueError::CyclicHierarchy);
+            }
+        }
+        self.dictionary_mut(oid)?;
+        if let Some(name) = self.get_partial_name_of(oid) {
+            self.check_new_name(parent, &name, Some(oid))?;
+        }
+
+        self.detach(oid);
+        match parent {
+            Some(parent) => {
+                // The Kids array of the new parent may be an indirect object
+                let kids_id = match self
+                    .get_dictionary(parent)
+                    .and_then(|dict| dict.get(b"Kids"))
+                {
+                    Ok(Object::Reference(id)) => Some(*id),
+                    _ => None,
+                };
+                match kids_id.map(|id| self.get_object_mut(id).and_then(Object::as_array_mut)) {
+                    Some(Ok(kids)) => kids.push(oid.into()),
+                    _ => push_kid(self.dictionary_mut(parent)?, oid),
+                }
+                self.dictionary_mut(oid)?.set_in_place("Parent", parent);
+            }
+            None => {
+                if let Some(fields) = self.fields_array_mut() {
+                    fields.push(oid.into());
+                }
+            }
+        }
+
+        self.reindex();
+        Ok(())
+    }
+
+    /// Turns a kid into a standalone top level field named `name`. The entries it inherited from
+    /// its ancestors (type, flags, value, ...) are copied onto it first, so it keeps behaving the
+    /// same. Returns the index of the new field.
+    ///
+    /// Fails with `ValueError::InvalidName` or `ValueError::NameTaken` if `name` is not a valid
+    /// partial name or a top level field already has it, leaving the document untouched.
+    pub fn promote_kid(&mut self, oid: ObjectId, name: &str) -> Result<usize, ValueError> {
+        self.check_new_name(None, name, Some(oid))?;
+        let inherited = self.inherited_entries(oid);
+        let kid = self.dictionary_mut(oid)?;
+        for (key, value) in inherited {
+            if !kid.has(key) {
+                kid.set(key.to_vec(), value);
+            }
+        }
+        kid.set_in_place("T", Object::string_literal(encode_text_string(name)));
+
+        self.set_parent_of(oid, None)?;
+
+        self.index_of(oid).ok_or(ValueError::NotFound)
+    }
+
+    /// Gets the entries a field or widget object inherits from its ancestors, taking each from
+    /// the nearest ancestor that has it
+    pub(crate) fn inherited_entries(&self, oid: ObjectId) -> Vec<(&'static [u8], Object)> {
+        let mut inherited = Vec::new();
+        let mut ancestor = self.get_parent_of(oid);
+        while let Some(id) = ancestor {
+            if let Ok(dict) = self.get_dictionary(id) {
+                for key in INHERITABLE_KEYS {
+                    if !inherited.iter().any(|(k, _): &(&[u8], Object)| k == key) {
+                        if let Ok(value) = dict.get(key) {
+                            inherited.push((*key, value.clone()));
+                        }
+                    }
+                }
+            }
+            ancestor = self.get_parent_of(id);
+            if ancestor == Some(oid) {
+                break;
+            }
+        }
+        inherited
+    }
+
+    /// Groups several top level fields under a new parent field named `name`, so their qualified
+    /// names become `name.<partial name>`. Returns the id of the new parent.
+    ///
+    /// Fails with `ValueError::InvalidName` or `ValueError::NameTaken` if `name` is not a valid
+    /// partial name or a top level field already has it, or if two of the fields have the same
+    /// partial name. The document is left untouched on failure.
+    pub fn merge_fields(&mut self, fields: &[FieldId], name: &str) -> Result<ObjectId, ValueError> {
+        let ids = fields
+            .iter()
+            .map(|field| field.index_in(self).map(|n| self.form_ids[n]))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ValueError::NotFound)?;
+        self.check_new_name(None, name, None)?;
+        let mut names = ids
+            .iter()
+            .filter_map(|&id| self.get_partial_name_of(id))
+            .collect::<Vec<_>>();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        if names.len() != count {
+            return Err(ValueError::NameTaken);
+        }
+
+        let mut parent = Dictionary::new();
+        parent.set("T", Object::string_literal(encode_text_string(name)));
+        parent.set("Kids", Object::Array(Vec::new()));
+        let parent_id = self.document.add_object(parent);
+        if let Some(fields) = self.fields_array_mut() {
+            fields.push(parent_id.into());
+        }
+
+        for id in ids {
+            self.set_parent_of(id, Some(parent_id))?;
+        }
+
+        Ok(parent_id)
+    }
+
+    /// Checks that a field named `name` can be placed under `parent`, or at the top level with
+    /// `None`: the name must be valid and no other field than `except` may have it there
+    pub(crate) fn check_new_name(
+        &self,
+        parent: Option<ObjectId>,
+        name: &str,
+        except: Option<ObjectId>,
+    ) -> Result<(), ValueError> {
+        if name.is_empty() || name.contains('.') {
+            return Err(ValueError::InvalidName);
+        }
+
+        let siblings = match parent {
+            Some(parent) => self.kids_of(parent),
+            None => self.top_level_fields(),
+        };
+        let taken = siblings.into_iter().any(|sibling| {
+            Some(sibling) != except && self.get_partial_name_of(sibling).as_deref() == Some(name)
+        });
+        if taken {
+            Err(ValueError::NameTaken)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns true if `ancestor` is `oid` or one of the objects up its `/Parent` chain
+    fn is_ancestor_or_self(&self, ancestor: ObjectId, oid: ObjectId) -> bool {
+        let mut current = Some(oid);
+        let mut depth = 0;
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            depth += 1;
+            if depth > MAX_FIELD_DEPTH {
+                return false;
+            }
+            current = self.get_parent_of(id);
+        }
+        false
+    }
+
+    /// Gets the top level fields, listed in the AcroForm `/Fields` array
+    pub(crate) fn top_level_fields(&self) -> Vec<ObjectId> {
+        let fields = self
+            .acroform()
+            .and_then(|acroform| acroform.get(b"Fields").ok())
+            .and_then(|fields| self.dereference(fields).ok());
+
+        match fields {
+            Some((_, Object::Array(fields))) => fields
+                .iter()
+                .filter_map(|field| field.as_reference().ok())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Gets the kids of any field object
+    pub(crate) fn kids_of(&self, oid: ObjectId) -> Vec<ObjectId> {
+        let kids = self
+            .get_dictionary(oid)
+            .and_then(|dict| dict.get(b"Kids"))
+            .and_then(|kids| self.dereference(kids));
+
+        match kids {
+            Ok((_, Object::Array(kids))) => kids
+                .iter()
+                .filter_map(|kid| kid.as_reference().ok())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Removes an object from the `/Kids` of its parent, or from the AcroForm `/Fields`
+    pub(crate) fn detach(&mut self, oid: ObjectId) {
+        let is_target = |obj: &Object| matches!(obj, Object::Reference(id) if *id == oid);
+
+        match self.get_parent_of(oid) {
+            Some(parent) => {
+                let kids_id = match self
+                    .get_dictionary(parent)
+                    .and_then(|dict| dict.get(b"Kids"))
+                {
+                    Ok(Object::Reference(id)) => Some(*id),
+                    _ => None,
+                };
+                let kids = match kids_id {
+                    Some(id) => self.get_object_mut(id).and_then(Object::as_array_mut),
+                    None => self
+                        .get_object_mut(parent)
+                        .and_then(Object::as_dict_mut)
+                        .and_then(|dict| dict.get_mut(b"Kids"))
+                        .and_then(Object::as_array_mut),
+                };
+                if let Ok(kids) = kids {
+                    kids.retain(|kid| !is_target(kid));
+                }
+                if let Ok(dict) = self.get_object_mut(oid).and_then(Object::as_dict_mut) {
+                    dict.remove(b"Parent");
+                }
+            }
+            None => {
+                if let Some(fields) = self.fields_array_mut() {
+                    fields.retain(|field| !is_target(field));
+                }
+            }
+        }
+    }
+
+    /// Removes the references to any of `ids` from the `/Annots` of every page and from the
+    /// AcroForm `/Fields` and calculation order (`/CO`) arrays
+    pub(crate) fn remove_references(&mut self, ids: &[ObjectId]) {
+        let is_target = |obj: &Object| matches!(obj, Object::Reference(id) if ids.contains(id));
+
+        let pages = self.page_iter().collect::<Vec<_>>();
+        for page in pages {
+            let annots_id = match self
+                .get_dictionary(page)
+                .and_then(|dict| dict.get(b"Annots"))
+            {
+                Ok(Object::Reference(id)) => Some(*id),
+                _ => None,
+            };
+            let annots = match annots_id {
+                Some(id) => self.get_object_mut(id).and_then(Object::as_array_mut),
+                None => self
+                    .get_object_mut(page)
+                    .and_then(Object::as_dict_mut)
+                    .and_then(|dict| dict.get_mut(b"Annots"))
+                    .and_then(Object::as_array_mut),
+            };
+            if let Ok(annots) = annots {
+                annots.retain(|annot| !is_target(annot));
+            }
+        }
+
+        if let Some(fields) = self.fields_array_mut() {
+            fields.retain(|field| !is_target(field));
+        }
+
+        let co_id = match self.acroform().map(|acroform| acroform.get(b"CO")) {
+            Some(Ok(Object::Reference(id))) => Some(*id),
+            _ => None,
+        };
+        let co = match co_id {
+            Some(id) => self.get_object_mut(id).ok(),
+            None => self
+                .acroform_mut()
+                .and_then(|acroform| acroform.get_mut(b"CO").ok()),
+        };
+        if let Some(Object::Array(co)) = co {
+            co.retain(|field| !is_target(field));
+        }
+    }
+
+    /// Gets a mutable reference to the dictionary of a field or widget
+    ///
+    /// Fails with `ValueError::NotFound` if the object does not exist and with
+    /// `ValueError::MalformedField` if it is not a dictionary.
+    pub(crate) fn dictionary_mut(&mut self, oid: ObjectId) -> Result<&mut Dictionary, ValueError> {
+        match self.object_mut(oid) {
+            Some(Object::Dictionary(dict)) => Ok(dict),
+            Some(_) => Err(ValueError::MalformedField(FieldError::NotADictionary(oid))),
+            None => Err(ValueError::NotFound),
+        }
+    }
+}
+
+/// Appends a reference to the `/Kids` array of a field, creating the array if needed
+pub(crate) fn push_kid(parent: &mut Dictionary, kid: ObjectId) {
+    match parent.get_mut(b"Kids") {
+        Ok(Object::Array(kids)) => kids.push(kid.into()),
+        _ => parent.set("Kids", Object::Array(vec![kid.into()])),
+    }
+}
@@ -0,0 +1,220 @@
+use image::{ColorType, DynamicImage, GenericImageView, ImageFormat};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream};
+
+use crate::geometry::AppearanceFrame;
+use crate::style::background_operations;
+use crate::utils::DictionaryExt;
+use crate::{FieldKey, FieldType, Form, ValueError};
+
+/// Name of the image in the resources of the icon and appearance streams
+const IMAGE_NAME: &str = "Img";
+
+/// How the icon of a push button is scaled to its widget (`/MK /IF`). The icon is centered in
+/// every case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IconFit {
+    /// Scaled proportionally to the largest size that fits the widget
+    #[default]
+    Fit,
+    /// Scaled to fill the widget, ignoring the proportions of the image
+    Stretch,
+    /// Scaled down proportionally when it is larger than the widget, one pixel per point
+    /// otherwise
+    Shrink,
+    /// Drawn at one pixel per point, cropped to the widget
+    Original,
+}
+
+impl IconFit {
+    /// Builds the icon fit dictionary telling viewers how to scale the icon
+    fn to_dictionary(self) -> Dictionary {
+        let (scale_when, proportional) = match self {
+            IconFit::Fit => ("A", true),
+            IconFit::Stretch => ("A", false),
+            IconFit::Shrink => ("B", true),
+            IconFit::Original => ("N", true),
+        };
+        dictionary! {
+            "SW" => scale_when,
+            "S" => if proportional { "P" } else { "A" },
+            "A" => vec![0.5.into(), 0.5.into()],
+        }
+    }
+
+    /// Gets the size at which an image of `width` by `height` pixels is drawn in a frame
+    fn scaled_size(self, width: f32, height: f32, frame: &AppearanceFrame) -> (f32, f32) {
+        // One pixel per point, in the user space of the page
+        let (width, height) = (width / frame.user_unit, height / frame.user_unit);
+        let fit = (frame.width / width).min(frame.height / height);
+        match self {
+            IconFit::Fit => (width * fit, height * fit),
+            IconFit::Stretch => (frame.width, frame.height),
+            IconFit::Shrink => (width * fit.min(1.0), height * fit.min(1.0)),
+            IconFit::Original => (width, height),
+        }
+    }
+}
+
+impl Form {
+    /// Shows an image as the icon of the push button at index `n`, in place of its caption.
+    /// `image` holds a JPEG or PNG file, which is embedded as an image XObject, with its
+    /// transparency as a soft mask. `fit` tells how the image is scaled to the widget.
+    ///
+    /// The image is set as the normal icon of the button (`/MK /I`) and drawn in the normal
+    /// appearance of every widget, over the background of the widget.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field isn't a push button, or
+    /// `ValueError::InvalidImage` if the image cannot be decoded.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_button_icon(
+        &mut self,
+        n: impl FieldKey,
+        image: &[u8],
+        fit: IconFit,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        if self.try_get_type(n)? != FieldType::Button {
+            return Err(self.type_mismatch(n, &[FieldType::Button]));
+        }
+
+        let decoded = image::load_from_memory(image).map_err(|_| ValueError::InvalidImage)?;
+        let (width, height) = decoded.dimensions();
+        let (width, height) = (width as f32, height as f32);
+        let image_id = self.add_image(image, &decoded);
+
+        // The icon viewers use when they regenerate the appearance of the button
+        let icon = image_form_dictionary(image_id, [0.0, 0.0, width, height]);
+        let content = image_content([width, 0.0, 0.0, height, 0.0, 0.0])?;
+        let icon = self.document.add_object(Stream::new(icon, content));
+
+        let style = self.get_widget_appearance(n).unwrap_or_default();
+        for widget in self.widgets_of(self.form_ids[n]) {
+            let frame = self.appearance_frame(widget).ok_or(ValueError::NotFound)?;
+            let (icon_width, icon_height) = fit.scaled_size(width, height, &frame);
+            let x = 0.5 * (frame.width - icon_width);
+            let y = 0.5 * (frame.height - icon_height);
+
+            let mut dict = image_form_dictionary(image_id, [0.0, 0.0, frame.width, frame.height]);
+            frame.apply(&mut dict);
+            let mut content = Content {
+                operations: background_operations(&frame, &style),
+            }
+            .encode()
+            .map_err(|_| ValueError::NotFound)?;
+            content.extend(image_content([icon_width, 0.0, 0.0, icon_height, x, y])?);
+            let appearance = self.document.add_object(Stream::new(dict, content));
+
+            self.write_icon_entries(widget, icon, fit, appearance)
+                .map_err(|_| ValueError::NotFound)?;
+        }
+        self.mark_modified(n);
+
+        Ok(())
+    }
+
+    /// Embeds an image as an image XObject. JPEG files are embedded as they are, other images
+    /// are decoded and compressed, with their alpha channel as a soft mask.
+    pub(crate) fn add_image(&mut self, bytes: &[u8], decoded: &DynamicImage) -> ObjectId {
+        let (width, height) = decoded.dimensions();
+        let gray = matches!(
+            decoded.color(),
+            ColorType::L8 | ColorType::L16 | ColorType::La8 | ColorType::La16
+        );
+        let mut dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width as i64,
+            "Height" => height as i64,
+            "ColorSpace" => if gray { "DeviceGray" } else { "DeviceRGB" },
+            "BitsPerComponent" => 8,
+        };
+
+        if image::guess_format(bytes).is_ok_and(|format| format == ImageFormat::Jpeg) {
+            dict.set("Filter", "DCTDecode");
+            return self.document.add_object(Stream::new(dict, bytes.to_vec()));
+        }
+
+        if decoded.color().has_alpha() {
+            let alpha = decoded
+                .to_rgba8()
+                .pixels()
+                .map(|pixel| pixel[3])
+                .collect::<Vec<_>>();
+            let mut mask = Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => width as i64,
+                    "Height" => height as i64,
+                    "ColorSpace" => "DeviceGray",
+                    "BitsPerComponent" => 8,
+                },
+                alpha,
+            );
+            let _ = mask.compress();
+            dict.set("SMask", self.document.add_object(mask));
+        }
+
+        let pixels = if gray {
+            decoded.to_luma8().into_raw()
+        } else {
+            decoded.to_rgb8().into_raw()
+        };
+        let mut stream = Stream::new(dict, pixels);
+        let _ = stream.compress();
+        self.document.add_object(stream)
+    }
+
+    /// Sets the icon entries of a widget and its new normal appearance. The caption is no longer
+    /// shown, and the down appearance, which would show it, is dropped.
+    fn write_icon_entries(
+        &mut self,
+        widget: ObjectId,
+        icon: ObjectId,
+        fit: IconFit,
+        appearance: ObjectId,
+    ) -> Result<(), lopdf::Error> {
+        let dict = self.get_dictionary(widget)?;
+        let mut mk = match dict.get(b"MK") {
+            Ok(mk) => self.dereference(mk)?.1.as_dict()?.clone(),
+            Err(_) => Dictionary::new(),
+        };
+        mk.set_in_place("I", icon);
+        mk.set_in_place("IF", fit.to_dictionary());
+        // Icon only, no caption
+        mk.set_in_place("TP", 1);
+
+        let dict = self.get_object_mut(widget)?.as_dict_mut()?;
+        dict.set_in_place("MK", mk);
+        dict.set_in_place("AP", dictionary! { "N" => appearance });
+        Ok(())
+    }
+}
+
+/// Creates the dictionary of a form XObject drawing an image within `bbox`
+fn image_form_dictionary(image: ObjectId, bbox: [f32; 4]) -> Dictionary {
+    dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Form",
+        "BBox" => bbox.iter().map(|&v| v.into()).collect::<Vec<Object>>(),
+        "Resources" => dictionary! {
+            "XObject" => dictionary! { IMAGE_NAME => image },
+        },
+    }
+}
+
+/// Builds the content drawing the image of a form XObject with the transformation `matrix`
+fn image_content(matrix: [f32; 6]) -> Result<Vec<u8>, ValueError> {
+    let operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new("cm", matrix.iter().map(|&v| v.into()).collect()),
+        Operation::new("Do", vec![Object::Name(IMAGE_NAME.as_bytes().to_vec())]),
+        Operation::new("Q", vec![]),
+    ];
+    Content { operations }
+        .encode()
+        .map_err(|_| ValueError::NotFound)
+}
@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::signature::{byte_ranges, is_placeholder};
+use crate::utils::strip_xref_stream_keys;
+use crate::writer::{object_to_bytes, write_indirect_object, write_object};
+use crate::Form;
+
+impl Form {
+    /// Saves the form as an incremental update appended to `original`, the bytes of the file the
+    /// form was loaded from. Only the objects that differ from the original are written, so every
+    /// byte of the original file, including the ranges covered by existing signatures, is kept.
+    ///
+    /// The update uses the same kind of cross-reference section as the last revision of the
+    /// original: a classic table, or a cross-reference stream for files relying on them.
+    ///
+    /// Before anything is written to `target`, the output is reloaded and every signature of the
+    /// original is checked to keep its `/ByteRange` and to cover the same bytes as in the
+    /// original; an `InvalidData` error is returned otherwise.
+    pub fn save_incremental<W: Write>(
+        &mut self,
+        original: &[u8],
+        target: &mut W,
+    ) -> Result<(), io::Error> {
+        let base = Document::load_mem(original)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+        self.regenerate_pending_appearances();
+
+        // Adding our PieceInfo to the catalog of a signed document, or of one carrying usage
+        // rights, would be reported as a disallowed change by some validators
+        if self.signature_byte_ranges().is_empty() && !self.has_usage_rights() {
+            self.record_piece_info();
+        }
+
+        let mut output = original.to_vec();
+        if !output.ends_with(b"\n") {
+            output.push(b'\n');
+        }
+
+        // Write the changed and new objects
+        let mut entries: BTreeMap<u32, XrefEntry> = BTreeMap::new();
+        for (id, object) in self.objects() {
+            if is_structural(object) {
+                continue;
+            }
+            let changed = match base.objects.get(&id) {
+                Some(old) => object_to_bytes(old) != object_to_bytes(object),
+                None => true,
+            };
+            if changed {
+                entries.insert(id.0, XrefEntry::InUse(output.len(), id.1));
+                write_indirect_object(&mut output, id, object)?;
+            }
+        }
+
+        // Objects that were deleted become free entries
+        let removed = base
+            .objects
+            .iter()
+            .filter(|(id, object)| !is_structural(object) && !self.contains_object(**id))
+            .map(|(id, _)| *id)
+            .collect::<Vec<ObjectId>>();
+        for id in removed {
+            entries.insert(id.0, XrefEntry::Free(id.1.saturating_add(1)));
+        }
+
+        if entries.is_empty() {
+            return target.write_all(original);
+        }
+
+        let mut trailer = self.document.trailer.clone();
+        strip_xref_stream_keys(&mut trailer);
+        let prev = find_startxref(original);
+        if let Some(prev) = prev {
+            trailer.set("Prev", prev as i64);
+        }
+
+        let size = self.document.max_id.max(base.max_id) + 1;
+        let uses_xref_stream = prev
+            .and_then(|prev| original.get(prev..))
+            .is_some_and(|section| !section.starts_with(b"xref"));
+
+        let xref_start = output.len();
+        if uses_xref_stream {
+            // The stream is an object of its own, it takes the next free number
+            entries.insert(size, XrefEntry::InUse(xref_start, 0));
+            trailer.set("Size", i64::from(size + 1));
+            let stream = xref_stream(trailer, &entries);
+            write_indirect_object(&mut output, (size, 0), &Object::Stream(stream))?;
+        } else {
+            write_xref_table(&mut output, &entries)?;
+            trailer.set("Size", i64::from(size));
+            output.extend_from_slice(b"trailer\n");
+            write_object(&mut output, &Object::Dictionary(trailer))?;
+            output.push(b'\n');
+        }
+        write!(output, "startxref\n{}\n%%EOF\n", xref_start)?;
+
+        verify_signatures(original, &base, &output)?;
+
+        target.write_all(&output)
+    }
+}
+
+/// Checks that the signatures of the original file still hold in the output: once reloaded,
+/// each signature dictionary must have the same `/ByteRange`, covering the same bytes
+fn verify_signatures(original: &[u8], base: &Document, output: &[u8]) -> Result<(), io::Error> {
+    let signatures = signature_ranges(base);
+    if signatures.is_empty() {
+        return Ok(());
+    }
+    let reloaded = Document::load_mem(output)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+    let updated = signature_ranges(&reloaded);
+
+    for (id, ranges) in signatures {
+        // A signature whose ranges don't fit the original was broken already
+        let signed = match signed_bytes(original, &ranges) {
+            Some(signed) => signed,
+            None => continue,
+        };
+        if updated.get(&id) != Some(&ranges) || signed_bytes(output, &ranges) != Some(signed) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the update would change the bytes signed by the object {} {} R",
+                    id.0, id.1
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Gets the bytes of a file covered by the ranges of a signature, `None` if they don't fit in it
+fn signed_bytes<'a>(file: &'a [u8], ranges: &[(usize, usize)]) -> Option<Vec<&'a [u8]>> {
+    ranges
+        .iter()
+        .map(|&(offset, length)| file.get(offset..offset.checked_add(length)?))
+        .collect()
+}
+
+/// Gets the byte ranges of the signature dictionaries of a document, by the id of the object
+/// holding them: the signature dictionary itself, or a field with a direct `/V` dictionary.
+/// Placeholders of signatures prepared but not signed yet are left out.
+fn signature_ranges(doc: &Document) -> BTreeMap<ObjectId, Vec<(usize, usize)>> {
+    doc.objects
+        .iter()
+        .filter_map(|(&id, object)| {
+            let dict = object.as_dict().ok()?;
+            let value = match dict.get(b"V") {
+                Ok(Object::Dictionary(value)) => value,
+                _ => dict,
+            };
+            let ranges = byte_ranges(value)?;
+            if ranges.is_empty() || is_placeholder(&ranges) {
+                None
+            } else {
+                Some((id, ranges))
+            }
+        })
+        .collect()
+}
+
+/// An entry of the cross-reference table of the update
+pub(crate) enum XrefEntry {
+    /// Offset and generation of an object written in the update
+    InUse(usize, u16),
+    /// Generation to use if the object number is reused
+    Free(u16),
+}
+
+/// Object streams and cross-reference streams are rebuilt by the writer, they are never copied
+fn is_structural(object: &Object) -> bool {
+    matches!(object.type_name(), Ok("ObjStm") | Ok("XRef"))
+}
+
+/// Finds the offset of the last cross-reference section of a file
+fn find_startxref(bytes: &[u8]) -> Option<usize> {
+    let keyword = b"startxref";
+    let position = bytes
+        .windows(keyword.len())
+        .rposition(|window| window == keyword)?;
+
+    let number = bytes[position + keyword.len()..]
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take_while(|b| b.is_ascii_digit())
+        .map(|&b| b as char)
+        .collect::<String>();
+
+    number.parse().ok()
+}
+
+/// Groups the ids of the entries into runs of consecutive numbers, as `(first id, count)`
+fn subsections(entries: &BTreeMap<u32, XrefEntry>) -> Vec<(u32, usize)> {
+    let mut res: Vec<(u32, usize)> = Vec::new();
+    for &id in entries.keys() {
+        match res.last_mut() {
+            Some((start, count)) if *start + *count as u32 == id => *count += 1,
+            _ => res.push((id, 1)),
+        }
+    }
+    res
+}
+
+/// Builds a cross-reference stream holding the entries, with the trailer entries in its
+/// dictionary
+fn xref_stream(trailer: Dictionary, entries: &BTreeMap<u32, XrefEntry>) -> Stream {
+    let mut dict = trailer;
+    dict.set("Type", "XRef");
+    dict.set(
+        "Index",
+        subsections(entries)
+            .into_iter()
+            .flat_map(|(start, count)| vec![Object::from(i64::from(start)), (count as i64).into()])
+            .collect::<Vec<_>>(),
+    );
+    // Type (1 byte), offset or next free object (4 bytes) and generation (2 bytes)
+    dict.set("W", vec![1.into(), 4.into(), 2.into()]);
+
+    let mut content = Vec::new();
+    for entry in entries.values() {
+        let (kind, field2, generation) = match *entry {
+            XrefEntry::InUse(offset, generation) => (1u8, offset as u32, generation),
+            XrefEntry::Free(generation) => (0u8, 0, generation),
+        };
+        content.push(kind);
+        content.extend_from_slice(&field2.to_be_bytes());
+        content.extend_from_slice(&generation.to_be_bytes());
+    }
+
+    Stream::new(dict, content)
+}
+
+/// Writes a classic cross-reference table with one subsection per run of consecutive ids
+pub(crate) fn write_xref_table(
+    output: &mut Vec<u8>,
+    entries: &BTreeMap<u32, XrefEntry>,
+) -> io::Result<()> {
+    output.extend_from_slice(b"xref\n");
+
+    let ids = entries.keys().copied().collect::<Vec<_>>();
+    let mut ids = ids.iter();
+    for (start, count) in subsections(entries) {
+        writeln!(output, "{} {}", start, count)?;
+        for id in ids.by_ref().take(count) {
+            match entries[id] {
+                XrefEntry::InUse(offset, generation) => {
+                    writeln!(output, "{:010} {:05} n\r", offset, generation)?
+                }
+                XrefEntry::Free(generation) => writeln!(output, "{:010} {:05} f\r", 0, generation)?,
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use crate::{ChoiceOption, FieldType, Form};
+
+/// Metadata of a field parsed once, when the form is loaded, so that repeated queries don't
+/// walk its dictionaries, kids and appearances again. It is parsed again whenever the field is
+/// edited through `Form`, and entirely when the field tree changes.
+#[derive(Debug, Clone)]
+pub(crate) struct FieldInfo {
+    pub(crate) field_type: FieldType,
+    pub(crate) name: Option<String>,
+    pub(crate) flags: u32,
+    /// Items of list boxes and combo boxes
+    pub(crate) options: Vec<ChoiceOption>,
+    /// On states of the buttons of a radio group, or the on state of a checkbox
+    pub(crate) states: Vec<String>,
+    pub(crate) rect: Option<[f32; 4]>,
+    pub(crate) page: Option<usize>,
+}
+
+impl Form {
+    /// Parses the metadata of every field
+    pub(crate) fn build_field_info(&mut self) {
+        // Parse from the dictionaries, not from the metadata being replaced
+        self.field_info = Vec::new();
+        let info = (0..self.len())
+            .map(|n| Some(self.parse_field_info(n)))
+            .collect();
+        self.field_info = info;
+    }
+
+    /// Parses the metadata of the field at index `n` again, after it was edited
+    pub(crate) fn refresh_field_info(&mut self, n: usize) {
+        match self.field_info.get_mut(n) {
+            Some(info) => *info = None,
+            None => return,
+        }
+        let info = self.parse_field_info(n);
+        self.field_info[n] = Some(info);
+    }
+
+    /// Gets the parsed metadata of the field at index `n`, if it was parsed
+    pub(crate) fn field_info(&self, n: usize) -> Option<&FieldInfo> {
+        self.field_info.get(n).and_then(Option::as_ref)
+    }
+
+    /// Parses the field tree and the metadata of the fields again. Call it after editing
+    /// `document` directly, e.g. adding fields or changing their types, flags or options, as the
+    /// metadata of the fields is only read once from the document. `form_ids` is rebuilt from the
+    /// field tree, along with the index `FieldId`s are resolved through.
+    pub fn reindex_fields(&mut self) {
+        self.reindex();
+    }
+
+    /// Gets the on state of every button of the radio group at index `n`
+    pub(crate) fn radio_states(&self, n: usize) -> Vec<String> {
+        match self.field_info(n) {
+            Some(info) => info.states.clone(),
+            None => self.get_possibilities(self.form_ids[n]),
+        }
+    }
+
+    /// Gets the on state of the checkbox at index `n`
+    pub(crate) fn check_box_on_state(&self, n: usize) -> String {
+        match self.field_info(n).and_then(|info| info.states.first()) {
+            Some(state) => state.clone(),
+            None => self.field_on_state(self.form_ids[n]),
+        }
+    }
+
+    /// Parses the metadata of the field at index `n`. A malformed field is kept as a field of
+    /// unknown type rather than failing the load.
+    fn parse_field_info(&self, n: usize) -> FieldInfo {
+        let field_type = self.try_get_type(n).unwrap_or(FieldType::Unknown);
+        let states = match field_type {
+            FieldType::Radio => self.radio_states(n),
+            FieldType::CheckBox => vec![self.check_box_on_state(n)],
+            _ => Vec::new(),
+        };
+
+        FieldInfo {
+            field_type,
+            name: self.try_get_name(n).ok().flatten(),
+            flags: self.get_field_flags_raw(n),
+            options: self.get_options(n),
+            states,
+            rect: self.get_rect(n),
+            page: self.get_page(n),
+        }
+    }
+}
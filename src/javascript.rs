@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+
+use lopdf::{dictionary, Dictionary, Object};
+
+use crate::encoding::{decode_text_string, encode_text_string};
+use crate::{Form, ValueError};
+
+/// A document-level JavaScript of the catalog `/Names /JavaScript` tree, run by viewers when
+/// the document is opened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentScript {
+    /// The name of the script in the tree
+    pub name: String,
+    /// The script of the JavaScript action, or `None` if the action is not a JavaScript action
+    pub script: Option<String>,
+}
+
+impl Form {
+    /// Gets the document-level JavaScripts, in the order of the name tree
+    pub fn get_document_scripts(&self) -> Vec<DocumentScript> {
+        self.name_tree(b"JavaScript")
+            .into_iter()
+            .map(|(name, action)| {
+                let script = self
+                    .dereference(&action)
+                    .and_then(|(_, action)| action.as_dict())
+                    .ok()
+                    .and_then(|action| self.action_script(action));
+                DocumentScript {
+                    name: decode_text_string(&name),
+                    script,
+                }
+            })
+            .collect()
+    }
+
+    /// Adds the document-level JavaScript `script` named `name`, e.g. helper functions called
+    /// by the scripts of the fields. A script with the same name is replaced.
+    ///
+    /// Fails with `ValueError::NotFound` if the document has no catalog.
+    pub fn add_document_script(&mut self, name: &str, script: &str) -> Result<(), ValueError> {
+        let key = encode_text_string(name);
+        let mut entries = self.name_tree(b"JavaScript");
+        entries.retain(|(existing, _)| decode_text_string(existing) != name);
+
+        let action = self.document.add_object(dictionary! {
+            "S" => "JavaScript",
+            "JS" => Object::string_literal(encode_text_string(script)),
+        });
+        entries.push((key, action.into()));
+        self.set_name_tree(b"JavaScript", entries)
+    }
+
+    /// Removes the document-level JavaScript named `name`
+    ///
+    /// Fails with `ValueError::NotFound` if there is no such script.
+    pub fn remove_document_script(&mut self, name: &str) -> Result<(), ValueError> {
+        let mut entries = self.name_tree(b"JavaScript");
+        let len = entries.len();
+        entries.retain(|(existing, _)| decode_text_string(existing) != name);
+        if entries.len() == len {
+            return Err(ValueError::NotFound);
+        }
+        self.set_name_tree(b"JavaScript", entries)
+    }
+
+    /// Removes every JavaScript of the document, for pipelines that must deliver script-free
+    /// files: the document-level scripts, the JavaScript additional actions (`/AA`) of the
+    /// fields, widgets, pages and catalog, the JavaScript actions (`/A`) of buttons and links,
+    /// and a JavaScript `/OpenAction`. The other actions are kept.
+    pub fn remove_all_javascript(&mut self) {
+        let _ = self.set_name_tree(b"JavaScript", Vec::new());
+
+        // Indirect JavaScript actions, and additional-actions dictionaries referenced by id
+        let mut scripts = HashSet::new();
+        let mut additional = HashSet::new();
+        for (id, object) in self.objects() {
+            let dict = match object {
+                Object::Dictionary(dict) => dict,
+                Object::Stream(stream) => &stream.dict,
+                _ => continue,
+            };
+            if is_javascript_action(dict) {
+                scripts.insert(id);
+            }
+            if let Ok(Object::Reference(aa)) = dict.get(b"AA") {
+                additional.insert(*aa);
+            }
+        }
+        let is_script = |action: &Object| match action {
+            Object::Reference(id) => scripts.contains(id),
+            Object::Dictionary(action) => is_javascript_action(action),
+            _ => false,
+        };
+
+        // Only the objects that may hold scripts are modified, the others stay shared with the
+        // template of the form
+        let candidates = self
+            .objects()
+            .filter(|(id, object)| match object {
+                Object::Dictionary(dict) => may_hold_scripts(dict),
+                Object::Stream(stream) => may_hold_scripts(&stream.dict),
+                _ => false,
+            } || additional.contains(id))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in candidates {
+            let dict = match self.get_object_mut(id) {
+                Ok(Object::Dictionary(dict)) => dict,
+                Ok(Object::Stream(stream)) => &mut stream.dict,
+                _ => continue,
+            };
+            for key in [&b"A"[..], b"OpenAction", b"Next"] {
+                if dict.get(key).is_ok_and(is_script) {
+                    dict.remove(key);
+                }
+            }
+            if let Ok(Object::Array(next)) = dict.get_mut(b"Next") {
+                next.retain(|action| !is_script(action));
+            }
+            if additional.contains(&id) {
+                remove_scripts(dict, &is_script);
+            }
+            let empty = match dict.get_mut(b"AA") {
+                Ok(Object::Dictionary(aa)) => {
+                    remove_scripts(aa, &is_script);
+                    aa.is_empty()
+                }
+                _ => false,
+            };
+            if empty {
+                dict.remove(b"AA");
+            }
+        }
+
+        for id in scripts {
+            self.remove_object(id);
+        }
+    }
+}
+
+/// Returns true if the dictionary has one of the entries `remove_all_javascript` edits
+fn may_hold_scripts(dict: &Dictionary) -> bool {
+    [&b"A"[..], b"OpenAction", b"Next", b"AA"]
+        .iter()
+        .any(|key| dict.has(key))
+}
+
+/// Returns true if the dictionary is a JavaScript action
+fn is_javascript_action(dict: &Dictionary) -> bool {
+    matches!(dict.get(b"S").and_then(Object::as_name), Ok(b"JavaScript"))
+}
+
+/// Removes the JavaScript actions of an additional-actions dictionary
+fn remove_scripts(aa: &mut Dictionary, is_script: &dyn Fn(&Object) -> bool) {
+    let keys = aa
+        .iter()
+        .filter(|(_, action)| is_script(action))
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    for key in keys {
+        aa.remove(&key);
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{FieldType, FieldValue, FillReport, Form};
+
+/// A field as exported by `Form::to_json`
+#[derive(Serialize)]
+struct JsonField {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    field_type: FieldType,
+    value: Option<FieldValue>,
+}
+
+impl Form {
+    /// Exports the fully qualified name, type and value of every field as a JSON array
+    pub fn to_json(&self) -> String {
+        let fields = (0..self.len())
+            .map(|n| JsonField {
+                name: self.qualified_name(self.form_ids[n]),
+                field_type: self.try_get_type(n).unwrap_or(FieldType::Unknown),
+                value: self.get_value(n),
+            })
+            .collect::<Vec<_>>();
+
+        // Serializing plain strings and enums cannot fail
+        serde_json::to_string(&fields).unwrap_or_default()
+    }
+
+    /// Fills the form from a JSON object mapping field names to values, see `fill_from_map`.
+    ///
+    /// Strings, numbers and `null` (which clears the field) are given as text, booleans toggle
+    /// checkboxes and arrays select the options of list boxes.
+    pub fn fill_from_json(&mut self, json: &str) -> Result<FillReport, serde_json::Error> {
+        let object: HashMap<String, Value> = serde_json::from_str(json)?;
+
+        let mut values = HashMap::new();
+        for (name, value) in object {
+            let value = match value {
+                Value::Bool(checked) => FieldValue::Checked(checked),
+                Value::String(text) => FieldValue::Text(text),
+                Value::Null => FieldValue::Text(String::new()),
+                Value::Array(items) => FieldValue::Choices(items.iter().map(json_text).collect()),
+                value => FieldValue::Text(json_text(&value)),
+            };
+            values.insert(name, value);
+        }
+
+        Ok(self.fill_from_map(&values))
+    }
+}
+
+fn json_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
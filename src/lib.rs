@@ -1,35 +1,177 @@
 #[macro_use]
 extern crate bitflags;
 #[macro_use]
-extern crate derive_error;
-
+extern crate log;
+
+mod action;
+mod appearance;
+mod attachments;
+#[cfg(feature = "barcode")]
+mod barcode;
+mod bidi;
+mod builder;
+mod calculate;
+mod certification;
+mod choice;
+mod cjk;
+mod create;
+#[cfg(feature = "chrono")]
+mod date;
+mod deferred;
+mod diff;
+mod dss;
+mod duplicate;
+/// Conversions between Rust strings and the encodings of PDF strings: PDFDocEncoding and UTF-16BE
+/// for text strings like field names and values, WinAnsiEncoding for the text drawn with the
+/// standard fonts in appearance streams
+pub mod encoding;
+mod encryption;
+mod field;
+mod field_id;
+mod flags;
+mod format;
+mod geometry;
+mod hierarchy;
+mod icon;
+mod incremental;
+mod info;
+mod javascript;
+#[cfg(feature = "serde")]
+mod json;
+mod loader;
+mod lock;
+mod merge;
+mod metadata;
+mod metrics;
+mod name_tree;
+mod names;
+mod objects;
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "barcode")]
+mod pdf417;
+mod piece_info;
+mod preservation;
+mod reader;
+mod save;
+mod save_policy;
+mod signature;
+mod signature_appearance;
+mod signing;
+mod style;
+mod tab_order;
+mod template;
+mod timestamp;
 mod utils;
-
-use std::collections::VecDeque;
+mod validate;
+mod value;
+mod version;
+mod writer;
+mod xfa;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs::File;
 use std::io;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::str;
+use std::sync::Arc;
 
-use bitflags::_core::str::from_utf8;
-
-use lopdf::content::{Content, Operation};
+use lopdf::content::Operation;
 use lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
-
+use thiserror::Error;
+
+pub use crate::action::{ActionTrigger, AdditionalAction, ButtonAction, SubmitFormat};
+pub use crate::appearance::{Color, TextAlignment};
+pub use crate::attachments::EmbeddedFile;
+#[cfg(feature = "barcode")]
+pub use crate::barcode::{QrErrorCorrection, Symbology};
+pub use crate::builder::FormBuilder;
+pub use crate::certification::MdpPermissions;
+use crate::choice::export_value;
+pub use crate::choice::ChoiceOption;
+pub use crate::cjk::CjkFont;
+pub use crate::create::{ChoiceFieldOptions, TextFieldOptions};
+pub use crate::diff::FieldDiff;
+use crate::encoding::{decode_text_string, encode_text_string};
+pub use crate::encryption::{EncryptionOptions, Permissions};
+pub use crate::field::{Field, FieldMut, Fields};
+pub use crate::field_id::{FieldId, FieldKey};
+pub use crate::flags::{AnnotationFlags, ButtonFieldFlags, ChoiceFieldFlags, TextFieldFlags};
+pub use crate::geometry::PageTransform;
+pub use crate::hierarchy::FieldNode;
+pub use crate::icon::IconFit;
+use crate::info::FieldInfo;
+pub use crate::javascript::DocumentScript;
+pub use crate::loader::{FormLoader, LoadOptions};
+pub use crate::lock::FieldLock;
+pub use crate::merge::{merge, NameSuffix};
+pub use crate::metadata::Metadata;
+pub use crate::metrics::{measure_text, StandardFont};
+use crate::objects::{Objects, SharedObjects};
+pub use crate::reader::{FieldSnapshot, FormReader};
+pub use crate::save::{SaveOptions, StreamCompression};
+use crate::save_policy::SaveMode;
+pub use crate::save_policy::SavePolicy;
+pub use crate::signature::SignatureStatus;
+pub use crate::signature_appearance::SignatureAppearance;
+pub use crate::signing::SignatureSlot;
+pub use crate::style::{BorderStyle, WidgetStyle};
+pub use crate::tab_order::TabPolicy;
+pub use crate::template::{FormInstance, FormTemplate};
+pub use crate::timestamp::TimestampClient;
 use crate::utils::*;
+pub use crate::validate::{ValidationIssue, ValidationIssueKind};
+pub use crate::value::{CopyReport, FieldValue, FillReport};
+pub use crate::version::{AssociatedFile, PdfVersion};
 
 /// A PDF Form that contains fillable fields
 ///
 /// Use this struct to load an existing PDF with a fillable form using the `load` method.  It will
 /// analyze the PDF and identify the fields. Then you can get and set the content of the fields by
 /// index.
+///
+/// Methods taking a field index or a `FieldId` don't panic when the field is not part of the
+/// form: they fail with `ValueError::NotFound`, or return `None` or an empty value. The
+/// exceptions are `get_type`, `get_name`, `get_state` and `get_object_id`, whose `try_` variants
+/// return a `FieldError` instead.
+///
+/// `Form` is `Send + Sync`. To serve field metadata from several threads while a single writer
+/// fills values, take a `FormReader` snapshot with `reader`.
+#[derive(Clone)]
 pub struct Form {
+    /// The parsed document. On a form spawned from a `FormTemplate`, it only holds the objects
+    /// the form added or modified, the others being shared with the template.
     pub document: Document,
     pub form_ids: Vec<ObjectId>,
+    names: HashMap<String, usize>,
+    field_indices: HashMap<ObjectId, usize>,
+    page_fields: Vec<Vec<usize>>,
+    field_info: Vec<Option<FieldInfo>>,
+    appearance_overrides: HashMap<String, AppearanceOverride>,
+    modified_fields: BTreeSet<String>,
+    preservation_mode: bool,
+    truncate_to_max_len: bool,
+    deferred_appearances: bool,
+    sync_xfa: bool,
+    save_policy: SavePolicy,
+    original: Option<Arc<Vec<u8>>>,
+    pending_appearances: HashMap<ObjectId, TextAppearanceOptions>,
+    cjk_font: CjkFont,
+    target_version: Option<PdfVersion>,
+    sort_by_position: bool,
+    shared_objects: Option<SharedObjects>,
+    removed_objects: BTreeSet<ObjectId>,
 }
 
+/// A closure that post-processes, or fully replaces, the operations generated for the
+/// appearance stream of a field
+pub type AppearanceOverride = Arc<dyn Fn(&mut Vec<Operation>) + Send + Sync>;
+
 /// The possible types of fillable form fields in a PDF
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum FieldType {
     Button,
     Radio,
@@ -37,41 +179,155 @@ pub enum FieldType {
     ListBox,
     ComboBox,
     Text,
+    Signature,
     Unknown,
 }
 
-#[derive(Debug, Error)]
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldType::Button => "push button",
+            FieldType::Radio => "radio button",
+            FieldType::CheckBox => "check box",
+            FieldType::ListBox => "list box",
+            FieldType::ComboBox => "combo box",
+            FieldType::Text => "text field",
+            FieldType::Signature => "signature field",
+            FieldType::Unknown => "unknown field",
+        })
+    }
+}
+
 /// Errors that may occur while loading a PDF
+#[derive(Debug, Error)]
 pub enum LoadError {
     /// An Lopdf Error
-    LopdfError(lopdf::Error),
+    #[error("the document could not be parsed")]
+    LopdfError(#[from] lopdf::Error),
     /// The reference `ObjectId` did not point to any values
-    #[error(non_std, no_from)]
+    #[error("the reference {} {} R does not point to any object", .0 .0, .0 .1)]
     NoSuchReference(ObjectId),
     /// An element that was expected to be a reference was not a reference
+    #[error("an element that should be a reference is not a reference")]
     NotAReference,
-    // Add: Error for incorrect structures
-    #[error(msg_embedded, non_std, no_from)]
-    StructureError(String)
+    /// The structure of the form is invalid
+    #[error("invalid form structure: {0}")]
+    StructureError(String),
+    /// Merging would discard private or unknown entries of a form in preservation mode
+    #[error("merging would discard private or unknown entries in preservation mode")]
+    WouldDiscardData,
+}
+
+/// The field an error is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldContext {
+    /// The index of the field in the form
+    pub index: usize,
+    /// The fully qualified name of the field, if it has one
+    pub name: Option<String>,
+}
+
+impl fmt::Display for FieldContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "field `{}` (index {})", name, self.index),
+            None => write!(f, "field at index {}", self.index),
+        }
+    }
 }
 
 /// Errors That may occur while setting values in a form
 #[derive(Debug, Error)]
 pub enum ValueError {
     /// The method used to set the state is incompatible with the type of the field
-    TypeMismatch,
+    #[error("{field} is a {found}, expected a {}", type_list(.expected))]
+    TypeMismatch {
+        field: FieldContext,
+        expected: Vec<FieldType>,
+        found: FieldType,
+    },
     /// One or more selected values are not valid choices
+    #[error("one or more selected values are not valid choices")]
     InvalidSelection,
     /// Multiple values were selected when only one was allowed
+    #[error("multiple values were selected when only one is allowed")]
     TooManySelected,
     /// Readonly field cannot be edited
-    Readonly,
+    #[error("{field} is read-only")]
+    Readonly { field: FieldContext },
     /// Field not found
+    #[error("the field, page or entry was not found")]
+    NotFound,
+    /// The operation would discard private or unknown entries while preservation mode is enabled
+    #[error("the operation would discard private or unknown entries in preservation mode")]
+    WouldDiscardData,
+    /// Signature fields cannot be filled with values, they are handled by the signing functions
+    #[error("{field} is a signature field, which is filled by the signing functions")]
+    SignatureField { field: FieldContext },
+    /// A field name must not be empty nor contain a period, which separates the partial names
+    /// of a fully qualified name
+    #[error("a field name must not be empty nor contain a period")]
+    InvalidName,
+    /// A sibling field already has this name
+    #[error("a sibling field already has this name")]
+    NameTaken,
+    /// A field cannot be moved under itself or one of its descendants
+    #[error("a field cannot be moved under itself or one of its descendants")]
+    CyclicHierarchy,
+    /// The image could not be decoded, or its format is not supported
+    #[error("the image could not be decoded, or its format is not supported")]
+    InvalidImage,
+    /// The text is longer than the maximum length (`/MaxLen`) of the field
+    #[error("the text is {got} characters long, the maximum is {max}")]
+    TooLong { max: usize, got: usize },
+    /// The text has a character that the barcode symbology cannot encode
+    #[error("the character {0:?} cannot be encoded")]
+    InvalidCharacter(char),
+    /// The field is malformed
+    #[error("the field is malformed")]
+    MalformedField(#[source] FieldError),
+}
+
+/// Errors that may occur while reading a field
+#[derive(Debug, Error)]
+pub enum FieldError {
+    /// The index is out of range, or the field is not part of the form anymore
+    #[error("the index is out of range, or the field is not part of the form anymore")]
     NotFound,
+    /// The field object is missing from the document or is not a dictionary
+    #[error("the field object {} {} R is missing or not a dictionary", .0 .0, .0 .1)]
+    NotADictionary(ObjectId),
+    /// The field has no field type (`/FT`)
+    #[error("the field object {} {} R has no field type", .0 .0, .0 .1)]
+    MissingFieldType(ObjectId),
+    /// The field flags (`/Ff`) of the field are not a number
+    #[error("the field flags of the field object {} {} R are not a number", .0 .0, .0 .1)]
+    InvalidFlags(ObjectId),
+}
+
+impl From<FieldError> for ValueError {
+    fn from(error: FieldError) -> Self {
+        match error {
+            FieldError::NotFound => ValueError::NotFound,
+            error => ValueError::MalformedField(error),
+        }
+    }
+}
+
+/// Lists field types for error messages, e.g. `list box or combo box`
+fn type_list(types: &[FieldType]) -> String {
+    types
+        .iter()
+        .map(FieldType::to_string)
+        .collect::<Vec<_>>()
+        .join(" or ")
 }
 
 /// The current state of a form field
-#[derive(Debug)]
+///
+/// `tooltip` and `mapping_name` are the alternate name (`/TU`) shown by viewers and used by
+/// accessibility tools, and the name used when exporting the field data (`/TM`).
+#[derive(Debug, Clone, PartialEq)]
 pub enum FieldState {
     /// Push buttons have no state
     Button,
@@ -81,47 +337,103 @@ pub enum FieldState {
         options: Vec<String>,
         readonly: bool,
         required: bool,
+        tooltip: Option<String>,
+        mapping_name: Option<String>,
     },
-    /// The toggle state of the checkbox
+    /// The toggle state of the checkbox, and `on_value`, the name of its on state (often `Yes`,
+    /// but also `On`, `1` or an export value), which is its value when checked
     CheckBox {
         is_checked: bool,
+        on_value: String,
         readonly: bool,
         required: bool,
+        tooltip: Option<String>,
+        mapping_name: Option<String>,
     },
-    /// `selected` is the list of selected options from `options`
+    /// `selected` is the list of the export values of the selected options from `options`
     ListBox {
         selected: Vec<String>,
-        options: Vec<String>,
+        options: Vec<ChoiceOption>,
         multiselect: bool,
         readonly: bool,
         required: bool,
+        tooltip: Option<String>,
+        mapping_name: Option<String>,
     },
-    /// `selected` is the list of selected options from `options`
+    /// `selected` is the list of the export values of the selected options from `options`, or
+    /// the text typed in an editable combo box
     ComboBox {
         selected: Vec<String>,
-        options: Vec<String>,
+        options: Vec<ChoiceOption>,
         editable: bool,
         readonly: bool,
         required: bool,
+        tooltip: Option<String>,
+        mapping_name: Option<String>,
     },
     /// User Text Input
     Text {
         text: String,
         readonly: bool,
         required: bool,
+        tooltip: Option<String>,
+        mapping_name: Option<String>,
+    },
+    /// Whether the signature field holds a signature, with the signer and signing time recorded
+    /// in the signature dictionary (`/Name` and `/M`, the latter as a raw PDF date string)
+    Signature {
+        signed: bool,
+        signer_name: Option<String>,
+        sign_date: Option<String>,
     },
     /// Unknown fields have no state
     Unknown,
 }
 
+/// Options controlling how the appearance stream of a text field is regenerated
+///
+/// Every option left as `None` keeps the behavior dictated by the field's default appearance.
+#[derive(Debug, Clone, Default)]
+pub struct TextAppearanceOptions {
+    /// Font size in points, instead of the one of the default appearance. When it isn't set and
+    /// the default appearance asks for an automatic (`0`) size, the largest size at which the
+    /// text fits the widget is used.
+    pub font_size: Option<f32>,
+    /// Smallest size the automatic font size can shrink to, 4 points by default
+    pub min_font_size: Option<f32>,
+    /// Largest size the automatic font size can grow to, 12 points by default
+    pub max_font_size: Option<f32>,
+    /// Extra space between characters, in unscaled text space units (`Tc` operator)
+    pub char_spacing: Option<f32>,
+    /// Extra space added to every space character, in unscaled text space units (`Tw` operator)
+    pub word_spacing: Option<f32>,
+    /// Horizontal scaling of the glyphs as a percentage of their normal width (`Tz` operator).
+    /// Values below 100 condense the text so long values fit narrow boxes.
+    pub horizontal_scaling: Option<f32>,
+    /// Name of the font resource to use instead of the one of the default appearance, e.g.
+    /// `Cour` or `TiRo`
+    pub font_name: Option<String>,
+    /// Color of the text, instead of the one of the default appearance
+    pub color: Option<Color>,
+    /// Horizontal alignment of the text. Defaults to the quadding (`/Q`) of the field.
+    pub alignment: Option<TextAlignment>,
+    /// Whether the text is broken into several lines to fit the width of the widget. Defaults
+    /// to the multiline flag of the field.
+    pub multiline: Option<bool>,
+    /// Whether the value is shown as formatted by the format script of the field, when it is
+    /// one of the standard number, percent and date formats of Acrobat, see
+    /// `Form::set_text_formatted`
+    pub apply_format: bool,
+}
+
 trait PdfObjectDeref {
-    fn deref<'a>(&self, doc: &'a Document) -> Result<&'a Object, LoadError>;
+    fn deref<'a, D: Objects>(&self, doc: &'a D) -> Result<&'a Object, LoadError>;
 }
 
 impl PdfObjectDeref for Object {
-    fn deref<'a>(&self, doc: &'a Document) -> Result<&'a Object, LoadError> {
+    fn deref<'a, D: Objects>(&self, doc: &'a D) -> Result<&'a Object, LoadError> {
         match *self {
-            Object::Reference(oid) => doc.objects.get(&oid).ok_or(LoadError::NoSuchReference(oid)),
+            Object::Reference(oid) => doc.object(oid).ok_or(LoadError::NoSuchReference(oid)),
             _ => Err(LoadError::NotAReference),
         }
     }
@@ -131,148 +443,57 @@ impl Form {
     /// Takes a reader containing a PDF with a fillable form, analyzes the content, and attempts to
     /// identify all of the fields the form has.
     pub fn load_from<R: io::Read>(reader: R) -> Result<Self, LoadError> {
-        let doc = Document::load_from(reader)?;
-        Self::load_doc(doc)
+        FormLoader::new().load_from(reader)
     }
 
     /// Takes a path to a PDF with a fillable form, analyzes the file, and attempts to identify all
     /// of the fields the form has.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
-        let doc = Document::load(path)?;
-        Self::load_doc(doc)
+        FormLoader::new().load(path)
     }
 
-    pub fn load2<P: AsRef<Path>>(path: P) -> Result<Form, LoadError> {
-        let doc = Document::load(path)?;
-        Self::load_doc2(doc)
+    /// Takes a path to a PDF with a fillable form and identifies its fields like `load`, with
+    /// `options` choosing how malformed documents are handled, e.g. `LoadOptions::strict()` or
+    /// `LoadOptions::lenient()`
+    pub fn load_with<P: AsRef<Path>>(path: P, options: LoadOptions) -> Result<Self, LoadError> {
+        FormLoader::with_options(options).load(path)
     }
-    // New method for reading documents; it handles inline dictionaries and some unexpected errors.
-    // Also aimed to make error messages more descriptive.
-    // To use this function, use _load2_ instead of _load_, which uses _load_doc_ from the original _forms_pdf_ crate.
-    fn load_doc2(document: Document) -> Result<Self, LoadError> {    
-        let mut form_ids = Vec::new();
-        let mut queue = VecDeque::new(); 
-
-        {// Block so borrow of doc ends before doc is moved into the result
-
-        // 0. Get root dict
-        let root_dict = &document
-                            .trailer
-                            .get(b"Root")?  
-                            .deref(&document)?
-                            .as_dict()?;
-
-        // 1. Get an AcroForm object (it could be of different types, see 2.)
-        let acroform_obj  = match root_dict.get(b"AcroForm") {
-                                Ok(o) => o,
-                                Err(_) => return Err(LoadError::StructureError("Key \"AcroForm\" doesn't exist in document".into()))
-                            };
-
-        // 2. Get the fields object contained in AcroForm, which can be a reference or an inline dictionary
-        let fields_obj = match acroform_obj {
-            Object::Reference(obj_id) => {
-                let acroform = match document.objects.get(obj_id) {
-                    Some(Object::Dictionary(dict)) => dict,
-                    Some(_) => return Err(LoadError::StructureError("AcroForm cannot be parsed to a dictionary".into())),
-                    None => return Err(LoadError::StructureError("Invalid reference to AcroForm".into())),
-                };
-                match acroform.get(b"Fields") {
-                    Ok(obj) => obj,
-                    Err(_) => return Err(LoadError::StructureError("Key \"Fields\" doesn't exist in AcroForm".into())),
-                }
-            }
-            Object::Dictionary(dict) => {
-                match dict.get(b"Fields") {
-                    Ok(obj) => obj,
-                    Err(_) => return Err(LoadError::StructureError("Key \"Fields\" doesn't exist in AcroForm".into())),
-                }
-            }
-            _ => return Err(LoadError::StructureError("AcroForm is not a reference neither a dictionary".into())),
-        };
 
-        // 3. Get the fields in an array and transform it into a double-ended queue.
-        // Again, the fields object obtained in 2. can be either a reference or a dictionary.
-        let fields_array = {
-            match fields_obj {
-            Object::Array(arr) => arr,
-            Object::Reference(obj_id) => {
-                let deref_obj = document.get_object(*obj_id)?;
-                deref_obj.as_array()?
-            },
-            _ => return Err(LoadError::NotAReference),
-        }};
-
-        queue.extend(fields_array.iter().cloned());
-
-        // 4. Iterate the field queue, from parents to children
-        while let Some(objref) = queue.pop_front() {
-            let obj = match objref.deref(&document) {
-                Ok(o) => o,
-                Err(_) => continue, // Skip if the field cannot be dereferenced, maybe other fields can be read
-            };
-
-            if let Object::Dictionary(ref dict) = *obj {
-                // If the field has a "FT" key, then it receives input and it is added to the list of field IDs (form_ids)
-                if dict.get(b"FT").is_ok() {
-                    if let Ok(reference) = objref.as_reference() {
-                        form_ids.push(reference);
-                    }
-                }
+    /// Loads a form skipping the fields that cannot be read and reporting descriptive errors
+    #[deprecated(note = "use `Form::load_with` and `LoadOptions::lenient()` instead")]
+    pub fn load2<P: AsRef<Path>>(path: P) -> Result<Form, LoadError> {
+        Form::load_with(path, LoadOptions::lenient())
+    }
 
-                // Another option is that the field has children. If that's the case, add them to the queue
-                if let Ok(&Object::Array(ref kids)) = dict.get(b"Kids") {
-                    queue.extend(kids.iter().cloned());
-                }
-            }
-        }
-        }
-        
-        // 5. Return the original document and the vector with the IDs that store a form field
-        Ok(Form {
+    /// Builds a form from a document and the ids of its fields
+    fn from_parts(document: Document, form_ids: Vec<ObjectId>) -> Self {
+        let mut form = Form {
             document,
             form_ids,
-        })
-    }
-
-    fn load_doc(mut document: Document) -> Result<Self, LoadError> {
-        let mut form_ids = Vec::new();
-        let mut queue = VecDeque::new();
-        // Block so borrow of doc ends before doc is moved into the result
-        {
-            let acroform = document
-                .objects
-                .get_mut(
-                    &document
-                        .trailer
-                        .get(b"Root")?
-                        .deref(&document)?
-                        .as_dict()?
-                        .get(b"AcroForm")?
-                        .as_reference()?,
-                )
-                .ok_or(LoadError::NotAReference)?
-                .as_dict_mut()?;
-
-            let fields_list = acroform.get(b"Fields")?.as_array()?;
-            queue.append(&mut VecDeque::from(fields_list.clone()));
-
-            // Iterate over the fields
-            while let Some(objref) = queue.pop_front() {
-                let obj = objref.deref(&document)?;
-                if let Object::Dictionary(ref dict) = *obj {
-                    // If the field has FT, it actually takes input.  Save this
-                    if dict.get(b"FT").is_ok() {
-                        form_ids.push(objref.as_reference().unwrap());
-                    }
-
-                    // If this field has kids, they might have FT, so add them to the queue
-                    if let Ok(&Object::Array(ref kids)) = dict.get(b"Kids") {
-                        queue.append(&mut VecDeque::from(kids.clone()));
-                    }
-                }
-            }
-        }
-        Ok(Form { document, form_ids })
+            names: HashMap::new(),
+            field_indices: HashMap::new(),
+            page_fields: Vec::new(),
+            field_info: Vec::new(),
+            appearance_overrides: HashMap::new(),
+            modified_fields: BTreeSet::new(),
+            preservation_mode: false,
+            truncate_to_max_len: false,
+            deferred_appearances: false,
+            sync_xfa: false,
+            save_policy: SavePolicy::default(),
+            original: None,
+            pending_appearances: HashMap::new(),
+            cjk_font: CjkFont::default(),
+            target_version: None,
+            sort_by_position: false,
+            shared_objects: None,
+            removed_objects: BTreeSet::new(),
+        };
+        form.build_id_index();
+        form.build_field_info();
+        form.build_name_index();
+        form.build_page_index();
+        form
     }
 
     /// Returns the number of fields the form has
@@ -287,60 +508,80 @@ impl Form {
 
     /// Gets the type of field of the given index
     ///
-    /// # Panics
-    /// This function will panic if the index is greater than the number of fields
-    pub fn get_type(&self, n: usize) -> FieldType {
-        // unwraps should be fine because load should have verified everything exists
-        let field = self
-            .document
-            .objects
-            .get(&self.form_ids[n])
-            .unwrap()
-            .as_dict()
-            .unwrap();
-
-        let type_str = field.get(b"FT").unwrap().as_name_str().unwrap();
-        if type_str == "Btn" {
-            let flags = ButtonFlags::from_bits_truncate(get_field_flags(field));
-            if flags.intersects(ButtonFlags::RADIO | ButtonFlags::NO_TOGGLE_TO_OFF) {
+    /// Returns `FieldType::Unknown` if the index is greater than the number of fields, the field
+    /// is not part of the form or it is malformed. See `try_get_type` to tell these cases apart.
+    pub fn get_type(&self, n: impl FieldKey) -> FieldType {
+        self.try_get_type(n).unwrap_or(FieldType::Unknown)
+    }
+
+    /// Gets the type of field of the given index
+    ///
+    /// Fails with `FieldError::NotFound` if the index is greater than the number of fields or
+    /// the field is not part of the form, or with another `FieldError` if the field is
+    /// malformed.
+    pub fn try_get_type(&self, n: impl FieldKey) -> Result<FieldType, FieldError> {
+        let n = self.try_resolve(n)?;
+        if let Some(info) = self.field_info(n) {
+            return Ok(info.field_type);
+        }
+        let oid = self.form_ids[n];
+        let field = self.field_dictionary(oid)?;
+        let flags = self.field_flags(oid)?;
+
+        let type_str = field
+            .get(b"FT")
+            .and_then(Object::as_name_str)
+            .map_err(|_| FieldError::MissingFieldType(oid))?;
+        let field_type = if type_str == "Btn" {
+            let flags = ButtonFieldFlags::from_bits_truncate(flags);
+            if flags.intersects(ButtonFieldFlags::RADIO | ButtonFieldFlags::NO_TOGGLE_TO_OFF) {
                 FieldType::Radio
-            } else if flags.intersects(ButtonFlags::PUSHBUTTON) {
+            } else if flags.intersects(ButtonFieldFlags::PUSHBUTTON) {
                 FieldType::Button
             } else {
                 FieldType::CheckBox
             }
         } else if type_str == "Ch" {
-            let flags = ChoiceFlags::from_bits_truncate(get_field_flags(field));
-            if flags.intersects(ChoiceFlags::COBMO) {
+            let flags = ChoiceFieldFlags::from_bits_truncate(flags);
+            if flags.intersects(ChoiceFieldFlags::COMBO) {
                 FieldType::ComboBox
             } else {
                 FieldType::ListBox
             }
         } else if type_str == "Tx" {
             FieldType::Text
+        } else if type_str == "Sig" {
+            FieldType::Signature
         } else {
             FieldType::Unknown
-        }
+        };
+        Ok(field_type)
     }
 
     /// Gets the name of field of the given index
     ///
-    /// # Panics
-    /// This function will panic if the index is greater than the number of fields
-    pub fn get_name(&self, n: usize) -> Option<String> {
-        // unwraps should be fine because load should have verified everything exists
-        let field = self
-            .document
-            .objects
-            .get(&self.form_ids[n])
-            .unwrap()
-            .as_dict()
-            .unwrap();
+    /// Returns `None` if the index is greater than the number of fields, the field is not part
+    /// of the form or it is malformed. See `try_get_name` to tell these cases apart.
+    pub fn get_name(&self, n: impl FieldKey) -> Option<String> {
+        self.try_get_name(n).ok().flatten()
+    }
+
+    /// Gets the name of field of the given index
+    ///
+    /// Fails with `FieldError::NotFound` if the index is greater than the number of fields or
+    /// the field is not part of the form, or with another `FieldError` if the field is
+    /// malformed.
+    pub fn try_get_name(&self, n: impl FieldKey) -> Result<Option<String>, FieldError> {
+        let n = self.try_resolve(n)?;
+        if let Some(info) = self.field_info(n) {
+            return Ok(info.name.clone());
+        }
+        let field = self.field_dictionary(self.form_ids[n])?;
 
         // The "T" key refers to the name of the field
         match field.get(b"T") {
-            Ok(Object::String(data, _)) => String::from_utf8(data.clone()).ok(),
-            _ => None,
+            Ok(Object::String(data, _)) => Ok(Some(decode_text_string(data))),
+            _ => Ok(None),
         }
     }
 
@@ -348,7 +589,7 @@ impl Form {
     pub fn get_all_types(&self) -> Vec<FieldType> {
         let mut res = Vec::with_capacity(self.len());
         for i in 0..self.len() {
-            res.push(self.get_type(i))
+            res.push(self.try_get_type(i).unwrap_or(FieldType::Unknown))
         }
         res
     }
@@ -357,600 +598,310 @@ impl Form {
     pub fn get_all_names(&self) -> Vec<Option<String>> {
         let mut res = Vec::with_capacity(self.len());
         for i in 0..self.len() {
-            res.push(self.get_name(i))
+            res.push(self.try_get_name(i).ok().flatten())
         }
         res
     }
 
     /// Gets the state of field of the given index
     ///
-    /// # Panics
-    /// This function will panic if the index is greater than the number of fields
-    pub fn get_state(&self, n: usize) -> FieldState {
-        let field = self
-            .document
-            .objects
-            .get(&self.form_ids[n])
-            .unwrap()
-            .as_dict()
-            .unwrap();
-        match self.get_type(n) {
+    /// Returns `FieldState::Unknown` if the index is greater than the number of fields, the
+    /// field is not part of the form or it is malformed. See `try_get_state` to tell these cases
+    /// apart.
+    pub fn get_state(&self, n: impl FieldKey) -> FieldState {
+        self.try_get_state(n).unwrap_or(FieldState::Unknown)
+    }
+
+    /// Gets the state of field of the given index
+    ///
+    /// Fails with `FieldError::NotFound` if the index is greater than the number of fields or
+    /// the field is not part of the form, or with another `FieldError` if the field is
+    /// malformed.
+    pub fn try_get_state(&self, n: impl FieldKey) -> Result<FieldState, FieldError> {
+        let n = self.try_resolve(n)?;
+        let oid = self.form_ids[n];
+        let field = self.field_dictionary(oid)?;
+        let field_type = self.try_get_type(n)?;
+        if field_type == FieldType::Unknown {
+            return Ok(FieldState::Unknown);
+        }
+        let flags = self.field_flags(oid)?;
+        let readonly = FieldFlags::from_bits_truncate(flags).intersects(FieldFlags::READONLY);
+        let required = FieldFlags::from_bits_truncate(flags).intersects(FieldFlags::REQUIRED);
+        let state = match field_type {
             FieldType::Button => FieldState::Button,
             FieldType::Radio => FieldState::Radio {
-                selected: match field.get(b"V") {
-                    Ok(name) => name.as_name_str().unwrap().to_owned(),
-                    _ => match field.get(b"AS") {
-                        Ok(name) => name.as_name_str().unwrap().to_owned(),
-                        _ => "".to_owned(),
-                    },
-                },
-                options: self.get_possibilities(self.form_ids[n]),
-                readonly: is_read_only(field),
-                required: is_required(field),
+                selected: field
+                    .get(b"V")
+                    .or_else(|_| field.get(b"AS"))
+                    .ok()
+                    .and_then(get_name_or_text)
+                    .unwrap_or_default(),
+                options: self.radio_states(n),
+                readonly,
+                required,
+                tooltip: get_tooltip(field),
+                mapping_name: get_mapping_name(field),
             },
             FieldType::CheckBox => FieldState::CheckBox {
-                is_checked: match field.get(b"V") {
-                    Ok(name) => name.as_name_str().unwrap() == "Yes",
-                    _ => match field.get(b"AS") {
-                        Ok(name) => name.as_name_str().unwrap() == "Yes",
-                        _ => false,
-                    },
-                },
-                readonly: is_read_only(field),
-                required: is_required(field),
+                is_checked: field
+                    .get(b"V")
+                    .or_else(|_| field.get(b"AS"))
+                    .ok()
+                    .and_then(get_name_or_text)
+                    .is_some_and(|value| !value.is_empty() && value != "Off"),
+                on_value: self.check_box_on_state(n),
+                readonly,
+                required,
+                tooltip: get_tooltip(field),
+                mapping_name: get_mapping_name(field),
             },
             FieldType::ListBox => FieldState::ListBox {
-                // V field in a list box can be either text for one option, an array for many
-                // options, or null
-                selected: match field.get(b"V") {
-                    Ok(selection) => match *selection {
-                        Object::String(ref s, StringFormat::Literal) => {
-                            vec![str::from_utf8(&s).unwrap().to_owned()]
-                        }
-                        Object::Array(ref chosen) => {
-                            let mut res = Vec::new();
-                            for obj in chosen {
-                                if let Object::String(ref s, StringFormat::Literal) = *obj {
-                                    res.push(str::from_utf8(&s).unwrap().to_owned());
-                                }
-                            }
-                            res
-                        }
-                        _ => Vec::new(),
-                    },
-                    _ => Vec::new(),
-                },
-                // The options is an array of either text elements or arrays where the second
-                // element is what we want
-                options: match field.get(b"Opt") {
-                    Ok(&Object::Array(ref options)) => options
-                        .iter()
-                        .map(|x| match *x {
-                            Object::String(ref s, StringFormat::Literal) => {
-                                str::from_utf8(&s).unwrap().to_owned()
-                            }
-                            Object::Array(ref arr) => {
-                                if let Object::String(ref s, StringFormat::Literal) = &arr[1] {
-                                    str::from_utf8(&s).unwrap().to_owned()
-                                } else {
-                                    String::new()
-                                }
-                            }
-                            _ => String::new(),
-                        })
-                        .filter(|x| !x.is_empty())
-                        .collect(),
-                    _ => Vec::new(),
-                },
+                selected: get_choice_selection(field),
+                options: self.choice_options(n),
                 multiselect: {
-                    let flags = ChoiceFlags::from_bits_truncate(get_field_flags(field));
-                    flags.intersects(ChoiceFlags::MULTISELECT)
+                    let flags = ChoiceFieldFlags::from_bits_truncate(flags);
+                    flags.intersects(ChoiceFieldFlags::MULTISELECT)
                 },
-                readonly: is_read_only(field),
-                required: is_required(field),
+                readonly,
+                required,
+                tooltip: get_tooltip(field),
+                mapping_name: get_mapping_name(field),
             },
             FieldType::ComboBox => FieldState::ComboBox {
-                // V field in a list box can be either text for one option, an array for many
-                // options, or null
-                selected: match field.get(b"V") {
-                    Ok(selection) => match *selection {
-                        Object::String(ref s, StringFormat::Literal) => {
-                            vec![str::from_utf8(&s).unwrap().to_owned()]
-                        }
-                        Object::Array(ref chosen) => {
-                            let mut res = Vec::new();
-                            for obj in chosen {
-                                if let Object::String(ref s, StringFormat::Literal) = *obj {
-                                    res.push(str::from_utf8(&s).unwrap().to_owned());
-                                }
-                            }
-                            res
-                        }
-                        _ => Vec::new(),
-                    },
-                    _ => Vec::new(),
-                },
-                // The options is an array of either text elements or arrays where the second
-                // element is what we want
-                options: match field.get(b"Opt") {
-                    Ok(&Object::Array(ref options)) => options
-                        .iter()
-                        .map(|x| match *x {
-                            Object::String(ref s, StringFormat::Literal) => {
-                                str::from_utf8(&s).unwrap().to_owned()
-                            }
-                            Object::Array(ref arr) => {
-                                if let Object::String(ref s, StringFormat::Literal) = &arr[1] {
-                                    str::from_utf8(&s).unwrap().to_owned()
-                                } else {
-                                    String::new()
-                                }
-                            }
-                            _ => String::new(),
-                        })
-                        .filter(|x| !x.is_empty())
-                        .collect(),
-                    _ => Vec::new(),
-                },
+                selected: get_choice_selection(field),
+                options: self.choice_options(n),
                 editable: {
-                    let flags = ChoiceFlags::from_bits_truncate(get_field_flags(field));
+                    let flags = ChoiceFieldFlags::from_bits_truncate(flags);
 
-                    flags.intersects(ChoiceFlags::EDIT)
+                    flags.intersects(ChoiceFieldFlags::EDIT)
                 },
-                readonly: is_read_only(field),
-                required: is_required(field),
+                readonly,
+                required,
+                tooltip: get_tooltip(field),
+                mapping_name: get_mapping_name(field),
             },
             FieldType::Text => FieldState::Text {
                 text: match field.get(b"V") {
-                    Ok(&Object::String(ref s, StringFormat::Literal)) => {
-                        str::from_utf8(&s.clone()).unwrap().to_owned()
-                    }
+                    Ok(value) => get_text_string(value).unwrap_or_default(),
                     _ => "".to_owned(),
                 },
-                readonly: is_read_only(field),
-                required: is_required(field),
+                readonly,
+                required,
+                tooltip: get_tooltip(field),
+                mapping_name: get_mapping_name(field),
             },
+            FieldType::Signature => self.signature_state(field),
             FieldType::Unknown => FieldState::Unknown,
-        }
+        };
+        Ok(state)
     }
 
     /// Gets the object of field of the given index
     ///
     /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn get_object_id(&self, n: usize) -> ObjectId {
-        self.form_ids[n]
+    /// Will panic if n is larger than the number of fields or the field is not part of the form
+    pub fn get_object_id(&self, n: impl FieldKey) -> ObjectId {
+        self.try_get_object_id(n)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
-    /// If the field at index `n` is a text field, fills in that field with the text `s`.
-    /// If it is not a text field, returns ValueError
+    /// Gets the object of field of the given index
     ///
-    /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn set_text(&mut self, n: usize, s: String) -> Result<(), ValueError> {
-        match self.get_state(n) {
-            FieldState::Text { .. } => {
-                let field = self
-                    .document
-                    .objects
-                    .get_mut(&self.form_ids[n])
-                    .unwrap()
-                    .as_dict_mut()
-                    .unwrap();
-
-                field.set("V", Object::string_literal(s.into_bytes()));
-
-                // Regenerate text appearance confoming the new text but ignore the result
-                let _ = self.regenerate_text_appearance(n);
-
-                Ok(())
-            }
-            _ => Err(ValueError::TypeMismatch),
-        }
+    /// Fails with `FieldError::NotFound` if the index is greater than the number of fields or
+    /// the field is not part of the form.
+    pub fn try_get_object_id(&self, n: impl FieldKey) -> Result<ObjectId, FieldError> {
+        let n = self.try_resolve(n)?;
+        Ok(self.form_ids[n])
     }
 
-    // New function to write text that uses the extended function _regenerate_text_appearance2_
-    pub fn set_text_fs(&mut self, n: usize, s: String, f:i32) -> Result<(), ValueError> {
-        if let FieldState::Text { .. } = self.get_state(n) {
-            let field = self
-                .document
-                .objects
-                .get_mut(&self.form_ids[n])
-                .unwrap()
-                .as_dict_mut()
-                .unwrap();
-
-            field.set("V", Object::string_literal(s.into_bytes()));
-
-            // Regenerate the text appearance using the new function. Issues a warning in case
-            // it was not regenerated correctly
-            if let Err(e) = self.regenerate_text_appearance2(n, f) {
-                println!("Text apperance regeneration failed: {e}"); 
-            }
-
-            Ok(())
-        } else { Err(ValueError::TypeMismatch) }
+    /// Gets the dictionary of a field
+    fn field_dictionary(&self, oid: ObjectId) -> Result<&Dictionary, FieldError> {
+        self.get_dictionary(oid)
+            .map_err(|_| FieldError::NotADictionary(oid))
     }
 
-    // New function to write text that uses the extended function _regenerate_text_appearance2_
-    // Additionally, this function marks the filled PDF fields as read-only
-    pub fn set_text_fs_ro(&mut self, n: usize, s: String, f:i32) -> Result<(), ValueError> {
-        if let FieldState::Text { .. } = self.get_state(n) {
-            let field = self
-                .document
-                .objects
-                .get_mut(&self.form_ids[n])
-                .unwrap()
-                .as_dict_mut()
-                .unwrap();
-
-            field.set("V", Object::string_literal(s.into_bytes()));
-
-            //This block sets the read-only flag (bit 0 of Ff)            
-            let mut v = 0;
-            match field.get(b"Ff") {
-                Ok(f) => {
-                    if let Object::Integer(val) = f {
-                    v = *val;
-                    }
-                }
-                Err(_) => { v = 0; }
-            }
-            let new_flags = v | 1 << 0;
-            field.set(b"Ff", new_flags);
-
-            // Regenerate the text appearance using the new function. Issues a warning in case
-            // it was not regenerated correctly
-            if let Err(e) = self.regenerate_text_appearance2(n, f) {
-                println!("Text apperance regeneration failed: {e}"); 
-            }
-
-            Ok(())
-        } else { Err(ValueError::TypeMismatch) }
+    /// Reads the field flags (`/Ff`) of a field, which may be stored as an indirect object or
+    /// as a real number
+    pub(crate) fn field_flags(&self, oid: ObjectId) -> Result<u32, FieldError> {
+        let flags = match self.field_dictionary(oid)?.get(b"Ff") {
+            Ok(flags) => flags,
+            Err(_) => return Ok(0),
+        };
+        match self.dereference(flags) {
+            Ok((_, Object::Integer(flags))) => Ok(*flags as u32),
+            Ok((_, Object::Real(flags))) => Ok(*flags as i64 as u32),
+            _ => Err(FieldError::InvalidFlags(oid)),
+        }
     }
 
-    /// Regenerates the appearance for the field at index `n` due to an alteration of the
-    /// original TextField value, the AP will be updated accordingly.
-    ///
-    /// # Incomplete
-    /// This function is not exhaustive as not parse the original TextField orientation
-    /// or the text alignment and other kind of enrichments, also doesn't discover for
-    /// the global document DA.
+    /// If the field at index `n` is a text field, fills in that field with the text `s`.
+    /// If it is not a text field, returns ValueError
     ///
-    /// A more sophisticated parser is needed here
-    fn regenerate_text_appearance(&mut self, n: usize) -> Result<(), lopdf::Error> {
-        let field = {
-            self.document
-                .objects
-                .get(&self.form_ids[n])
-                .unwrap()
-                .as_dict()
-                .unwrap()
-        };
-
-        // The value of the object (should be a string)
-        let value = field.get(b"V")?.to_owned();
-
-        // The default appearance of the object (should be a string)
-        let da = field.get(b"DA")?.to_owned();
-
-        // The default appearance of the object (should be a string)
-        let rect = field
-            .get(b"Rect")?
-            .as_array()?
-            .iter()
-            .map(|object| {
-                object
-                    .as_f64()
-                    .unwrap_or(object.as_i64().unwrap_or(0) as f64) as f32
-            })
-            .collect::<Vec<_>>();
-
-        // Gets the object stream
-        let object_id = field.get(b"AP")?.as_dict()?.get(b"N")?.as_reference()?;
-        let stream = self.document.get_object_mut(object_id)?.as_stream_mut()?;
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_text(&mut self, n: impl FieldKey, s: String) -> Result<(), ValueError> {
+        self.set_text_with(n, s, &TextAppearanceOptions::default())
+    }
 
-        // Decode and get the content, even if is compressed
-        let mut content = {
-            if let Ok(content) = stream.decompressed_content() {
-                Content::decode(&content)?
-            } else {
-                Content::decode(&stream.content)?
-            }
+    /// Fills in a text field, using the font size `f` if its default appearance asks for an
+    /// automatic size
+    #[deprecated(note = "use `set_text_with` and `TextAppearanceOptions::font_size`")]
+    pub fn set_text_fs(&mut self, n: impl FieldKey, s: String, f: i32) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let options = TextAppearanceOptions {
+            font_size: self.automatic_font_size(n, f),
+            ..Default::default()
         };
 
-        // Ignored operators
-        let ignored_operators = vec![
-            "bt", "tc", "tw", "tz", "g", "tm", "tr", "tf", "tj", "et", "q", "bmc", "emc",
-        ];
-
-        // Remove these ignored operators as we have to generate the text and fonts again
-        content.operations.retain(|operation| {
-            !ignored_operators.contains(&operation.operator.to_lowercase().as_str())
-        });
-
-        // Let's construct the text widget
-        content.operations.append(&mut vec![
-            Operation::new("BMC", vec!["Tx".into()]),
-            Operation::new("q", vec![]),
-            Operation::new("BT", vec![]),
-        ]);
-
-        let font = parse_font(match da {
-            Object::String(ref bytes, _) => Some(from_utf8(bytes)?),
-            _ => None,
-        });
-
-        // Define some helping font variables
-        let font_name = (font.0).0;
-        let font_size = (font.0).1;
-        let font_color = font.1;
-
-        // Set the font type and size and color
-        content.operations.append(&mut vec![
-            Operation::new("Tf", vec![font_name.into(), font_size.into()]),
-            Operation::new(
-                font_color.0,
-                match font_color.0 {
-                    "k" => vec![
-                        font_color.1.into(),
-                        font_color.2.into(),
-                        font_color.3.into(),
-                        font_color.4.into(),
-                    ],
-                    "rg" => vec![
-                        font_color.1.into(),
-                        font_color.2.into(),
-                        font_color.3.into(),
-                    ],
-                    _ => vec![font_color.1.into()],
-                },
-            ),
-        ]);
+        self.set_text_with(n, s, &options)
+    }
 
-        // Calculate the text offset
-        let x = 2.0; // Suppose this fixed offset as we should have known the border here
+    /// If the field at index `n` is a text field, fills in that field with the text `s` and
+    /// regenerates its appearance according to `options`.
+    /// If it is not a text field, returns ValueError. Text longer than the maximum length of the
+    /// field is refused with `ValueError::TooLong`, or truncated if `set_truncate_to_max_len`
+    /// was enabled.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_text_with(
+        &mut self,
+        n: impl FieldKey,
+        s: String,
+        options: &TextAppearanceOptions,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_not_signature(n)?;
+        self.check_not_locked(n)?;
+        if self.try_get_type(n)? == FieldType::Text {
+            let s = self.check_max_len(n, s)?;
+            let field = self.dictionary_mut(self.form_ids[n])?;
+
+            field.set_in_place("V", Object::string_literal(encode_text_string(&s)));
+
+            // Issues a warning in case the appearance was not regenerated correctly
+            if let Err(e) = self.update_text_appearance(n, options) {
+                warn!("Text appearance regeneration failed: {e}");
+            }
 
-        // Formula picked up from Poppler
-        let dy = rect[1] - rect[3];
-        let y = if dy > 0.0 {
-            0.5 * dy - 0.4 * font_size as f32
+            self.mark_modified(n);
+            Ok(())
         } else {
-            0.5 * font_size as f32
-        };
-
-        // Set the text bounds, first are fixed at "1 0 0 1" and then the calculated x,y
-        content.operations.append(&mut vec![Operation::new(
-            "Tm",
-            vec![1.into(), 0.into(), 0.into(), 1.into(), x.into(), y.into()],
-        )]);
-
-        // Set the text value and some finalizing operations
-        content.operations.append(&mut vec![
-            Operation::new("Tj", vec![value]),
-            Operation::new("ET", vec![]),
-            Operation::new("Q", vec![]),
-            Operation::new("EMC", vec![]),
-        ]);
-
-        // Set the new content to the original stream and compress it
-        if let Ok(encoded_content) = content.encode() {
-            stream.set_plain_content(encoded_content);
-            let _ = stream.compress();
+            Err(self.type_mismatch(n, &[FieldType::Text]))
         }
-
-        Ok(())
     }
-    
-    // Extended function to regenerate the appearance. Additionally, it takes an i32 argument
-    // that serves as the font size for the text of unselected fields (represented
-    // in the stream contained in the object with key AP-N). Ensuring this integer is not zero
-    // makes the new values of the fields visible when opening the PDF.
-    fn regenerate_text_appearance2(&mut self, n: usize, f: i32) -> Result<(), lopdf::Error> {
-        let field = {
-            self.document
-                .objects
-                .get(&self.form_ids[n])
-                .unwrap()
-                .as_dict()
-                .unwrap().clone()
-        };
-
-        // The value of the object (should be a string)
-        let value = field.get(b"V")?.to_owned();
-
-        // The default appearance of the object (should be a string)
-        let da_default = concat!("/Helv {f} Tf 0 g").as_bytes().to_vec();
-        let da = match field.get(b"DA") {
-            Ok(Object::String(bytes, _)) => {
-                let s = std::str::from_utf8(bytes).unwrap_or("");
-
-                if s.contains("0 Tf") || s.trim().is_empty() {
-                    Object::string_literal(da_default)
-                } else {
-                    Object::String(bytes.clone(), StringFormat::Literal)
-                }
-            }
-            _ => Object::string_literal(da_default)
-        };
 
-        // The default appearance of the object (should be a string)
-        let rect = field
-            .get(b"Rect")?
-            .as_array()?
-            .iter()
-            .map(|object| {
-                object
-                    .as_f64()
-                    .unwrap_or(object.as_i64().unwrap_or(0) as f64) as f32
-            })
-            .collect::<Vec<_>>();
-
-        // Gets the object stream
-        // Fix: This block was made more robust to allow the AP key
-        // to be absent and assign a new one with a default value
-        let object_id = match field.get(b"AP") {
-            Ok(Object::Dictionary(ap_dict)) => {
-                Some(ap_dict.get(b"N").and_then(|n| n.as_reference())?)
-            }
-            _ => None,
-        };
+    /// Chooses whether text longer than the maximum length (`/MaxLen`) of a field is truncated
+    /// instead of being refused with `ValueError::TooLong`
+    pub fn set_truncate_to_max_len(&mut self, enabled: bool) {
+        self.truncate_to_max_len = enabled;
+    }
 
-        let object_id = match object_id {
-            Some(id) => id,
-            None => {
-                // New empty stream for AP
-                use lopdf::{Stream};
-
-                let stream = Stream::new(Dictionary::new(), Vec::new());
-                let new_id = self.document.new_object_id();
-                self.document.objects.insert(new_id, Object::Stream(stream));
-
-                let field_mut = self.document
-                .objects
-                .get_mut(&self.form_ids[n])
-                .unwrap()
-                .as_dict_mut()
-                .unwrap();
-
-                // AP dict with N key to new stream
-                let mut ap_dict = Dictionary::new();
-                ap_dict.set("N", Object::Reference(new_id));
-                field_mut.set("AP", Object::Dictionary(ap_dict));
-
-                new_id
-            }
-        };
+    /// Returns true if text longer than the maximum length of a field is truncated
+    pub fn truncate_to_max_len(&self) -> bool {
+        self.truncate_to_max_len
+    }
 
-        let stream = self.document.get_object_mut(object_id)?.as_stream_mut()?;
+    /// Gets the maximum length (`/MaxLen`) of the text field at index `n`, which may be
+    /// inherited from its parent
+    pub fn get_max_len(&self, n: impl FieldKey) -> Option<usize> {
+        let n = self.try_resolve(n).ok()?;
+        self.inherited_field_entry(self.form_ids[n], b"MaxLen")
+            .and_then(|max_len| max_len.as_i64().ok())
+            .map(|max_len| max_len.max(0) as usize)
+    }
 
-        // Decode and get the content, even if is compressed
-        let mut content = {
-            if let Ok(content) = stream.decompressed_content() {
-                Content::decode(&content)?
-            } else {
-                Content::decode(&stream.content)?
-            }
+    /// Applies the maximum length of a text field to `s`
+    fn check_max_len(&self, n: usize, s: String) -> Result<String, ValueError> {
+        let max = match self.get_max_len(n) {
+            Some(max) => max,
+            None => return Ok(s),
         };
 
-        // Ignored operators
-        let ignored_operators = vec![
-            "bt", "tc", "tw", "tz", "g", "tm", "tr", "tf", "tj", "et", "q", "bmc", "emc",
-        ];
-
-        // Remove these ignored operators as we have to generate the text and fonts again
-        content.operations.retain(|operation| {
-            !ignored_operators.contains(&operation.operator.to_lowercase().as_str())
-        });
-
-        // Let's construct the text widget
-        content.operations.append(&mut vec![
-            Operation::new("BMC", vec!["Tx".into()]),
-            Operation::new("q", vec![]),
-            Operation::new("BT", vec![]),
-        ]);
-
-        // This block and the next were modified to parse the DA
-        // (either the one found in the document or the default assigned).
-        // If the font size is 0, it is replaced by the function argument _f_
-        let font = parse_font(match da {
-            Object::String(ref bytes, _) => Some(from_utf8(bytes)?),  //Parsear esto mejor para encontrar una manera de capturar el tamaño de fuente
-            _ => Some("((\"0\", 0), (\"g\", 0, 0, 0))")
-        });
-
-        // Define some helping font variables
-        let font_name = (font.0).0;
-        let font_size_da = (font.0).1;
-        let font_size = if let 0 = font_size_da { f } else { font_size_da };
-        let font_color = font.1;
-
-        // Set the font type and size and color
-        content.operations.append(&mut vec![
-            Operation::new("Tf", vec![font_name.into(), font_size.into()]),
-            Operation::new(
-                font_color.0,
-                match font_color.0 {
-                    "k" => vec![
-                        font_color.1.into(),
-                        font_color.2.into(),
-                        font_color.3.into(),
-                        font_color.4.into(),
-                    ],
-                    "rg" => vec![
-                        font_color.1.into(),
-                        font_color.2.into(),
-                        font_color.3.into(),
-                    ],
-                    _ => vec![font_color.1.into()],
-                },
-            ),
-        ]);
-
-        // Calculate the text offset
-        let x = 2.0; // Suppose this fixed offset as we should have known the border here
-
-        // Formula picked up from Poppler
-        let dy = rect[1] - rect[3];
-        let y = if dy > 0.0 {
-            0.5 * dy - 0.4 * font_size as f32
+        let got = s.chars().count();
+        if got <= max {
+            Ok(s)
+        } else if self.truncate_to_max_len {
+            Ok(s.chars().take(max).collect())
         } else {
-            0.5 * font_size as f32
-        };
+            Err(ValueError::TooLong { max, got })
+        }
+    }
 
-        // Set the text bounds, first are fixed at "1 0 0 1" and then the calculated x,y
-        content.operations.append(&mut vec![Operation::new(
-            "Tm",
-            vec![1.into(), 0.into(), 0.into(), 1.into(), x.into(), y.into()],
-        )]);
-
-        // Set the text value and some finalizing operations
-        content.operations.append(&mut vec![
-            Operation::new("Tj", vec![value]),
-            Operation::new("ET", vec![]),
-            Operation::new("Q", vec![]),
-            Operation::new("EMC", vec![]),
-        ]);
-
-        // Set the new content to the original stream and compress it
-        if let Ok(encoded_content) = content.encode() {
-            stream.set_plain_content(encoded_content);
-            let _ = stream.compress();
+    /// Gets the font size `f` as an override if the default appearance of the field at index `n`
+    /// asks for an automatic size, as `set_text_fs` only applies it then
+    fn automatic_font_size(&self, n: usize, f: i32) -> Option<f32> {
+        if self.default_appearance(self.form_ids[n]).font_size <= 0.0 {
+            Some(f as f32)
+        } else {
+            None
         }
+    }
 
-        //self.document.objects.insert(self.form_ids[n], Object::Dictionary(field));
-        Ok(())
+    /// Fills in a text field like `set_text_fs` and marks it as read-only
+    #[deprecated(note = "use `set_text_with` and `set_readonly`")]
+    pub fn set_text_fs_ro(
+        &mut self,
+        n: impl FieldKey,
+        s: String,
+        f: i32,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let options = TextAppearanceOptions {
+            font_size: self.automatic_font_size(n, f),
+            ..Default::default()
+        };
+        self.set_text_with(n, s, &options)?;
+        self.set_readonly(n, true)
     }
 
     /// If the field at index `n` is a checkbox field, toggles the check box based on the value
     /// `is_checked`.
     /// If it is not a checkbox field, returns ValueError
     ///
-    /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn set_check_box(&mut self, n: usize, is_checked: bool) -> Result<(), ValueError> {
-        match self.get_state(n) {
-            FieldState::CheckBox { .. } => {
-                let field = self
-                    .document
-                    .objects
-                    .get_mut(&self.form_ids[n])
-                    .unwrap()
-                    .as_dict_mut()
-                    .unwrap();
-
-                let on = get_on_value(field);
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_check_box(&mut self, n: impl FieldKey, is_checked: bool) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_not_signature(n)?;
+        self.check_not_locked(n)?;
+        match self.try_get_type(n)? {
+            FieldType::CheckBox => {
+                let oid = self.form_ids[n];
+                let on_value = self.check_box_on_state(n);
+
+                // Each widget has its own on state, usually the same as the field's
+                for widget in self.widgets_of(oid) {
+                    let on = self.on_state(widget);
+
+                    // Viewers show nothing for boxes without appearance streams, so create them
+                    if let Err(e) = self.ensure_check_box_appearance(widget, &on) {
+                        warn!("Checkbox appearance generation failed: {e}");
+                    }
+
+                    let state = if is_checked { on.as_str() } else { "Off" };
+                    self.dictionary_mut(widget)?
+                        .set_in_place("AS", Object::Name(state.as_bytes().to_vec()));
+                }
+
+                let field = self.dictionary_mut(oid)?;
+
                 let state = Object::Name(
-                    if is_checked { on.as_str() } else { "Off" }
+                    if is_checked { on_value.as_str() } else { "Off" }
                         .to_owned()
                         .into_bytes(),
                 );
 
-                field.set("V", state.clone());
-                field.set("AS", state);
+                field.set_in_place("V", state.clone());
+                if field.has(b"Rect") {
+                    field.set_in_place("AS", state);
+                }
 
+                self.mark_modified(n);
                 Ok(())
             }
-            _ => Err(ValueError::TypeMismatch),
+            _ => Err(self.type_mismatch(n, &[FieldType::CheckBox])),
         }
     }
 
@@ -958,69 +909,127 @@ impl Form {
     /// `choice`
     /// If it is not a radio button field or the choice is not a valid option, returns ValueError
     ///
-    /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn set_radio(&mut self, n: usize, choice: String) -> Result<(), ValueError> {
-        match self.get_state(n) {
-            FieldState::Radio { options, .. } => {
-                if options.contains(&choice) {
-                    let field = self
-                        .document
-                        .objects
-                        .get_mut(&self.form_ids[n])
-                        .unwrap()
-                        .as_dict_mut()
-                        .unwrap();
-                    field.set("V", Object::Name(choice.into_bytes()));
+    /// `Off` deselects every button of the group, like `clear_radio`, unless it is an option.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_radio(&mut self, n: impl FieldKey, choice: String) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_not_signature(n)?;
+        self.check_not_locked(n)?;
+        match self.try_get_type(n)? {
+            FieldType::Radio => {
+                let options = self.radio_states(n);
+                if choice == "Off" && !options.contains(&choice) {
+                    self.clear_radio(n)
+                } else if options.contains(&choice) {
+                    let field = self.dictionary_mut(self.form_ids[n])?;
+                    field.set_in_place("V", Object::Name(choice.clone().into_bytes()));
+
+                    // Turn on the widgets whose on state is the choice and turn off the others,
+                    // otherwise viewers keep showing the previous selection
+                    let kids = self.kids_of(self.form_ids[n]);
+                    for (kid, state) in kids.into_iter().zip(options) {
+                        let appearance = if state == choice {
+                            state
+                        } else {
+                            "Off".to_owned()
+                        };
+                        let kid = self.get_object_mut(kid).and_then(Object::as_dict_mut);
+                        if let Ok(kid) = kid {
+                            kid.set_in_place("AS", Object::Name(appearance.into_bytes()));
+                        }
+                    }
+
+                    self.mark_modified(n);
                     Ok(())
                 } else {
                     Err(ValueError::InvalidSelection)
                 }
             }
-            _ => Err(ValueError::TypeMismatch),
+            _ => Err(self.type_mismatch(n, &[FieldType::Radio])),
+        }
+    }
+
+    /// If the field at index `n` is a radio field, deselects all of its buttons: its value and
+    /// the appearance state of every button are set to `Off`
+    /// If it is not a radio button field, returns ValueError
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn clear_radio(&mut self, n: impl FieldKey) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_not_signature(n)?;
+        self.check_not_locked(n)?;
+        if self.try_get_type(n)? != FieldType::Radio {
+            return Err(self.type_mismatch(n, &[FieldType::Radio]));
+        }
+
+        let oid = self.form_ids[n];
+        let off = Object::Name(b"Off".to_vec());
+        for widget in std::iter::once(oid).chain(self.kids_of(oid)) {
+            let widget = self.get_object_mut(widget).and_then(Object::as_dict_mut);
+            match widget {
+                Ok(widget) if widget.has(b"AS") || widget.has(b"Rect") => {
+                    widget.set_in_place("AS", off.clone())
+                }
+                _ => {}
+            }
         }
+        self.dictionary_mut(oid)?.set_in_place("V", off);
+
+        self.mark_modified(n);
+        Ok(())
     }
 
     /// If the field at index `n` is a listbox field, selects the options in `choice`
     /// If it is not a listbox field or one of the choices is not a valid option, or if too many choices are selected, returns ValueError
     ///
-    /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn set_list_box(&mut self, n: usize, choices: Vec<String>) -> Result<(), ValueError> {
-        match self.get_state(n) {
-            FieldState::ListBox {
-                options,
-                multiselect,
-                ..
-            } => {
-                if choices.iter().fold(true, |a, h| options.contains(h) && a) {
+    /// Options are designated by their export value or by the text shown for them. The export
+    /// values are stored, with the indices of the selected options (`/I`), and the top index
+    /// (`/TI`) scrolls to the first of them when it would be hidden.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_list_box(
+        &mut self,
+        n: impl FieldKey,
+        choices: Vec<String>,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_not_signature(n)?;
+        self.check_not_locked(n)?;
+        match self.try_get_type(n)? {
+            FieldType::ListBox => {
+                let options = self.choice_options(n);
+                let multiselect = ChoiceFieldFlags::from_bits_truncate(self.get_field_flags_raw(n))
+                    .intersects(ChoiceFieldFlags::MULTISELECT);
+                let choices = choices
+                    .iter()
+                    .map(|choice| export_value(&options, choice).map(str::to_owned))
+                    .collect::<Option<Vec<_>>>();
+                if let Some(choices) = choices {
                     if !multiselect && choices.len() > 1 {
                         Err(ValueError::TooManySelected)
                     } else {
-                        let field = self
-                            .document
-                            .objects
-                            .get_mut(&self.form_ids[n])
-                            .unwrap()
-                            .as_dict_mut()
-                            .unwrap();
+                        let field = self.dictionary_mut(self.form_ids[n])?;
                         match choices.len() {
-                            0 => field.set("V", Object::Null),
-                            1 => field.set(
+                            0 => field.set_in_place("V", Object::Null),
+                            1 => field.set_in_place(
                                 "V",
                                 Object::String(
-                                    choices[0].clone().into_bytes(),
+                                    encode_text_string(&choices[0]),
                                     StringFormat::Literal,
                                 ),
                             ),
-                            _ => field.set(
+                            _ => field.set_in_place(
                                 "V",
                                 Object::Array(
                                     choices
                                         .iter()
                                         .map(|x| {
                                             Object::String(
-                                                x.clone().into_bytes(),
+                                                encode_text_string(x),
                                                 StringFormat::Literal,
                                             )
                                         })
@@ -1028,106 +1037,230 @@ impl Form {
                                 ),
                             ),
                         };
+
+                        self.update_selection_indices(n)?;
+                        if let Err(e) = self.update_choice_appearance(n) {
+                            warn!("List box appearance regeneration failed: {e}");
+                        }
+
+                        self.mark_modified(n);
                         Ok(())
                     }
                 } else {
                     Err(ValueError::InvalidSelection)
                 }
             }
-            _ => Err(ValueError::TypeMismatch),
+            _ => Err(self.type_mismatch(n, &[FieldType::ListBox])),
         }
     }
 
     /// If the field at index `n` is a combobox field, selects the options in `choice`
     /// If it is not a combobox field or one of the choices is not a valid option, or if too many choices are selected, returns ValueError
     ///
-    /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn set_combo_box(&mut self, n: usize, choice: String) -> Result<(), ValueError> {
-        match self.get_state(n) {
-            FieldState::ComboBox {
-                options, editable, ..
-            } => {
-                if options.contains(&choice) || editable {
-                    let field = self
-                        .document
-                        .objects
-                        .get_mut(&self.form_ids[n])
-                        .unwrap()
-                        .as_dict_mut()
-                        .unwrap();
-                    field.set(
+    /// Options are designated by their export value or by the text shown for them. The export
+    /// value is stored, or the text itself in editable combo boxes when it isn't an option.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_combo_box(&mut self, n: impl FieldKey, choice: String) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_not_signature(n)?;
+        self.check_not_locked(n)?;
+        match self.try_get_type(n)? {
+            FieldType::ComboBox => {
+                let options = self.choice_options(n);
+                let editable = ChoiceFieldFlags::from_bits_truncate(self.get_field_flags_raw(n))
+                    .intersects(ChoiceFieldFlags::EDIT);
+                let choice = match export_value(&options, &choice) {
+                    Some(export) => Some(export.to_owned()),
+                    None if editable => Some(choice),
+                    None => None,
+                };
+                if let Some(choice) = choice {
+                    let field = self.dictionary_mut(self.form_ids[n])?;
+                    field.set_in_place(
                         "V",
-                        Object::String(choice.into_bytes(), StringFormat::Literal),
+                        Object::String(encode_text_string(&choice), StringFormat::Literal),
                     );
+
+                    if let Err(e) = self.update_choice_appearance(n) {
+                        warn!("Combo box appearance regeneration failed: {e}");
+                    }
+
+                    self.mark_modified(n);
                     Ok(())
                 } else {
                     Err(ValueError::InvalidSelection)
                 }
             }
-            _ => Err(ValueError::TypeMismatch),
+            _ => Err(self.type_mismatch(n, &[FieldType::ComboBox])),
         }
     }
 
-    /// Removes the field at index `n`
+    /// Removes the field at index `n` together with its widget annotations. The references to
+    /// them are removed too: from the `/Kids` of the parent field, the AcroForm `/Fields` and
+    /// `/CO` arrays and the `/Annots` of the pages. The indices of the following fields shift
+    /// down by one.
     ///
-    /// # Panics
-    /// Will panic if n is larger than the number of fields
-    pub fn remove_field(&mut self, n: usize) -> Result<(), ValueError> {
-        self.document
-            .remove_object(&self.get_object_id(n))
-            .map_err(|_| ValueError::NotFound)
+    /// In preservation mode, fails with `ValueError::WouldDiscardData` if the field or one of its
+    /// kid widgets carries entries this crate doesn't know about.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn remove_field(&mut self, n: impl FieldKey) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_preservation(n)?;
+        let oid = self.form_ids[n];
+        if self.get_dictionary(oid).is_err() {
+            return Err(ValueError::NotFound);
+        }
+        self.mark_modified(n);
+
+        let mut removed = self.kids_of(oid);
+        removed.push(oid);
+        self.detach(oid);
+        self.remove_references(&removed);
+        for id in &removed {
+            self.remove_object(*id);
+        }
+
+        self.reindex();
+        Ok(())
+    }
+
+    /// Registers a closure that is called every time the appearance of the field named `name` is
+    /// regenerated. It receives the generated operations and may post-process them or replace
+    /// them entirely. Registering a new closure for the same name replaces the previous one.
+    ///
+    /// Like `get_index_by_name`, `name` is the fully qualified name of the field, or a partial
+    /// name when no other field has it; an override registered under the qualified name takes
+    /// precedence.
+    pub fn set_appearance_override<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(&mut Vec<Operation>) + Send + Sync + 'static,
+    {
+        self.appearance_overrides
+            .insert(name.to_owned(), Arc::new(f));
+    }
+
+    /// Removes the appearance override registered for the field named `name`, if any
+    pub fn remove_appearance_override(&mut self, name: &str) -> bool {
+        self.appearance_overrides.remove(name).is_some()
     }
 
     /// Saves the form to the specified path
     pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), io::Error> {
-        self.document.save(path).map(|_| ())
+        match self.save_mode()? {
+            SaveMode::Rewrite => {
+                self.prepare_full_save();
+                self.write_document(&mut BufWriter::new(File::create(path)?))
+            }
+            SaveMode::Incremental(original) => {
+                self.save_incremental(&original, &mut File::create(path)?)
+            }
+        }
     }
 
     /// Saves the form to the specified path
     pub fn save_to<W: Write>(&mut self, target: &mut W) -> Result<(), io::Error> {
-        self.document.save_to(target)
+        match self.save_mode()? {
+            SaveMode::Rewrite => {
+                self.prepare_full_save();
+                self.write_document(target)
+            }
+            SaveMode::Incremental(original) => self.save_incremental(&original, target),
+        }
     }
 
-    fn get_possibilities(&self, oid: ObjectId) -> Vec<String> {
-        let mut res = Vec::new();
-        let kids_obj = self
+    /// Brings the document up to date before it is fully rewritten
+    fn prepare_full_save(&mut self) {
+        self.prepare_save(true);
+    }
+
+    /// Brings the document up to date before it is fully rewritten, recording the modified
+    /// fields in the `/PieceInfo` of the document if `piece_info` is set
+    pub(crate) fn prepare_save(&mut self, piece_info: bool) {
+        self.regenerate_pending_appearances();
+        if piece_info {
+            self.record_piece_info();
+        }
+        self.apply_target_version();
+        strip_xref_stream_keys(&mut self.document.trailer);
+    }
+
+    /// Gets the AcroForm dictionary of the document, whether it is inline in the catalog or an
+    /// indirect object
+    fn acroform(&self) -> Option<&Dictionary> {
+        let catalog = self.catalog().ok()?;
+        let (_, acroform) = self.dereference(catalog.get(b"AcroForm").ok()?).ok()?;
+        acroform.as_dict().ok()
+    }
+
+    /// Mutable version of `acroform`
+    fn acroform_mut(&mut self) -> Option<&mut Dictionary> {
+        let root_id = self
             .document
-            .objects
-            .get(&oid)
-            .unwrap()
-            .as_dict()
-            .unwrap()
-            .get(b"Kids");
-        if let Ok(&Object::Array(ref kids)) = kids_obj {
-            for (i, kid) in kids.iter().enumerate() {
-                let mut found = false;
-                if let Ok(&Object::Dictionary(ref appearance_states)) = kid
-                    .deref(&self.document)
-                    .unwrap()
-                    .as_dict()
-                    .unwrap()
-                    .get(b"AP")
-                {
-                    if let Ok(&Object::Dictionary(ref normal_appearance)) =
-                        appearance_states.get(b"N")
-                    {
-                        for (key, _) in normal_appearance {
-                            if key != b"Off" {
-                                res.push(from_utf8(key).unwrap_or("").to_owned());
-                                found = true;
-                                break;
-                            }
-                        }
-                    }
-                }
+            .trailer
+            .get(b"Root")
+            .ok()?
+            .as_reference()
+            .ok()?;
+        let acroform_id = match self.get_dictionary(root_id).ok()?.get(b"AcroForm") {
+            Ok(Object::Reference(id)) => *id,
+            _ => root_id,
+        };
 
-                if !found {
-                    res.push(i.to_string());
-                }
-            }
+        let dict = self.get_object_mut(acroform_id).ok()?.as_dict_mut().ok()?;
+        if acroform_id == root_id {
+            dict.get_mut(b"AcroForm").ok()?.as_dict_mut().ok()
+        } else {
+            Some(dict)
         }
+    }
 
-        res
+    /// Gets the `/Fields` array of the AcroForm, resolving it if it is an indirect object
+    fn fields_array_mut(&mut self) -> Option<&mut Vec<Object>> {
+        let fields_id = match self.acroform_mut()?.get(b"Fields") {
+            Ok(Object::Reference(id)) => Some(*id),
+            _ => None,
+        };
+
+        match fields_id {
+            Some(id) => self.get_object_mut(id).ok()?.as_array_mut().ok(),
+            None => self
+                .acroform_mut()?
+                .get_mut(b"Fields")
+                .ok()?
+                .as_array_mut()
+                .ok(),
+        }
+    }
+
+    /// Walks the field tree again after it was restructured, so `form_ids` lists the current
+    /// fields
+    fn reindex(&mut self) {
+        if let Ok(form_ids) = FormLoader::new().tolerant(true).collect_field_ids(self) {
+            self.form_ids = form_ids;
+        }
+        if self.sort_by_position {
+            self.sort_form_ids();
+        }
+        self.build_id_index();
+        self.build_field_info();
+        self.build_name_index();
+        self.build_page_index();
+    }
+
+    /// Gets the on state of every kid of a radio button group, kids without appearance states
+    /// being named after their position
+    fn get_possibilities(&self, oid: ObjectId) -> Vec<String> {
+        self.kids_of(oid)
+            .into_iter()
+            .enumerate()
+            .map(|(i, kid)| {
+                self.appearance_on_state(kid)
+                    .unwrap_or_else(|| i.to_string())
+            })
+            .collect()
     }
 }
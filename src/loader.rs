@@ -0,0 +1,261 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use lopdf::{Document, Object, ObjectId};
+
+use crate::objects::Objects;
+use crate::{Form, LoadError, PdfObjectDeref};
+
+/// Options controlling how `Form::load_with` and `FormLoader` read a document
+///
+/// The default options are those of `Form::load`: any field that cannot be read aborts the load
+/// with the underlying error, and an inline AcroForm dictionary is accepted. `strict` and
+/// `lenient` are the two ends of the range.
+///
+/// ```no_run
+/// use pdf_forms2::{Form, LoadOptions};
+///
+/// let form = Form::load_with(
+///     "path/to/pdf",
+///     LoadOptions {
+///         skip_broken_fields: true,
+///         ..LoadOptions::strict()
+///     },
+/// )
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Fields that cannot be dereferenced or that appear more than once in the field tree are
+    /// skipped instead of failing the load
+    pub skip_broken_fields: bool,
+    /// An AcroForm dictionary written inline in the catalog is accepted. Otherwise the AcroForm
+    /// must be an indirect object.
+    pub follow_inline_acroform: bool,
+    /// Structural problems are reported as `LoadError::StructureError` with a description of
+    /// what is wrong instead of the bare lopdf error
+    pub verbose_errors: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            skip_broken_fields: false,
+            follow_inline_acroform: true,
+            verbose_errors: false,
+        }
+    }
+}
+
+impl LoadOptions {
+    /// Options that only accept well-formed documents: any field that cannot be read aborts
+    /// the load, and the AcroForm must be an indirect object
+    pub fn strict() -> Self {
+        LoadOptions {
+            follow_inline_acroform: false,
+            ..LoadOptions::default()
+        }
+    }
+
+    /// Options that read as much of a damaged document as possible: broken fields are skipped,
+    /// an inline AcroForm is accepted, and structural problems are described
+    pub fn lenient() -> Self {
+        LoadOptions {
+            skip_broken_fields: true,
+            follow_inline_acroform: true,
+            verbose_errors: true,
+        }
+    }
+}
+
+/// Loads forms with configurable tolerance to malformed documents
+///
+/// The default loader uses the default `LoadOptions`: any field that cannot be read aborts the
+/// load with the underlying error.
+///
+/// ```no_run
+/// use pdf_forms2::FormLoader;
+///
+/// let form = FormLoader::new()
+///     .tolerant(true)
+///     .verbose_errors(true)
+///     .load("path/to/pdf")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FormLoader {
+    options: LoadOptions,
+}
+
+impl FormLoader {
+    /// Creates a strict loader that accepts inline AcroForm dictionaries
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a loader with the given options
+    pub fn with_options(options: LoadOptions) -> Self {
+        FormLoader { options }
+    }
+
+    /// When enabled, fields that cannot be dereferenced are skipped instead of failing the load
+    pub fn tolerant(mut self, tolerant: bool) -> Self {
+        self.options.skip_broken_fields = tolerant;
+        self
+    }
+
+    /// When enabled, an AcroForm dictionary written inline in the catalog is accepted. Otherwise
+    /// the AcroForm must be an indirect object.
+    pub fn inline_acroform(mut self, inline_acroform: bool) -> Self {
+        self.options.follow_inline_acroform = inline_acroform;
+        self
+    }
+
+    /// When enabled, structural problems are reported as `LoadError::StructureError` with a
+    /// description of what is wrong instead of the bare lopdf error
+    pub fn verbose_errors(mut self, verbose_errors: bool) -> Self {
+        self.options.verbose_errors = verbose_errors;
+        self
+    }
+
+    /// Takes a path to a PDF with a fillable form, analyzes the file, and attempts to identify all
+    /// of the fields the form has.
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Form, LoadError> {
+        let buffer = fs::read(path).map_err(lopdf::Error::from)?;
+        self.load_buffer(buffer)
+    }
+
+    /// Takes a reader containing a PDF with a fillable form, analyzes the content, and attempts to
+    /// identify all of the fields the form has.
+    pub fn load_from<R: io::Read>(&self, mut reader: R) -> Result<Form, LoadError> {
+        let mut buffer = Vec::new();
+        reader
+            .read_to_end(&mut buffer)
+            .map_err(lopdf::Error::from)?;
+        self.load_buffer(buffer)
+    }
+
+    /// Loads a form from the bytes of a file, which are kept if the form may have to be saved
+    /// incrementally
+    fn load_buffer(&self, buffer: Vec<u8>) -> Result<Form, LoadError> {
+        let doc = Document::load_mem(&buffer)?;
+        let mut form = self.load_document(doc)?;
+        form.keep_original(buffer);
+        Ok(form)
+    }
+
+    /// Analyzes an already parsed document and attempts to identify all of the fields it has
+    pub fn load_document(&self, document: Document) -> Result<Form, LoadError> {
+        let form_ids = self.collect_field_ids(&document)?;
+
+        Ok(Form::from_parts(document, form_ids))
+    }
+
+    /// Walks the field tree of a document and collects the ids of the fields that take input
+    pub(crate) fn collect_field_ids(
+        &self,
+        document: &impl Objects,
+    ) -> Result<Vec<ObjectId>, LoadError> {
+        let mut form_ids = Vec::new();
+        let mut queue = VecDeque::new();
+        // Guards against loops in the `/Kids` arrays and fields listed twice
+        let mut visited = HashSet::new();
+
+        let root_dict = document
+            .trailer()
+            .get(b"Root")?
+            .deref(document)?
+            .as_dict()?;
+
+        // The AcroForm can be a reference or, if allowed, an inline dictionary
+        let acroform_obj = root_dict
+            .get(b"AcroForm")
+            .map_err(|e| self.error(e.into(), "Key \"AcroForm\" doesn't exist in document"))?;
+        let acroform = match acroform_obj {
+            Object::Reference(oid) => match document.object(*oid) {
+                Some(Object::Dictionary(dict)) => dict,
+                Some(_) => {
+                    return Err(self.error(
+                        lopdf::Error::Type.into(),
+                        "AcroForm cannot be parsed to a dictionary",
+                    ))
+                }
+                None => {
+                    return Err(self.error(
+                        LoadError::NoSuchReference(*oid),
+                        "Invalid reference to AcroForm",
+                    ))
+                }
+            },
+            Object::Dictionary(dict) if self.options.follow_inline_acroform => dict,
+            _ => {
+                return Err(self.error(
+                    LoadError::NotAReference,
+                    "AcroForm is not a reference neither a dictionary",
+                ))
+            }
+        };
+
+        // The Fields array may be stored as an indirect object, as in documents using object
+        // streams
+        let fields_obj = acroform
+            .get(b"Fields")
+            .map_err(|e| self.error(e.into(), "Key \"Fields\" doesn't exist in AcroForm"))?;
+        let fields = document
+            .dereference(fields_obj)?
+            .1
+            .as_array()
+            .map_err(|e| self.error(e.into(), "Fields of AcroForm is not an array"))?;
+        // The queue borrows the entries of the arrays, which are never modified while walking
+        queue.extend(fields.iter());
+
+        // Iterate the field queue, from parents to children
+        while let Some(objref) = queue.pop_front() {
+            if let Object::Reference(oid) = *objref {
+                if !visited.insert(oid) {
+                    if self.options.skip_broken_fields {
+                        continue;
+                    }
+                    return Err(LoadError::StructureError(format!(
+                        "the field {} {} R appears more than once in the field tree",
+                        oid.0, oid.1
+                    )));
+                }
+            }
+
+            let obj = match objref.deref(document) {
+                Ok(obj) => obj,
+                // Maybe other fields can be read
+                Err(_) if self.options.skip_broken_fields => continue,
+                Err(e) => return Err(e),
+            };
+
+            if let Object::Dictionary(ref dict) = *obj {
+                // If the field has FT, it actually takes input. Save this
+                if dict.get(b"FT").is_ok() {
+                    form_ids.push(objref.as_reference()?);
+                }
+
+                // If this field has kids, they might have FT, so add them to the queue. The
+                // Kids array itself may be stored as an indirect object
+                if let Ok(kids) = dict.get(b"Kids") {
+                    if let Ok((_, Object::Array(kids))) = document.dereference(kids) {
+                        queue.extend(kids.iter());
+                    }
+                }
+            }
+        }
+        Ok(form_ids)
+    }
+
+    /// Picks the descriptive or the plain version of an error depending on the verbosity
+    fn error(&self, plain: LoadError, description: &str) -> LoadError {
+        if self.options.verbose_errors {
+            LoadError::StructureError(description.into())
+        } else {
+            plain
+        }
+    }
+}
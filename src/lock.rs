@@ -0,0 +1,163 @@
+use lopdf::{dictionary, Dictionary, Object};
+
+use crate::encoding::{decode_text_string, encode_text_string};
+use crate::utils::DictionaryExt;
+use crate::{FieldKey, FieldType, Form, ValueError};
+
+/// The fields a signature locks once it is signed, the `/Lock` dictionary of a signature field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldLock {
+    /// Every field of the form
+    All,
+    /// The fields with the given fully qualified names, and their descendants
+    Include(Vec<String>),
+    /// Every field but those with the given fully qualified names, and their descendants
+    Exclude(Vec<String>),
+}
+
+impl FieldLock {
+    /// Returns true if the lock applies to the field with the fully qualified name `name`
+    fn locks(&self, name: &str) -> bool {
+        let listed = |names: &[String]| {
+            names.iter().any(|locked| {
+                name.strip_prefix(locked.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+        };
+        match self {
+            FieldLock::All => true,
+            FieldLock::Include(names) => listed(names),
+            FieldLock::Exclude(names) => !listed(names),
+        }
+    }
+
+    fn to_dictionary(&self) -> Dictionary {
+        let fields = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| Object::string_literal(encode_text_string(name)))
+                .collect::<Vec<_>>()
+        };
+        match self {
+            FieldLock::All => dictionary! {
+                "Type" => "SigFieldLock",
+                "Action" => "All",
+            },
+            FieldLock::Include(names) => dictionary! {
+                "Type" => "SigFieldLock",
+                "Action" => "Include",
+                "Fields" => fields(names),
+            },
+            FieldLock::Exclude(names) => dictionary! {
+                "Type" => "SigFieldLock",
+                "Action" => "Exclude",
+                "Fields" => fields(names),
+            },
+        }
+    }
+}
+
+impl Form {
+    /// Sets the fields that become read-only once the signature field at index `n` is signed,
+    /// or removes its lock with `None`. `Form` refuses to change the value of a locked field
+    /// with `ValueError::Readonly`, and viewers show them as read-only.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field is not a signature field, or
+    /// `ValueError::Readonly` if it is already signed.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_signature_lock(
+        &mut self,
+        n: impl FieldKey,
+        lock: Option<FieldLock>,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        if !self.is_signature_field(n) {
+            return Err(self.type_mismatch(n, &[FieldType::Signature]));
+        }
+        let oid = self.form_ids[n];
+        if self.signed_fields().contains(&oid) {
+            return Err(ValueError::Readonly {
+                field: self.field_context(n),
+            });
+        }
+
+        let field = self.dictionary_mut(oid)?;
+        match lock {
+            Some(lock) => field.set_in_place("Lock", lock.to_dictionary()),
+            None => {
+                field.remove(b"Lock");
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the fields locked by the signature field at index `n` once it is signed, or `None`
+    /// if the field is not a signature field or has no lock
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_signature_lock(&self, n: impl FieldKey) -> Option<FieldLock> {
+        let n = self.try_resolve(n).ok()?;
+        if !self.is_signature_field(n) {
+            return None;
+        }
+        self.lock_of(self.form_ids[n])
+    }
+
+    /// Returns true if the field at index `n` is locked by a signed signature field
+    ///
+    /// Returns false if n is larger than the number of fields or the field is not part of the form.
+    pub fn is_locked(&self, n: impl FieldKey) -> bool {
+        let n = match self.try_resolve(n) {
+            Ok(n) => n,
+            Err(_) => return false,
+        };
+        let name = match self.get_qualified_name(n) {
+            Some(name) => name,
+            None => return false,
+        };
+        self.signed_fields()
+            .into_iter()
+            .filter_map(|oid| self.lock_of(oid))
+            .any(|lock| lock.locks(&name))
+    }
+
+    /// Refuses to change the value of a field locked by a signed signature field
+    pub(crate) fn check_not_locked(&self, n: usize) -> Result<(), ValueError> {
+        if self.is_locked(n) {
+            Err(ValueError::Readonly {
+                field: self.field_context(n),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads the `/Lock` dictionary of the signature field `oid`
+    fn lock_of(&self, oid: lopdf::ObjectId) -> Option<FieldLock> {
+        let field = self.get_dictionary(oid).ok()?;
+        let (_, lock) = self.dereference(field.get(b"Lock").ok()?).ok()?;
+        let lock = lock.as_dict().ok()?;
+
+        let names = || {
+            lock.get(b"Fields")
+                .and_then(|fields| self.dereference(fields))
+                .and_then(|(_, fields)| fields.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|name| match name {
+                    Object::String(bytes, _) => Some(decode_text_string(bytes)),
+                    _ => None,
+                })
+                .collect()
+        };
+        match lock.get(b"Action").and_then(Object::as_name).ok()? {
+            b"All" => Some(FieldLock::All),
+            b"Include" => Some(FieldLock::Include(names())),
+            b"Exclude" => Some(FieldLock::Exclude(names())),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+
+use lopdf::{Object, ObjectId};
+
+use crate::encoding::encode_text_string;
+use crate::utils::DictionaryExt;
+use crate::writer::object_to_bytes;
+use crate::{Form, LoadError};
+
+/// How `merge` renames a top level field whose name is already used by a field of a document
+/// merged before it. The kids of a renamed field keep their partial names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameSuffix {
+    /// An underscore and the first number giving a free name: `name_2`, `name_3`...
+    #[default]
+    Counter,
+    /// An underscore and the position of the document in the merged list, counting from 1:
+    /// `name_doc3` for a field of the third document, followed by a counter if that name is
+    /// taken as well
+    Document,
+}
+
+impl NameSuffix {
+    /// Finds a free name for a field named `name` of the document at `position`
+    fn rename(self, name: &str, position: usize, taken: &HashSet<String>) -> String {
+        let base = match self {
+            NameSuffix::Counter => name.to_owned(),
+            NameSuffix::Document => format!("{}_doc{}", name, position),
+        };
+        if self == NameSuffix::Document && !taken.contains(&base) {
+            return base;
+        }
+        (2..)
+            .map(|i| format!("{}_{}", base, i))
+            .find(|candidate| !taken.contains(candidate))
+            .unwrap()
+    }
+}
+
+/// The old and new names of the fonts renamed while merging
+type FontRenames = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The AcroForm entries of a document appended by `merge`
+struct AppendedForm {
+    fields: Vec<ObjectId>,
+    default_appearance: Option<Vec<u8>>,
+    fonts: Vec<(Vec<u8>, Object)>,
+    calculation_order: Vec<Object>,
+    need_appearances: bool,
+    sig_flags: i64,
+}
+
+/// Concatenates several forms into one document: the pages of every form are appended, in order,
+/// to the pages of the first one, and their fields to its AcroForm. The objects of each form are
+/// renumbered after those already merged, so every reference stays valid, and the objects that
+/// cannot be reached from its trailer are dropped.
+///
+/// Top level fields whose name is already taken by a field of a previous form are renamed as
+/// told by `suffix`, so no two fields of the merged form share a fully qualified name. The
+/// fonts of the default resources (`/DR`) are merged: a font whose name is already used by a
+/// different font of a previous form is renamed, and the default appearances (`/DA`) of the
+/// appended fields are rewritten to use the new name. The calculation order,
+/// `/NeedAppearances` and `/SigFlags` are combined. Fields inherit the default appearance
+/// (`/DA`) of their original AcroForm. The XFA form of the first form, which would only describe
+/// its own fields, is removed. The catalogs of the other forms are dropped with the objects only
+/// they referenced (outlines, names...).
+///
+/// Pending appearances are regenerated, as before saving. Fails with
+/// `LoadError::StructureError` if `forms` is empty or a document has no page tree, or with
+/// `LoadError::WouldDiscardData` if preservation mode is enabled on the first form or on the
+/// appended one and a field of the appended form holds entries this crate doesn't know about.
+pub fn merge<I>(forms: I, suffix: NameSuffix) -> Result<Form, LoadError>
+where
+    I: IntoIterator<Item = Form>,
+{
+    let mut forms = forms.into_iter();
+    let mut merged = forms
+        .next()
+        .ok_or_else(|| LoadError::StructureError("No form to merge".to_owned()))?;
+    merged.prepare_save(true);
+
+    let mut names = merged
+        .top_level_fields()
+        .into_iter()
+        .filter_map(|oid| merged.get_partial_name_of(oid))
+        .collect::<HashSet<_>>();
+
+    for (i, form) in forms.enumerate() {
+        merged.append_form(form, i + 2, suffix, &mut names)?;
+        if let Some(acroform) = merged.acroform_mut() {
+            acroform.remove(b"XFA");
+        }
+    }
+
+    merged.reindex();
+    Ok(merged)
+}
+
+impl Form {
+    /// Appends the pages and fields of `form`, the document at `position` in the merged list
+    fn append_form(
+        &mut self,
+        mut form: Form,
+        position: usize,
+        suffix: NameSuffix,
+        names: &mut HashSet<String>,
+    ) -> Result<(), LoadError> {
+        form.preservation_mode |= self.preservation_mode;
+        form.check_preservation_all()
+            .map_err(|_| LoadError::WouldDiscardData)?;
+        // The objects of the form are renumbered and moved into this one
+        form.unshare();
+        form.prepare_save(true);
+        form.document.prune_objects();
+        form.document
+            .renumber_objects_with(self.document.max_id + 1);
+
+        let appended = form.appended_form(position, suffix, names);
+        let pages = form.pages_root()?;
+        let count = form
+            .get_dictionary(pages)
+            .and_then(|pages| pages.get(b"Count"))
+            .and_then(Object::as_i64)
+            .unwrap_or(0);
+
+        let appended_ids = form.document.objects.keys().copied().collect::<Vec<_>>();
+        self.document.max_id = self.document.max_id.max(form.document.max_id);
+        self.document.objects.extend(form.document.objects);
+
+        // The page tree of the form becomes a node of the merged page tree
+        let root = self.pages_root()?;
+        self.get_object_mut(pages)
+            .and_then(Object::as_dict_mut)?
+            .set_in_place("Parent", root);
+        let root = self.get_object_mut(root).and_then(Object::as_dict_mut)?;
+        let total = root.get(b"Count").and_then(Object::as_i64).unwrap_or(0) + count;
+        root.set_in_place("Count", total);
+        match root.get_mut(b"Kids") {
+            Ok(Object::Array(kids)) => kids.push(pages.into()),
+            _ => root.set_in_place("Kids", vec![pages.into()]),
+        }
+
+        self.append_fields(appended)?;
+
+        // The catalog of the form, its AcroForm and the objects only they referenced are left
+        // unreachable
+        let reachable = self.reachable_objects();
+        for id in appended_ids {
+            if !reachable.contains(&id) {
+                self.remove_object(id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames the top level fields of this form whose name is taken and gathers its AcroForm
+    /// entries, once its objects were renumbered
+    fn appended_form(
+        &mut self,
+        position: usize,
+        suffix: NameSuffix,
+        names: &mut HashSet<String>,
+    ) -> AppendedForm {
+        let fields = self.top_level_fields();
+        for &oid in &fields {
+            let name = match self.get_partial_name_of(oid) {
+                Some(name) => name,
+                None => continue,
+            };
+            let name = if names.contains(&name) {
+                let name = suffix.rename(&name, position, names);
+                if let Ok(dict) = self.get_object_mut(oid).and_then(Object::as_dict_mut) {
+                    dict.set_in_place("T", Object::string_literal(encode_text_string(&name)));
+                }
+                name
+            } else {
+                name
+            };
+            names.insert(name);
+        }
+
+        let acroform = self.acroform();
+        let entry = |key: &[u8]| {
+            acroform
+                .and_then(|acroform| acroform.get(key).ok())
+                .and_then(|entry| self.dereference(entry).ok())
+                .map(|(_, entry)| entry.clone())
+        };
+        let fonts = entry(b"DR")
+            .and_then(|dr| dr.as_dict().ok()?.get(b"Font").ok().cloned())
+            .and_then(|fonts| {
+                let (_, fonts) = self.dereference(&fonts).ok()?;
+                let fonts = fonts.as_dict().ok()?;
+                Some(
+                    fonts
+                        .iter()
+                        .map(|(name, font)| (name.clone(), font.clone()))
+                        .collect(),
+                )
+            })
+            .unwrap_or_default();
+
+        AppendedForm {
+            fields,
+            default_appearance: entry(b"DA").and_then(|da| da.as_str().ok().map(<[u8]>::to_vec)),
+            fonts,
+            calculation_order: match entry(b"CO") {
+                Some(Object::Array(order)) => order,
+                _ => Vec::new(),
+            },
+            need_appearances: matches!(entry(b"NeedAppearances"), Some(Object::Boolean(true))),
+            sig_flags: entry(b"SigFlags")
+                .and_then(|flags| flags.as_i64().ok())
+                .unwrap_or(0),
+        }
+    }
+
+    /// Adds the fields and AcroForm entries of an appended form to the AcroForm
+    fn append_fields(&mut self, appended: AppendedForm) -> Result<(), LoadError> {
+        if appended.fields.is_empty() {
+            return Ok(());
+        }
+        self.ensure_acroform()
+            .map_err(|_| LoadError::StructureError("Cannot create the AcroForm".to_owned()))?;
+
+        let renamed = self.merge_default_fonts(&appended.fonts)?;
+
+        let default_appearance = self
+            .acroform()
+            .and_then(|acroform| acroform.get(b"DA").ok())
+            .and_then(|da| da.as_str().ok())
+            .map(<[u8]>::to_vec);
+        let appended_appearance = appended
+            .default_appearance
+            .map(|da| rename_fonts(&da, &renamed));
+        if let Some(da) = appended_appearance {
+            if default_appearance.as_ref() != Some(&da) {
+                for &oid in &appended.fields {
+                    let dict = self.get_object_mut(oid).and_then(Object::as_dict_mut)?;
+                    if !dict.has(b"DA") {
+                        dict.set_in_place("DA", Object::string_literal(da.clone()));
+                    }
+                }
+            }
+        }
+
+        if !renamed.is_empty() {
+            self.rename_appearance_fonts(&appended.fields, &renamed);
+        }
+
+        if self.fields_array_mut().is_none() {
+            if let Some(acroform) = self.acroform_mut() {
+                acroform.set("Fields", Vec::<Object>::new());
+            }
+        }
+        if let Some(fields) = self.fields_array_mut() {
+            fields.extend(appended.fields.iter().map(|&oid| Object::from(oid)));
+        }
+
+        let acroform = self
+            .acroform_mut()
+            .ok_or_else(|| LoadError::StructureError("Cannot find the AcroForm".to_owned()))?;
+        if !appended.calculation_order.is_empty() {
+            match acroform.get_mut(b"CO") {
+                Ok(Object::Array(order)) => order.extend(appended.calculation_order),
+                _ => acroform.set_in_place("CO", appended.calculation_order),
+            }
+        }
+        if appended.need_appearances {
+            acroform.set_in_place("NeedAppearances", true);
+        }
+        if appended.sig_flags != 0 {
+            let flags = acroform
+                .get(b"SigFlags")
+                .and_then(Object::as_i64)
+                .unwrap_or(0);
+            acroform.set_in_place("SigFlags", flags | appended.sig_flags);
+        }
+
+        Ok(())
+    }
+
+    /// Adds the fonts of the default resources of an appended form to those of this form. A font
+    /// whose name is already used by a different font is added under a new name, and the pairs
+    /// of old and new names are returned.
+    fn merge_default_fonts(
+        &mut self,
+        fonts: &[(Vec<u8>, Object)],
+    ) -> Result<FontRenames, LoadError> {
+        let appended_names = fonts
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<HashSet<_>>();
+        let mut renamed = Vec::new();
+
+        for (name, font) in fonts {
+            let name = String::from_utf8_lossy(name).into_owned();
+            let same_font = match self.default_font(&name) {
+                None => {
+                    self.default_fonts_mut()?.set(name, font.clone());
+                    continue;
+                }
+                Some(existing) => {
+                    let existing = self.dereference(existing);
+                    match (existing, self.dereference(font)) {
+                        (Ok((_, a)), Ok((_, b))) => object_to_bytes(a) == object_to_bytes(b),
+                        _ => false,
+                    }
+                }
+            };
+            if same_font {
+                continue;
+            }
+
+            let new_name = (2..)
+                .map(|i| format!("{}_{}", name, i))
+                .find(|candidate| {
+                    self.default_font(candidate).is_none()
+                        && !appended_names.contains(candidate.as_bytes())
+                })
+                .unwrap();
+            self.default_fonts_mut()?
+                .set(new_name.clone(), font.clone());
+            renamed.push((name.into_bytes(), new_name.into_bytes()));
+        }
+
+        Ok(renamed)
+    }
+
+    /// Rewrites the default appearances (`/DA`) of the given fields and their descendants to use
+    /// the new names of renamed fonts
+    fn rename_appearance_fonts(&mut self, fields: &[ObjectId], renamed: &[(Vec<u8>, Vec<u8>)]) {
+        let mut visited = HashSet::new();
+        let mut stack = fields.to_vec();
+        while let Some(oid) = stack.pop() {
+            if !visited.insert(oid) {
+                continue;
+            }
+            stack.extend(self.kids_of(oid));
+
+            if let Ok(dict) = self.get_object_mut(oid).and_then(Object::as_dict_mut) {
+                if let Ok(da) = dict.get(b"DA").and_then(Object::as_str) {
+                    let da = rename_fonts(da, renamed);
+                    dict.set_in_place("DA", Object::string_literal(da));
+                }
+            }
+        }
+    }
+
+    /// Gets the id of the root of the page tree
+    fn pages_root(&self) -> Result<ObjectId, LoadError> {
+        self.catalog()
+            .and_then(|catalog| catalog.get(b"Pages"))
+            .and_then(Object::as_reference)
+            .map_err(|_| LoadError::StructureError("The document has no page tree".to_owned()))
+    }
+}
+
+/// Replaces the font names of a default appearance string (`/Helv 12 Tf 0 g`) that were renamed
+fn rename_fonts(da: &[u8], renamed: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(da.len());
+    let mut rest = da;
+    while !rest.is_empty() {
+        let end = rest
+            .iter()
+            .position(u8::is_ascii_whitespace)
+            .unwrap_or(rest.len());
+        let (token, tail) = rest.split_at(end);
+        let new_name = token.strip_prefix(b"/").and_then(|name| {
+            renamed
+                .iter()
+                .find(|(old, _)| old.as_slice() == name)
+                .map(|(_, new)| new)
+        });
+        match new_name {
+            Some(new_name) => {
+                res.push(b'/');
+                res.extend_from_slice(new_name);
+            }
+            None => res.extend_from_slice(token),
+        }
+
+        // Keep the white-space between the tokens as it is
+        let spaces = tail.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        res.extend_from_slice(&tail[..spaces]);
+        rest = &tail[spaces..];
+    }
+    res
+}
@@ -0,0 +1,241 @@
+use lopdf::{dictionary, Dictionary, Object, Stream};
+
+use crate::encoding::{decode_text_string, encode_text_string};
+use crate::utils::DictionaryExt;
+use crate::xfa::escape_xml;
+use crate::{Form, ValueError};
+
+/// The metadata of a document, the entries of its document information dictionary (`/Info`)
+///
+/// Dates are PDF date strings, e.g. `D:20240131120000+01'00'`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// The application that created the original document
+    pub creator: Option<String>,
+    /// The application that produced the PDF
+    pub producer: Option<String>,
+    pub creation_date: Option<String>,
+    pub modification_date: Option<String>,
+}
+
+impl Metadata {
+    /// Gets the entries of the information dictionary and their values
+    fn entries(&self) -> [(&'static str, &Option<String>); 8] {
+        [
+            ("Title", &self.title),
+            ("Author", &self.author),
+            ("Subject", &self.subject),
+            ("Keywords", &self.keywords),
+            ("Creator", &self.creator),
+            ("Producer", &self.producer),
+            ("CreationDate", &self.creation_date),
+            ("ModDate", &self.modification_date),
+        ]
+    }
+
+    /// Builds an XMP packet holding the metadata with the Dublin Core, XMP basic and Adobe PDF
+    /// schemas, for `Form::set_xmp_metadata`
+    pub fn to_xmp(&self) -> String {
+        let mut properties = String::new();
+        let mut property = |name: &str, value: &Option<String>, wrap: fn(&str) -> String| {
+            // Dates that cannot be converted are left out
+            match value.as_deref().map(wrap) {
+                Some(value) if !value.is_empty() => {
+                    properties.push_str(&format!("<{0}>{1}</{0}>\n", name, value));
+                }
+                _ => {}
+            }
+        };
+        let alt = |value: &str| {
+            format!(
+                "<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>",
+                escape_xml(value)
+            )
+        };
+        let seq =
+            |value: &str| format!("<rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq>", escape_xml(value));
+        let text = |value: &str| escape_xml(value);
+        let date = |value: &str| xmp_date(value).unwrap_or_default();
+
+        property("dc:title", &self.title, alt);
+        property("dc:creator", &self.author, seq);
+        property("dc:description", &self.subject, alt);
+        property("pdf:Keywords", &self.keywords, text);
+        property("xmp:CreatorTool", &self.creator, text);
+        property("pdf:Producer", &self.producer, text);
+        property("xmp:CreateDate", &self.creation_date, date);
+        property("xmp:ModifyDate", &self.modification_date, date);
+
+        format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+             <rdf:Description rdf:about=\"\" \
+             xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+             xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" \
+             xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\">\n\
+             {}</rdf:Description>\n\
+             </rdf:RDF>\n\
+             </x:xmpmeta>\n\
+             <?xpacket end=\"w\"?>",
+            properties
+        )
+    }
+}
+
+impl Form {
+    /// Gets the metadata of the document information dictionary (`/Info`)
+    pub fn metadata(&self) -> Metadata {
+        let info = self
+            .document
+            .trailer
+            .get(b"Info")
+            .and_then(|info| self.dereference(info))
+            .and_then(|(_, info)| info.as_dict())
+            .ok();
+        let entry = |key: &[u8]| match info.map(|info| info.get(key)) {
+            Some(Ok(Object::String(bytes, _))) => Some(decode_text_string(bytes)),
+            _ => None,
+        };
+
+        Metadata {
+            title: entry(b"Title"),
+            author: entry(b"Author"),
+            subject: entry(b"Subject"),
+            keywords: entry(b"Keywords"),
+            creator: entry(b"Creator"),
+            producer: entry(b"Producer"),
+            creation_date: entry(b"CreationDate"),
+            modification_date: entry(b"ModDate"),
+        }
+    }
+
+    /// Writes `metadata` to the document information dictionary (`/Info`), creating it if the
+    /// document has none. Entries set to `None` are removed, the other entries of the
+    /// dictionary are kept. The XMP metadata stream is left as it is, see `set_xmp_metadata` to
+    /// keep both in sync.
+    pub fn set_metadata(&mut self, metadata: &Metadata) {
+        let info = match self.document.trailer.get(b"Info") {
+            Ok(Object::Reference(id)) if self.get_dictionary(*id).is_ok() => *id,
+            info => {
+                let info = match info {
+                    Ok(Object::Dictionary(info)) => info.clone(),
+                    _ => Dictionary::new(),
+                };
+                let id = self.document.add_object(info);
+                self.document.trailer.set("Info", id);
+                id
+            }
+        };
+        let info = match self.dictionary_mut(info) {
+            Ok(info) => info,
+            Err(_) => return,
+        };
+
+        for (key, value) in metadata.entries() {
+            match value {
+                Some(value) => {
+                    let bytes = if key.ends_with("Date") {
+                        value.as_bytes().to_vec()
+                    } else {
+                        encode_text_string(value)
+                    };
+                    info.set_in_place(key, Object::string_literal(bytes));
+                }
+                None => {
+                    info.remove(key.as_bytes());
+                }
+            }
+        }
+    }
+
+    /// Gets the XMP metadata stream (`/Metadata`) of the catalog, or `None` if the document
+    /// has none
+    pub fn xmp_metadata(&self) -> Option<String> {
+        let catalog = self.catalog().ok()?;
+        let (_, stream) = self.dereference(catalog.get(b"Metadata").ok()?).ok()?;
+        let stream = stream.as_stream().ok()?;
+        let content = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        Some(String::from_utf8_lossy(&content).into_owned())
+    }
+
+    /// Replaces the XMP metadata stream (`/Metadata`) of the catalog with the packet `xmp`,
+    /// e.g. one built by `Metadata::to_xmp`. The stream is added uncompressed, so that tools
+    /// that don't parse PDF files can still find it, unless the form is saved with
+    /// `StreamCompression::Compress`.
+    ///
+    /// Fails with `ValueError::NotFound` if the document has no catalog.
+    pub fn set_xmp_metadata(&mut self, xmp: &str) -> Result<(), ValueError> {
+        let stream = Object::Stream(Stream::new(
+            dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+            xmp.as_bytes().to_vec(),
+        ));
+
+        let root = self
+            .document
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|_| ValueError::NotFound)?;
+        match self.dictionary_mut(root)?.get(b"Metadata") {
+            Ok(Object::Reference(id)) => {
+                let id = *id;
+                self.insert_object(id, stream);
+            }
+            _ => {
+                let id = self.document.add_object(stream);
+                self.dictionary_mut(root)?.set_in_place("Metadata", id);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Converts a PDF date string to the ISO 8601 form of XMP dates
+fn xmp_date(date: &str) -> Option<String> {
+    let date = date.strip_prefix("D:").unwrap_or(date);
+    let len = date.bytes().take_while(u8::is_ascii_digit).count();
+    let (digits, zone) = date.split_at(len);
+    let part = |start: usize| digits.get(start..start + 2);
+
+    let mut iso = digits.get(..4)?.to_owned();
+    for (start, separator) in [(4, '-'), (6, '-')] {
+        match part(start) {
+            Some(part) => {
+                iso.push(separator);
+                iso.push_str(part);
+            }
+            None => return Some(iso),
+        }
+    }
+    let hour = match part(8) {
+        Some(hour) => hour,
+        None => return Some(iso),
+    };
+    iso.push_str(&format!("T{}:{}", hour, part(10).unwrap_or("00")));
+    if let Some(second) = part(12) {
+        iso.push(':');
+        iso.push_str(second);
+    }
+
+    match zone.chars().next() {
+        Some('Z') => iso.push('Z'),
+        Some(sign @ ('+' | '-')) => {
+            let offset = zone
+                .chars()
+                .filter(char::is_ascii_digit)
+                .collect::<String>();
+            let hours = offset.get(..2).unwrap_or("00");
+            let minutes = offset.get(2..4).unwrap_or("00");
+            iso.push_str(&format!("{}{}:{}", sign, hours, minutes));
+        }
+        _ => {}
+    }
+    Some(iso)
+}
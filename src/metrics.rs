@@ -0,0 +1,193 @@
+/// Width of the glyphs of the standard fonts that have no bundled widths, and of the characters
+/// missing from the bundled tables, in thousandths of the font size
+const DEFAULT_GLYPH_WIDTH: u16 = 500;
+
+/// Width of every glyph of the Courier family
+const COURIER_GLYPH_WIDTH: u16 = 600;
+
+/// Widths of the printable ASCII characters (space to tilde) from the Adobe font metrics (AFM)
+/// of the standard fonts, in thousandths of the font size
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[rustfmt::skip]
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+#[rustfmt::skip]
+const TIMES_ROMAN_WIDTHS: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444,
+    921, 722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889, 722, 722,
+    556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611, 333, 278, 333, 469, 500,
+    333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778, 500, 500,
+    500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+#[rustfmt::skip]
+const TIMES_BOLD_WIDTHS: [u16; 95] = [
+    250, 333, 555, 500, 500, 1000, 833, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    930, 722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944, 722, 778,
+    611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667, 333, 278, 333, 581, 500,
+    333, 500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833, 556, 500,
+    556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444, 394, 220, 394, 520,
+];
+
+#[rustfmt::skip]
+const TIMES_ITALIC_WIDTHS: [u16; 95] = [
+    250, 333, 420, 500, 500, 833, 778, 214, 333, 333, 500, 675, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 675, 675, 675, 500,
+    920, 611, 611, 667, 722, 611, 611, 722, 722, 333, 444, 667, 556, 833, 667, 722,
+    611, 722, 611, 500, 556, 722, 611, 833, 611, 556, 556, 389, 278, 389, 422, 500,
+    333, 500, 500, 444, 500, 444, 278, 500, 500, 278, 278, 444, 278, 722, 500, 500,
+    500, 500, 389, 389, 278, 500, 444, 667, 444, 444, 389, 400, 275, 400, 541,
+];
+
+#[rustfmt::skip]
+const TIMES_BOLD_ITALIC_WIDTHS: [u16; 95] = [
+    250, 389, 555, 500, 500, 833, 778, 278, 333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 333, 333, 570, 570, 570, 500,
+    832, 667, 667, 667, 722, 667, 667, 722, 778, 389, 500, 667, 611, 889, 722, 722,
+    611, 722, 667, 556, 611, 722, 667, 889, 667, 611, 611, 333, 278, 333, 570, 500,
+    333, 500, 500, 444, 500, 444, 333, 500, 556, 278, 278, 500, 278, 778, 556, 500,
+    500, 500, 389, 389, 278, 556, 444, 667, 500, 444, 389, 348, 220, 348, 570,
+];
+
+/// One of the standard 14 fonts every PDF reader provides, except the symbolic Symbol and
+/// ZapfDingbats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StandardFont {
+    Helvetica,
+    HelveticaBold,
+    HelveticaOblique,
+    HelveticaBoldOblique,
+    TimesRoman,
+    TimesBold,
+    TimesItalic,
+    TimesBoldItalic,
+    Courier,
+    CourierBold,
+    CourierOblique,
+    CourierBoldOblique,
+}
+
+impl StandardFont {
+    /// Finds the standard font matching the `/BaseFont` of a font dictionary. The subset prefix
+    /// of embedded fonts (`ABCDEF+`) is ignored, and the metric-compatible Arial, Times New Roman
+    /// and Courier New fonts are matched too.
+    pub fn from_base_font(base_font: &str) -> Option<Self> {
+        let name = match base_font.split_once('+') {
+            Some((prefix, name)) if prefix.len() == 6 => name,
+            _ => base_font,
+        };
+        let name = name
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | ',' | '_'))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let name = name.trim_end_matches("mt").trim_end_matches("ps");
+
+        let family = if name.starts_with("helvetica") || name.starts_with("arial") {
+            [
+                StandardFont::Helvetica,
+                StandardFont::HelveticaBold,
+                StandardFont::HelveticaOblique,
+                StandardFont::HelveticaBoldOblique,
+            ]
+        } else if name.starts_with("times") {
+            [
+                StandardFont::TimesRoman,
+                StandardFont::TimesBold,
+                StandardFont::TimesItalic,
+                StandardFont::TimesBoldItalic,
+            ]
+        } else if name.starts_with("courier") {
+            [
+                StandardFont::Courier,
+                StandardFont::CourierBold,
+                StandardFont::CourierOblique,
+                StandardFont::CourierBoldOblique,
+            ]
+        } else {
+            return None;
+        };
+
+        let bold = name.contains("bold");
+        let slanted = name.contains("italic") || name.contains("oblique");
+        Some(family[usize::from(bold) + 2 * usize::from(slanted)])
+    }
+
+    /// Gets the width of a character in thousandths of the font size
+    fn glyph_width(self, c: char) -> u16 {
+        let widths = match self {
+            StandardFont::Helvetica | StandardFont::HelveticaOblique => &HELVETICA_WIDTHS,
+            StandardFont::HelveticaBold | StandardFont::HelveticaBoldOblique => {
+                &HELVETICA_BOLD_WIDTHS
+            }
+            StandardFont::TimesRoman => &TIMES_ROMAN_WIDTHS,
+            StandardFont::TimesBold => &TIMES_BOLD_WIDTHS,
+            StandardFont::TimesItalic => &TIMES_ITALIC_WIDTHS,
+            StandardFont::TimesBoldItalic => &TIMES_BOLD_ITALIC_WIDTHS,
+            StandardFont::Courier
+            | StandardFont::CourierBold
+            | StandardFont::CourierOblique
+            | StandardFont::CourierBoldOblique => return COURIER_GLYPH_WIDTH,
+        };
+
+        // Accented letters are as wide as the letter they are built on
+        let c = base_letter(c);
+        match c {
+            ' '..='~' => widths[c as usize - ' ' as usize],
+            _ => DEFAULT_GLYPH_WIDTH,
+        }
+    }
+}
+
+/// Measures the width of a line of text drawn with a standard font at the given size, in the
+/// same unit as the size
+pub fn measure_text(font: StandardFont, size: f32, text: &str) -> f32 {
+    let width = text
+        .chars()
+        .map(|c| u32::from(font.glyph_width(c)))
+        .sum::<u32>();
+
+    width as f32 * size / 1000.0
+}
+
+/// Gets the unaccented letter of the Latin-1 accented letters, other characters are returned
+/// unchanged
+fn base_letter(c: char) -> char {
+    match c {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' => 'Y',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        _ => c,
+    }
+}
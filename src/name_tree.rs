@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use lopdf::{dictionary, Dictionary, Object, ObjectId};
+
+use crate::utils::DictionaryExt;
+use crate::{Form, ValueError};
+
+impl Form {
+    /// Gets the entries of the name tree `key` of the catalog `/Names` (e.g. `JavaScript` or
+    /// `EmbeddedFiles`), in the order of the tree
+    pub(crate) fn name_tree(&self, key: &[u8]) -> Vec<(Vec<u8>, Object)> {
+        let mut entries = Vec::new();
+        if let Some(root) = self.name_tree_root(key) {
+            self.collect_name_tree(root, &mut entries, &mut HashSet::new());
+        }
+        entries
+    }
+
+    /// Replaces the name tree `key` of the catalog `/Names` with a single node holding
+    /// `entries` sorted by name, or removes the tree when there are no entries. The nodes of
+    /// the previous tree are removed from the document.
+    ///
+    /// Fails with `ValueError::NotFound` if the document has no catalog.
+    pub(crate) fn set_name_tree(
+        &mut self,
+        key: &[u8],
+        mut entries: Vec<(Vec<u8>, Object)>,
+    ) -> Result<(), ValueError> {
+        let mut nodes = HashSet::new();
+        if let Some(root) = self.name_tree_root(key) {
+            self.collect_name_tree(root, &mut Vec::new(), &mut nodes);
+        }
+        for id in nodes {
+            self.remove_object(id);
+        }
+
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let node = if entries.is_empty() {
+            None
+        } else {
+            let names = entries
+                .into_iter()
+                .flat_map(|(name, value)| vec![Object::string_literal(name), value])
+                .collect::<Vec<_>>();
+            Some(self.document.add_object(dictionary! { "Names" => names }))
+        };
+
+        let root = self
+            .document
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .map_err(|_| ValueError::NotFound)?;
+        let names = match self.dictionary_mut(root)?.get(b"Names") {
+            Ok(Object::Reference(id)) => Some(*id),
+            _ => None,
+        };
+        let names = match names {
+            Some(id) => self.dictionary_mut(id)?,
+            None => {
+                let catalog = self.dictionary_mut(root)?;
+                if !matches!(catalog.get(b"Names"), Ok(Object::Dictionary(_))) {
+                    if node.is_none() {
+                        return Ok(());
+                    }
+                    catalog.set("Names", Dictionary::new());
+                }
+                match catalog.get_mut(b"Names") {
+                    Ok(Object::Dictionary(names)) => names,
+                    _ => return Err(ValueError::NotFound),
+                }
+            }
+        };
+        match node {
+            Some(node) => names.set_in_place(key.to_vec(), node),
+            None => {
+                names.remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the root node of the name tree `key` of the catalog `/Names`
+    fn name_tree_root(&self, key: &[u8]) -> Option<&Object> {
+        let catalog = self.catalog().ok()?;
+        let (_, names) = self.dereference(catalog.get(b"Names").ok()?).ok()?;
+        names.as_dict().ok()?.get(key).ok()
+    }
+
+    /// Collects the entries of the name tree node `node` and of its kids, and the ids of the
+    /// nodes visited
+    fn collect_name_tree(
+        &self,
+        node: &Object,
+        entries: &mut Vec<(Vec<u8>, Object)>,
+        nodes: &mut HashSet<ObjectId>,
+    ) {
+        if let Object::Reference(id) = node {
+            // Guard against kids pointing back to an ancestor
+            if !nodes.insert(*id) {
+                return;
+            }
+        }
+        let node = match self.dereference(node) {
+            Ok((_, Object::Dictionary(node))) => node,
+            _ => return,
+        };
+
+        if let Ok(Object::Array(names)) = node.get(b"Names") {
+            for pair in names.chunks(2) {
+                if let [Object::String(name, _), value] = pair {
+                    entries.push((name.clone(), value.clone()));
+                }
+            }
+        }
+        if let Ok(Object::Array(kids)) = node.get(b"Kids") {
+            for kid in kids {
+                self.collect_name_tree(kid, entries, nodes);
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use crate::{FieldState, FieldType, Form, ValueError};
+
+impl Form {
+    /// Builds the map from field names to indices. Fully qualified names (`parent.child`) are
+    /// always present; partial names are added too when they don't clash with another name.
+    pub(crate) fn build_name_index(&mut self) {
+        let mut names = HashMap::new();
+
+        for (n, &oid) in self.form_ids.iter().enumerate() {
+            if let Some(name) = self.qualified_name(oid) {
+                names.entry(name).or_insert(n);
+            }
+        }
+        for n in 0..self.len() {
+            if let Ok(Some(name)) = self.try_get_name(n) {
+                names.entry(name).or_insert(n);
+            }
+        }
+
+        self.names = names;
+    }
+
+    /// Gets the index of the field with the given fully qualified or partial name
+    pub fn get_index_by_name(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Gets the type of the field with the given name
+    ///
+    /// Fails with `ValueError::NotFound` if no field has the name, or
+    /// `ValueError::MalformedField` if the field is malformed.
+    pub fn get_type_by_name(&self, name: &str) -> Result<FieldType, ValueError> {
+        Ok(self.try_get_type(self.index_by_name(name)?)?)
+    }
+
+    /// Gets the state of the field with the given name
+    ///
+    /// Fails with `ValueError::NotFound` if no field has the name, or
+    /// `ValueError::MalformedField` if the field is malformed.
+    pub fn get_state_by_name(&self, name: &str) -> Result<FieldState, ValueError> {
+        Ok(self.try_get_state(self.index_by_name(name)?)?)
+    }
+
+    /// Fills in the text field with the given name, see `set_text`
+    pub fn set_text_by_name(&mut self, name: &str, s: String) -> Result<(), ValueError> {
+        self.set_text(self.index_by_name(name)?, s)
+    }
+
+    /// Toggles the checkbox with the given name, see `set_check_box`
+    pub fn set_check_box_by_name(
+        &mut self,
+        name: &str,
+        is_checked: bool,
+    ) -> Result<(), ValueError> {
+        self.set_check_box(self.index_by_name(name)?, is_checked)
+    }
+
+    /// Selects an option of the radio field with the given name, see `set_radio`
+    pub fn set_radio_by_name(&mut self, name: &str, choice: String) -> Result<(), ValueError> {
+        self.set_radio(self.index_by_name(name)?, choice)
+    }
+
+    /// Selects options of the list box with the given name, see `set_list_box`
+    pub fn set_list_box_by_name(
+        &mut self,
+        name: &str,
+        choices: Vec<String>,
+    ) -> Result<(), ValueError> {
+        self.set_list_box(self.index_by_name(name)?, choices)
+    }
+
+    /// Selects an option of the combo box with the given name, see `set_combo_box`
+    pub fn set_combo_box_by_name(&mut self, name: &str, choice: String) -> Result<(), ValueError> {
+        self.set_combo_box(self.index_by_name(name)?, choice)
+    }
+
+    fn index_by_name(&self, name: &str) -> Result<usize, ValueError> {
+        self.get_index_by_name(name).ok_or(ValueError::NotFound)
+    }
+}
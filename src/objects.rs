@@ -0,0 +1,332 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::mem;
+use std::sync::Arc;
+
+use lopdf::{Dictionary, Document, Error, Object, ObjectId};
+
+use crate::incremental::{write_xref_table, XrefEntry};
+use crate::writer::{write_indirect_object, write_object};
+use crate::Form;
+
+/// Same limit as lopdf on the number of references followed to reach an object
+const DEREF_LIMIT: usize = 128;
+
+/// Same limit as lopdf on the depth of the page tree
+const PAGE_TREE_DEPTH_LIMIT: usize = 256;
+
+/// The objects of a document, shared between the forms spawned from the same `FormTemplate`
+pub(crate) type SharedObjects = Arc<BTreeMap<ObjectId, Object>>;
+
+/// Read access to the objects of a document, either a whole `Document` or a form whose objects
+/// are split between those it shares with a template and those it changed
+pub(crate) trait Objects {
+    fn trailer(&self) -> &Dictionary;
+
+    /// Gets the object with the given id, without following references
+    fn object(&self, id: ObjectId) -> Option<&Object>;
+
+    /// Follows `object` while it is a reference, returning the id of the last object reached
+    fn dereference<'a>(
+        &'a self,
+        mut object: &'a Object,
+    ) -> lopdf::Result<(Option<ObjectId>, &'a Object)> {
+        let mut id = None;
+        for _ in 0..=DEREF_LIMIT {
+            match object.as_reference() {
+                Ok(reference) => {
+                    id = Some(reference);
+                    object = self.object(reference).ok_or(Error::ObjectNotFound)?;
+                }
+                Err(_) => return Ok((id, object)),
+            }
+        }
+        Err(Error::ReferenceLimit)
+    }
+}
+
+impl Objects for Document {
+    fn trailer(&self) -> &Dictionary {
+        &self.trailer
+    }
+
+    fn object(&self, id: ObjectId) -> Option<&Object> {
+        self.objects.get(&id)
+    }
+}
+
+impl Objects for Form {
+    fn trailer(&self) -> &Dictionary {
+        &self.document.trailer
+    }
+
+    fn object(&self, id: ObjectId) -> Option<&Object> {
+        match self.document.objects.get(&id) {
+            Some(object) => Some(object),
+            None if self.removed_objects.contains(&id) => None,
+            None => self.shared_objects.as_ref()?.get(&id),
+        }
+    }
+}
+
+/// The lookups of `Document`, answered from the objects the form changed first and from those
+/// it shares with its template otherwise. When the form shares nothing, `document` holds every
+/// object and these behave exactly like the methods of `Document` of the same name.
+impl Form {
+    /// Gets an object by id, following references
+    pub(crate) fn get_object(&self, id: ObjectId) -> lopdf::Result<&Object> {
+        let object = self.object(id).ok_or(Error::ObjectNotFound)?;
+        self.dereference(object).map(|(_, object)| object)
+    }
+
+    /// Gets an object by id for modification, following references. A shared object is copied
+    /// into the form first, the other forms keep the original.
+    pub(crate) fn get_object_mut(&mut self, id: ObjectId) -> lopdf::Result<&mut Object> {
+        let object = self.object(id).ok_or(Error::ObjectNotFound)?;
+        let id = self.dereference(object)?.0.unwrap_or(id);
+        self.object_mut(id).ok_or(Error::ObjectNotFound)
+    }
+
+    /// Gets an object by id for modification, without following references. A shared object is
+    /// copied into the form first.
+    pub(crate) fn object_mut(&mut self, id: ObjectId) -> Option<&mut Object> {
+        if !self.document.objects.contains_key(&id) {
+            let object = self.object(id)?.clone();
+            self.document.objects.insert(id, object);
+        }
+        self.document.objects.get_mut(&id)
+    }
+
+    pub(crate) fn dereference<'a>(
+        &'a self,
+        object: &'a Object,
+    ) -> lopdf::Result<(Option<ObjectId>, &'a Object)> {
+        Objects::dereference(self, object)
+    }
+
+    pub(crate) fn get_dictionary(&self, id: ObjectId) -> lopdf::Result<&Dictionary> {
+        self.get_object(id).and_then(Object::as_dict)
+    }
+
+    pub(crate) fn catalog(&self) -> lopdf::Result<&Dictionary> {
+        self.document
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+            .and_then(|id| self.get_dictionary(id))
+    }
+
+    /// Iterates over the ids of the pages, in order, like `Document::page_iter`
+    pub(crate) fn page_iter(&self) -> impl Iterator<Item = ObjectId> {
+        let mut pages = Vec::new();
+        let mut stack = Vec::new();
+        // Bounds the walk of page trees with loops
+        let mut limit = self.object_count();
+        if let Ok(root) = self
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Pages"))
+            .and_then(Object::as_reference)
+        {
+            stack.push(self.kids(root));
+        }
+        while let Some(kids) = stack.last_mut() {
+            let (kid, rest) = match kids.split_first() {
+                Some(split) => split,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            *kids = rest;
+            if limit == 0 {
+                break;
+            }
+            limit -= 1;
+
+            if let Ok(id) = kid.as_reference() {
+                match self.get_dictionary(id).and_then(Dictionary::type_name) {
+                    Ok("Page") => pages.push(id),
+                    Ok("Pages") if stack.len() < PAGE_TREE_DEPTH_LIMIT => stack.push(self.kids(id)),
+                    _ => {}
+                }
+            }
+        }
+        pages.into_iter()
+    }
+
+    fn kids(&self, id: ObjectId) -> &[Object] {
+        self.get_dictionary(id)
+            .and_then(|node| node.get(b"Kids"))
+            .and_then(Object::as_array)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns true if the document has an object with this id
+    pub(crate) fn contains_object(&self, id: ObjectId) -> bool {
+        self.object(id).is_some()
+    }
+
+    /// Adds an object under the given id, replacing the object that had it
+    pub(crate) fn insert_object(&mut self, id: ObjectId, object: Object) {
+        self.removed_objects.remove(&id);
+        self.document.objects.insert(id, object);
+    }
+
+    /// Removes an object from the document, returning true if it existed. A shared object is
+    /// only removed from this form.
+    pub(crate) fn remove_object(&mut self, id: ObjectId) -> bool {
+        let changed = self.document.objects.remove(&id).is_some();
+        let shared = self
+            .shared_objects
+            .as_ref()
+            .is_some_and(|objects| objects.contains_key(&id));
+        (shared && self.removed_objects.insert(id)) || changed
+    }
+
+    /// Iterates over every object of the document, in the order of their ids
+    pub(crate) fn objects(&self) -> impl Iterator<Item = (ObjectId, &Object)> {
+        let shared = self
+            .shared_objects
+            .iter()
+            .flat_map(|objects| objects.iter())
+            .filter(|(id, _)| {
+                !self.document.objects.contains_key(id) && !self.removed_objects.contains(id)
+            });
+        let mut objects = self
+            .document
+            .objects
+            .iter()
+            .chain(shared)
+            .map(|(&id, object)| (id, object))
+            .collect::<Vec<_>>();
+        objects.sort_unstable_by_key(|&(id, _)| id);
+        objects.into_iter()
+    }
+
+    fn object_count(&self) -> usize {
+        self.document.objects.len()
+            + self
+                .shared_objects
+                .as_ref()
+                .map_or(0, |objects| objects.len())
+    }
+
+    /// Gets the ids of the objects that can be reached from the trailer, like the ones
+    /// returned by `Document::traverse_objects`
+    pub(crate) fn reachable_objects(&self) -> HashSet<ObjectId> {
+        let mut reachable = HashSet::new();
+        let mut pending = self
+            .document
+            .trailer
+            .iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        while let Some(object) = pending.pop() {
+            match object {
+                Object::Array(items) => pending.extend(items),
+                Object::Dictionary(dict) => pending.extend(dict.iter().map(|(_, value)| value)),
+                Object::Stream(stream) => {
+                    pending.extend(stream.dict.iter().map(|(_, value)| value))
+                }
+                Object::Reference(id) if reachable.insert(*id) => pending.extend(self.object(*id)),
+                _ => {}
+            }
+        }
+        reachable
+    }
+
+    /// Removes the objects that can't be reached from the trailer, like
+    /// `Document::prune_objects`, and returns their ids
+    pub(crate) fn prune_objects(&mut self) -> Vec<ObjectId> {
+        if self.shared_objects.is_none() {
+            return self.document.prune_objects();
+        }
+        let reachable = self.reachable_objects();
+        let unreachable = self
+            .objects()
+            .map(|(id, _)| id)
+            .filter(|id| !reachable.contains(id))
+            .collect::<Vec<_>>();
+        for &id in &unreachable {
+            self.remove_object(id);
+        }
+        unreachable
+    }
+
+    /// Gets the whole document, which has to be assembled when the form shares objects
+    pub(crate) fn full_document(&self) -> Cow<'_, Document> {
+        if self.shared_objects.is_none() {
+            return Cow::Borrowed(&self.document);
+        }
+        let mut document = Document::with_version(self.document.version.as_str());
+        document.trailer = self.document.trailer.clone();
+        document.max_id = self.document.max_id;
+        document.objects = self
+            .objects()
+            .map(|(id, object)| (id, object.clone()))
+            .collect();
+        Cow::Owned(document)
+    }
+
+    /// Copies the shared objects into the form, so `document` holds the whole document again
+    pub(crate) fn unshare(&mut self) {
+        if let Some(shared) = self.shared_objects.take() {
+            let mut objects = Arc::try_unwrap(shared).unwrap_or_else(|shared| (*shared).clone());
+            for id in mem::take(&mut self.removed_objects) {
+                objects.remove(&id);
+            }
+            objects.append(&mut self.document.objects);
+            self.document.objects = objects;
+        }
+    }
+
+    /// Moves every object of the form into a map the clones of the form share, each clone
+    /// copying only the objects it modifies
+    pub(crate) fn share(&mut self) {
+        self.unshare();
+        let objects = mem::take(&mut self.document.objects);
+        self.shared_objects = Some(Arc::new(objects));
+    }
+
+    /// Returns true while the form has no object of its own besides those it shares
+    pub(crate) fn is_unchanged(&self) -> bool {
+        self.document.objects.is_empty() && self.removed_objects.is_empty()
+    }
+
+    /// Writes the whole document, reading the objects where they are instead of assembling a
+    /// `Document` first
+    pub(crate) fn write_document<W: Write>(&mut self, target: &mut W) -> io::Result<()> {
+        if self.shared_objects.is_none() {
+            return self.document.save_to(target);
+        }
+
+        let mut output = Vec::new();
+        writeln!(output, "%PDF-{}", self.document.version)?;
+        let mut entries = BTreeMap::new();
+        entries.insert(0, XrefEntry::Free(65535));
+        for (id, object) in self.objects() {
+            if matches!(
+                object.type_name(),
+                Ok("ObjStm") | Ok("XRef") | Ok("Linearized")
+            ) {
+                continue;
+            }
+            entries.insert(id.0, XrefEntry::InUse(output.len(), id.1));
+            write_indirect_object(&mut output, id, object)?;
+        }
+
+        let xref_start = output.len();
+        write_xref_table(&mut output, &entries)?;
+        self.document
+            .trailer
+            .set("Size", i64::from(self.document.max_id + 1));
+        output.extend_from_slice(b"trailer\n");
+        write_object(
+            &mut output,
+            &Object::Dictionary(self.document.trailer.clone()),
+        )?;
+        write!(output, "\nstartxref\n{}\n%%EOF", xref_start)?;
+        target.write_all(&output)
+    }
+}
@@ -0,0 +1,50 @@
+use lopdf::Object;
+use rayon::prelude::*;
+
+use crate::Form;
+
+impl Form {
+    /// Regenerates the appearance streams of the fields left out of date in deferred mode,
+    /// building the streams of the fields concurrently on the rayon thread pool. The streams are
+    /// then written into the document in a single pass.
+    ///
+    /// Produces the same appearances as the serial regeneration of the pending fields done on
+    /// save or when leaving deferred mode, with the options the text fields were set with.
+    /// Fields whose appearance cannot be generated, e.g. without a widget, are reported and left
+    /// untouched.
+    pub fn regenerate_all_appearances_parallel(&mut self) {
+        if self.pending_appearances.is_empty() {
+            return;
+        }
+
+        // Creating the missing streams and registering fonts needs the document mutably
+        let mut jobs = Vec::new();
+        for n in 0..self.len() {
+            if !self.pending_appearances.contains_key(&self.form_ids[n]) {
+                continue;
+            }
+            if let Some(content) = self.appearance_content(n) {
+                match self.prepare_appearance(n, &content) {
+                    Ok(target) => jobs.push((n, target, content)),
+                    Err(e) => warn!("Appearance regeneration failed: {e}"),
+                }
+            }
+        }
+
+        let form = &*self;
+        let streams = jobs
+            .par_iter()
+            .map(|(n, target, content)| (target.0, form.build_appearance(*n, target, content)))
+            .collect::<Vec<_>>();
+
+        for (id, stream) in streams {
+            match stream {
+                Ok(stream) => {
+                    self.insert_object(id, Object::Stream(stream));
+                }
+                Err(e) => warn!("Appearance regeneration failed: {e}"),
+            }
+        }
+        self.pending_appearances.clear();
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeSet;
+
+use lopdf::{Dictionary, Object, StringFormat};
+
+use crate::encoding::{decode_text_string, encode_text_string};
+use crate::utils::{pdf_date_now, DictionaryExt};
+use crate::Form;
+
+/// The key under which this crate stores its page-piece data dictionary
+const PIECE_INFO_KEY: &str = "pdf_forms2";
+
+impl Form {
+    /// Remembers that the field at index `n` was modified, so it is listed in the PieceInfo
+    /// written on save, parses its metadata again and, if enabled, copies its value into the
+    /// XFA data
+    pub(crate) fn mark_modified(&mut self, n: usize) {
+        self.refresh_field_info(n);
+        if let Some(name) = self.qualified_name(self.form_ids[n]) {
+            self.modified_fields.insert(name);
+        }
+        if self.sync_xfa {
+            self.write_xfa_value(n);
+        }
+    }
+
+    /// Returns the fully qualified names of the fields modified since the form was loaded
+    pub fn modified_fields(&self) -> Vec<String> {
+        self.modified_fields.iter().cloned().collect()
+    }
+
+    /// Writes the private data dictionary of this crate into the `/PieceInfo` of the document
+    /// catalog, as recommended by the spec for applications editing a document. Data dictionaries
+    /// written by other applications are left untouched. The fields listed by earlier saves are
+    /// kept and `/LastModified` is updated. Does nothing if no field was modified.
+    pub(crate) fn record_piece_info(&mut self) {
+        if self.modified_fields.is_empty() {
+            return;
+        }
+        let modified_fields = self.modified_fields.clone();
+
+        let root_id = match self
+            .document
+            .trailer
+            .get(b"Root")
+            .and_then(Object::as_reference)
+        {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+
+        // The PieceInfo may be inline in the catalog or an indirect dictionary
+        let piece_info_id = match self.get_dictionary(root_id) {
+            Ok(catalog) => match catalog.get(b"PieceInfo") {
+                Ok(Object::Reference(id)) => Some(*id),
+                _ => None,
+            },
+            Err(_) => return,
+        };
+
+        let piece_info = match piece_info_id {
+            Some(id) => self.get_object_mut(id).and_then(Object::as_dict_mut),
+            None => {
+                let catalog = match self.get_object_mut(root_id).and_then(Object::as_dict_mut) {
+                    Ok(catalog) => catalog,
+                    Err(_) => return,
+                };
+                if !matches!(catalog.get(b"PieceInfo"), Ok(Object::Dictionary(_))) {
+                    catalog.set_in_place("PieceInfo", Object::Dictionary(Dictionary::new()));
+                }
+                catalog.get_mut(b"PieceInfo").and_then(Object::as_dict_mut)
+            }
+        };
+
+        let piece_info = match piece_info {
+            Ok(piece_info) => piece_info,
+            Err(_) => return,
+        };
+        let mut data = match piece_info.get(PIECE_INFO_KEY.as_bytes()) {
+            Ok(Object::Dictionary(data)) => data.clone(),
+            _ => Dictionary::new(),
+        };
+        let mut private = match data.get(b"Private") {
+            Ok(Object::Dictionary(private)) => private.clone(),
+            _ => Dictionary::new(),
+        };
+
+        let mut names = match private.get(b"ModifiedFields") {
+            Ok(Object::Array(fields)) => fields
+                .iter()
+                .filter_map(|name| name.as_str().ok())
+                .map(decode_text_string)
+                .collect(),
+            _ => BTreeSet::new(),
+        };
+        names.extend(modified_fields);
+        let fields = names
+            .iter()
+            .map(|name| Object::String(encode_text_string(name), StringFormat::Literal))
+            .collect::<Vec<_>>();
+
+        private.set(
+            "Application",
+            Object::string_literal(concat!("pdf_forms2 ", env!("CARGO_PKG_VERSION"))),
+        );
+        private.set("ModifiedFields", Object::Array(fields));
+        data.set("LastModified", Object::string_literal(pdf_date_now()));
+        data.set("Private", Object::Dictionary(private));
+        piece_info.set_in_place(PIECE_INFO_KEY, Object::Dictionary(data));
+    }
+}
@@ -0,0 +1,84 @@
+use lopdf::{Dictionary, Object, ObjectId};
+
+use crate::utils::get_unknown_keys;
+use crate::{FieldKey, Form, ValueError};
+
+impl Form {
+    /// Enables or disables preservation mode. While enabled, operations that would drop entries
+    /// this crate doesn't understand (PieceInfo, proprietary keys, ...) are refused with
+    /// `ValueError::WouldDiscardData` instead of silently discarding them: removing a field,
+    /// pruning unused fields or widgets when saving with `SaveOptions::prune`, and merging the
+    /// form into another with `merge`.
+    ///
+    /// Regardless of this mode, editing a value never drops or reorders the other entries of a
+    /// field dictionary.
+    pub fn set_preservation_mode(&mut self, enabled: bool) {
+        self.preservation_mode = enabled;
+    }
+
+    /// Returns true if preservation mode is enabled
+    pub fn preservation_mode(&self) -> bool {
+        self.preservation_mode
+    }
+
+    /// Gets the keys of the field at index `n` that are not defined by the spec, which usually
+    /// hold private data of the application that produced the document
+    ///
+    /// Returns an empty list if n is larger than the number of fields or the field is not part of
+    /// the form.
+    pub fn get_unknown_keys(&self, n: impl FieldKey) -> Vec<String> {
+        let n = match self.try_resolve(n) {
+            Ok(n) => n,
+            Err(_) => return Vec::new(),
+        };
+        match self.get_dictionary(self.form_ids[n]) {
+            Ok(field) => get_unknown_keys(field),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Fails if preservation mode is enabled and the field at index `n` or one of its kid widgets
+    /// holds unknown entries
+    pub(crate) fn check_preservation(&self, n: usize) -> Result<(), ValueError> {
+        let oid = self.form_ids[n];
+        self.check_preservation_of(std::iter::once(oid).chain(self.kids_of(oid)))
+    }
+
+    /// Fails if preservation mode is enabled and a field of the form or one of their widgets holds
+    /// unknown entries
+    pub(crate) fn check_preservation_all(&self) -> Result<(), ValueError> {
+        (0..self.len()).try_for_each(|n| self.check_preservation(n))
+    }
+
+    /// Fails if preservation mode is enabled and one of the field or widget dictionaries `ids`
+    /// holds unknown entries. Other objects are ignored.
+    pub(crate) fn check_preservation_of(
+        &self,
+        ids: impl IntoIterator<Item = ObjectId>,
+    ) -> Result<(), ValueError> {
+        if !self.preservation_mode {
+            return Ok(());
+        }
+
+        let discards = ids
+            .into_iter()
+            .filter_map(|id| self.get_dictionary(id).ok())
+            .filter(|dict| is_field_or_widget(dict))
+            .any(|dict| !get_unknown_keys(dict).is_empty());
+        if discards {
+            Err(ValueError::WouldDiscardData)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Returns true if the dictionary is a field or a widget annotation
+fn is_field_or_widget(dict: &Dictionary) -> bool {
+    dict.has(b"FT")
+        || dict.has(b"Parent")
+        || matches!(
+            dict.get(b"Subtype").and_then(Object::as_name),
+            Ok(b"Widget")
+        )
+}
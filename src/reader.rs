@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use lopdf::ObjectId;
+
+use crate::{FieldState, FieldType, Form};
+
+/// The metadata and value of a single field, as captured by a `FormReader`
+#[derive(Debug, Clone)]
+pub struct FieldSnapshot {
+    pub object_id: ObjectId,
+    pub name: Option<String>,
+    pub qualified_name: Option<String>,
+    pub field_type: FieldType,
+    pub state: FieldState,
+}
+
+/// A read-only snapshot of the fields of a `Form`
+///
+/// The snapshot is taken once by `Form::reader` and shared behind an `Arc`, so cloning a reader is
+/// cheap and readers can be handed to other threads while the `Form` keeps being filled. Readers
+/// don't see the changes made to the form after they were taken.
+#[derive(Debug, Clone)]
+pub struct FormReader {
+    fields: Arc<Vec<FieldSnapshot>>,
+    names: Arc<HashMap<String, usize>>,
+}
+
+impl FormReader {
+    /// Returns the number of fields the form has
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns true if empty
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Gets all the fields of the snapshot, in the same order as the indices of the form
+    pub fn fields(&self) -> &[FieldSnapshot] {
+        &self.fields
+    }
+
+    /// Gets the field of the given index, if it exists
+    pub fn get(&self, n: usize) -> Option<&FieldSnapshot> {
+        self.fields.get(n)
+    }
+
+    /// Gets the index of the field with the given fully qualified or partial name, following
+    /// the same rules as `Form::get_index_by_name`
+    pub fn get_index_by_name(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+}
+
+impl Form {
+    /// Takes a read-only snapshot of the names, types and states of all the fields, which can be
+    /// cloned cheaply and shared between threads
+    pub fn reader(&self) -> FormReader {
+        let fields = (0..self.len())
+            .map(|n| FieldSnapshot {
+                object_id: self.form_ids[n],
+                name: self.try_get_name(n).ok().flatten(),
+                qualified_name: self.get_qualified_name(n),
+                field_type: self.try_get_type(n).unwrap_or(FieldType::Unknown),
+                state: self.try_get_state(n).unwrap_or(FieldState::Unknown),
+            })
+            .collect();
+
+        FormReader {
+            fields: Arc::new(fields),
+            names: Arc::new(self.names.clone()),
+        }
+    }
+}
+
+// `Form` and `FormReader` must stay usable from several threads
+const _: fn() = || {
+    fn assert<T: Send + Sync>() {}
+    assert::<Form>();
+    assert::<FormReader>();
+};
@@ -0,0 +1,120 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use lopdf::ObjectId;
+
+use crate::save_policy::SaveMode;
+use crate::Form;
+
+/// What happens to the streams of the document when it is saved with `Form::save_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StreamCompression {
+    /// Streams are written as they are
+    #[default]
+    Unchanged,
+    /// Uncompressed streams are compressed with Flate, unless that doesn't make them smaller
+    Compress,
+    /// Streams compressed with Flate or LZW are written uncompressed, e.g. to read them in a text
+    /// editor. Images are left as they are.
+    Decompress,
+}
+
+/// Options controlling how `Form::save_with` writes the document
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    compression: StreamCompression,
+    prune: bool,
+    deterministic: bool,
+}
+
+impl SaveOptions {
+    /// Creates options that write the document like `Form::save`
+    pub fn new() -> Self {
+        SaveOptions::default()
+    }
+
+    /// Sets whether the streams are compressed, decompressed or left as they are
+    pub fn compression(mut self, compression: StreamCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets whether the objects no longer referenced from the trailer, e.g. the widgets and
+    /// appearances left behind by `remove_field`, are dropped. In preservation mode, the save
+    /// fails if a dropped field or widget holds entries this crate doesn't know about.
+    pub fn prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    /// Sets whether the output only depends on the content of the form, so saving the same form
+    /// twice produces byte-identical files. The objects are numbered from 1 in the order of their
+    /// current numbers, and the `/PieceInfo` of the document, which holds the time of the save,
+    /// is not updated.
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+}
+
+impl Form {
+    /// Drops the objects that can no longer be reached from the trailer of the document, such as
+    /// the widgets left behind by `remove_field` or appearance streams that were replaced, and
+    /// returns their ids. `save` writes every object of the document, reachable or not, so
+    /// pruning them shrinks the output.
+    pub fn prune_unused_objects(&mut self) -> Vec<ObjectId> {
+        let removed = self.prune_objects();
+        if !removed.is_empty() {
+            self.pending_appearances
+                .retain(|id, _| !removed.contains(id));
+            self.reindex();
+        }
+        removed
+    }
+
+    /// Saves the form to the specified path, as controlled by `options`
+    pub fn save_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &SaveOptions,
+    ) -> Result<(), io::Error> {
+        // Refuse before the file is truncated
+        self.save_mode()?;
+        let mut file = File::create(path)?;
+        self.save_with_to(&mut file, options)
+    }
+
+    /// Saves the form to the specified target, as controlled by `options`. Compression, pruning
+    /// and renumbering are applied to the written copy, the form itself keeps its objects.
+    ///
+    /// When the save policy requires an incremental update, it is written without applying
+    /// `options`, which would change the existing objects.
+    pub fn save_with_to<W: Write>(
+        &mut self,
+        target: &mut W,
+        options: &SaveOptions,
+    ) -> Result<(), io::Error> {
+        if let SaveMode::Incremental(original) = self.save_mode()? {
+            return self.save_incremental(&original, target);
+        }
+        self.prepare_save(!options.deterministic);
+
+        let mut document = self.full_document().into_owned();
+        if options.prune {
+            let removed = document.prune_objects();
+            self.check_preservation_of(removed)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+        match options.compression {
+            StreamCompression::Unchanged => {}
+            StreamCompression::Compress => document.compress(),
+            StreamCompression::Decompress => document.decompress(),
+        }
+        if options.deterministic {
+            document.renumber_objects();
+        }
+
+        document.save_to(target)
+    }
+}
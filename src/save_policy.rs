@@ -0,0 +1,96 @@
+use std::io;
+use std::sync::Arc;
+
+use crate::Form;
+
+/// What `save`, `save_to` and `save_with` do with a document that a full rewrite would damage:
+/// a signed document, or a Reader-enabled document, whose signatures would no longer match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SavePolicy {
+    /// Rewrite the document anyway
+    #[default]
+    Allow,
+    /// Rewrite the document, logging a warning
+    Warn,
+    /// Refuse to save with an error, leaving the target untouched
+    Error,
+    /// Save as an incremental update of the file the form was loaded from, like
+    /// `save_incremental`
+    IncrementalOnly,
+}
+
+/// How the next save writes the document
+pub(crate) enum SaveMode {
+    /// The whole document is rewritten
+    Rewrite,
+    /// An incremental update is appended to the bytes of the original file
+    Incremental(Arc<Vec<u8>>),
+}
+
+impl Form {
+    /// Returns true if the document carries usage rights (`/Perms /UR3`), the signature that
+    /// enables saving filled forms in Adobe Reader. Any change outside of an incremental update
+    /// breaks that signature, and Reader then warns that the document was modified.
+    pub fn has_usage_rights(&self) -> bool {
+        self.catalog()
+            .and_then(|catalog| catalog.get(b"Perms"))
+            .and_then(|perms| self.dereference(perms))
+            .and_then(|(_, perms)| perms.as_dict())
+            .is_ok_and(|perms| perms.has(b"UR3") || perms.has(b"UR"))
+    }
+
+    /// Chooses what saving does with a document whose signatures or usage rights a full rewrite
+    /// would invalidate. With `SavePolicy::IncrementalOnly`, the bytes of the file are kept when the
+    /// form is loaded from a path or a reader; forms built from a parsed `Document` have none,
+    /// and saving them fails.
+    pub fn set_save_policy(&mut self, policy: SavePolicy) {
+        self.save_policy = policy;
+    }
+
+    /// Returns what saving does with a document a full rewrite would damage
+    pub fn save_policy(&self) -> SavePolicy {
+        self.save_policy
+    }
+
+    /// Returns true if a full rewrite would damage the document
+    pub(crate) fn needs_incremental_save(&self) -> bool {
+        self.is_signed() || self.has_usage_rights()
+    }
+
+    /// Keeps the bytes of the file the form was loaded from, if they may be needed to save it
+    /// incrementally
+    pub(crate) fn keep_original(&mut self, original: Vec<u8>) {
+        if self.needs_incremental_save() {
+            self.original = Some(Arc::new(original));
+        }
+    }
+
+    /// Decides how the document is saved according to the save policy
+    pub(crate) fn save_mode(&self) -> Result<SaveMode, io::Error> {
+        if self.save_policy == SavePolicy::Allow || !self.needs_incremental_save() {
+            return Ok(SaveMode::Rewrite);
+        }
+        let damage = if self.is_signed() {
+            "the signatures"
+        } else {
+            "the usage rights"
+        };
+
+        match self.save_policy {
+            SavePolicy::Allow => Ok(SaveMode::Rewrite),
+            SavePolicy::Warn => {
+                warn!("Saving invalidates {damage} of the document");
+                Ok(SaveMode::Rewrite)
+            }
+            SavePolicy::Error => Err(io::Error::other(format!(
+                "saving would invalidate {damage} of the document"
+            ))),
+            SavePolicy::IncrementalOnly => match &self.original {
+                Some(original) => Ok(SaveMode::Incremental(Arc::clone(original))),
+                None => Err(io::Error::other(
+                    "the original file is needed to save the document incrementally",
+                )),
+            },
+        }
+    }
+}
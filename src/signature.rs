@@ -0,0 +1,197 @@
+use lopdf::{Dictionary, Object, ObjectId};
+
+use crate::encoding::decode_text_string;
+use crate::signing::BYTE_RANGE_PLACEHOLDER;
+use crate::{FieldId, FieldState, Form, ValueError};
+
+/// The result of checking the byte ranges of a signature field against the bytes of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureStatus {
+    /// The signature field
+    pub field: FieldId,
+    /// Whether the field holds a signature at all
+    pub signed: bool,
+    /// Whether the `/ByteRange` is well formed: it starts at the beginning of the file, stays
+    /// inside it and leaves out exactly the hex string holding the signature
+    pub byte_range_valid: bool,
+    /// The length of the revision that was signed, i.e. the end of the last byte range
+    pub signed_length: Option<usize>,
+    /// Whether bytes were appended to the file after the signed revision (incremental updates
+    /// such as form filling or further signatures)
+    pub modified_after_signing: bool,
+}
+
+impl Form {
+    /// Checks the `/ByteRange` of every signature field against `file`, the bytes of the document
+    /// this form was loaded from, and reports whether content was added after signing.
+    ///
+    /// This only validates the coverage of the signatures, the cryptographic digest in
+    /// `/Contents` is not verified.
+    pub fn verify_signatures(&self, file: &[u8]) -> Vec<SignatureStatus> {
+        let byte_ranges = self.signature_byte_ranges();
+
+        (0..self.len())
+            .filter(|&n| self.is_signature_field(n))
+            .map(|n| {
+                let oid = self.form_ids[n];
+                let ranges = byte_ranges
+                    .iter()
+                    .find(|(id, _)| *id == oid)
+                    .map(|(_, ranges)| ranges.as_slice());
+                let signed_length = ranges
+                    .and_then(|ranges| ranges.last())
+                    .map(|&(offset, length)| offset + length);
+
+                SignatureStatus {
+                    field: self.field_id_of(oid),
+                    signed: ranges.is_some(),
+                    byte_range_valid: ranges.is_some_and(|ranges| byte_range_valid(file, ranges)),
+                    signed_length,
+                    modified_after_signing: signed_length.is_some_and(|end| end < file.len()),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns true if a signature field of the form is signed. Rewriting a signed document
+    /// invalidates its signatures; see `set_save_policy` and `save_incremental` to fill the
+    /// remaining fields without breaking them.
+    pub fn is_signed(&self) -> bool {
+        !self.signed_fields().is_empty()
+    }
+
+    /// Gets the object ids of the signed signature fields
+    pub(crate) fn signed_fields(&self) -> Vec<ObjectId> {
+        self.signature_byte_ranges()
+            .into_iter()
+            .filter(|(_, ranges)| !is_placeholder(ranges))
+            .map(|(oid, _)| oid)
+            .collect()
+    }
+
+    /// Returns true if the field at index `n` is a signature field (`/FT /Sig`)
+    pub(crate) fn is_signature_field(&self, n: usize) -> bool {
+        self.get_dictionary(self.form_ids[n])
+            .and_then(|field| field.get(b"FT"))
+            .and_then(Object::as_name)
+            .map(|ft| ft == b"Sig")
+            .unwrap_or(false)
+    }
+
+    /// Refuses to write a value into a signature field, which would corrupt the signature slot
+    pub(crate) fn check_not_signature(&self, n: usize) -> Result<(), ValueError> {
+        if self.is_signature_field(n) {
+            Err(ValueError::SignatureField {
+                field: self.field_context(n),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Gets the `/ByteRange` of every signed signature field as `(offset, length)` pairs, together
+    /// with the object id of the field
+    pub(crate) fn signature_byte_ranges(&self) -> Vec<(ObjectId, Vec<(usize, usize)>)> {
+        let mut res = Vec::new();
+
+        for &oid in &self.form_ids {
+            let field = match self.get_dictionary(oid) {
+                Ok(field) => field,
+                Err(_) => continue,
+            };
+            if !matches!(field.get(b"FT").and_then(Object::as_name), Ok(b"Sig")) {
+                continue;
+            }
+
+            if let Some(ranges) = self.signature_value(field).and_then(byte_ranges) {
+                res.push((oid, ranges));
+            }
+        }
+
+        res
+    }
+
+    /// Builds the state of a signature field from its signature dictionary
+    pub(crate) fn signature_state(&self, field: &Dictionary) -> FieldState {
+        let value = self.signature_value(field);
+        let text = |key: &[u8]| match value.map(|value| value.get(key)) {
+            Some(Ok(Object::String(s, _))) => Some(decode_text_string(s)),
+            _ => None,
+        };
+
+        // Signatures prepared with `prepare_signature` are not signed yet
+        let prepared = value
+            .and_then(byte_ranges)
+            .is_some_and(|ranges| is_placeholder(&ranges));
+        FieldState::Signature {
+            signed: value.is_some() && !prepared,
+            signer_name: text(b"Name"),
+            sign_date: text(b"M"),
+        }
+    }
+
+    /// The value of a signed field is the signature dictionary, usually indirect
+    pub(crate) fn signature_value<'a>(&'a self, field: &'a Dictionary) -> Option<&'a Dictionary> {
+        match field.get(b"V") {
+            Ok(Object::Reference(id)) => self.get_dictionary(*id).ok(),
+            Ok(Object::Dictionary(dict)) => Some(dict),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the `/ByteRange` of a signature dictionary as `(offset, length)` pairs
+pub(crate) fn byte_ranges(value: &Dictionary) -> Option<Vec<(usize, usize)>> {
+    let byte_range = value.get(b"ByteRange").and_then(Object::as_array).ok()?;
+    let numbers = byte_range
+        .iter()
+        .filter_map(|n| n.as_i64().ok())
+        .map(|n| n.max(0) as usize)
+        .collect::<Vec<_>>();
+
+    Some(
+        numbers
+            .chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect(),
+    )
+}
+
+/// Returns true if the byte ranges are the placeholder of a signature prepared with
+/// `prepare_signature`, which is not signed yet
+pub(crate) fn is_placeholder(ranges: &[(usize, usize)]) -> bool {
+    ranges
+        .iter()
+        .any(|&(_, length)| length as i64 == BYTE_RANGE_PLACEHOLDER)
+}
+
+/// Checks that the byte ranges cover the start of the file in order, stay within it, and that
+/// every gap between them is a single hex string (the placeholder of `/Contents`)
+fn byte_range_valid(file: &[u8], ranges: &[(usize, usize)]) -> bool {
+    if ranges.is_empty() || ranges[0].0 != 0 {
+        return false;
+    }
+
+    let mut end = 0;
+    for (i, &(offset, length)) in ranges.iter().enumerate() {
+        if offset < end || offset + length > file.len() {
+            return false;
+        }
+        if i > 0 && !is_hex_string(&file[end..offset]) {
+            return false;
+        }
+        end = offset + length;
+    }
+
+    true
+}
+
+fn is_hex_string(bytes: &[u8]) -> bool {
+    match bytes {
+        [b'<', content @ .., b'>'] => content
+            .iter()
+            .all(|byte| byte.is_ascii_hexdigit() || byte.is_ascii_whitespace()),
+        _ => false,
+    }
+}
@@ -0,0 +1,318 @@
+use image::GenericImageView;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Object, ObjectId, Stream};
+
+use crate::appearance::{standard_font_dictionary, DEFAULT_FONT};
+use crate::encoding::encode_win_ansi;
+use crate::geometry::AppearanceFrame;
+use crate::utils::DictionaryExt;
+use crate::{measure_text, Color, FieldKey, FieldType, Form, StandardFont, ValueError};
+
+/// Margin around the image and the text, in user space units
+const MARGIN: f32 = 2.0;
+
+/// Largest font size of the text, when it fits
+const MAX_FONT_SIZE: f32 = 12.0;
+
+/// Distance between the baselines of two lines, relative to the font size
+const LEADING: f32 = 1.2;
+
+/// The content of the visible stamp of a signature field, built with `SignatureAppearance::new`
+/// and drawn by `Form::set_signature_appearance`
+///
+/// The text lists the signer, the reason, the location and the date that were given, one per
+/// line. With an image, e.g. a handwritten signature or a logo, the image takes the left half of
+/// the widget and the text the right half.
+#[derive(Debug, Clone, Default)]
+pub struct SignatureAppearance {
+    name: Option<String>,
+    reason: Option<String>,
+    location: Option<String>,
+    date: Option<String>,
+    image: Option<Vec<u8>>,
+    font_size: Option<f32>,
+}
+
+impl SignatureAppearance {
+    /// Creates an empty appearance
+    pub fn new() -> Self {
+        SignatureAppearance::default()
+    }
+
+    /// Sets the name of the signer, shown as `Digitally signed by <name>`
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Sets the reason of the signature, shown as `Reason: <reason>`
+    pub fn reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_owned());
+        self
+    }
+
+    /// Sets where the document was signed, shown as `Location: <location>`
+    pub fn location(mut self, location: &str) -> Self {
+        self.location = Some(location.to_owned());
+        self
+    }
+
+    /// Sets the date of the signature, shown as `Date: <date>` as it is given
+    pub fn date(mut self, date: &str) -> Self {
+        self.date = Some(date.to_owned());
+        self
+    }
+
+    /// Sets an image drawn next to the text, a JPEG or PNG file
+    pub fn image(mut self, image: &[u8]) -> Self {
+        self.image = Some(image.to_vec());
+        self
+    }
+
+    /// Sets the font size of the text, in points. By default the text is as large as fits the
+    /// widget, up to 12 points.
+    pub fn font_size(mut self, font_size: f32) -> Self {
+        self.font_size = Some(font_size);
+        self
+    }
+
+    /// Gets the lines of text of the stamp
+    fn lines(&self) -> Vec<String> {
+        vec![
+            self.name
+                .as_ref()
+                .map(|name| format!("Digitally signed by {}", name)),
+            self.reason
+                .as_ref()
+                .map(|reason| format!("Reason: {}", reason)),
+            self.location
+                .as_ref()
+                .map(|location| format!("Location: {}", location)),
+            self.date.as_ref().map(|date| format!("Date: {}", date)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+impl Form {
+    /// Draws the visible stamp of the signature field at index `n` in the normal appearance of
+    /// its widgets, with the layers Acrobat uses: the stream of the widget draws the `/FRM` form,
+    /// which draws the background layer `/n0`, left blank, and the layer `/n2` holding the text
+    /// and the image. Call it before the document is signed, as the appearance is covered by the
+    /// signature.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field is not a signature field,
+    /// `ValueError::InvalidImage` if the image cannot be decoded, or `ValueError::NotFound` if
+    /// the field has no widget with a rectangle.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_signature_appearance(
+        &mut self,
+        n: impl FieldKey,
+        appearance: &SignatureAppearance,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        if !self.is_signature_field(n) {
+            return Err(self.type_mismatch(n, &[FieldType::Signature]));
+        }
+
+        let image = match &appearance.image {
+            Some(bytes) => {
+                let decoded =
+                    image::load_from_memory(bytes).map_err(|_| ValueError::InvalidImage)?;
+                let (width, height) = decoded.dimensions();
+                let id = self.add_image(bytes, &decoded);
+                Some((id, width as f32, height as f32))
+            }
+            None => None,
+        };
+
+        for widget in self.widgets_of(self.form_ids[n]) {
+            let frame = self.appearance_frame(widget).ok_or(ValueError::NotFound)?;
+            let stream = self.signature_stream(&frame, appearance, image)?;
+            self.dictionary_mut(widget)?
+                .set_in_place("AP", dictionary! { "N" => stream });
+        }
+        self.refresh_field_info(n);
+
+        Ok(())
+    }
+
+    /// Builds the layered appearance stream of a signature widget and returns its id
+    fn signature_stream(
+        &mut self,
+        frame: &AppearanceFrame,
+        appearance: &SignatureAppearance,
+        image: Option<(ObjectId, f32, f32)>,
+    ) -> Result<ObjectId, ValueError> {
+        let bbox = AppearanceFrame {
+            rotation: 0,
+            ..*frame
+        };
+
+        // The background layer, which viewers may draw over when the signature is validated
+        let n0 = self.add_form_xobject(&bbox, Dictionary::new(), b"% DSBlank\n".to_vec());
+
+        let (font_name, font) = standard_font_dictionary(DEFAULT_FONT);
+        let mut resources = dictionary! {
+            "Font" => dictionary! { font_name.as_str() => font },
+        };
+        let operations = match image {
+            Some((image, width, height)) if !appearance.lines().is_empty() => {
+                resources.set("XObject", dictionary! { "Img" => image });
+                let half = 0.5 * frame.width;
+                let mut operations =
+                    image_operations([0.0, 0.0, half, frame.height], width, height);
+                operations.extend(text_operations(
+                    [half, 0.0, frame.width, frame.height],
+                    appearance,
+                    &font_name,
+                    frame.user_unit,
+                ));
+                operations
+            }
+            Some((image, width, height)) => {
+                resources.set("XObject", dictionary! { "Img" => image });
+                image_operations([0.0, 0.0, frame.width, frame.height], width, height)
+            }
+            None => text_operations(
+                [0.0, 0.0, frame.width, frame.height],
+                appearance,
+                &font_name,
+                frame.user_unit,
+            ),
+        };
+        let content = encode(operations)?;
+        let n2 = self.add_form_xobject(&bbox, resources, content);
+
+        let frm = self.add_form_xobject(
+            &bbox,
+            dictionary! { "XObject" => dictionary! { "n0" => n0, "n2" => n2 } },
+            b"q 1 0 0 1 0 0 cm /n0 Do Q q 1 0 0 1 0 0 cm /n2 Do Q\n".to_vec(),
+        );
+        // Only the outer stream is rotated with the page
+        Ok(self.add_form_xobject(
+            frame,
+            dictionary! { "XObject" => dictionary! { "FRM" => frm } },
+            b"q 1 0 0 1 0 0 cm /FRM Do Q\n".to_vec(),
+        ))
+    }
+
+    /// Adds a form XObject with the bounding box and matrix of `frame`
+    fn add_form_xobject(
+        &mut self,
+        frame: &AppearanceFrame,
+        resources: Dictionary,
+        content: Vec<u8>,
+    ) -> ObjectId {
+        let mut dict = dictionary! { "Resources" => resources };
+        frame.apply(&mut dict);
+        self.document.add_object(Stream::new(dict, content))
+    }
+}
+
+/// Builds the operations drawing the image `Img` of `width` by `height` pixels, scaled
+/// proportionally and centered within `area` (`[llx, lly, urx, ury]`)
+fn image_operations(area: [f32; 4], width: f32, height: f32) -> Vec<Operation> {
+    let (area_width, area_height) = (
+        area[2] - area[0] - 2.0 * MARGIN,
+        area[3] - area[1] - 2.0 * MARGIN,
+    );
+    let scale = (area_width / width).min(area_height / height).max(0.0);
+    let (width, height) = (width * scale, height * scale);
+    let x = area[0] + MARGIN + 0.5 * (area_width - width);
+    let y = area[1] + MARGIN + 0.5 * (area_height - height);
+
+    vec![
+        Operation::new("q", vec![]),
+        Operation::new(
+            "cm",
+            vec![
+                width.into(),
+                0.into(),
+                0.into(),
+                height.into(),
+                x.into(),
+                y.into(),
+            ],
+        ),
+        Operation::new("Do", vec![Object::Name(b"Img".to_vec())]),
+        Operation::new("Q", vec![]),
+    ]
+}
+
+/// Builds the operations drawing the lines of the stamp from the top left of `area`
+/// (`[llx, lly, urx, ury]`), with the font registered as `font_name`
+fn text_operations(
+    area: [f32; 4],
+    appearance: &SignatureAppearance,
+    font_name: &str,
+    user_unit: f32,
+) -> Vec<Operation> {
+    let lines = appearance.lines();
+    if lines.is_empty() {
+        return Vec::new();
+    }
+    let width = area[2] - area[0] - 2.0 * MARGIN;
+    let height = area[3] - area[1] - 2.0 * MARGIN;
+
+    // The largest size at which every line fits the area
+    let font_size = match appearance.font_size {
+        Some(font_size) => font_size / user_unit,
+        None => lines
+            .iter()
+            .map(|line| {
+                let line_width = measure_text(StandardFont::Helvetica, 1.0, line);
+                if line_width > 0.0 {
+                    width / line_width
+                } else {
+                    MAX_FONT_SIZE
+                }
+            })
+            .fold(height / (lines.len() as f32 * LEADING), f32::min)
+            .clamp(1.0, MAX_FONT_SIZE),
+    };
+
+    let mut operations = vec![
+        Operation::new("BT", vec![]),
+        Operation::new(
+            "Tf",
+            vec![
+                Object::Name(font_name.as_bytes().to_vec()),
+                font_size.into(),
+            ],
+        ),
+        Color::Gray(0.0).fill_operation(),
+    ];
+    let top = area[3] - MARGIN - font_size;
+    for (i, line) in lines.iter().enumerate() {
+        let y = top - i as f32 * font_size * LEADING;
+        operations.push(Operation::new(
+            "Tm",
+            vec![
+                1.into(),
+                0.into(),
+                0.into(),
+                1.into(),
+                (area[0] + MARGIN).into(),
+                y.into(),
+            ],
+        ));
+        operations.push(Operation::new(
+            "Tj",
+            vec![Object::string_literal(encode_win_ansi(line))],
+        ));
+    }
+    operations.push(Operation::new("ET", vec![]));
+    operations
+}
+
+/// Encodes the operations of a content stream
+fn encode(operations: Vec<Operation>) -> Result<Vec<u8>, ValueError> {
+    Content { operations }
+        .encode()
+        .map_err(|_| ValueError::NotFound)
+}
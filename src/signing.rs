@@ -0,0 +1,225 @@
+use std::ops::Range;
+
+use lopdf::{dictionary, Object, ObjectId, StringFormat};
+
+use crate::encoding::encode_text_string;
+use crate::utils::{pdf_date_now, DictionaryExt};
+use crate::{FieldKey, FieldType, Form, ValueError};
+
+/// Offset written in the `/ByteRange` of a prepared signature until the saved file is known,
+/// wide enough for the offsets of files up to 10 GB
+pub(crate) const BYTE_RANGE_PLACEHOLDER: i64 = 9_999_999_999;
+
+/// `SignaturesExist` and `AppendOnly` flags of the AcroForm `/SigFlags`
+const SIG_FLAGS: i64 = 3;
+
+impl Form {
+    /// Creates an empty signature field named `name` on the page at index `page` (starting at 0),
+    /// within `rect` (`[llx, lly, urx, ury]`, in page coordinates). Use a zero-sized rect for
+    /// an invisible signature.
+    ///
+    /// Returns the index of the new field. Fails with `ValueError::NotFound` if the page doesn't
+    /// exist, or `ValueError::NameTaken` if a top level field already has the name.
+    pub fn add_signature_field(
+        &mut self,
+        page: usize,
+        rect: [f32; 4],
+        name: &str,
+    ) -> Result<usize, ValueError> {
+        let page = self.page_id(page).ok_or(ValueError::NotFound)?;
+        self.check_new_name(None, name, None)?;
+
+        let mut field = self.widget_dictionary(page, rect);
+        field.set("FT", "Sig");
+        field.set("T", Object::string_literal(encode_text_string(name)));
+
+        self.add_field(page, field)
+    }
+
+    /// Prepares the signature field at index `n` to be signed by an external signing service:
+    /// its value becomes a signature dictionary (`adbe.pkcs7.detached`) whose `/Contents` is
+    /// `reserved_len` zero bytes, the room left for the CMS signature, and whose `/ByteRange`
+    /// is a placeholder. Once the form is saved, `SignatureSlot::locate` finds the slot in the
+    /// file, writes the actual byte range and gives the bytes to sign.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field is not a signature field, or
+    /// `ValueError::Readonly` if it is already signed.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn prepare_signature(
+        &mut self,
+        n: impl FieldKey,
+        reserved_len: usize,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.prepare_signature_value(
+            n,
+            reserved_len,
+            dictionary! {
+                "Type" => "Sig",
+                "Filter" => "Adobe.PPKLite",
+                "SubFilter" => "adbe.pkcs7.detached",
+                "M" => Object::string_literal(pdf_date_now()),
+            },
+        )
+        .map(|_| ())
+    }
+
+    /// Sets the value of the signature field at index `n` to the signature dictionary `value`,
+    /// completed with the placeholders of its `/ByteRange` and `/Contents`, and returns the id
+    /// of the signature dictionary
+    pub(crate) fn prepare_signature_value(
+        &mut self,
+        n: usize,
+        reserved_len: usize,
+        mut value: lopdf::Dictionary,
+    ) -> Result<ObjectId, ValueError> {
+        if !self.is_signature_field(n) {
+            return Err(self.type_mismatch(n, &[FieldType::Signature]));
+        }
+        let oid = self.form_ids[n];
+        let field = self.get_dictionary(oid).map_err(|_| ValueError::NotFound)?;
+        if self.signature_value(field).is_some() {
+            return Err(ValueError::Readonly {
+                field: self.field_context(n),
+            });
+        }
+
+        let placeholder = Object::Integer(BYTE_RANGE_PLACEHOLDER);
+        value.set(
+            "ByteRange",
+            vec![
+                0.into(),
+                placeholder.clone(),
+                placeholder.clone(),
+                placeholder,
+            ],
+        );
+        value.set(
+            "Contents",
+            Object::String(vec![0; reserved_len], StringFormat::Hexadecimal),
+        );
+        let value = self.document.add_object(value);
+        self.dictionary_mut(oid)?.set_in_place("V", value);
+
+        if let Some(acroform) = self.acroform_mut() {
+            let flags = acroform
+                .get(b"SigFlags")
+                .and_then(Object::as_i64)
+                .unwrap_or(0);
+            acroform.set_in_place("SigFlags", flags | SIG_FLAGS);
+        }
+        self.refresh_field_info(n);
+        Ok(value)
+    }
+}
+
+/// The slot of a prepared signature in a saved file, where the signature is embedded once it
+/// has been computed over the rest of the file
+///
+/// ```no_run
+/// # use pdf_forms2::{Form, SignatureSlot};
+/// # fn sign(data: &[u8]) -> Vec<u8> { unimplemented!() }
+/// let mut form = Form::load("path/to/pdf").unwrap();
+/// form.prepare_signature(0, 8192).unwrap();
+/// let mut pdf = Vec::new();
+/// form.save_to(&mut pdf).unwrap();
+///
+/// let slot = SignatureSlot::locate(&mut pdf).unwrap();
+/// let cms = sign(&slot.signed_bytes(&pdf));
+/// slot.embed(&mut pdf, &cms).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureSlot {
+    /// Range of the hex string of `/Contents` in the file, delimiters included
+    contents: Range<usize>,
+    /// Length of the file
+    len: usize,
+}
+
+impl SignatureSlot {
+    /// Finds the last prepared signature in the bytes of a saved file and replaces its
+    /// placeholder `/ByteRange` with the actual one, which covers the whole file except the
+    /// hex string of `/Contents`. Returns `None` if the file holds no prepared signature.
+    pub fn locate(pdf: &mut [u8]) -> Option<SignatureSlot> {
+        let placeholder = format!("[0 {0} {0} {0}]", BYTE_RANGE_PLACEHOLDER);
+        let byte_range = rfind(pdf, placeholder.as_bytes())?;
+
+        // The contents follow the byte range in the signature dictionary
+        let object_end = find(&pdf[byte_range..], b"endobj")
+            .map(|i| byte_range + i)
+            .unwrap_or(pdf.len());
+        let key = byte_range + find(&pdf[byte_range..object_end], b"/Contents")?;
+        let start = key + find(&pdf[key..object_end], b"<")?;
+        let end = start + find(&pdf[start..object_end], b">")? + 1;
+
+        let slot = SignatureSlot {
+            contents: start..end,
+            len: pdf.len(),
+        };
+        let [_, gap_start, gap_end, tail] = slot.byte_range();
+        let actual = format!("[0 {} {} {}]", gap_start, gap_end, tail);
+        let target = &mut pdf[byte_range..byte_range + placeholder.len()];
+        // The actual range is shorter, the rest is padded with spaces after the array
+        target.fill(b' ');
+        target[..actual.len()].copy_from_slice(actual.as_bytes());
+        Some(slot)
+    }
+
+    /// Gets the byte range of the signature, as the offset and length of the bytes before and
+    /// after the hex string of `/Contents`
+    pub fn byte_range(&self) -> [usize; 4] {
+        [
+            0,
+            self.contents.start,
+            self.contents.end,
+            self.len - self.contents.end,
+        ]
+    }
+
+    /// Gets the bytes covered by the signature, i.e. the data to hash and sign
+    pub fn signed_bytes(&self, pdf: &[u8]) -> Vec<u8> {
+        let mut data = pdf[..self.contents.start].to_vec();
+        data.extend_from_slice(&pdf[self.contents.end..]);
+        data
+    }
+
+    /// Returns the number of bytes reserved for the signature
+    pub fn capacity(&self) -> usize {
+        (self.contents.len() - 2) / 2
+    }
+
+    /// Writes the DER encoded CMS signature into the reserved `/Contents`, padded with zeros.
+    /// Fails with `ValueError::TooLong` if it is larger than the reserved room.
+    pub fn embed(&self, pdf: &mut [u8], cms: &[u8]) -> Result<(), ValueError> {
+        if cms.len() > self.capacity() {
+            return Err(ValueError::TooLong {
+                max: self.capacity(),
+                got: cms.len(),
+            });
+        }
+
+        let hex = cms
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+        let start = self.contents.start + 1;
+        pdf[start..start + hex.len()].copy_from_slice(hex.as_bytes());
+        Ok(())
+    }
+}
+
+/// Finds the first occurrence of `needle` in `haystack`
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Finds the last occurrence of `needle` in `haystack`
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
@@ -0,0 +1,365 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Object, ObjectId};
+
+use crate::geometry::{number, AppearanceFrame};
+use crate::utils::DictionaryExt;
+use crate::{Color, FieldKey, FieldType, Form, TextAppearanceOptions, ValueError};
+
+/// Tag of the marked content holding the background and border of a widget. They are drawn as
+/// artifacts, decorations with no meaning of their own, and kept when the text is redrawn.
+pub(crate) const ARTIFACT_TAG: &[u8] = b"Artifact";
+
+/// Length of the dashes and gaps of dashed borders, in user space units
+const DASH_LENGTH: i64 = 3;
+
+/// The style of the border of a widget (`/BS /S`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderStyle {
+    /// A rectangle around the widget
+    #[default]
+    Solid,
+    /// A dashed rectangle around the widget
+    Dashed,
+    /// A rectangle with a lighter top left and a darker bottom right, embossing the widget
+    Beveled,
+    /// A rectangle with a darker top left and a lighter bottom right, engraving the widget
+    Inset,
+    /// A single line along the bottom of the widget
+    Underline,
+}
+
+impl BorderStyle {
+    fn from_name(name: &[u8]) -> Self {
+        match name {
+            b"D" => BorderStyle::Dashed,
+            b"B" => BorderStyle::Beveled,
+            b"I" => BorderStyle::Inset,
+            b"U" => BorderStyle::Underline,
+            _ => BorderStyle::Solid,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BorderStyle::Solid => "S",
+            BorderStyle::Dashed => "D",
+            BorderStyle::Beveled => "B",
+            BorderStyle::Inset => "I",
+            BorderStyle::Underline => "U",
+        }
+    }
+}
+
+/// The look of the widgets of a field: the colors of their border and background (`/MK`) and
+/// the width and style of their border (`/BS`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WidgetStyle {
+    /// Color of the border, none for no border
+    pub border_color: Option<Color>,
+    /// Color of the background, none for a transparent background
+    pub background_color: Option<Color>,
+    /// Width of the border in points, 1 by default
+    pub border_width: f32,
+    /// Style of the border
+    pub style: BorderStyle,
+}
+
+impl Default for WidgetStyle {
+    fn default() -> Self {
+        WidgetStyle {
+            border_color: None,
+            background_color: None,
+            border_width: 1.0,
+            style: BorderStyle::Solid,
+        }
+    }
+}
+
+/// Builds the operations painting the background and the border of a widget within a frame.
+/// They are marked as an artifact so they survive the regeneration of the text drawn over them.
+pub(crate) fn background_operations(
+    frame: &AppearanceFrame,
+    style: &WidgetStyle,
+) -> Vec<Operation> {
+    let (width, height) = (frame.width, frame.height);
+    let mut operations = vec![
+        Operation::new("BMC", vec![Object::Name(ARTIFACT_TAG.to_vec())]),
+        Operation::new("q", vec![]),
+    ];
+    if let Some(background) = style.background_color {
+        operations.extend(vec![
+            background.fill_operation(),
+            Operation::new("re", vec![0.into(), 0.into(), width.into(), height.into()]),
+            Operation::new("f", vec![]),
+        ]);
+    }
+
+    // The border width is given in points, the frame is in the user space of the page
+    let border_width = style.border_width / frame.user_unit;
+    if let Some(border) = style.border_color.filter(|_| border_width > 0.0) {
+        let inset = 0.5 * border_width;
+        operations.push(border.stroke_operation());
+        operations.push(Operation::new("w", vec![border_width.into()]));
+        match style.style {
+            BorderStyle::Underline => operations.extend(vec![
+                Operation::new("m", vec![0.into(), inset.into()]),
+                Operation::new("l", vec![width.into(), inset.into()]),
+                Operation::new("S", vec![]),
+            ]),
+            border_style => {
+                if border_style == BorderStyle::Dashed {
+                    operations.push(Operation::new(
+                        "d",
+                        vec![vec![DASH_LENGTH.into()].into(), 0.into()],
+                    ));
+                }
+                operations.extend(vec![
+                    Operation::new(
+                        "re",
+                        vec![
+                            inset.into(),
+                            inset.into(),
+                            (width - border_width).into(),
+                            (height - border_width).into(),
+                        ],
+                    ),
+                    Operation::new("S", vec![]),
+                ]);
+                if let Some((light, dark)) = match border_style {
+                    BorderStyle::Beveled => Some((Color::Gray(1.0), Color::Gray(0.5))),
+                    BorderStyle::Inset => Some((Color::Gray(0.5), Color::Gray(0.75))),
+                    _ => None,
+                } {
+                    operations.extend(bevel_operations(frame, border_width, light, dark));
+                }
+            }
+        }
+    }
+    operations.extend(vec![
+        Operation::new("Q", vec![]),
+        Operation::new("EMC", vec![]),
+    ]);
+    operations
+}
+
+/// Builds the operations filling the inner edges of a beveled or inset border, `light` along
+/// the top and left sides and `dark` along the bottom and right ones
+fn bevel_operations(frame: &AppearanceFrame, w: f32, light: Color, dark: Color) -> Vec<Operation> {
+    let (width, height) = (frame.width, frame.height);
+    let point = |operator: &str, x: f32, y: f32| Operation::new(operator, vec![x.into(), y.into()]);
+
+    vec![
+        light.fill_operation(),
+        point("m", w, w),
+        point("l", w, height - w),
+        point("l", width - w, height - w),
+        point("l", width - 2.0 * w, height - 2.0 * w),
+        point("l", 2.0 * w, height - 2.0 * w),
+        point("l", 2.0 * w, 2.0 * w),
+        Operation::new("f", vec![]),
+        dark.fill_operation(),
+        point("m", width - w, height - w),
+        point("l", width - w, w),
+        point("l", w, w),
+        point("l", 2.0 * w, 2.0 * w),
+        point("l", width - 2.0 * w, 2.0 * w),
+        point("l", width - 2.0 * w, height - 2.0 * w),
+        Operation::new("f", vec![]),
+    ]
+}
+
+impl Form {
+    /// Gets the look of the widget of the field at index `n`, from its `/MK` and `/BS` entries.
+    /// For fields with several widgets, this is the look of the first one.
+    ///
+    /// Returns `None` if the field has no widget.
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_widget_appearance(&self, n: impl FieldKey) -> Option<WidgetStyle> {
+        let n = self.try_resolve(n).ok()?;
+        let widget = self.widget_of(self.form_ids[n])?;
+        let widget = self.get_dictionary(widget).ok()?;
+        let entry = |dict: &Dictionary, key: &[u8]| {
+            dict.get(key)
+                .and_then(|value| self.dereference(value))
+                .map(|(_, value)| value.clone())
+                .ok()
+        };
+        let color = |mk: &Dictionary, key: &[u8]| match entry(mk, key) {
+            Some(Object::Array(components)) => {
+                Color::from_components(&components.iter().map(number).collect::<Vec<_>>())
+            }
+            _ => None,
+        };
+
+        let mut style = WidgetStyle::default();
+        if let Some(Object::Dictionary(mk)) = entry(widget, b"MK") {
+            style.border_color = color(&mk, b"BC");
+            style.background_color = color(&mk, b"BG");
+        }
+        if let Some(Object::Dictionary(bs)) = entry(widget, b"BS") {
+            if let Some(width) = entry(&bs, b"W") {
+                style.border_width = number(&width);
+            }
+            if let Some(Object::Name(name)) = entry(&bs, b"S") {
+                style.style = BorderStyle::from_name(&name);
+            }
+        }
+        Some(style)
+    }
+
+    /// Sets the look of every widget of the field at index `n`: the colors of the border and
+    /// background (`/MK /BC` and `/MK /BG`), and the width and style of the border (`/BS`).
+    ///
+    /// The appearance streams are redrawn so the style shows in every viewer, including those
+    /// that don't regenerate appearances. Fields with no appearance get one first, from their
+    /// current value.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_widget_appearance(
+        &mut self,
+        n: impl FieldKey,
+        style: WidgetStyle,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let widgets = self.widgets_of(self.form_ids[n]);
+        if widgets.is_empty() {
+            return Err(ValueError::NotFound);
+        }
+
+        for &widget in &widgets {
+            self.write_widget_style(widget, &style)
+                .map_err(|_| ValueError::NotFound)?;
+        }
+
+        // Draw the value of fields lacking an appearance, then restyle every appearance
+        let has_appearance = self
+            .get_dictionary(self.form_ids[n])
+            .is_ok_and(|field| field.has(b"AP"));
+        match self.try_get_type(n)? {
+            FieldType::Text if !has_appearance => self
+                .regenerate_text_appearance(n, &TextAppearanceOptions::default())
+                .map_err(|_| ValueError::NotFound)?,
+            FieldType::ListBox | FieldType::ComboBox if !has_appearance => self
+                .regenerate_choice_appearance(n)
+                .map_err(|_| ValueError::NotFound)?,
+            FieldType::CheckBox | FieldType::Radio => {
+                for &widget in &widgets {
+                    let on = self.on_state(widget);
+                    self.ensure_check_box_appearance(widget, &on)
+                        .map_err(|_| ValueError::NotFound)?;
+                }
+            }
+            _ => {}
+        }
+        for widget in widgets {
+            self.restyle_widget(widget, &style)
+                .map_err(|_| ValueError::NotFound)?;
+        }
+        self.mark_modified(n);
+
+        Ok(())
+    }
+
+    /// Writes a style into the `/MK` and `/BS` entries of a widget
+    fn write_widget_style(
+        &mut self,
+        widget: ObjectId,
+        style: &WidgetStyle,
+    ) -> Result<(), lopdf::Error> {
+        let color = |color: Option<Color>| {
+            color
+                .map(Color::components)
+                .unwrap_or_default()
+                .into_iter()
+                // Rounded, so the components are written as they were given
+                .map(|c| Object::Real((f64::from(c) * 1e4).round() / 1e4))
+                .collect::<Vec<_>>()
+        };
+
+        let dict = self.get_dictionary(widget)?;
+        let mut mk = match dict.get(b"MK") {
+            Ok(mk) => self.dereference(mk)?.1.as_dict()?.clone(),
+            Err(_) => Dictionary::new(),
+        };
+        let mut bs = match dict.get(b"BS") {
+            Ok(bs) => self.dereference(bs)?.1.as_dict()?.clone(),
+            Err(_) => Dictionary::new(),
+        };
+
+        mk.set_in_place("BC", color(style.border_color));
+        mk.set_in_place("BG", color(style.background_color));
+        bs.set_in_place("W", style.border_width);
+        bs.set_in_place("S", style.style.name());
+        if style.style == BorderStyle::Dashed {
+            bs.set_in_place("D", vec![Object::Integer(DASH_LENGTH)]);
+        } else {
+            bs.remove(b"D");
+        }
+
+        let dict = self.get_object_mut(widget)?.as_dict_mut()?;
+        dict.set_in_place("MK", mk);
+        dict.set_in_place("BS", bs);
+        Ok(())
+    }
+
+    /// Replaces the background and border drawn in the normal appearance streams of a widget,
+    /// one per state for buttons
+    fn restyle_widget(
+        &mut self,
+        widget: ObjectId,
+        style: &WidgetStyle,
+    ) -> Result<(), lopdf::Error> {
+        let frame = self.appearance_frame(widget).ok_or(lopdf::Error::DictKey)?;
+        let normal = self
+            .get_dictionary(widget)?
+            .get(b"AP")
+            .and_then(|ap| self.dereference(ap))
+            .and_then(|(_, ap)| ap.as_dict())
+            .and_then(|ap| ap.get(b"N"));
+        let streams = match normal {
+            Ok(Object::Reference(id)) => match self.get_object(*id)? {
+                Object::Dictionary(states) => states
+                    .iter()
+                    .filter_map(|(_, state)| state.as_reference().ok())
+                    .collect(),
+                _ => vec![*id],
+            },
+            Ok(Object::Dictionary(states)) => states
+                .iter()
+                .filter_map(|(_, state)| state.as_reference().ok())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let background = background_operations(&frame, style);
+        for id in streams {
+            let stream = self.get_object_mut(id)?.as_stream_mut()?;
+            let mut content = match stream.decompressed_content() {
+                Ok(content) => Content::decode(&content)?,
+                Err(_) => Content::decode(&stream.content)?,
+            };
+
+            // Drop the previous background and border
+            let mut depth = 0;
+            content.operations.retain(|operation| {
+                let is_artifact = matches!(operation.operands.first(),
+                    Some(Object::Name(name)) if name == ARTIFACT_TAG);
+                match operation.operator.as_str() {
+                    "BMC" | "BDC" if depth > 0 || is_artifact => depth += 1,
+                    "EMC" if depth > 0 => depth -= 1,
+                    _ if depth > 0 => {}
+                    _ => return true,
+                }
+                false
+            });
+            content.operations.splice(0..0, background.iter().cloned());
+
+            stream.set_plain_content(content.encode()?);
+            let _ = stream.compress();
+        }
+        Ok(())
+    }
+}
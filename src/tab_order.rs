@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet};
+
+use lopdf::{Object, ObjectId};
+
+use crate::utils::DictionaryExt;
+use crate::{Form, ValueError};
+
+/// The order in which viewers move the focus between the fields of a page with the Tab key,
+/// the `/Tabs` of the page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabPolicy {
+    /// In rows from the top, from left to right within a row (`/R`)
+    Row,
+    /// In columns from the left, from top to bottom within a column (`/C`)
+    Column,
+    /// In the order of the structure tree of a tagged document (`/S`)
+    Structure,
+}
+
+impl TabPolicy {
+    fn name(self) -> &'static str {
+        match self {
+            TabPolicy::Row => "R",
+            TabPolicy::Column => "C",
+            TabPolicy::Structure => "S",
+        }
+    }
+}
+
+impl Form {
+    /// Gets the indices of the fields on the page at index `page` (starting at 0) in the order
+    /// viewers navigate them: in rows or columns when the `/Tabs` of the page asks for it, or
+    /// else in the order of its `/Annots`, which structure order also falls back to. Fields with
+    /// several widgets on the page are listed at their first widget.
+    pub fn tab_order(&self, page: usize) -> Vec<usize> {
+        let page = match self.page_id(page) {
+            Some(page) => page,
+            None => return Vec::new(),
+        };
+        let widgets = self.page_widgets(page);
+
+        let tabs = self
+            .get_dictionary(page)
+            .and_then(|page| page.get(b"Tabs"))
+            .and_then(Object::as_name)
+            .ok();
+        let ordered = match tabs {
+            Some(b"R") | Some(b"C") => {
+                let widgets = widgets
+                    .into_iter()
+                    .filter_map(|(widget, n)| Some((n, self.widget_rect(widget)?)))
+                    .collect();
+                if tabs == Some(b"R") {
+                    row_order(widgets)
+                } else {
+                    column_order(widgets)
+                }
+            }
+            _ => widgets.into_iter().map(|(_, n)| n).collect(),
+        };
+
+        let mut fields = Vec::new();
+        for n in ordered {
+            if !fields.contains(&n) {
+                fields.push(n);
+            }
+        }
+        fields
+    }
+
+    /// Sets the tab order of the page at index `page` (starting at 0) through its `/Tabs`. For
+    /// rows and columns, the widgets are also reordered in the `/Annots` of the page, so that
+    /// viewers following the annotation order navigate them the same way; the other
+    /// annotations keep their place.
+    ///
+    /// Fails with `ValueError::NotFound` if the page doesn't exist.
+    pub fn set_tab_order(&mut self, page: usize, policy: TabPolicy) -> Result<(), ValueError> {
+        let page = self.page_id(page).ok_or(ValueError::NotFound)?;
+
+        if policy != TabPolicy::Structure {
+            let widgets = self
+                .page_widgets(page)
+                .into_iter()
+                .filter_map(|(widget, _)| Some((widget, self.widget_rect(widget)?)))
+                .collect::<Vec<_>>();
+            let slots = widgets
+                .iter()
+                .map(|&(widget, _)| widget)
+                .collect::<HashSet<_>>();
+            let mut sorted = if policy == TabPolicy::Row {
+                row_order(widgets)
+            } else {
+                column_order(widgets)
+            }
+            .into_iter();
+
+            let annots = match self.dictionary_mut(page)?.get(b"Annots") {
+                Ok(Object::Reference(id)) => Some(*id),
+                _ => None,
+            };
+            let annots = match annots {
+                Some(id) => self.get_object_mut(id).ok(),
+                None => self.dictionary_mut(page)?.get_mut(b"Annots").ok(),
+            };
+            if let Some(Object::Array(annots)) = annots {
+                // The sorted widgets take the slots of the widgets, in order
+                for annot in annots.iter_mut() {
+                    if annot.as_reference().is_ok_and(|id| slots.contains(&id)) {
+                        if let Some(widget) = sorted.next() {
+                            *annot = widget.into();
+                        }
+                    }
+                }
+            }
+        }
+
+        self.dictionary_mut(page)?
+            .set_in_place("Tabs", Object::Name(policy.name().as_bytes().to_vec()));
+        Ok(())
+    }
+
+    /// Gets the widgets of fields in the `/Annots` of a page, in order, with the index of their
+    /// field
+    fn page_widgets(&self, page: ObjectId) -> Vec<(ObjectId, usize)> {
+        let index = self.page_iter().position(|id| id == page);
+        let mut fields = HashMap::new();
+        for n in index
+            .map(|index| self.fields_on_page(index))
+            .unwrap_or_default()
+        {
+            for widget in self.widgets_of(self.form_ids[n]) {
+                fields.insert(widget, n);
+            }
+        }
+
+        match self
+            .get_dictionary(page)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|annots| self.dereference(annots))
+        {
+            Ok((_, Object::Array(annots))) => annots
+                .iter()
+                .filter_map(|annot| annot.as_reference().ok())
+                .filter_map(|id| Some((id, *fields.get(&id)?)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Sorts items by their rectangle `[llx, lly, urx, ury]` in rows from the top, and from left
+/// to right within a row. An item belongs to the row of the item above it when its middle is
+/// above the bottom of that item, so that slightly misaligned fields share a row.
+pub(crate) fn row_order<T>(items: Vec<(T, [f32; 4])>) -> Vec<T> {
+    let mut items = items
+        .into_iter()
+        .map(|(item, [x0, y0, x1, y1])| (item, [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]))
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| b.1[3].total_cmp(&a.1[3]));
+
+    let mut rows: Vec<Vec<(T, [f32; 4])>> = Vec::new();
+    let mut row_bottom = 0.0;
+    for item in items {
+        let middle = 0.5 * (item.1[1] + item.1[3]);
+        match rows.last_mut() {
+            Some(row) if middle >= row_bottom => row.push(item),
+            _ => {
+                row_bottom = item.1[1];
+                rows.push(vec![item]);
+            }
+        }
+    }
+
+    rows.into_iter()
+        .flat_map(|mut row| {
+            row.sort_by(|a, b| a.1[0].total_cmp(&b.1[0]));
+            row.into_iter().map(|(item, _)| item)
+        })
+        .collect()
+}
+
+/// Sorts items by their rectangle `[llx, lly, urx, ury]` in columns from the left, and from
+/// top to bottom within a column
+pub(crate) fn column_order<T>(items: Vec<(T, [f32; 4])>) -> Vec<T> {
+    // Rows of the rectangles turned a quarter clockwise
+    row_order(
+        items
+            .into_iter()
+            .map(|(item, [llx, lly, urx, ury])| (item, [-ury, -urx, -lly, -llx]))
+            .collect(),
+    )
+}
@@ -0,0 +1,97 @@
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+use crate::{Form, FormLoader, LoadError};
+
+/// A form parsed and indexed once, used to produce many filled copies
+///
+/// The objects of the document are loaded once and shared by every `FormInstance` spawned from
+/// the template. An instance only keeps its own copy of the objects it modifies, e.g. the
+/// dictionaries and appearance streams of the fields it fills, so filling many copies of a
+/// large form costs little more memory than the form itself. Saving an instance reads the
+/// shared objects where they are, without copying them.
+///
+/// ```no_run
+/// use pdf_forms2::FormTemplate;
+///
+/// let template = FormTemplate::load("path/to/pdf").unwrap();
+/// for (i, name) in ["Alice", "Bob"].iter().enumerate() {
+///     let mut form = template.instance();
+///     form.set_text(0, name.to_string()).unwrap();
+///     form.save(format!("path/to/output-{}.pdf", i)).unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FormTemplate {
+    form: Form,
+}
+
+impl FormTemplate {
+    /// Loads a template from a path with the default loader
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        Ok(Self::from_form(FormLoader::new().load(path)?))
+    }
+
+    /// Loads a template from a reader with the default loader
+    pub fn load_from<R: io::Read>(reader: R) -> Result<Self, LoadError> {
+        Ok(Self::from_form(FormLoader::new().load_from(reader)?))
+    }
+
+    /// Uses an already loaded form as template
+    pub fn from_form(mut form: Form) -> Self {
+        form.share();
+        FormTemplate { form }
+    }
+
+    /// Gets the form the template was made from. Its objects are shared, so its `document`
+    /// holds none of them.
+    pub fn form(&self) -> &Form {
+        &self.form
+    }
+
+    /// Spawns a new fillable instance sharing the objects of the template
+    pub fn instance(&self) -> FormInstance {
+        FormInstance {
+            form: self.form.clone(),
+        }
+    }
+}
+
+/// A fillable copy of a `FormTemplate`
+///
+/// It dereferences to `Form`, so every method of `Form` can be called on it. The objects the
+/// instance modifies are copied from the template on the first change, and `document` only holds
+/// these copies and the objects the instance added.
+#[derive(Clone)]
+pub struct FormInstance {
+    form: Form,
+}
+
+impl FormInstance {
+    /// Returns true while the instance has not modified, added or removed any object of the
+    /// template
+    pub fn is_shared(&self) -> bool {
+        self.form.is_unchanged()
+    }
+
+    /// Turns the instance into an independent form, whose `document` holds every object
+    pub fn into_form(mut self) -> Form {
+        self.form.unshare();
+        self.form
+    }
+}
+
+impl Deref for FormInstance {
+    type Target = Form;
+
+    fn deref(&self) -> &Form {
+        &self.form
+    }
+}
+
+impl DerefMut for FormInstance {
+    fn deref_mut(&mut self) -> &mut Form {
+        &mut self.form
+    }
+}
@@ -0,0 +1,84 @@
+use std::io::{self, Write};
+
+use lopdf::dictionary;
+
+use crate::{FieldKey, Form, SignatureSlot, ValueError};
+
+/// A client of an RFC 3161 time stamping authority, which `Form::save_timestamped` calls to
+/// time-stamp the document
+pub trait TimestampClient {
+    /// Requests a time-stamp token for `data`, the bytes of the document covered by the
+    /// timestamp, whose digest is the message imprint of the request. Returns the DER encoded
+    /// `TimeStampToken` sent back by the authority.
+    fn timestamp(&self, data: &[u8]) -> Result<Vec<u8>, io::Error>;
+}
+
+impl Form {
+    /// Prepares the signature field at index `n` to hold a document timestamp
+    /// (`/DocTimeStamp`, `ETSI.RFC3161`) rather than a signature: the time-stamp token of an
+    /// authority over the rest of the file proves the document existed in this state at that
+    /// time. Like `prepare_signature`, `/Contents` is `reserved_len` zero bytes and the
+    /// `/ByteRange` is a placeholder, which `SignatureSlot::locate` completes once the form is
+    /// saved.
+    ///
+    /// Fails with `ValueError::TypeMismatch` if the field is not a signature field, or
+    /// `ValueError::Readonly` if it is already signed.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn prepare_document_timestamp(
+        &mut self,
+        n: impl FieldKey,
+        reserved_len: usize,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        // The time comes from the token, there is no `/M`
+        self.prepare_signature_value(
+            n,
+            reserved_len,
+            dictionary! {
+                "Type" => "DocTimeStamp",
+                "Filter" => "Adobe.PPKLite",
+                "SubFilter" => "ETSI.RFC3161",
+            },
+        )
+        .map(|_| ())
+    }
+
+    /// Saves the form to `target` with a document timestamp in the signature field at index
+    /// `n`: the field is prepared with `prepare_document_timestamp`, the form is saved as
+    /// `save_to` would, then `client` time-stamps the bytes covered by the timestamp and the
+    /// token is embedded. Saving a signed document with the `IncrementalOnly` save policy keeps
+    /// its signatures, which is how long-term archives are time-stamped again.
+    ///
+    /// Fails with `InvalidInput` if the field is not part of the form or cannot hold a
+    /// timestamp, `InvalidData` if the token is larger than `reserved_len`, or the error of the
+    /// client.
+    pub fn save_timestamped<W: Write>(
+        &mut self,
+        target: &mut W,
+        n: impl FieldKey,
+        client: &dyn TimestampClient,
+        reserved_len: usize,
+    ) -> Result<(), io::Error> {
+        let n = self
+            .try_resolve(n)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        self.prepare_document_timestamp(n, reserved_len)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+
+        let mut pdf = Vec::new();
+        self.save_to(&mut pdf)?;
+        let slot = SignatureSlot::locate(&mut pdf).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the timestamp slot was not saved",
+            )
+        })?;
+        let token = client.timestamp(&slot.signed_bytes(&pdf))?;
+        slot.embed(&mut pdf, &token)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        target.write_all(&pdf)
+    }
+}
@@ -1,127 +1,193 @@
 use lopdf::{Dictionary, Object};
 
-use crate::from_utf8;
+use crate::encoding::decode_text_string;
 
 bitflags! {
     pub struct FieldFlags: u32 {
         const READONLY          = 0x1;
         const REQUIRED          = 0x2;
+        const NO_EXPORT         = 0x4;
     }
 }
 
-bitflags! {
-    pub struct ButtonFlags: u32 {
-        const NO_TOGGLE_TO_OFF  = 0x8000;
-        const RADIO             = 0x10000;
-        const PUSHBUTTON        = 0x20000;
-        const RADIO_IN_UNISON   = 0x4000000;
-
+/// Reads the field flags (`/Ff`) of a field, 0 when they are missing or not a number
+pub fn get_field_flags(field: &Dictionary) -> u32 {
+    match field.get(b"Ff") {
+        Ok(Object::Integer(flags)) => *flags as u32,
+        Ok(Object::Real(flags)) => *flags as i64 as u32,
+        _ => 0,
     }
 }
 
-bitflags! {
-    pub struct ChoiceFlags: u32 {
-        const COBMO             = 0x20000;
-        const EDIT              = 0x40000;
-        const SORT              = 0x80000;
-        const MULTISELECT       = 0x200000;
-        const DO_NOT_SPELLCHECK = 0x800000;
-        const COMMIT_ON_CHANGE  = 0x8000000;
-    }
+/// Formats the current UTC time as a PDF date string (`D:YYYYMMDDHHmmSSZ`)
+pub fn pdf_date_now() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
 }
 
-pub fn is_read_only(field: &Dictionary) -> bool {
-    let flags = FieldFlags::from_bits_truncate(get_field_flags(field));
+/// Keys defined by the spec for field dictionaries and widget annotations. Anything else is
+/// private data from another application.
+const STANDARD_FIELD_KEYS: &[&[u8]] = &[
+    b"Type",
+    b"Subtype",
+    b"FT",
+    b"Parent",
+    b"Kids",
+    b"T",
+    b"TU",
+    b"TM",
+    b"Ff",
+    b"V",
+    b"DV",
+    b"AA",
+    b"DA",
+    b"Q",
+    b"DS",
+    b"RV",
+    b"MaxLen",
+    b"Opt",
+    b"TI",
+    b"I",
+    b"Lock",
+    b"SV",
+    b"Rect",
+    b"Contents",
+    b"P",
+    b"NM",
+    b"M",
+    b"F",
+    b"AP",
+    b"AS",
+    b"Border",
+    b"C",
+    b"StructParent",
+    b"OC",
+    b"H",
+    b"MK",
+    b"A",
+    b"BS",
+    b"AF",
+    b"CA",
+    b"ca",
+    b"BM",
+    b"Lang",
+];
+
+/// Returns the keys of a field dictionary that are not defined by the spec
+pub fn get_unknown_keys(field: &Dictionary) -> Vec<String> {
+    field
+        .iter()
+        .map(|(key, _)| key)
+        .filter(|key| !STANDARD_FIELD_KEYS.contains(&key.as_slice()))
+        .map(|key| String::from_utf8_lossy(key).into_owned())
+        .collect()
+}
 
-    flags.intersects(FieldFlags::READONLY)
+/// Dictionary helpers that keep the order of the existing entries
+pub trait DictionaryExt {
+    /// Like `Dictionary::set`, but an existing entry is updated where it is instead of being
+    /// moved to the end of the dictionary
+    fn set_in_place<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Vec<u8>>,
+        V: Into<Object>;
 }
 
-pub fn is_required(field: &Dictionary) -> bool {
-    let flags = FieldFlags::from_bits_truncate(get_field_flags(field));
+impl DictionaryExt for Dictionary {
+    fn set_in_place<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Vec<u8>>,
+        V: Into<Object>,
+    {
+        let key = key.into();
+        match self.get_mut(&key) {
+            Ok(entry) => *entry = value.into(),
+            Err(_) => self.set(key, value),
+        }
+    }
+}
 
-    flags.intersects(FieldFlags::REQUIRED)
+/// Removes from a trailer the entries that only make sense in the stream dictionary of a
+/// cross-reference stream, or that point into the previous layout of the file. Documents using
+/// xref streams keep them in their trailer once loaded, but they are written back with a classic
+/// cross-reference table.
+pub fn strip_xref_stream_keys(trailer: &mut Dictionary) {
+    for key in &[
+        &b"Type"[..],
+        b"W",
+        b"Index",
+        b"Filter",
+        b"DecodeParms",
+        b"Length",
+        b"XRefStm",
+        b"Prev",
+    ] {
+        trailer.remove(key);
+    }
 }
 
-pub fn get_field_flags(field: &Dictionary) -> u32 {
-    field
-        .get(b"Ff")
-        .unwrap_or(&Object::Integer(0))
-        .as_i64()
-        .unwrap() as u32
+/// Reads a text string object, whether it is written as a literal or in hexadecimal
+pub fn get_text_string(object: &Object) -> Option<String> {
+    match object {
+        Object::String(bytes, _) => Some(decode_text_string(bytes)),
+        _ => None,
+    }
 }
 
-pub fn get_on_value(field: &Dictionary) -> String {
-    let mut option = None;
-    if let Ok(ap) = field.get(b"AP") {
-        if let Ok(dict) = ap.as_dict() {
-            if let Ok(values) = dict.get(b"N") {
-                if let Ok(options) = values.as_dict() {
-                    for (name, _) in options {
-                        if let Ok(name) = from_utf8(name) {
-                            if name != "Off" && option.is_none() {
-                                option = Some(name.into());
-                            }
-                        }
-                    }
-                }
-            }
-        }
+/// Reads the alternate name (`/TU`) of a field, shown as its tooltip
+pub fn get_tooltip(field: &Dictionary) -> Option<String> {
+    field.get(b"TU").ok().and_then(get_text_string)
+}
+
+/// Reads the mapping name (`/TM`) of a field, used when exporting its data
+pub fn get_mapping_name(field: &Dictionary) -> Option<String> {
+    field.get(b"TM").ok().and_then(get_text_string)
+}
+
+/// Reads the value of a button: a name, or a text string in some malformed files
+pub fn get_name_or_text(object: &Object) -> Option<String> {
+    match object {
+        Object::Name(name) => Some(String::from_utf8_lossy(name).into_owned()),
+        _ => get_text_string(object),
     }
+}
 
-    option.unwrap_or("Yes".into())
+/// Gets the selected items of a choice field: its value is a text string for one item or an
+/// array for several
+pub fn get_choice_selection(field: &Dictionary) -> Vec<String> {
+    field.get(b"V").map(selection_items).unwrap_or_default()
 }
 
-pub fn parse_font(font_string: Option<&str>) -> ((&str, i32), (&str, i32, i32, i32, i32)) {
-    // The default font object (/Helv 12 Tf 0 g)
-    let default_font = ("Helv", 12);
-    let default_color = ("g", 0, 0, 0, 0);
-
-    // Build the font basing on the default appearance, if exists, if not,
-    // assume a default font (surely to be improved!)
-    match font_string {
-        Some(font_string) => {
-            let font = font_string
-                .trim_start_matches('/')
-                .split("Tf")
-                .collect::<Vec<_>>();
-
-            if font.len() < 2 {
-                (default_font, default_color)
-            } else {
-                let font_family = font[0].trim().split(' ').collect::<Vec<_>>();
-                let font_color = font[1].trim().split(' ').collect::<Vec<_>>();
-
-                let font = if font_family.len() >= 2 {
-                    (font_family[0], font_family[1].parse::<i32>().unwrap_or(0))
-                } else {
-                    default_font
-                };
-
-                let color = if font_color.len() == 2 {
-                    ("g", font_color[0].parse::<i32>().unwrap_or(0), 0, 0, 0)
-                } else if font_color.len() == 4 {
-                    (
-                        "rg",
-                        font_color[0].parse::<i32>().unwrap_or(0),
-                        font_color[1].parse::<i32>().unwrap_or(0),
-                        font_color[2].parse::<i32>().unwrap_or(0),
-                        0,
-                    )
-                } else if font_color.len() == 5 {
-                    (
-                        "k",
-                        font_color[0].parse::<i32>().unwrap_or(0),
-                        font_color[1].parse::<i32>().unwrap_or(0),
-                        font_color[2].parse::<i32>().unwrap_or(0),
-                        font_color[3].parse::<i32>().unwrap_or(0),
-                    )
-                } else {
-                    default_color
-                };
-
-                (font, color)
-            }
-        }
-        _ => (default_font, default_color),
+/// Gets the items of a selection, the value or default value of a choice field
+pub fn selection_items(selection: &Object) -> Vec<String> {
+    match selection {
+        Object::Array(chosen) => chosen.iter().filter_map(get_text_string).collect(),
+        selection => get_text_string(selection).into_iter().collect(),
     }
 }
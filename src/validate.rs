@@ -0,0 +1,110 @@
+use crate::choice::export_value;
+use crate::{FieldState, Form, TextFieldFlags};
+
+/// A problem found by `Form::validate` in the value of a field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// The index of the field
+    pub index: usize,
+    /// The fully qualified name of the field, if it has one
+    pub name: Option<String>,
+    /// What is wrong with the value
+    pub kind: ValidationIssueKind,
+}
+
+/// The problems `Form::validate` looks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssueKind {
+    /// The field is required but empty: blank text, an unchecked checkbox, no radio button or
+    /// item selected, or an unsigned signature field
+    MissingRequired,
+    /// A selected item of a list box, or of a combo box that cannot be edited, is not one of its
+    /// items (`/Opt`)
+    InvalidSelection(String),
+    /// The text is longer than the maximum length (`/MaxLen`) of the field
+    TooLong { length: usize, max_len: usize },
+    /// The value of a radio group matches none of its buttons
+    UnknownRadioState(String),
+}
+
+impl Form {
+    /// Checks the values of the fields before the form is saved or submitted: required fields
+    /// left empty, selections that are not items of their choice field, text longer than the
+    /// maximum length of its field, and radio values that select none of the buttons. Every
+    /// issue found is returned, in the order of the fields.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        for n in 0..self.len() {
+            let required = TextFieldFlags::from_bits_truncate(self.get_field_flags_raw(n))
+                .intersects(TextFieldFlags::REQUIRED);
+            let mut kinds = Vec::new();
+
+            let empty = match self.try_get_state(n).unwrap_or(FieldState::Unknown) {
+                FieldState::Text { text, .. } => {
+                    if let Some(max_len) = self.get_max_len(n) {
+                        let length = text.chars().count();
+                        if length > max_len {
+                            kinds.push(ValidationIssueKind::TooLong { length, max_len });
+                        }
+                    }
+                    text.is_empty()
+                }
+                FieldState::CheckBox { is_checked, .. } => !is_checked,
+                FieldState::Radio {
+                    selected, options, ..
+                } => {
+                    let off = selected.is_empty() || selected == "Off";
+                    if !off && !options.contains(&selected) {
+                        kinds.push(ValidationIssueKind::UnknownRadioState(selected));
+                    }
+                    off
+                }
+                FieldState::ListBox {
+                    selected, options, ..
+                } => {
+                    kinds.extend(
+                        selected
+                            .iter()
+                            .filter(|item| {
+                                !item.is_empty() && export_value(&options, item).is_none()
+                            })
+                            .map(|item| ValidationIssueKind::InvalidSelection(item.clone())),
+                    );
+                    selected.is_empty()
+                }
+                FieldState::ComboBox {
+                    selected,
+                    options,
+                    editable,
+                    ..
+                } => {
+                    if !editable {
+                        kinds.extend(
+                            selected
+                                .iter()
+                                .filter(|item| {
+                                    !item.is_empty() && export_value(&options, item).is_none()
+                                })
+                                .map(|item| ValidationIssueKind::InvalidSelection(item.clone())),
+                        );
+                    }
+                    selected.iter().all(String::is_empty)
+                }
+                FieldState::Signature { signed, .. } => !signed,
+                FieldState::Button | FieldState::Unknown => false,
+            };
+            if required && empty {
+                kinds.insert(0, ValidationIssueKind::MissingRequired);
+            }
+
+            issues.extend(kinds.into_iter().map(|kind| ValidationIssue {
+                index: n,
+                name: self.get_qualified_name(n),
+                kind,
+            }));
+        }
+
+        issues
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+
+use lopdf::Object;
+
+use crate::choice::{export_value, selection_object};
+use crate::encoding::encode_text_string;
+use crate::utils::{get_name_or_text, get_text_string, selection_items, DictionaryExt};
+use crate::{FieldKey, FieldState, FieldType, Form, ValueError};
+
+/// The value of a form field, independent of the type-specific setters
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(untagged))]
+pub enum FieldValue {
+    /// The contents of a text field
+    Text(String),
+    /// The toggle state of a checkbox
+    Checked(bool),
+    /// The selected option of a radio button group
+    Radio(String),
+    /// The selected option of a combo box
+    Choice(String),
+    /// The selected options of a list box
+    Choices(Vec<String>),
+}
+
+impl FieldValue {
+    /// The types of fields `Form::set_value` fills with the value
+    pub(crate) fn field_types(&self) -> &'static [FieldType] {
+        match self {
+            FieldValue::Text(_) => &[FieldType::Text],
+            FieldValue::Checked(_) => &[FieldType::CheckBox],
+            FieldValue::Radio(_) => &[FieldType::Radio],
+            FieldValue::Choice(_) | FieldValue::Choices(_) => {
+                &[FieldType::ComboBox, FieldType::ListBox]
+            }
+        }
+    }
+}
+
+/// The types of fields that hold a value
+const FILLABLE_TYPES: [FieldType; 5] = [
+    FieldType::Text,
+    FieldType::CheckBox,
+    FieldType::Radio,
+    FieldType::ListBox,
+    FieldType::ComboBox,
+];
+
+/// The outcome of `Form::fill_from_map`
+#[derive(Debug, Default)]
+pub struct FillReport {
+    /// Names of the fields that were filled
+    pub filled: Vec<String>,
+    /// Names that don't match any field of the form
+    pub not_found: Vec<String>,
+    /// Names of the fields whose value could not be set, with the reason
+    pub failed: Vec<(String, ValueError)>,
+}
+
+impl FillReport {
+    /// Returns true if every value was set
+    pub fn is_complete(&self) -> bool {
+        self.not_found.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// The outcome of `Form::copy_values_from`, listing fields by fully qualified name
+#[derive(Debug, Default)]
+pub struct CopyReport {
+    /// Fields whose value was copied
+    pub copied: Vec<String>,
+    /// Fields of the source form that have no counterpart in this form
+    pub not_found: Vec<String>,
+    /// Fields whose counterpart in this form has another type, which were left untouched
+    pub type_mismatch: Vec<String>,
+    /// Fields whose value could not be set, with the reason
+    pub failed: Vec<(String, ValueError)>,
+}
+
+impl CopyReport {
+    /// Returns true if every value of the source form was copied
+    pub fn is_complete(&self) -> bool {
+        self.not_found.is_empty() && self.type_mismatch.is_empty() && self.failed.is_empty()
+    }
+}
+
+impl Form {
+    /// Gets the value of a field, `None` for push buttons and unknown fields
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_value(&self, n: impl FieldKey) -> Option<FieldValue> {
+        match self.try_get_state(n).ok()? {
+            FieldState::Text { text, .. } => Some(FieldValue::Text(text)),
+            FieldState::CheckBox { is_checked, .. } => Some(FieldValue::Checked(is_checked)),
+            FieldState::Radio { selected, .. } => Some(FieldValue::Radio(selected)),
+            FieldState::ComboBox { selected, .. } => Some(FieldValue::Choice(
+                selected.into_iter().next().unwrap_or_default(),
+            )),
+            FieldState::ListBox { selected, .. } => Some(FieldValue::Choices(selected)),
+            FieldState::Button | FieldState::Signature { .. } | FieldState::Unknown => None,
+        }
+    }
+
+    /// Sets the value of a field, dispatching to the setter matching the type of the field.
+    /// A single `Choice` is accepted by list boxes and a single-element `Choices` by combo boxes.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_value(&mut self, n: impl FieldKey, value: FieldValue) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        let expected = value.field_types();
+        match (self.try_get_type(n)?, value) {
+            (FieldType::Text, FieldValue::Text(text)) => self.set_text(n, text),
+            (FieldType::CheckBox, FieldValue::Checked(checked)) => self.set_check_box(n, checked),
+            (FieldType::Radio, FieldValue::Radio(choice)) => self.set_radio(n, choice),
+            (FieldType::ComboBox, FieldValue::Choice(choice)) => self.set_combo_box(n, choice),
+            (FieldType::ComboBox, FieldValue::Choices(mut choices)) if choices.len() == 1 => {
+                self.set_combo_box(n, choices.remove(0))
+            }
+            (FieldType::ListBox, FieldValue::Choices(choices)) => self.set_list_box(n, choices),
+            (FieldType::ListBox, FieldValue::Choice(choice)) => self.set_list_box(n, vec![choice]),
+            _ => Err(self.type_mismatch(n, expected)),
+        }
+    }
+
+    /// Gets the default value (`/DV`) of a field, which it takes back when the form is reset.
+    /// `None` if the field has no default value, or is a push button or an unknown field.
+    ///
+    /// Returns `None` if n is larger than the number of fields or the field is not part of the
+    /// form.
+    pub fn get_default_value(&self, n: impl FieldKey) -> Option<FieldValue> {
+        let n = self.try_resolve(n).ok()?;
+        let default = self.inherited_field_entry(self.form_ids[n], b"DV")?;
+
+        match self.try_get_type(n).ok()? {
+            FieldType::Text => get_text_string(default).map(FieldValue::Text),
+            FieldType::CheckBox => {
+                get_name_or_text(default).map(|state| FieldValue::Checked(state != "Off"))
+            }
+            FieldType::Radio => get_name_or_text(default).map(FieldValue::Radio),
+            FieldType::ComboBox => selection_items(default)
+                .into_iter()
+                .next()
+                .map(FieldValue::Choice),
+            FieldType::ListBox => Some(FieldValue::Choices(selection_items(default))),
+            FieldType::Button | FieldType::Signature | FieldType::Unknown => None,
+        }
+    }
+
+    /// Sets the default value (`/DV`) of a field. The value must have the variant matching the
+    /// type of the field, as for `set_value`, and the current value is left untouched.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn set_default_value(
+        &mut self,
+        n: impl FieldKey,
+        value: FieldValue,
+    ) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.check_not_signature(n)?;
+        self.check_not_locked(n)?;
+        let oid = self.form_ids[n];
+
+        let expected = match value {
+            FieldValue::Choice(_) => &[FieldType::ComboBox][..],
+            FieldValue::Choices(_) => &[FieldType::ListBox],
+            _ => value.field_types(),
+        };
+        let default = match (self.try_get_state(n)?, value) {
+            (FieldState::Text { .. }, FieldValue::Text(text)) => {
+                Object::string_literal(encode_text_string(&text))
+            }
+            (FieldState::ComboBox { options, .. }, FieldValue::Choice(text)) => {
+                let text = export_value(&options, &text).unwrap_or(&text);
+                Object::string_literal(encode_text_string(text))
+            }
+            (FieldState::CheckBox { .. }, FieldValue::Checked(checked)) => {
+                let state = if checked {
+                    self.field_on_state(oid)
+                } else {
+                    "Off".to_owned()
+                };
+                Object::Name(state.into_bytes())
+            }
+            (FieldState::Radio { options, .. }, FieldValue::Radio(choice)) => {
+                if !options.contains(&choice) {
+                    return Err(ValueError::InvalidSelection);
+                }
+                Object::Name(choice.into_bytes())
+            }
+            (FieldState::ListBox { options, .. }, FieldValue::Choices(choices)) => {
+                let choices = choices
+                    .iter()
+                    .map(|choice| export_value(&options, choice).map(str::to_owned))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(ValueError::InvalidSelection)?;
+                selection_object(&choices)
+            }
+            _ => return Err(self.type_mismatch(n, expected)),
+        };
+
+        self.get_object_mut(oid)
+            .and_then(Object::as_dict_mut)
+            .map_err(|_| ValueError::NotFound)?
+            .set_in_place("DV", default);
+        self.mark_modified(n);
+        Ok(())
+    }
+
+    /// Resets a field like the Reset Form action of viewers: it takes its default value back or,
+    /// without one, is emptied (text erased, boxes unchecked, nothing selected). Its appearance
+    /// is regenerated.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn reset_field(&mut self, n: impl FieldKey) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        match self.get_default_value(n) {
+            Some(value) => self.set_value(n, value),
+            None => self.clear_value(n),
+        }
+    }
+
+    /// Blanks a field so a template can be filled again: its value (`/V`) is removed rather
+    /// than emptied, checkboxes and radio buttons are turned `Off`, the selected indices (`/I`)
+    /// of list boxes are dropped and an empty appearance is generated. Unlike `reset_field`, the
+    /// default value is ignored.
+    ///
+    /// Fails with `ValueError::TypeMismatch` for push buttons, signature fields and unknown
+    /// fields.
+    ///
+    /// Fails with `ValueError::NotFound` if n is larger than the number of fields or the field is
+    /// not part of the form.
+    pub fn clear_field(&mut self, n: impl FieldKey) -> Result<(), ValueError> {
+        let n = self.try_resolve(n)?;
+        self.clear_value(n)?;
+
+        let field = self
+            .get_object_mut(self.form_ids[n])
+            .and_then(Object::as_dict_mut)
+            .map_err(|_| ValueError::NotFound)?;
+        field.remove(b"V");
+        field.remove(b"I");
+        Ok(())
+    }
+
+    /// Resets every field of the form, see `reset_field`. Push buttons, signature fields and
+    /// unknown fields are left untouched.
+    pub fn reset_form(&mut self) -> Result<(), ValueError> {
+        for n in 0..self.len() {
+            match self.try_get_type(n)? {
+                FieldType::Button | FieldType::Signature | FieldType::Unknown => {}
+                _ => self.reset_field(n)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Empties a field: text fields get an empty value, checkboxes and radio buttons are turned
+    /// off and choice fields have nothing selected
+    fn clear_value(&mut self, n: usize) -> Result<(), ValueError> {
+        match self.try_get_type(n)? {
+            FieldType::Text => self.set_text(n, String::new()),
+            FieldType::CheckBox => self.set_check_box(n, false),
+            FieldType::ListBox => self.set_list_box(n, Vec::new()),
+            FieldType::Radio => self.clear_radio(n),
+            FieldType::ComboBox => {
+                self.check_not_signature(n)?;
+                self.check_not_locked(n)?;
+                self.get_object_mut(self.form_ids[n])
+                    .and_then(Object::as_dict_mut)
+                    .map_err(|_| ValueError::NotFound)?
+                    .remove(b"V");
+                if let Err(e) = self.update_choice_appearance(n) {
+                    warn!("Choice appearance generation failed: {e}");
+                }
+
+                self.mark_modified(n);
+                Ok(())
+            }
+            FieldType::Button | FieldType::Signature | FieldType::Unknown => {
+                Err(self.type_mismatch(n, &FILLABLE_TYPES))
+            }
+        }
+    }
+
+    /// Fills the fields named by the keys of `values`, which can be fully qualified or partial
+    /// names. Values are coerced to the type of each field where it makes sense: text is accepted
+    /// by every field type (`"true"`, `"yes"`, `"on"` and `"1"` check a checkbox), and a combo box
+    /// or radio selection can be given with any of the selection variants.
+    ///
+    /// Every value is attempted, the fields that could not be filled are listed in the report.
+    pub fn fill_from_map(&mut self, values: &HashMap<String, FieldValue>) -> FillReport {
+        let mut report = FillReport::default();
+
+        let mut names = values.keys().collect::<Vec<_>>();
+        names.sort();
+
+        for name in names {
+            let n = match self.get_index_by_name(name) {
+                Some(n) => n,
+                None => {
+                    report.not_found.push(name.clone());
+                    continue;
+                }
+            };
+
+            let value = coerce_value(
+                &self.try_get_state(n).unwrap_or(FieldState::Unknown),
+                values[name].clone(),
+            );
+            match self.set_value(n, value) {
+                Ok(()) => report.filled.push(name.clone()),
+                Err(e) => report.failed.push((name.clone(), e)),
+            }
+        }
+
+        report
+    }
+
+    /// Copies the values of the fields of `other` into the fields of this form with the same
+    /// fully qualified name, e.g. to migrate the data of a filled form to a new revision of the
+    /// form. A value is only copied between fields of the same type, and a combo box without a
+    /// selection clears its counterpart. Push buttons, signature fields and unknown fields are
+    /// skipped.
+    ///
+    /// Every field is attempted, the fields that could not be copied are listed in the report.
+    pub fn copy_values_from(&mut self, other: &Form) -> CopyReport {
+        let mut report = CopyReport::default();
+
+        for m in 0..other.len() {
+            let value = match other.get_value(m) {
+                Some(value) => value,
+                None => continue,
+            };
+            let name = match other.get_qualified_name(m) {
+                Some(name) => name,
+                None => continue,
+            };
+            let n = match self.get_index_by_name(&name) {
+                Some(n) => n,
+                None => {
+                    report.not_found.push(name);
+                    continue;
+                }
+            };
+            if self.try_get_type(n).unwrap_or(FieldType::Unknown)
+                != other.try_get_type(m).unwrap_or(FieldType::Unknown)
+            {
+                report.type_mismatch.push(name);
+                continue;
+            }
+
+            let result = match value {
+                FieldValue::Choice(choice) if choice.is_empty() => self.clear_field(n),
+                value => self.set_value(n, value),
+            };
+            match result {
+                Ok(()) => report.copied.push(name),
+                Err(e) => report.failed.push((name, e)),
+            }
+        }
+
+        report
+    }
+}
+
+/// Converts a value to the variant expected by a field in the given state, leaving it untouched
+/// when there is no sensible conversion
+fn coerce_value(state: &FieldState, value: FieldValue) -> FieldValue {
+    match (state, value) {
+        (FieldState::CheckBox { .. }, FieldValue::Text(text)) => FieldValue::Checked(matches!(
+            text.trim().to_lowercase().as_str(),
+            "true" | "yes" | "on" | "1"
+        )),
+        (FieldState::Radio { .. }, FieldValue::Text(choice))
+        | (FieldState::Radio { .. }, FieldValue::Choice(choice)) => FieldValue::Radio(choice),
+        (FieldState::ComboBox { .. }, FieldValue::Text(choice))
+        | (FieldState::ComboBox { .. }, FieldValue::Radio(choice)) => FieldValue::Choice(choice),
+        (FieldState::ListBox { .. }, FieldValue::Text(choice))
+        | (FieldState::ListBox { .. }, FieldValue::Radio(choice)) => {
+            FieldValue::Choices(vec![choice])
+        }
+        (_, value) => value,
+    }
+}
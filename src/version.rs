@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use lopdf::{Object, ObjectId};
+
+use crate::encoding::{decode_text_string, encode_utf16be};
+use crate::{FieldKey, Form};
+
+/// The PDF version a form is written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfVersion {
+    /// ISO 32000-1 (PDF 1.7)
+    Pdf17,
+    /// ISO 32000-2 (PDF 2.0)
+    Pdf20,
+}
+
+impl PdfVersion {
+    fn as_str(self) -> &'static str {
+        match self {
+            PdfVersion::Pdf17 => "1.7",
+            PdfVersion::Pdf20 => "2.0",
+        }
+    }
+}
+
+/// A file associated with a field through its `/AF` array (PDF 2.0)
+#[derive(Debug, Clone)]
+pub struct AssociatedFile {
+    /// The file name from `/UF`, or `/F` if there is no Unicode name
+    pub name: String,
+    /// The `/AFRelationship` of the file (`Source`, `Data`, `Alternative`, ...)
+    pub relationship: Option<String>,
+    /// The `/Desc` of the file specification
+    pub description: Option<String>,
+}
+
+/// Entries of field dictionaries holding text strings, or arrays of them, that may be UTF-8
+/// in PDF 2.0
+const TEXT_STRING_KEYS: &[&[u8]] = &[b"T", b"TU", b"TM", b"V", b"DV", b"Opt"];
+
+/// Byte order mark of the UTF-8 text strings of PDF 2.0
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+impl Form {
+    /// Sets the PDF version the document is written as by `save` and `save_to`. `None` keeps the
+    /// version of the loaded document.
+    ///
+    /// Targeting PDF 2.0 removes the deprecated `/NeedAppearances` entry of the AcroForm, so the
+    /// appearance streams of the fields must be up to date. Targeting PDF 1.7 rewrites the UTF-8
+    /// text strings allowed by PDF 2.0 as UTF-16BE.
+    pub fn set_target_version(&mut self, version: Option<PdfVersion>) {
+        self.target_version = version;
+    }
+
+    /// Gets the PDF version the document is written as, if one was set
+    pub fn target_version(&self) -> Option<PdfVersion> {
+        self.target_version
+    }
+
+    /// Gets the files associated with the field at index `n` through its `/AF` entry
+    ///
+    /// Returns an empty list if n is larger than the number of fields or the field is not part of
+    /// the form.
+    pub fn get_associated_files(&self, n: impl FieldKey) -> Vec<AssociatedFile> {
+        let n = match self.try_resolve(n) {
+            Ok(n) => n,
+            Err(_) => return Vec::new(),
+        };
+        let field = match self.get_dictionary(self.form_ids[n]) {
+            Ok(field) => field,
+            Err(_) => return Vec::new(),
+        };
+        let files = match field.get(b"AF").and_then(|af| self.dereference(af)) {
+            Ok((_, Object::Array(files))) => files,
+            _ => return Vec::new(),
+        };
+
+        files
+            .iter()
+            .filter_map(|spec| self.dereference(spec).ok()?.1.as_dict().ok())
+            .map(|spec| {
+                let text = |key: &[u8]| match spec.get(key) {
+                    Ok(Object::String(bytes, _)) => Some(decode_text_string(bytes)),
+                    _ => None,
+                };
+
+                AssociatedFile {
+                    name: text(b"UF").or_else(|| text(b"F")).unwrap_or_default(),
+                    relationship: spec
+                        .get(b"AFRelationship")
+                        .and_then(Object::as_name_str)
+                        .ok()
+                        .map(str::to_owned),
+                    description: text(b"Desc"),
+                }
+            })
+            .collect()
+    }
+
+    /// Gets the ids of every object of the field tree: the fields, the non-terminal fields
+    /// grouping them and their widgets
+    fn field_tree_objects(&self) -> Vec<ObjectId> {
+        let mut seen = HashSet::new();
+        let mut pending = self.top_level_fields();
+        pending.extend(&self.form_ids);
+        while let Some(oid) = pending.pop() {
+            if seen.insert(oid) {
+                pending.extend(self.kids_of(oid));
+            }
+        }
+        seen.into_iter().collect()
+    }
+
+    /// Applies the target version, if any, to the document before it is saved
+    pub(crate) fn apply_target_version(&mut self) {
+        let version = match self.target_version {
+            Some(version) => version,
+            None => return,
+        };
+
+        self.document.version = version.as_str().to_owned();
+
+        match version {
+            PdfVersion::Pdf20 => {
+                if let Some(acroform) = self.acroform_mut() {
+                    acroform.remove(b"NeedAppearances");
+                }
+            }
+            PdfVersion::Pdf17 => {
+                for oid in self.field_tree_objects() {
+                    self.convert_utf8_strings(oid);
+                }
+            }
+        }
+    }
+
+    /// Rewrites the UTF-8 text strings of a field dictionary as UTF-16BE, including those of
+    /// its arrays (list box values, choice options) and of the indirect objects its entries
+    /// refer to. Only the objects holding such strings are modified.
+    fn convert_utf8_strings(&mut self, oid: ObjectId) {
+        let field = match self.get_dictionary(oid) {
+            Ok(field) => field,
+            Err(_) => return,
+        };
+
+        let mut direct = false;
+        let mut indirect = Vec::new();
+        for key in TEXT_STRING_KEYS {
+            match field.get(key) {
+                Ok(Object::Reference(id)) => indirect.push(*id),
+                Ok(value) => direct |= has_utf8(value),
+                Err(_) => {}
+            }
+        }
+        if direct {
+            if let Ok(field) = self.get_object_mut(oid).and_then(Object::as_dict_mut) {
+                for key in TEXT_STRING_KEYS {
+                    if let Ok(value) = field.get_mut(key) {
+                        convert_utf8(value);
+                    }
+                }
+            }
+        }
+        for id in indirect {
+            if self.get_object(id).is_ok_and(has_utf8) {
+                if let Ok(value) = self.get_object_mut(id) {
+                    convert_utf8(value);
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if the value is a UTF-8 text string or an array holding one
+fn has_utf8(value: &Object) -> bool {
+    match value {
+        Object::String(bytes, _) => bytes.starts_with(UTF8_BOM),
+        Object::Array(items) => items.iter().any(has_utf8),
+        _ => false,
+    }
+}
+
+/// Rewrites a UTF-8 text string, or the strings of an array, as UTF-16BE
+fn convert_utf8(value: &mut Object) {
+    match value {
+        Object::String(bytes, _) if bytes.starts_with(UTF8_BOM) => {
+            *bytes = encode_utf16be(&decode_text_string(bytes));
+        }
+        Object::Array(items) => items.iter_mut().for_each(convert_utf8),
+        _ => {}
+    }
+}
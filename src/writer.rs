@@ -0,0 +1,100 @@
+use std::io::{self, Write};
+
+use lopdf::{Dictionary, Object, ObjectId, StringFormat};
+
+/// Serializes a single object the way it would appear in a PDF file
+pub fn write_object<W: Write>(out: &mut W, object: &Object) -> io::Result<()> {
+    match object {
+        Object::Null => out.write_all(b"null"),
+        Object::Boolean(value) => write!(out, "{}", value),
+        Object::Integer(value) => write!(out, "{}", value),
+        Object::Real(value) => write!(out, "{}", value),
+        Object::Name(name) => write_name(out, name),
+        Object::String(text, format) => write_string(out, text, format),
+        Object::Array(array) => {
+            out.write_all(b"[")?;
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b" ")?;
+                }
+                write_object(out, item)?;
+            }
+            out.write_all(b"]")
+        }
+        Object::Dictionary(dict) => write_dictionary(out, dict),
+        Object::Stream(stream) => {
+            let mut dict = stream.dict.clone();
+            dict.set("Length", stream.content.len() as i64);
+            write_dictionary(out, &dict)?;
+            out.write_all(b"stream\n")?;
+            out.write_all(&stream.content)?;
+            out.write_all(b"\nendstream")
+        }
+        Object::Reference((id, generation)) => write!(out, "{} {} R", id, generation),
+    }
+}
+
+/// Serializes an indirect object definition (`id gen obj ... endobj`)
+pub fn write_indirect_object<W: Write>(
+    out: &mut W,
+    id: ObjectId,
+    object: &Object,
+) -> io::Result<()> {
+    writeln!(out, "{} {} obj", id.0, id.1)?;
+    write_object(out, object)?;
+    out.write_all(b"\nendobj\n")
+}
+
+/// Serializes an object into a byte vector, used to compare objects with each other
+pub fn object_to_bytes(object: &Object) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    // Writing into a vector cannot fail
+    let _ = write_object(&mut bytes, object);
+    bytes
+}
+
+fn write_dictionary<W: Write>(out: &mut W, dict: &Dictionary) -> io::Result<()> {
+    out.write_all(b"<<")?;
+    for (key, value) in dict.iter() {
+        write_name(out, key)?;
+        out.write_all(b" ")?;
+        write_object(out, value)?;
+    }
+    out.write_all(b">>")
+}
+
+fn write_name<W: Write>(out: &mut W, name: &[u8]) -> io::Result<()> {
+    out.write_all(b"/")?;
+    for &byte in name {
+        // Delimiters, white-space and bytes outside the printable range are written as #xx
+        if b" \t\n\r\x0C()<>[]{}/%#".contains(&byte) || !(33..=126).contains(&byte) {
+            write!(out, "#{:02X}", byte)?;
+        } else {
+            out.write_all(&[byte])?;
+        }
+    }
+    Ok(())
+}
+
+fn write_string<W: Write>(out: &mut W, text: &[u8], format: &StringFormat) -> io::Result<()> {
+    match format {
+        StringFormat::Literal => {
+            out.write_all(b"(")?;
+            for &byte in text {
+                match byte {
+                    b'(' | b')' | b'\\' => out.write_all(&[b'\\', byte])?,
+                    b'\r' => out.write_all(b"\\r")?,
+                    _ => out.write_all(&[byte])?,
+                }
+            }
+            out.write_all(b")")
+        }
+        StringFormat::Hexadecimal => {
+            out.write_all(b"<")?;
+            for byte in text {
+                write!(out, "{:02X}", byte)?;
+            }
+            out.write_all(b">")
+        }
+    }
+}
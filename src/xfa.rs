@@ -0,0 +1,301 @@
+use std::ops::Range;
+
+use lopdf::{Object, ObjectId};
+
+use crate::utils::get_text_string;
+use crate::{FieldType, FieldValue, Form};
+
+/// An element of the XFA data holding a value, i.e. without child elements
+struct DataValue {
+    /// Local names of the elements from the data root down to the element
+    path: Vec<String>,
+    /// Range of the content of the element, or of the whole tag of an empty element
+    range: Range<usize>,
+    empty: bool,
+}
+
+impl Form {
+    /// Returns true if the AcroForm also holds an XFA form (`/XFA`), i.e. the form is a hybrid
+    /// AcroForm and XFA form. Viewers supporting XFA, such as Adobe Reader, render the XFA form
+    /// from its own data instead of the values and appearances of the AcroForm fields, which is
+    /// why values filled with this crate may not show in them.
+    pub fn has_xfa(&self) -> bool {
+        self.acroform().is_some_and(|acroform| acroform.has(b"XFA"))
+    }
+
+    /// Gets the `datasets` packet of the XFA form, the XML holding the data rendered by XFA
+    /// viewers, or `None` if the form has no XFA form or its XFA form has no data
+    pub fn xfa_datasets(&self) -> Option<String> {
+        let (_, xml, range) = self.xfa_datasets_location()?;
+        Some(xml[range].to_owned())
+    }
+
+    /// Chooses whether the setters of a hybrid AcroForm and XFA form also write the value of the
+    /// field into the `datasets` packet of the XFA form, so XFA viewers such as Adobe Reader show
+    /// the same values as the AcroForm. The value goes into the data element named like the
+    /// field, whose parents match the parent fields, ignoring the `[0]` indices of XFA names.
+    /// Data elements are never created, and list boxes with several selected items are left as
+    /// they are.
+    pub fn set_xfa_sync(&mut self, enabled: bool) {
+        self.sync_xfa = enabled;
+    }
+
+    /// Returns true if the setters also update the XFA data
+    pub fn xfa_sync(&self) -> bool {
+        self.sync_xfa
+    }
+
+    /// Writes the value of the field at index `n` into the matching element of the XFA data,
+    /// if there is one
+    pub(crate) fn write_xfa_value(&mut self, n: usize) {
+        // Push buttons and signatures have no value in the data
+        if matches!(
+            self.try_get_type(n).unwrap_or(FieldType::Unknown),
+            FieldType::Button | FieldType::Signature | FieldType::Unknown
+        ) {
+            return;
+        }
+        let value = match self.get_value(n) {
+            Some(FieldValue::Text(text))
+            | Some(FieldValue::Choice(text))
+            | Some(FieldValue::Radio(text)) => text,
+            Some(FieldValue::Choices(mut choices)) if choices.len() <= 1 => {
+                choices.pop().unwrap_or_default()
+            }
+            Some(FieldValue::Choices(_)) => return,
+            Some(FieldValue::Checked(true)) => self.check_box_on_state(n),
+            // The off value of XFA check boxes
+            Some(FieldValue::Checked(false)) => "0".to_owned(),
+            None => String::new(),
+        };
+        let name = match self.get_qualified_name(n) {
+            Some(name) => name,
+            None => return,
+        };
+        let (id, xml, datasets) = match self.xfa_datasets_location() {
+            Some(location) => location,
+            None => return,
+        };
+        let data = match find_element(&xml[datasets.clone()], "data") {
+            Some(data) => datasets.start + data.start..datasets.start + data.end,
+            None => return,
+        };
+        let element = match find_data_value(&xml, data, &name) {
+            Some(element) => element,
+            None => return,
+        };
+
+        let escaped = escape_xml(&value);
+        let replacement = if element.empty {
+            let tag = &xml[element.range.clone()];
+            let qualified_name = tag_name(&tag[1..]);
+            format!(
+                "{}>{}</{}>",
+                tag.trim_end_matches('>').trim_end_matches('/').trim_end(),
+                escaped,
+                qualified_name
+            )
+        } else {
+            escaped
+        };
+        let xml = format!(
+            "{}{}{}",
+            &xml[..element.range.start],
+            replacement,
+            &xml[element.range.end..]
+        );
+
+        if let Ok(stream) = self.get_object_mut(id).and_then(Object::as_stream_mut) {
+            let compressed = stream.dict.has(b"Filter");
+            stream.set_plain_content(xml.into_bytes());
+            if compressed {
+                let _ = stream.compress();
+            }
+        }
+    }
+
+    /// Finds the `datasets` packet of the XFA form: the stream holding it, the content of the
+    /// stream and the range of the packet in that content. The XFA form is either an array of
+    /// packet names and streams, or a single stream holding the whole XDP document.
+    pub(crate) fn xfa_datasets_location(&self) -> Option<(ObjectId, String, Range<usize>)> {
+        let xfa = self.acroform()?.get(b"XFA").ok()?;
+        let streams = match self.dereference(xfa).ok()? {
+            (_, Object::Array(packets)) => {
+                // The stream named `datasets`, or else every packet, as some producers write
+                // the XDP document split at arbitrary places
+                let named = packets.chunks(2).find_map(|packet| match packet {
+                    [name, Object::Reference(id)]
+                        if get_text_string(name).as_deref() == Some("datasets") =>
+                    {
+                        Some(*id)
+                    }
+                    _ => None,
+                });
+                match named {
+                    Some(id) => vec![id],
+                    None => packets
+                        .iter()
+                        .filter_map(|packet| packet.as_reference().ok())
+                        .collect(),
+                }
+            }
+            (_, _) => vec![xfa.as_reference().ok()?],
+        };
+
+        streams.into_iter().find_map(|id| {
+            let stream = self.get_object(id).ok()?.as_stream().ok()?;
+            let content = stream
+                .decompressed_content()
+                .unwrap_or_else(|_| stream.content.clone());
+            let xml = String::from_utf8_lossy(&content).into_owned();
+            let range = find_element(&xml, "datasets")?;
+            Some((id, xml, range))
+        })
+    }
+}
+
+/// Finds the first element of an XML document with the local name `local_name`, whatever its
+/// namespace prefix, and returns the range of the element from its start tag to its end tag
+pub(crate) fn find_element(xml: &str, local_name: &str) -> Option<Range<usize>> {
+    let mut offset = 0;
+    while let Some(i) = xml[offset..].find('<') {
+        let start = offset + i;
+        offset = start + 1;
+        let name = tag_name(&xml[start + 1..]);
+        if local_part(name) != local_name {
+            continue;
+        }
+
+        let open_end = start + xml[start..].find('>')? + 1;
+        if xml[..open_end].ends_with("/>") {
+            return Some(start..open_end);
+        }
+        let close = format!("</{}", name);
+        let close_start = open_end + xml[open_end..].find(&close)?;
+        let end = close_start + xml[close_start..].find('>')? + 1;
+        return Some(start..end);
+    }
+    None
+}
+
+/// Finds the element of the XFA data, within the range `data` of `xml`, that holds the value of
+/// the field with the fully qualified name `name`
+fn find_data_value(xml: &str, data: Range<usize>, name: &str) -> Option<DataValue> {
+    // XFA field names carry the index of the element among its siblings, e.g. `form1[0].Name[0]`
+    let mut parts = name
+        .split('.')
+        .map(|part| match part.find('[') {
+            Some(i) => (&part[..i], part[i + 1..].trim_end_matches(']').parse().ok()),
+            None => (part, None),
+        })
+        .collect::<Vec<(&str, Option<usize>)>>();
+    let (local_name, index) = parts.pop()?;
+
+    let candidates = data_values(xml, data)
+        .into_iter()
+        .filter(|value| {
+            // Below the `data` element, the data groups must be parent fields, in order, as
+            // unnamed subforms have no data group. The record of the root subform may be left
+            // out of the names of the fields.
+            let (last, parents) = match value.path[1..].split_last() {
+                Some(split) => split,
+                None => return false,
+            };
+            let parents = match parents.split_first() {
+                Some((record, rest)) if !parts.iter().any(|(name, _)| name == record) => rest,
+                _ => parents,
+            };
+            let mut names = parts.iter().map(|(name, _)| *name);
+            last == local_name
+                && parents
+                    .iter()
+                    .all(|parent| names.any(|name| name == parent))
+        })
+        .collect::<Vec<_>>();
+    candidates.into_iter().nth(index.unwrap_or(0))
+}
+
+/// Lists the elements without child elements within the range `data` of `xml`
+fn data_values(xml: &str, data: Range<usize>) -> Vec<DataValue> {
+    let mut values = Vec::new();
+    // Local name, end of the start tag and whether the element has children of the elements
+    // being read
+    let mut open: Vec<(String, usize, bool)> = Vec::new();
+    let mut offset = data.start;
+
+    while let Some(i) = xml[offset..data.end].find('<') {
+        let start = offset + i;
+        let rest = &xml[start..data.end];
+        let skip_to = |end: &str| rest.find(end).map(|i| start + i + end.len());
+
+        offset = if rest.starts_with("<!--") {
+            skip_to("-->")
+        } else if rest.starts_with("<![CDATA[") {
+            skip_to("]]>")
+        } else {
+            skip_to(">")
+        }
+        .unwrap_or(data.end);
+
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            continue;
+        }
+        let tag = &xml[start..offset];
+        if tag.starts_with("</") {
+            if let Some((name, content_start, has_children)) = open.pop() {
+                if !has_children {
+                    let mut path = open
+                        .iter()
+                        .map(|(name, _, _)| name.clone())
+                        .collect::<Vec<_>>();
+                    path.push(name);
+                    values.push(DataValue {
+                        path,
+                        range: content_start..start,
+                        empty: false,
+                    });
+                }
+            }
+            continue;
+        }
+
+        if let Some(parent) = open.last_mut() {
+            parent.2 = true;
+        }
+        if tag.ends_with("/>") {
+            let mut path = open
+                .iter()
+                .map(|(name, _, _)| name.clone())
+                .collect::<Vec<_>>();
+            path.push(local_part(tag_name(&tag[1..])).to_owned());
+            values.push(DataValue {
+                path,
+                range: start..offset,
+                empty: true,
+            });
+        } else {
+            open.push((local_part(tag_name(&tag[1..])).to_owned(), offset, false));
+        }
+    }
+
+    values
+}
+
+/// Escapes the characters of a text that are markup in XML
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Gets the name of the tag starting right after a `<`
+fn tag_name(tag: &str) -> &str {
+    tag.split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .unwrap_or_default()
+}
+
+/// Strips the namespace prefix of an XML name
+fn local_part(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
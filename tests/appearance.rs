@@ -0,0 +1,193 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::content::Operation;
+use lopdf::{Dictionary, Object};
+
+use pdf_forms2::{measure_text, Form, StandardFont, TextAlignment, TextAppearanceOptions};
+
+use common::{appearance, operands, pdf_with_fields, text_field, PdfBuilder};
+
+fn load() -> Form {
+    let mut automatic = text_field("automatic", 650);
+    automatic.set("DA", Object::string_literal("/Helv 0 Tf 0 g"));
+    let pdf = pdf_with_fields(vec![text_field("fixed", 700), automatic]);
+    Form::load_from(Cursor::new(pdf)).unwrap()
+}
+
+#[test]
+fn font_size_overrides_the_default_appearance() {
+    let mut form = load();
+    let options = TextAppearanceOptions {
+        font_size: Some(8.5),
+        ..Default::default()
+    };
+    for n in 0..2 {
+        form.set_text_with(n, "value".to_owned(), &options).unwrap();
+        assert_eq!(operands(&appearance(&form, n), "Tf"), vec![vec![8.5]]);
+    }
+
+    // The deprecated setter only fills in automatic sizes
+    #[allow(deprecated)]
+    for n in 0..2 {
+        form.set_text_fs(n, "value".to_owned(), 9).unwrap();
+    }
+    assert_eq!(operands(&appearance(&form, 0), "Tf"), vec![vec![12.0]]);
+    assert_eq!(operands(&appearance(&form, 1), "Tf"), vec![vec![9.0]]);
+}
+
+#[test]
+fn spacing_and_scaling_operators_are_written() {
+    let mut form = load();
+    form.set_text(0, "a b".to_owned()).unwrap();
+    let plain = appearance(&form, 0);
+    for operator in ["Tc", "Tw", "Tz"] {
+        assert!(operands(&plain, operator).is_empty());
+    }
+
+    let options = TextAppearanceOptions {
+        char_spacing: Some(0.5),
+        word_spacing: Some(2.0),
+        horizontal_scaling: Some(80.0),
+        ..Default::default()
+    };
+    form.set_text_with(0, "a b".to_owned(), &options).unwrap();
+    let operations = appearance(&form, 0);
+    assert_eq!(operands(&operations, "Tc"), vec![vec![0.5]]);
+    assert_eq!(operands(&operations, "Tw"), vec![vec![2.0]]);
+    assert_eq!(operands(&operations, "Tz"), vec![vec![80.0]]);
+
+    // The operators come before the text they apply to
+    let position = |operator: &str| {
+        operations
+            .iter()
+            .position(|operation| operation.operator == operator)
+            .unwrap()
+    };
+    assert!(position("Tz") < position("Tj"));
+
+    // Condensed, the right-aligned text is narrower and starts further right
+    let condensed = TextAppearanceOptions {
+        horizontal_scaling: Some(50.0),
+        alignment: Some(TextAlignment::Right),
+        ..Default::default()
+    };
+    let normal = TextAppearanceOptions {
+        alignment: Some(TextAlignment::Right),
+        ..Default::default()
+    };
+    form.set_text_with(0, "value".to_owned(), &normal).unwrap();
+    let normal_x = operands(&appearance(&form, 0), "Tm")[0][4];
+    form.set_text_with(0, "value".to_owned(), &condensed)
+        .unwrap();
+    let condensed_x = operands(&appearance(&form, 0), "Tm")[0][4];
+    assert!(condensed_x > normal_x);
+}
+
+#[test]
+fn overrides_are_keyed_by_qualified_name() {
+    let mut pdf = PdfBuilder::new();
+    let name_id = pdf.widget(text_field("name", 700));
+    let city_id = pdf.widget(text_field("city", 650));
+    let top_id = pdf.widget(text_field("name", 600));
+    let person_id = pdf.parent("person", &[name_id, city_id]);
+    let pdf = pdf.finish(&[person_id, top_id], Dictionary::new(), Dictionary::new());
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+
+    let marker = |tag: &'static str| {
+        move |operations: &mut Vec<Operation>| {
+            operations.push(Operation::new("MP", vec![Object::Name(tag.into())]))
+        }
+    };
+    form.set_appearance_override("person.name", marker("qualified"));
+    form.set_appearance_override("name", marker("top"));
+    form.set_appearance_override("city", marker("partial"));
+
+    let markers = |form: &Form, field: &str| {
+        let n = form.get_index_by_name(field).unwrap();
+        appearance(form, n)
+            .iter()
+            .filter(|operation| operation.operator == "MP")
+            .map(|operation| operation.operands[0].as_name_str().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    for field in ["person.name", "person.city", "name"] {
+        let n = form.get_index_by_name(field).unwrap();
+        form.set_text(n, "value".to_owned()).unwrap();
+    }
+    assert_eq!(markers(&form, "person.name"), vec!["qualified"]);
+    assert_eq!(markers(&form, "person.city"), vec!["partial"]);
+    assert_eq!(markers(&form, "name"), vec!["top"]);
+}
+
+/// Gets the horizontal position of the first line of text of a field
+fn text_x(form: &Form, n: usize) -> f32 {
+    operands(&appearance(form, n), "Tm")[0][4]
+}
+
+#[test]
+fn text_follows_the_quadding_of_the_field() {
+    let mut pdf = PdfBuilder::new();
+    let mut ids = Vec::new();
+    for (i, &q) in [Some(0), Some(1), Some(2), None].iter().enumerate() {
+        let mut field = text_field(&format!("q{}", i), 700 - 50 * i as i64);
+        if let Some(q) = q {
+            field.set("Q", q);
+        }
+        ids.push(pdf.widget(field));
+    }
+    let mut acroform = Dictionary::new();
+    acroform.set("Q", 2);
+    let pdf = pdf.finish(&ids, acroform, Dictionary::new());
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+
+    for n in 0..4 {
+        form.set_text(n, "value".to_owned()).unwrap();
+    }
+    let (left, center, right) = (text_x(&form, 0), text_x(&form, 1), text_x(&form, 2));
+    assert!(left < center && center < right);
+    assert!((center - (left + right) / 2.0).abs() < 0.01);
+    // Without a quadding of its own, a field uses the one of the AcroForm
+    assert_eq!(text_x(&form, 3), right);
+
+    // The alignment of the options wins over the quadding
+    let options = TextAppearanceOptions {
+        alignment: Some(TextAlignment::Left),
+        ..Default::default()
+    };
+    form.set_text_with(2, "value".to_owned(), &options).unwrap();
+    assert_eq!(text_x(&form, 2), left);
+}
+
+#[test]
+fn automatic_font_size_fits_the_widget() {
+    let mut form = load();
+    let size = |form: &Form| operands(&appearance(form, 1), "Tf")[0][0];
+
+    // Short text is drawn at the largest size, long text shrinks to fit the 200 points wide
+    // widget, with 2 points of padding on each side
+    form.set_text(1, "value".to_owned()).unwrap();
+    assert_eq!(size(&form), 12.0);
+    let long = "The quick brown fox jumps over the lazy dog again";
+    form.set_text(1, long.to_owned()).unwrap();
+    let fitted = size(&form);
+    assert!(fitted < 12.0);
+    assert!(measure_text(StandardFont::Helvetica, fitted, long) <= 196.0);
+    assert!(measure_text(StandardFont::Helvetica, fitted + 0.5, long) > 196.0);
+
+    // The bounds of the automatic size can be changed
+    let options = TextAppearanceOptions {
+        min_font_size: Some(9.0),
+        max_font_size: Some(10.0),
+        ..Default::default()
+    };
+    form.set_text_with(1, "value".to_owned(), &options).unwrap();
+    assert_eq!(size(&form), 10.0);
+    form.set_text_with(1, long.repeat(3), &options).unwrap();
+    assert_eq!(size(&form), 9.0);
+
+    // Fixed sizes of the default appearance are kept
+    form.set_text(0, long.to_owned()).unwrap();
+    assert_eq!(operands(&appearance(&form, 0), "Tf"), vec![vec![12.0]]);
+}
@@ -0,0 +1,311 @@
+#![cfg(feature = "barcode")]
+
+mod common;
+
+use std::io::Cursor;
+
+use pdf_forms2::{Form, Symbology, ValueError};
+
+use common::{appearance, operands, pdf_with_fields, text_field};
+
+/// Fills a text field of `width` by `height` points with `data` drawn as a barcode, and gets the
+/// rectangles of the dark modules, without the background
+fn bars(symbology: Symbology, data: &str, width: i64, height: i64) -> Vec<Vec<f32>> {
+    let mut field = text_field("code", 100);
+    field.set(
+        "Rect",
+        vec![
+            100.into(),
+            100.into(),
+            (100 + width).into(),
+            (100 + height).into(),
+        ],
+    );
+    let mut form = Form::load_from(Cursor::new(pdf_with_fields(vec![field]))).unwrap();
+    form.fill_barcode_with(0, data, symbology).unwrap();
+    operands(&appearance(&form, 0), "re")
+        .into_iter()
+        .skip(1)
+        .collect()
+}
+
+/// Rebuilds the modules of a linear barcode as `1` for bars and `0` for spaces
+fn linear(bars: &[Vec<f32>]) -> String {
+    let module = bars.iter().map(|bar| bar[2]).fold(f32::INFINITY, f32::min);
+    let cells = |length: f32| (length / module).round() as usize;
+    let mut modules = String::new();
+    let mut end = bars[0][0];
+    for bar in bars {
+        modules.push_str(&"0".repeat(cells(bar[0] - end)));
+        modules.push_str(&"1".repeat(cells(bar[2])));
+        end = bar[0] + bar[2];
+    }
+    modules
+}
+
+/// Rebuilds the dark modules of a 2D symbol, row by row from the top, from rectangles one
+/// module high
+fn matrix(bars: &[Vec<f32>]) -> Vec<Vec<bool>> {
+    let module = bars[0][3];
+    let cells = |length: f32| (length / module).round() as usize;
+    let left = bars.iter().map(|bar| bar[0]).fold(f32::INFINITY, f32::min);
+    let top = bars
+        .iter()
+        .map(|bar| bar[1] + bar[3])
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    let mut modules: Vec<Vec<bool>> = Vec::new();
+    for bar in bars {
+        let (x, y, len) = (
+            cells(bar[0] - left),
+            cells(top - bar[1] - bar[3]),
+            cells(bar[2]),
+        );
+        if modules.len() <= y {
+            modules.resize(y + 1, Vec::new());
+        }
+        let row = &mut modules[y];
+        if row.len() < x + len {
+            row.resize(x + len, false);
+        }
+        row[x..x + len].iter_mut().for_each(|dark| *dark = true);
+    }
+    let width = modules.iter().map(Vec::len).max().unwrap();
+    for row in &mut modules {
+        row.resize(width, false);
+    }
+    modules
+}
+
+fn bits(modules: &[bool]) -> String {
+    modules
+        .iter()
+        .map(|&dark| if dark { '1' } else { '0' })
+        .collect()
+}
+
+#[test]
+fn code128_matches_reference_patterns() {
+    // Code set B: start B, "H", "i", the check symbol (104 + 40 + 2 * 73) % 103 = 84 and stop
+    let expected = [
+        "11010010000",
+        "11000101000",
+        "10000110100",
+        "10011110100",
+        "1100011101011",
+    ]
+    .concat();
+    assert_eq!(linear(&bars(Symbology::Code128, "Hi", 300, 40)), expected);
+
+    // Code set C for an even number of digits: start C, 12, 34, 56, the check symbol
+    // (105 + 12 + 2 * 34 + 3 * 56) % 103 = 44 and stop
+    let expected = [
+        "11010011100",
+        "10110011100",
+        "10001011000",
+        "11100010110",
+        "10001101110",
+        "1100011101011",
+    ]
+    .concat();
+    assert_eq!(
+        linear(&bars(Symbology::Code128, "123456", 300, 40)),
+        expected
+    );
+}
+
+/// Format information of the medium error correction level, by mask, from the specification
+const FORMAT_MEDIUM: [&str; 8] = [
+    "101010000010010",
+    "101000100100101",
+    "101111001111100",
+    "101101101001011",
+    "100010111111001",
+    "100000011001110",
+    "100111110010111",
+    "100101010100000",
+];
+
+/// Generator polynomial of 10 error correction codewords from the specification, as powers of α
+/// from the highest degree
+const GENERATOR_10: [usize; 11] = [0, 251, 67, 46, 61, 118, 70, 64, 94, 32, 45];
+
+fn qr_mask(mask: usize, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// Computes the error correction codewords of `data` with the generator of the specification
+fn qr_error_correction(data: &[u8]) -> Vec<u8> {
+    let mut exp = [0u8; 256];
+    let mut log = [0usize; 256];
+    let mut value = 1u16;
+    for (power, entry) in exp.iter_mut().enumerate().take(255) {
+        *entry = value as u8;
+        log[value as usize] = power;
+        value <<= 1;
+        if value & 0x100 != 0 {
+            value ^= 0x11D;
+        }
+    }
+
+    let mut remainder = data.to_vec();
+    remainder.resize(data.len() + 10, 0);
+    for i in 0..data.len() {
+        let factor = remainder[i];
+        if factor == 0 {
+            continue;
+        }
+        for (j, &power) in GENERATOR_10.iter().enumerate() {
+            remainder[i + j] ^= exp[(log[factor as usize] + power) % 255];
+        }
+    }
+    remainder.split_off(data.len())
+}
+
+#[test]
+fn qr_code_matches_reference_encoding() {
+    let modules = matrix(&bars(Symbology::QrCode(Default::default()), "Hi", 200, 200));
+    let size = modules.len();
+    assert_eq!(size, 21);
+    let at = |x: usize, y: usize| modules[y][x];
+
+    // Finder patterns with their light separators, and the timing patterns
+    for (left, top) in [(0, 0), (14, 0), (0, 14)] {
+        for dy in 0..7 {
+            for dx in 0..7 {
+                let ring = dx.min(dy).min(6 - dx).min(6 - dy);
+                assert_eq!(at(left + dx, top + dy), ring != 1, "finder at {left} {top}");
+            }
+        }
+    }
+    for i in 0..8 {
+        assert!(!at(7, i) && !at(i, 7) && !at(13, i) && !at(i, 13));
+    }
+    for i in 8..13 {
+        assert_eq!(at(i, 6), i % 2 == 0);
+        assert_eq!(at(6, i), i % 2 == 0);
+    }
+    assert!(at(8, 13));
+
+    // Both copies of the format information hold the same entry of the specification, read
+    // from the most significant bit
+    let first = |i: usize| match i {
+        0..=5 => at(8, i),
+        6 => at(8, 7),
+        7 => at(8, 8),
+        8 => at(7, 8),
+        _ => at(14 - i, 8),
+    };
+    let second = |i: usize| match i {
+        0..=7 => at(size - 1 - i, 8),
+        _ => at(8, size - 15 + i),
+    };
+    let format = bits(&(0..15).rev().map(first).collect::<Vec<_>>());
+    assert_eq!(format, bits(&(0..15).rev().map(second).collect::<Vec<_>>()));
+    let mask = FORMAT_MEDIUM
+        .iter()
+        .position(|&entry| entry == format)
+        .expect("format information of the medium level");
+
+    // Codewords read in the zigzag order, two columns at a time from the bottom right
+    let function = |x: usize, y: usize| {
+        let finder = if x < 9 {
+            y < 9 || y >= size - 8
+        } else {
+            x >= size - 8 && y < 9
+        };
+        finder || x == 6 || y == 6
+    };
+    let mut stream = Vec::new();
+    let mut right = size - 1;
+    let mut upward = true;
+    loop {
+        if right == 6 {
+            right = 5;
+        }
+        for i in 0..size {
+            let y = if upward { size - 1 - i } else { i };
+            for x in [right, right - 1] {
+                if !function(x, y) {
+                    stream.push(at(x, y) ^ qr_mask(mask, x, y));
+                }
+            }
+        }
+        upward = !upward;
+        if right < 2 {
+            break;
+        }
+        right -= 2;
+    }
+    let codewords = stream
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, &bit| acc << 1 | u8::from(bit)))
+        .collect::<Vec<_>>();
+    assert_eq!(codewords.len(), 26);
+
+    // Byte mode, 2 bytes, "Hi", the terminator and the padding codewords
+    let data = [
+        0x40, 0x24, 0x86, 0x90, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11, 0xEC,
+        0x11,
+    ];
+    assert_eq!(codewords[..16], data);
+    assert_eq!(codewords[16..], qr_error_correction(&data)[..]);
+}
+
+/// Gets the cluster of a PDF417 codeword from the widths of its bars and spaces
+fn cluster(modules: &[bool]) -> usize {
+    let mut widths = Vec::new();
+    let mut run = 1;
+    for i in 1..=modules.len() {
+        if i < modules.len() && modules[i] == modules[i - 1] {
+            run += 1;
+        } else {
+            widths.push(run);
+            run = 1;
+        }
+    }
+    assert_eq!(widths.len(), 8, "{}", bits(modules));
+    assert!(modules[0] && !modules[16]);
+    assert!(widths.iter().all(|width| (1..=6).contains(width)));
+    (widths[0] + widths[4] + 18 - widths[2] - widths[6]) % 9
+}
+
+#[test]
+fn pdf417_rows_follow_the_specification() {
+    let modules = matrix(&bars(Symbology::Pdf417, "Hello, PDF417", 400, 200));
+    // 17 modules for the start pattern, each codeword and the row indicators, 18 for the stop
+    let width = modules[0].len();
+    assert_eq!((width - 35) % 17, 0);
+    let codewords = (width - 35) / 17;
+    assert!(codewords >= 3);
+
+    // Rows are three modules high, and there are at least three
+    assert_eq!(modules.len() % 3, 0);
+    assert!(modules.len() >= 9);
+    for (y, row) in modules.chunks(3).enumerate() {
+        assert!(row.iter().all(|line| *line == row[0]));
+        let line = &row[0];
+        assert_eq!(bits(&line[..17]), "11111111010101000");
+        assert_eq!(bits(&line[width - 18..]), "111111101000101001");
+        // The row indicators and the data codewords use the cluster of the row
+        for codeword in line[17..width - 18].chunks(17) {
+            assert_eq!(cluster(codeword), 3 * (y % 3));
+        }
+    }
+
+    let mut form =
+        Form::load_from(Cursor::new(pdf_with_fields(vec![text_field("code", 100)]))).unwrap();
+    let error = form
+        .fill_barcode_with(0, &"x".repeat(2000), Symbology::Pdf417)
+        .unwrap_err();
+    assert!(matches!(error, ValueError::TooLong { got: 2000, .. }));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::content::Content;
+use pdf_forms2::{
+    ButtonAction, ChoiceFieldOptions, FieldType, Form, FormBuilder, TextFieldOptions, ValueError,
+};
+
+use common::shown_text;
+
+/// The text written by the content of the page at index `page`
+fn labels(form: &Form, page: u32) -> Vec<String> {
+    let pages = form.document.get_pages();
+    let content = form.document.get_page_content(pages[&(page + 1)]).unwrap();
+    shown_text(&Content::decode(&content).unwrap().operations)
+}
+
+#[test]
+fn builder_places_labels_and_fields_on_its_pages() {
+    let options = ChoiceFieldOptions {
+        options: vec!["red".into(), "blue".into()],
+        ..Default::default()
+    };
+    let buttons = vec![
+        ([150.0, 600.0, 165.0, 615.0], "yes".to_owned()),
+        ([180.0, 600.0, 195.0, 615.0], "no".to_owned()),
+    ];
+    let mut form = FormBuilder::new([612.0, 792.0])
+        .label("Name", [72.0, 700.0], 12.0)
+        .text_field(
+            "name",
+            [150.0, 695.0, 400.0, 715.0],
+            TextFieldOptions::default(),
+        )
+        .label("Subscribe", [72.0, 660.0], 12.0)
+        .checkbox("subscribe", [150.0, 655.0, 165.0, 670.0], "Yes")
+        .page()
+        .radio_group("answer", buttons)
+        .combo_box("color", [150.0, 500.0, 300.0, 520.0], options.clone())
+        .list_box("colors", [150.0, 400.0, 300.0, 480.0], options)
+        .push_button(
+            "reset",
+            [150.0, 300.0, 220.0, 320.0],
+            "Reset",
+            ButtonAction::ResetForm,
+        )
+        .build()
+        .unwrap();
+
+    let mut pdf = Vec::new();
+    form.save_to(&mut pdf).unwrap();
+    let form = Form::load_from(Cursor::new(pdf)).unwrap();
+    assert_eq!(form.document.get_pages().len(), 2);
+    let types: Vec<_> = (0..form.len()).map(|n| form.get_type(n)).collect();
+    assert_eq!(
+        types,
+        [
+            FieldType::Text,
+            FieldType::CheckBox,
+            FieldType::Radio,
+            FieldType::ComboBox,
+            FieldType::ListBox,
+            FieldType::Button,
+        ]
+    );
+    assert_eq!(form.fields_on_page(0), [0, 1]);
+    assert_eq!(form.fields_on_page(1), [2, 3, 4, 5]);
+    assert_eq!(form.get_rect(0), Some([150.0, 695.0, 400.0, 715.0]));
+
+    assert_eq!(labels(&form, 0), ["Name", "Subscribe"]);
+    assert!(labels(&form, 1).is_empty());
+}
+
+#[test]
+fn builder_fails_on_the_first_invalid_field() {
+    let result = FormBuilder::new([612.0, 792.0])
+        .text_field("name", [0.0, 0.0, 10.0, 10.0], TextFieldOptions::default())
+        .checkbox("name", [0.0, 20.0, 10.0, 30.0], "Yes")
+        .build();
+    assert!(matches!(result, Err(ValueError::NameTaken)));
+}
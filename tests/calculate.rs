@@ -0,0 +1,103 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Dictionary, Object};
+use pdf_forms2::{FieldValue, Form};
+
+use common::{appearance, shown_text, text_field, PdfBuilder};
+
+/// Adds `script` to `field` as the JavaScript action of the additional action `trigger`
+fn with_script(mut field: Dictionary, trigger: &str, script: &str) -> Dictionary {
+    let mut actions = field
+        .get(b"AA")
+        .and_then(Object::as_dict)
+        .cloned()
+        .unwrap_or_default();
+    actions.set(
+        trigger,
+        dictionary! {
+            "S" => "JavaScript",
+            "JS" => Object::string_literal(script),
+        },
+    );
+    field.set("AA", actions);
+    field
+}
+
+/// Loads a form with the fields `items.a`, `items.b` and `shipping`, then `subtotal`, `total`
+/// and `largest` calculated from them
+fn load() -> Form {
+    let mut pdf = PdfBuilder::new();
+    let a = pdf.widget(text_field("a", 700));
+    let b = pdf.widget(text_field("b", 670));
+    let items = pdf.parent("items", &[a, b]);
+    let shipping = pdf.widget(text_field("shipping", 640));
+    let subtotal = text_field("subtotal", 610);
+    let subtotal = with_script(
+        subtotal,
+        "C",
+        "AFSimple_Calculate(\"SUM\", new Array(\"items\"));",
+    );
+    let subtotal = with_script(subtotal, "F", "AFNumber_Format(2, 0, 0, 0, \"$\", true);");
+    let subtotal = pdf.widget(subtotal);
+    let total = text_field("total", 580);
+    let total = with_script(
+        total,
+        "C",
+        "AFSimple_Calculate(\"SUM\", \"subtotal, shipping\");",
+    );
+    let total = pdf.widget(total);
+    let largest = text_field("largest", 550);
+    let largest = with_script(
+        largest,
+        "C",
+        "AFSimple_Calculate(\"MAX\", [\"items.a\", \"items.b\"]);",
+    );
+    let largest = pdf.widget(largest);
+
+    // The total depends on the subtotal, so it is calculated after it
+    let acroform = dictionary! {
+        "CO" => vec![subtotal.into(), total.into(), largest.into()],
+    };
+    let fields = [items, shipping, subtotal, total, largest];
+    let pdf = pdf.finish(&fields, acroform, Dictionary::new());
+    Form::load_from(Cursor::new(pdf)).unwrap()
+}
+
+fn text(value: &str) -> Option<FieldValue> {
+    Some(FieldValue::Text(value.to_owned()))
+}
+
+/// Gets the index of the field with the fully qualified name `name`
+fn index(form: &Form, name: &str) -> usize {
+    (0..form.len())
+        .find(|&n| form.get_qualified_name(n).as_deref() == Some(name))
+        .unwrap()
+}
+
+#[test]
+fn calculated_fields_are_updated_in_the_calculation_order() {
+    let mut form = load();
+    let [a, b, shipping, subtotal, total, largest] = [
+        "items.a", "items.b", "shipping", "subtotal", "total", "largest",
+    ]
+    .map(|name| index(&form, name));
+
+    form.set_text(a, "12.5".to_owned()).unwrap();
+    form.set_text(b, "30".to_owned()).unwrap();
+    // Values that are not numbers count as 0
+    form.set_text(shipping, "free".to_owned()).unwrap();
+    assert_eq!(form.recalculate(), [subtotal, total, largest]);
+    assert_eq!(form.get_value(subtotal), text("42.5"));
+    assert_eq!(form.get_value(total), text("42.5"));
+    assert_eq!(form.get_value(largest), text("30"));
+    // The result is shown as formatted by the format script of the field
+    assert_eq!(shown_text(&appearance(&form, subtotal)), vec!["$42.50"]);
+
+    // Only the fields whose value changes are updated
+    form.set_text(shipping, "7.5".to_owned()).unwrap();
+    assert_eq!(form.recalculate(), [total]);
+    assert_eq!(form.get_value(total), text("50"));
+    assert!(form.recalculate().is_empty());
+}
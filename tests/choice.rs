@@ -0,0 +1,180 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Dictionary, Object};
+use pdf_forms2::{ChoiceFieldFlags, ChoiceOption, FieldState, Form, ValueError};
+
+use common::{appearance, operands, pdf_with_fields, shown_text};
+
+const ITEMS: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+
+/// A choice field named `name` with the options `a` to `h`, 200 points wide and `height` high
+fn choice_field(name: &str, flags: ChoiceFieldFlags, y: i64, height: i64) -> Dictionary {
+    dictionary! {
+        "FT" => "Ch",
+        "T" => Object::string_literal(name),
+        "Ff" => i64::from(flags.bits()),
+        "DA" => Object::string_literal("/Helv 12 Tf 0 g"),
+        "Opt" => ITEMS.iter().map(|&item| Object::string_literal(item)).collect::<Vec<_>>(),
+        "Rect" => vec![100.into(), y.into(), 300.into(), (y + height).into()],
+    }
+}
+
+/// Loads a form with a list box of two visible rows, a multiple selection list box and a
+/// combo box
+fn load() -> Form {
+    let pdf = pdf_with_fields(vec![
+        choice_field("list", ChoiceFieldFlags::empty(), 700, 40),
+        choice_field("multiple", ChoiceFieldFlags::MULTISELECT, 600, 40),
+        choice_field("combo", ChoiceFieldFlags::COMBO, 500, 20),
+    ]);
+    Form::load_from(Cursor::new(pdf)).unwrap()
+}
+
+/// The export values of the options of the field at index `n`
+fn exports(form: &Form, n: usize) -> Vec<String> {
+    form.get_options(n)
+        .into_iter()
+        .map(|option| option.export)
+        .collect()
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|&item| item.to_owned()).collect()
+}
+
+#[test]
+fn list_boxes_show_the_visible_rows_and_highlight_the_selection() {
+    let mut form = load();
+
+    // 12 point rows, 13.8 points apart, leave room for two rows in the 36 points inside the
+    // padding. The list scrolls to the selected item.
+    form.set_list_box(0, strings(&["f"])).unwrap();
+    let operations = appearance(&form, 0);
+    assert_eq!(shown_text(&operations), vec!["f", "g"]);
+    let rectangles = operands(&operations, "re");
+    // The clipping path, then one highlight on the first row
+    assert_eq!(rectangles.len(), 2);
+    assert_eq!(rectangles[0], vec![1.0, 1.0, 198.0, 38.0]);
+    let highlight = &rectangles[1];
+    assert!((highlight[1] - (40.0 - 2.0 - 13.8)).abs() < 0.01);
+    assert!((highlight[3] - 13.8).abs() < 0.01);
+
+    // Both visible selected rows of a multiple selection are highlighted
+    form.set_list_box(1, strings(&["c", "d"])).unwrap();
+    let operations = appearance(&form, 1);
+    assert_eq!(shown_text(&operations), vec!["c", "d"]);
+    assert_eq!(operands(&operations, "re").len(), 3);
+
+    // Without a selection, the list starts at the top and nothing is highlighted
+    form.set_list_box(1, Vec::new()).unwrap();
+    let operations = appearance(&form, 1);
+    assert_eq!(shown_text(&operations), vec!["a", "b"]);
+    assert_eq!(operands(&operations, "re").len(), 1);
+
+    // A combo box shows its selected item only
+    form.set_combo_box(2, "e".to_owned()).unwrap();
+    assert_eq!(shown_text(&appearance(&form, 2)), vec!["e"]);
+    assert!(matches!(
+        form.get_state(2),
+        FieldState::ComboBox { selected, .. } if selected == ["e"]
+    ));
+}
+
+#[test]
+fn options_are_edited_and_stale_selections_dropped() {
+    let mut form = load();
+    form.set_list_box(1, strings(&["b", "h"])).unwrap();
+
+    form.add_option(1, ChoiceOption::with_display("i", "Item i"))
+        .unwrap();
+    assert_eq!(form.get_options(1)[8].display(), "Item i");
+    assert!(matches!(
+        form.add_option(1, "a"),
+        Err(ValueError::InvalidSelection)
+    ));
+
+    // Removing a selected option deselects it
+    form.remove_option(1, "b").unwrap();
+    assert_eq!(exports(&form, 1).len(), 8);
+    assert!(matches!(
+        form.get_state(1),
+        FieldState::ListBox { selected, .. } if selected == ["h"]
+    ));
+    assert!(matches!(
+        form.remove_option(1, "b"),
+        Err(ValueError::InvalidSelection)
+    ));
+
+    form.set_options(1, vec!["z".into(), "y".into(), "h".into()])
+        .unwrap();
+    assert_eq!(exports(&form, 1), ["z", "y", "h"]);
+    // The list scrolls to "h", the last of the new options
+    assert_eq!(shown_text(&appearance(&form, 1)), vec!["h"]);
+    assert!(matches!(
+        form.set_options(1, vec!["z".into(), "z".into()]),
+        Err(ValueError::InvalidSelection)
+    ));
+
+    // Once the field is sorted, new options are inserted in alphabetical order
+    let field = form.get_object_id(2);
+    form.document
+        .get_object_mut(field)
+        .unwrap()
+        .as_dict_mut()
+        .unwrap()
+        .set(
+            "Ff",
+            i64::from((ChoiceFieldFlags::COMBO | ChoiceFieldFlags::SORT).bits()),
+        );
+    form.reindex_fields();
+    form.add_option(2, "c2").unwrap();
+    assert_eq!(&exports(&form, 2)[2..4], ["c", "c2"]);
+
+    assert!(matches!(form.add_option(3, "a"), Err(ValueError::NotFound)));
+}
+
+fn field(form: &Form, n: usize) -> &Dictionary {
+    form.document.get_dictionary(form.get_object_id(n)).unwrap()
+}
+
+/// The indices of the selected items of the field at index `n` (`/I`)
+fn indices(form: &Form, n: usize) -> Option<Vec<i64>> {
+    let indices = field(form, n).get(b"I").ok()?.as_array().unwrap();
+    Some(indices.iter().map(|i| i.as_i64().unwrap()).collect())
+}
+
+/// The index of the first visible item of the field at index `n` (`/TI`)
+fn top_index(form: &Form, n: usize) -> Option<i64> {
+    field(form, n).get(b"TI").ok()?.as_i64().ok()
+}
+
+#[test]
+fn list_boxes_keep_the_indices_of_their_selection() {
+    let mut form = load();
+
+    // The list scrolls so the selection is one of its two visible rows
+    form.set_list_box(0, strings(&["f"])).unwrap();
+    assert_eq!(indices(&form, 0), Some(vec![5]));
+    assert_eq!(top_index(&form, 0), Some(5));
+    form.set_list_box(0, strings(&["g"])).unwrap();
+    assert_eq!(indices(&form, 0), Some(vec![6]));
+    assert_eq!(top_index(&form, 0), Some(5));
+
+    form.set_list_box(1, strings(&["e", "c"])).unwrap();
+    assert_eq!(indices(&form, 1), Some(vec![2, 4]));
+    assert_eq!(top_index(&form, 1), Some(2));
+    form.set_list_box(1, Vec::new()).unwrap();
+    assert_eq!(indices(&form, 1), None);
+    assert_eq!(top_index(&form, 1), None);
+
+    // Removing an option shifts the indices of the items after it
+    form.remove_option(0, "a").unwrap();
+    assert_eq!(indices(&form, 0), Some(vec![5]));
+    assert_eq!(top_index(&form, 0), Some(5));
+
+    // Combo boxes have no use for them
+    form.set_combo_box(2, "e".to_owned()).unwrap();
+    assert_eq!(indices(&form, 2), None);
+}
@@ -0,0 +1,200 @@
+//! Fixture builders shared by the integration tests
+
+#![allow(dead_code)]
+
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId};
+use pdf_forms2::Form;
+
+/// Builds a one-page document, placing every widget added to it on its page
+pub struct PdfBuilder {
+    pub doc: Document,
+    pub page_id: ObjectId,
+    pages_id: ObjectId,
+    annots: Vec<Object>,
+}
+
+impl PdfBuilder {
+    pub fn new() -> Self {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.new_object_id();
+        PdfBuilder {
+            doc,
+            page_id,
+            pages_id,
+            annots: Vec::new(),
+        }
+    }
+
+    /// Adds a widget annotation on the page, with a default rectangle if `field` has none
+    pub fn widget(&mut self, mut field: Dictionary) -> ObjectId {
+        field.set("Type", "Annot");
+        field.set("Subtype", "Widget");
+        field.set("P", self.page_id);
+        if !field.has(b"Rect") {
+            field.set("Rect", rect(700));
+        }
+        let id = self.doc.add_object(field);
+        self.annots.push(id.into());
+        id
+    }
+
+    /// Adds a non-terminal field named `name` and points each of `kids` back to it
+    pub fn parent(&mut self, name: &str, kids: &[ObjectId]) -> ObjectId {
+        let id = self.doc.add_object(dictionary! {
+            "T" => Object::string_literal(name),
+            "Kids" => kids.iter().map(|&kid| kid.into()).collect::<Vec<Object>>(),
+        });
+        for &kid in kids {
+            self.doc
+                .get_object_mut(kid)
+                .and_then(Object::as_dict_mut)
+                .unwrap()
+                .set("Parent", id);
+        }
+        id
+    }
+
+    /// Writes the document, with `fields` as the top-level fields and `acroform` and `catalog`
+    /// holding extra entries of the AcroForm and the catalog
+    pub fn finish(
+        mut self,
+        fields: &[ObjectId],
+        acroform: Dictionary,
+        catalog: Dictionary,
+    ) -> Vec<u8> {
+        self.doc.objects.insert(
+            self.page_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Page",
+                "Parent" => self.pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                "Annots" => self.annots,
+            }),
+        );
+        self.doc.objects.insert(
+            self.pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![self.page_id.into()],
+                "Count" => 1,
+            }),
+        );
+
+        let mut acroform = acroform;
+        acroform.set(
+            "Fields",
+            fields.iter().map(|&id| id.into()).collect::<Vec<Object>>(),
+        );
+        let acroform_id = self.doc.add_object(acroform);
+        let mut catalog = catalog;
+        catalog.set("Type", "Catalog");
+        catalog.set("Pages", self.pages_id);
+        catalog.set("AcroForm", acroform_id);
+        let catalog_id = self.doc.add_object(catalog);
+        self.doc.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        self.doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+}
+
+/// A text field named `name` with a 12 point Helvetica default appearance, `y` points up the page
+pub fn text_field(name: &str, y: i64) -> Dictionary {
+    dictionary! {
+        "FT" => "Tx",
+        "T" => Object::string_literal(name),
+        "DA" => Object::string_literal("/Helv 12 Tf 0 g"),
+        "Rect" => rect(y),
+    }
+}
+
+/// A 200 by 20 point rectangle starting 100 points from the left edge and `y` points up the page
+pub fn rect(y: i64) -> Vec<Object> {
+    vec![100.into(), y.into(), 300.into(), (y + 20).into()]
+}
+
+/// Builds a one-page document whose top-level fields are the given dictionaries, each placed
+/// as a widget on the page
+pub fn pdf_with_fields(fields: Vec<Dictionary>) -> Vec<u8> {
+    let mut pdf = PdfBuilder::new();
+    let ids = fields
+        .into_iter()
+        .map(|field| pdf.widget(field))
+        .collect::<Vec<_>>();
+    pdf.finish(&ids, Dictionary::new(), Dictionary::new())
+}
+
+/// Builds a one-page document with the single text field `name`
+pub fn sample_pdf() -> Vec<u8> {
+    pdf_with_fields(vec![text_field("name", 700)])
+}
+
+/// Builds a one-page document with an empty form
+pub fn blank_pdf() -> Vec<u8> {
+    pdf_with_fields(Vec::new())
+}
+
+/// Encodes a text string as UTF-16BE with a byte order mark
+pub fn utf16(s: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(s.encode_utf16().flat_map(u16::to_be_bytes));
+    bytes
+}
+
+/// Gets the operations of the normal appearance stream (`/AP /N`) of a widget, or of the on
+/// state named `state` of a checkbox or radio button
+pub fn appearance_of(form: &Form, widget: ObjectId, state: Option<&str>) -> Vec<Operation> {
+    let doc = &form.document;
+    let ap = doc.get_dictionary(widget).unwrap().get(b"AP").unwrap();
+    let ap = doc.dereference(ap).unwrap().1.as_dict().unwrap();
+    let mut normal = doc.dereference(ap.get(b"N").unwrap()).unwrap().1;
+    if let Some(state) = state {
+        let states = normal.as_dict().unwrap();
+        normal = doc
+            .dereference(states.get(state.as_bytes()).unwrap())
+            .unwrap()
+            .1;
+    }
+    let stream = normal.as_stream().unwrap();
+    let content = stream
+        .decompressed_content()
+        .unwrap_or_else(|_| stream.content.clone());
+    Content::decode(&content).unwrap().operations
+}
+
+/// Gets the operations of the normal appearance stream of the field at index `n`
+pub fn appearance(form: &Form, n: usize) -> Vec<Operation> {
+    appearance_of(form, form.get_object_id(n), None)
+}
+
+/// Gets the operands of every operation with the given operator, as numbers
+pub fn operands(operations: &[Operation], operator: &str) -> Vec<Vec<f32>> {
+    operations
+        .iter()
+        .filter(|operation| operation.operator == operator)
+        .map(|operation| {
+            operation
+                .operands
+                .iter()
+                .filter_map(|operand| match *operand {
+                    Object::Integer(n) => Some(n as f32),
+                    Object::Real(n) => Some(n as f32),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Gets the strings shown by the `Tj` operations, decoded as Latin-1
+pub fn shown_text(operations: &[Operation]) -> Vec<String> {
+    operations
+        .iter()
+        .filter(|operation| operation.operator == "Tj")
+        .filter_map(|operation| operation.operands.first()?.as_str().ok())
+        .map(|text| text.iter().map(|&b| b as char).collect())
+        .collect()
+}
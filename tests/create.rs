@@ -0,0 +1,243 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{Dictionary, Object};
+use pdf_forms2::{
+    ButtonAction, ChoiceFieldFlags, ChoiceFieldOptions, ChoiceOption, FieldState, FieldType,
+    FieldValue, Form, SubmitFormat, TextAlignment, TextFieldOptions, ValueError,
+};
+
+use common::{appearance, blank_pdf, shown_text};
+
+fn blank() -> Form {
+    Form::load_from(Cursor::new(blank_pdf())).unwrap()
+}
+
+fn field(form: &Form, n: usize) -> &Dictionary {
+    form.document.get_dictionary(form.get_object_id(n)).unwrap()
+}
+
+/// Saves the form and loads the output again
+fn reload(form: &mut Form) -> Form {
+    let mut pdf = Vec::new();
+    form.save_to(&mut pdf).unwrap();
+    Form::load_from(Cursor::new(pdf)).unwrap()
+}
+
+#[test]
+fn text_fields_are_created_on_a_page() {
+    let mut form = blank();
+    let rect = [100.0, 600.0, 300.0, 620.0];
+    let options = TextFieldOptions {
+        value: Some("created".to_owned()),
+        alignment: Some(TextAlignment::Right),
+        max_len: Some(10),
+        tooltip: Some("Your name".to_owned()),
+        ..Default::default()
+    };
+    let n = form.add_text_field(0, rect, "name", options).unwrap();
+    assert_eq!(n, 0);
+    assert_eq!(shown_text(&appearance(&form, n)), vec!["created"]);
+
+    let mut form = reload(&mut form);
+    assert_eq!(form.len(), 1);
+    assert_eq!(form.get_type(0), FieldType::Text);
+    assert_eq!(form.get_name(0).as_deref(), Some("name"));
+    assert_eq!(
+        form.get_value(0),
+        Some(FieldValue::Text("created".to_owned()))
+    );
+    assert_eq!(form.get_rect(0), Some(rect));
+    assert_eq!(form.get_page(0), Some(0));
+    assert_eq!(form.get_max_len(0), Some(10));
+    assert_eq!(form.get_tooltip(0).as_deref(), Some("Your name"));
+
+    assert!(matches!(
+        form.add_text_field(0, rect, "name", TextFieldOptions::default()),
+        Err(ValueError::NameTaken)
+    ));
+    assert!(matches!(
+        form.add_text_field(1, rect, "other", TextFieldOptions::default()),
+        Err(ValueError::NotFound)
+    ));
+    assert_eq!(form.len(), 1);
+}
+
+#[test]
+fn checkboxes_and_radio_groups_are_created_unselected() {
+    let mut form = blank();
+    let checkbox = form
+        .add_checkbox(0, [100.0, 600.0, 115.0, 615.0], "agree", "Agreed")
+        .unwrap();
+    let buttons = vec![
+        (0, [100.0, 550.0, 115.0, 565.0], "small".to_owned()),
+        (0, [130.0, 550.0, 145.0, 565.0], "large".to_owned()),
+    ];
+    let radio = form.add_radio_group("size", buttons).unwrap();
+    assert_eq!(form.get_type(checkbox), FieldType::CheckBox);
+    assert_eq!(form.get_type(radio), FieldType::Radio);
+    assert!(matches!(
+        form.get_state(checkbox),
+        FieldState::CheckBox { is_checked: false, ref on_value, .. } if on_value == "Agreed"
+    ));
+    assert!(matches!(
+        form.get_state(radio),
+        FieldState::Radio { ref selected, ref options, .. }
+            if selected == "Off" && options == &["small", "large"]
+    ));
+
+    form.set_check_box(checkbox, true).unwrap();
+    form.set_radio(radio, "large".to_owned()).unwrap();
+    let mut form = reload(&mut form);
+    assert!(matches!(
+        form.get_state(checkbox),
+        FieldState::CheckBox {
+            is_checked: true,
+            ..
+        }
+    ));
+    assert!(matches!(
+        form.get_state(radio),
+        FieldState::Radio { ref selected, .. } if selected == "large"
+    ));
+
+    // "Off" is the name of the unchecked state
+    for export_value in ["", "Off"].iter() {
+        assert!(matches!(
+            form.add_checkbox(0, [0.0, 0.0, 10.0, 10.0], "other", export_value),
+            Err(ValueError::InvalidSelection)
+        ));
+    }
+    assert!(matches!(
+        form.add_radio_group("empty", Vec::new()),
+        Err(ValueError::InvalidSelection)
+    ));
+}
+
+#[test]
+fn choice_fields_are_created_with_their_options() {
+    let mut form = blank();
+    let options = || ChoiceFieldOptions {
+        options: vec![
+            ChoiceOption::with_display("fr", "France"),
+            ChoiceOption::with_display("be", "Belgium"),
+            ChoiceOption::new("Chile"),
+        ],
+        selected: vec!["fr".to_owned()],
+        flags: ChoiceFieldFlags::SORT,
+        ..Default::default()
+    };
+    let combo = form
+        .add_combo_box(0, [100.0, 600.0, 300.0, 620.0], "country", options())
+        .unwrap();
+    let list = form
+        .add_list_box(0, [100.0, 500.0, 300.0, 580.0], "countries", options())
+        .unwrap();
+    // The combo box shows the displayed text of its selection
+    assert_eq!(shown_text(&appearance(&form, combo)), vec!["France"]);
+    assert_eq!(
+        shown_text(&appearance(&form, list)),
+        vec!["Belgium", "Chile", "France"]
+    );
+
+    let form = reload(&mut form);
+    assert_eq!(form.get_type(combo), FieldType::ComboBox);
+    assert_eq!(form.get_type(list), FieldType::ListBox);
+    match form.get_state(list) {
+        FieldState::ListBox {
+            selected, options, ..
+        } => {
+            assert_eq!(selected, ["fr"]);
+            let exports: Vec<_> = options
+                .iter()
+                .map(|option| option.export.as_str())
+                .collect();
+            assert_eq!(exports, ["be", "Chile", "fr"]);
+        }
+        state => panic!("{:?}", state),
+    }
+
+    let mut form = form;
+    let mut unknown = options();
+    unknown.selected = vec!["de".to_owned()];
+    assert!(matches!(
+        form.add_list_box(0, [0.0, 0.0, 10.0, 10.0], "other", unknown.clone()),
+        Err(ValueError::InvalidSelection)
+    ));
+    // Editable combo boxes accept any value
+    unknown.flags = ChoiceFieldFlags::EDIT;
+    assert!(form
+        .add_combo_box(0, [0.0, 0.0, 10.0, 10.0], "other", unknown)
+        .is_ok());
+    let mut several = options();
+    several.selected = vec!["fr".to_owned(), "be".to_owned()];
+    assert!(matches!(
+        form.add_list_box(0, [0.0, 0.0, 10.0, 10.0], "several", several.clone()),
+        Err(ValueError::TooManySelected)
+    ));
+    several.flags = ChoiceFieldFlags::MULTISELECT;
+    assert!(form
+        .add_list_box(0, [0.0, 0.0, 10.0, 10.0], "several", several)
+        .is_ok());
+}
+
+#[test]
+fn push_buttons_are_created_with_their_action() {
+    let mut form = blank();
+    let submit = ButtonAction::SubmitForm {
+        url: "https://example.com/submit".to_owned(),
+        format: SubmitFormat::Html,
+    };
+    let rect = [100.0, 600.0, 180.0, 620.0];
+    let send = form
+        .add_push_button(0, rect, "send", "Send", submit)
+        .unwrap();
+    let reset = form
+        .add_push_button(
+            0,
+            [200.0, 600.0, 280.0, 620.0],
+            "reset",
+            "Clear",
+            ButtonAction::ResetForm,
+        )
+        .unwrap();
+    assert_eq!(shown_text(&appearance(&form, send)), vec!["Send"]);
+
+    let form = reload(&mut form);
+    assert_eq!(form.get_type(send), FieldType::Button);
+    let dict = field(&form, send);
+    let caption = dict
+        .get(b"MK")
+        .and_then(Object::as_dict)
+        .unwrap()
+        .get(b"CA");
+    assert_eq!(caption.unwrap().as_str().unwrap(), b"Send");
+    let action = dict.get(b"A").and_then(Object::as_dict).unwrap();
+    assert_eq!(
+        action.get(b"S").and_then(Object::as_name_str).unwrap(),
+        "SubmitForm"
+    );
+    assert_eq!(
+        action.get(b"Flags").and_then(Object::as_i64).unwrap(),
+        1 << 2
+    );
+    let url = action
+        .get(b"F")
+        .and_then(Object::as_dict)
+        .unwrap()
+        .get(b"F");
+    assert_eq!(
+        url.unwrap().as_str().unwrap(),
+        b"https://example.com/submit"
+    );
+
+    let action = field(&form, reset)
+        .get(b"A")
+        .and_then(Object::as_dict)
+        .unwrap();
+    assert_eq!(
+        action.get(b"S").and_then(Object::as_name_str).unwrap(),
+        "ResetForm"
+    );
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use pdf_forms2::encoding::{
+    decode_pdf_doc, decode_text_string, encode_pdf_doc, encode_text_string, encode_utf16be,
+    encode_win_ansi,
+};
+
+use common::utf16;
+
+#[test]
+fn pdf_doc_encoding_matches_the_specification() {
+    // Latin-1 outside of the ranges where PDFDocEncoding differs
+    assert_eq!(encode_pdf_doc("Aé¿").unwrap(), vec![0x41, 0xE9, 0xBF]);
+    let cases = [
+        ('˘', 0x18),
+        ('˜', 0x1F),
+        ('•', 0x80),
+        ('—', 0x84),
+        ('ﬁ', 0x93),
+        ('Ÿ', 0x98),
+        ('ž', 0x9E),
+        ('€', 0xA0),
+    ];
+    for &(c, byte) in cases.iter() {
+        assert_eq!(encode_pdf_doc(&c.to_string()).unwrap(), vec![byte], "{}", c);
+        assert_eq!(decode_pdf_doc(&[byte]), c.to_string());
+    }
+
+    // Undefined codes and missing characters
+    assert_eq!(decode_pdf_doc(&[0x7F, 0x9F]), "\u{FFFD}\u{FFFD}");
+    assert_eq!(encode_pdf_doc("\u{FFFD}"), None);
+    assert_eq!(encode_pdf_doc("中"), None);
+}
+
+#[test]
+fn text_strings_pick_their_encoding() {
+    assert_eq!(encode_text_string("café"), b"caf\xE9".to_vec());
+    assert_eq!(encode_text_string("€5"), vec![0xA0, b'5']);
+    assert_eq!(encode_text_string("中文"), utf16("中文"));
+    assert_eq!(encode_utf16be("é"), vec![0xFE, 0xFF, 0x00, 0xE9]);
+    // "Ã©" in PDFDocEncoding would be read back as the UTF-8 encoding of "é"
+    assert_eq!(encode_text_string("Ã©"), utf16("Ã©"));
+
+    for text in ["plain", "café", "€ – ‰", "中文", "Ã©"].iter() {
+        assert_eq!(decode_text_string(&encode_text_string(text)), *text);
+    }
+    // UTF-8 with a byte order mark, as allowed by PDF 2.0
+    assert_eq!(decode_text_string(b"\xEF\xBB\xBFcaf\xC3\xA9"), "café");
+}
+
+#[test]
+fn win_ansi_encoding_replaces_missing_characters() {
+    assert_eq!(encode_win_ansi("é€™"), vec![0xE9, 0x80, 0x99]);
+    assert_eq!(encode_win_ansi("a中"), b"a?".to_vec());
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::io::Cursor;
+
+use aes::Aes128;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use pdf_forms2::{EncryptionOptions, Form, Permissions};
+
+use common::{pdf_with_fields, text_field};
+
+const USER_PASSWORD: &str = "user";
+const OWNER_PASSWORD: &str = "owner";
+
+/// Padding string of the standard security handler
+const PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Builds a one-page document with a single text field holding `secret`
+fn sample_pdf() -> Vec<u8> {
+    let mut field = text_field("name", 700);
+    field.set("V", Object::string_literal("secret"));
+    pdf_with_fields(vec![field])
+}
+
+fn pad_password(password: &str) -> Vec<u8> {
+    password
+        .bytes()
+        .chain(PADDING.iter().copied())
+        .take(32)
+        .collect()
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut s = (0..=255).collect::<Vec<u8>>();
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(s[i as usize]);
+            s.swap(i as usize, j as usize);
+            byte ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// Computes the file encryption key from the user password (algorithm 2, revision 4)
+fn file_key(encrypt: &Dictionary, file_id: &[u8]) -> Vec<u8> {
+    let o = encrypt.get(b"O").unwrap().as_str().unwrap();
+    let p = encrypt.get(b"P").unwrap().as_i64().unwrap() as i32;
+
+    let mut input = pad_password(USER_PASSWORD);
+    input.extend_from_slice(o);
+    input.extend_from_slice(&p.to_le_bytes());
+    input.extend_from_slice(file_id);
+    let mut key = md5::compute(&input).0.to_vec();
+    for _ in 0..50 {
+        key = md5::compute(&key).0.to_vec();
+    }
+    key
+}
+
+/// Decrypts the string of the object `id` (algorithm 1 with AES)
+fn decrypt_string(key: &[u8], id: ObjectId, data: &[u8]) -> Vec<u8> {
+    let mut input = key.to_vec();
+    input.extend_from_slice(&id.0.to_le_bytes()[..3]);
+    input.extend_from_slice(&id.1.to_le_bytes());
+    input.extend_from_slice(b"sAlT");
+    let object_key = md5::compute(&input).0;
+
+    let (iv, data) = data.split_at(16);
+    cbc::Decryptor::<Aes128>::new(&object_key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(data)
+        .unwrap()
+}
+
+#[test]
+fn encryption_round_trip() {
+    let permissions = Permissions::PRINT | Permissions::FILL_FORMS;
+    let options = EncryptionOptions::new(USER_PASSWORD, OWNER_PASSWORD).permissions(permissions);
+    let mut form = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    let field_id = form.get_object_id(0);
+    let mut encrypted = Vec::new();
+    form.save_encrypted_to(&mut encrypted, &options).unwrap();
+
+    let doc = Document::load_mem(&encrypted).unwrap();
+    let encrypt = doc.trailer.get(b"Encrypt").unwrap();
+    let encrypt = doc.dereference(encrypt).unwrap().1.as_dict().unwrap();
+    let file_id = match doc.trailer.get(b"ID").unwrap().as_array().unwrap().first() {
+        Some(Object::String(id, _)) => id.clone(),
+        _ => panic!("missing file id"),
+    };
+
+    // The owner password decrypts /O back to the padded user password (algorithm 7)
+    let mut owner_key = md5::compute(pad_password(OWNER_PASSWORD)).0.to_vec();
+    for _ in 0..50 {
+        owner_key = md5::compute(&owner_key).0.to_vec();
+    }
+    let mut user_password = encrypt.get(b"O").unwrap().as_str().unwrap().to_vec();
+    for round in (0..20u8).rev() {
+        let key = owner_key.iter().map(|b| b ^ round).collect::<Vec<_>>();
+        user_password = rc4(&key, &user_password);
+    }
+    assert_eq!(user_password, pad_password(USER_PASSWORD));
+
+    // The user password authenticates against /U (algorithm 6)
+    let key = file_key(encrypt, &file_id);
+    let mut input = PADDING.to_vec();
+    input.extend_from_slice(&file_id);
+    let mut expected = md5::compute(&input).0.to_vec();
+    for round in 0..20u8 {
+        let round_key = key.iter().map(|b| b ^ round).collect::<Vec<_>>();
+        expected = rc4(&round_key, &expected);
+    }
+    let u = encrypt.get(b"U").unwrap().as_str().unwrap();
+    assert_eq!(&u[..16], expected.as_slice());
+
+    // The field value is encrypted and decrypts with the file key
+    let field = doc.get_dictionary(field_id).unwrap();
+    let value = field.get(b"V").unwrap().as_str().unwrap();
+    assert_ne!(value, b"secret");
+    assert_eq!(decrypt_string(&key, field_id, value), b"secret");
+
+    let reloaded = Form::load_from(Cursor::new(&encrypted)).unwrap();
+    assert_eq!(reloaded.permissions(), permissions);
+    assert!(reloaded.permissions().can_fill_forms());
+    assert!(!reloaded.permissions().can_modify());
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use std::io::Cursor;
+
+use pdf_forms2::{FieldValue, Form, TextFieldOptions, ValueError};
+
+use common::{pdf_with_fields, text_field};
+
+fn text(value: &str) -> Option<FieldValue> {
+    Some(FieldValue::Text(value.to_owned()))
+}
+
+#[test]
+fn field_ids_follow_their_fields() {
+    // Listed bottom to top, so that sorting by position reverses them
+    let pdf = pdf_with_fields(vec![
+        text_field("a", 600),
+        text_field("b", 650),
+        text_field("c", 700),
+    ]);
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+    let ids = form.field_ids();
+    for (id, value) in ids.iter().zip(["a", "b", "c"]) {
+        form.set_text(id, value.to_owned()).unwrap();
+    }
+
+    form.sort_fields_by_position();
+    assert_eq!(form.get_name(0).as_deref(), Some("c"));
+    for (id, value) in ids.iter().zip(["a", "b", "c"]) {
+        assert_eq!(form.get_value(id), text(value));
+    }
+
+    form.remove_field(&ids[1]).unwrap();
+    assert_eq!(form.len(), 2);
+    assert_eq!(form.get_value(&ids[1]), None);
+    assert!(matches!(
+        form.set_text(&ids[1], "b".to_owned()),
+        Err(ValueError::NotFound)
+    ));
+
+    let n = form
+        .add_text_field(
+            0,
+            [100.0, 750.0, 300.0, 770.0],
+            "d",
+            TextFieldOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(form.get_name(n).as_deref(), Some("d"));
+    assert_eq!(form.get_value(&ids[0]), text("a"));
+    assert_eq!(form.get_value(&ids[2]), text("c"));
+    assert_eq!(form.field_id(n).unwrap().qualified_name(), Some("d"));
+}
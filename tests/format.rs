@@ -0,0 +1,82 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Dictionary, Object};
+use pdf_forms2::{FieldValue, Form};
+
+use common::{appearance, operands, pdf_with_fields, shown_text, text_field};
+
+/// A text field formatted by the JavaScript `script`
+fn formatted_field(name: &str, y: i64, script: &str) -> Dictionary {
+    let mut field = text_field(name, y);
+    field.set(
+        "AA",
+        dictionary! {
+            "F" => dictionary! {
+                "S" => "JavaScript",
+                "JS" => Object::string_literal(script),
+            },
+        },
+    );
+    field
+}
+
+fn load() -> Form {
+    let pdf = pdf_with_fields(vec![
+        formatted_field("price", 700, "AFNumber_Format(2, 0, 2, 0, \"$\", true);"),
+        formatted_field(
+            "balance",
+            650,
+            "AFNumber_Format(1, 2, 1, 0, \" kr\", false);",
+        ),
+        formatted_field("rate", 600, "AFPercent_Format(1, 0);"),
+        formatted_field("date", 550, "AFDate_FormatEx(\"mm/dd/yyyy\");"),
+        formatted_field("day", 500, "AFDate_Format(4);"),
+        text_field("plain", 450),
+    ]);
+    Form::load_from(Cursor::new(pdf)).unwrap()
+}
+
+#[test]
+fn values_are_shown_as_their_format_script_displays_them() {
+    let mut form = load();
+    let cases = [
+        (0, "1234.5", "$1,234.50"),
+        (0, "-3", "($3.00)"),
+        (1, "1234567.26", "1.234.567,3 kr"),
+        (2, "0.125", "12.5%"),
+        (3, "2024-03-01", "03/01/2024"),
+        (4, "2024-03-01", "1-Mar"),
+        // Values the script cannot read are shown as they are
+        (0, "soon", "soon"),
+    ];
+    for &(n, raw, shown) in cases.iter() {
+        form.set_text_formatted(n, raw.to_owned()).unwrap();
+        assert_eq!(form.get_value(n), Some(FieldValue::Text(raw.to_owned())));
+        assert_eq!(form.get_formatted_text(n).as_deref(), Some(shown));
+        assert_eq!(shown_text(&appearance(&form, n)), vec![shown], "{}", raw);
+    }
+
+    // Negative numbers are shown in red when the format asks for it
+    form.set_text_formatted(1, "-2".to_owned()).unwrap();
+    let operations = appearance(&form, 1);
+    assert_eq!(shown_text(&operations), vec!["2,0 kr"]);
+    assert_eq!(operands(&operations, "rg"), vec![vec![1.0, 0.0, 0.0]]);
+
+    // Without a format script, or when filled without formatting, the raw value is shown
+    form.set_text_formatted(5, "1234.5".to_owned()).unwrap();
+    assert_eq!(form.get_formatted_text(5), None);
+    assert_eq!(shown_text(&appearance(&form, 5)), vec!["1234.5"]);
+    form.set_text(0, "1234.5".to_owned()).unwrap();
+    assert_eq!(shown_text(&appearance(&form, 0)), vec!["1234.5"]);
+}
+
+#[test]
+fn date_patterns_are_read_from_the_scripts() {
+    let form = load();
+    assert_eq!(form.get_date_format(3).as_deref(), Some("mm/dd/yyyy"));
+    assert_eq!(form.get_date_format(4).as_deref(), Some("d-mmm"));
+    assert_eq!(form.get_date_format(0), None);
+    assert_eq!(form.get_date_format(5), None);
+}
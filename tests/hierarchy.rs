@@ -0,0 +1,111 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::Dictionary;
+use pdf_forms2::{Form, ValueError};
+
+use common::{text_field, PdfBuilder};
+
+/// Builds a one-page document with the text fields `person.name`, `person.city` and `name`
+fn sample_pdf() -> Vec<u8> {
+    let mut pdf = PdfBuilder::new();
+    let name_id = pdf.widget(text_field("name", 700));
+    let city_id = pdf.widget(text_field("city", 650));
+    let top_id = pdf.widget(text_field("name", 600));
+    let person_id = pdf.parent("person", &[name_id, city_id]);
+    pdf.finish(&[person_id, top_id], Dictionary::new(), Dictionary::new())
+}
+
+fn load() -> Form {
+    Form::load_from(Cursor::new(sample_pdf())).unwrap()
+}
+
+fn names(form: &Form) -> Vec<String> {
+    let mut names = (0..form.len())
+        .filter_map(|n| form.get_qualified_name(n))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+fn fields_cannot_be_moved_under_themselves() {
+    let mut form = load();
+    let kid = form.get_object_id(form.find_field("person.name").unwrap());
+    let person = form.get_parent_of(kid).unwrap();
+
+    assert!(matches!(
+        form.set_parent_of(person, Some(person)),
+        Err(ValueError::CyclicHierarchy)
+    ));
+    assert!(matches!(
+        form.set_parent_of(person, Some(kid)),
+        Err(ValueError::CyclicHierarchy)
+    ));
+    assert_eq!(form.get_parent_of(person), None);
+    assert_eq!(form.get_parent_of(kid), Some(person));
+}
+
+#[test]
+fn moved_fields_keep_unique_names() {
+    let mut form = load();
+    let top = form.get_object_id(form.find_field("name").unwrap());
+    let city = form.get_object_id(form.find_field("person.city").unwrap());
+    let person = form.get_parent_of(city).unwrap();
+    let before = names(&form);
+
+    assert!(matches!(
+        form.set_parent_of(top, Some(person)),
+        Err(ValueError::NameTaken)
+    ));
+    assert_eq!(form.get_parent_of(top), None);
+    assert_eq!(names(&form), before);
+
+    form.set_parent_of(city, None).unwrap();
+    assert_eq!(names(&form), vec!["city", "name", "person.name"]);
+}
+
+#[test]
+fn promoted_kids_need_a_free_top_level_name() {
+    let mut form = load();
+    let kid = form.get_object_id(form.find_field("person.name").unwrap());
+    let before = names(&form);
+
+    assert!(matches!(
+        form.promote_kid(kid, "name"),
+        Err(ValueError::NameTaken)
+    ));
+    assert!(matches!(
+        form.promote_kid(kid, "first.name"),
+        Err(ValueError::InvalidName)
+    ));
+    assert_eq!(form.get_partial_name_of(kid).as_deref(), Some("name"));
+    assert_eq!(names(&form), before);
+
+    let n = form.promote_kid(kid, "first_name").unwrap();
+    assert_eq!(form.get_qualified_name(n).as_deref(), Some("first_name"));
+}
+
+#[test]
+fn merged_fields_need_a_free_top_level_name() {
+    let mut form = load();
+    let top = [form.find_field("name").unwrap()];
+    let objects = form.document.objects.len();
+
+    assert!(matches!(
+        form.merge_fields(&top, "person"),
+        Err(ValueError::NameTaken)
+    ));
+    assert!(matches!(
+        form.merge_fields(&top, "a.b"),
+        Err(ValueError::InvalidName)
+    ));
+    assert_eq!(form.document.objects.len(), objects);
+
+    form.merge_fields(&top, "group").unwrap();
+    assert_eq!(
+        names(&form),
+        vec!["group.name", "person.city", "person.name"]
+    );
+}
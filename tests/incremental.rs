@@ -0,0 +1,183 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Dictionary, Document, Object};
+use pdf_forms2::{FieldValue, Form, SignatureSlot};
+
+use common::{text_field, PdfBuilder};
+
+/// Builds a one-page document with a single text field, `catalog` holding extra catalog entries
+fn sample_pdf(catalog: Dictionary) -> Vec<u8> {
+    let mut pdf = PdfBuilder::new();
+    let field_id = pdf.widget(text_field("name", 700));
+    pdf.finish(&[field_id], Dictionary::new(), catalog)
+}
+
+/// Builds the same document as `sample_pdf` by hand, with a cross-reference stream instead of
+/// a classic table
+fn xref_stream_pdf() -> Vec<u8> {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm 5 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Annots [4 0 R] >>",
+        "<< /Type /Annot /Subtype /Widget /FT /Tx /T (name) /DA (/Helv 12 Tf 0 g) \
+         /Rect [100 700 300 720] /P 3 0 R >>",
+        "<< /Fields [4 0 R] >>",
+    ];
+
+    let mut pdf = b"%PDF-1.7\n".to_vec();
+    // The free head of the list, then one entry per object and the stream itself
+    let mut entries = vec![[0, 0, 0, 0, 0, 0xFF, 0xFF]];
+    for (i, object) in objects.iter().enumerate() {
+        entries.push(in_use_entry(pdf.len()));
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+    let xref_start = pdf.len();
+    entries.push(in_use_entry(xref_start));
+
+    let content = entries.concat();
+    pdf.extend_from_slice(
+        format!(
+            "{} 0 obj\n<< /Type /XRef /Size {} /W [1 4 2] /Root 1 0 R /Length {} >>\nstream\n",
+            objects.len() + 1,
+            objects.len() + 2,
+            content.len()
+        )
+        .as_bytes(),
+    );
+    pdf.extend_from_slice(&content);
+    pdf.extend_from_slice(
+        format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", xref_start).as_bytes(),
+    );
+    pdf
+}
+
+fn in_use_entry(offset: usize) -> [u8; 7] {
+    let offset = (offset as u32).to_be_bytes();
+    [1, offset[0], offset[1], offset[2], offset[3], 0, 0]
+}
+
+/// Gets the offset of the last cross-reference section of a file
+fn startxref(pdf: &[u8]) -> usize {
+    let text = String::from_utf8_lossy(pdf);
+    let position = text.rfind("startxref").unwrap();
+    text[position + "startxref".len()..]
+        .split_whitespace()
+        .next()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+/// Fills the field and saves the form as an incremental update of `original`
+fn fill_incrementally(original: &[u8]) -> Vec<u8> {
+    let mut form = Form::load_from(Cursor::new(original)).unwrap();
+    form.set_text(0, "filled".to_owned()).unwrap();
+    let mut output = Vec::new();
+    form.save_incremental(original, &mut output).unwrap();
+    output
+}
+
+fn has_piece_info(pdf: &[u8]) -> bool {
+    let doc = Document::load_mem(pdf).unwrap();
+    doc.catalog().unwrap().has(b"PieceInfo")
+}
+
+#[test]
+fn usage_rights_skip_piece_info() {
+    let plain = sample_pdf(Dictionary::new());
+    assert!(has_piece_info(&fill_incrementally(&plain)));
+
+    let rights = sample_pdf(dictionary! {
+        "Perms" => dictionary! { "UR3" => dictionary! { "Type" => "Sig" } },
+    });
+    let output = fill_incrementally(&rights);
+    assert!(output.starts_with(&rights));
+    assert!(!has_piece_info(&output));
+}
+
+#[test]
+fn incremental_save_appends_to_original() {
+    for original in [sample_pdf(Dictionary::new()), xref_stream_pdf()] {
+        let output = fill_incrementally(&original);
+        assert!(output.len() > original.len());
+        assert_eq!(&output[..original.len()], original.as_slice());
+
+        let form = Form::load_from(Cursor::new(&output)).unwrap();
+        assert_eq!(
+            form.get_value(0),
+            Some(FieldValue::Text("filled".to_owned()))
+        );
+    }
+}
+
+#[test]
+fn incremental_xref_matches_original() {
+    // Classic cross-reference table
+    let original = sample_pdf(Dictionary::new());
+    assert!(original[startxref(&original)..].starts_with(b"xref"));
+    let output = fill_incrementally(&original);
+    let section = &output[startxref(&output)..];
+    assert!(startxref(&output) >= original.len());
+    assert!(section.starts_with(b"xref"));
+    let trailer = String::from_utf8_lossy(section);
+    assert!(trailer.contains(&format!("/Prev {}", startxref(&original))));
+
+    // Cross-reference stream
+    let original = xref_stream_pdf();
+    let output = fill_incrementally(&original);
+    let section = &output[startxref(&output)..];
+    assert!(startxref(&output) >= original.len());
+    assert!(!section.starts_with(b"xref"));
+    let stream = String::from_utf8_lossy(section);
+    assert!(stream.contains("/Type /XRef"));
+    assert!(stream.contains(&format!("/Prev {}", startxref(&original))));
+}
+
+/// Builds a document with a text field and a signed signature field
+fn signed_pdf() -> Vec<u8> {
+    let mut form = Form::load_from(Cursor::new(sample_pdf(Dictionary::new()))).unwrap();
+    let n = form
+        .add_signature_field(0, [100.0, 100.0, 300.0, 150.0], "signature")
+        .unwrap();
+    form.prepare_signature(n, 64).unwrap();
+    let mut pdf = Vec::new();
+    form.save_to(&mut pdf).unwrap();
+    let slot = SignatureSlot::locate(&mut pdf).unwrap();
+    slot.embed(&mut pdf, &[0x30, 0x82, 0x01, 0x00]).unwrap();
+    pdf
+}
+
+#[test]
+fn incremental_save_keeps_signed_bytes() {
+    let original = signed_pdf();
+    let output = fill_incrementally(&original);
+    let form = Form::load_from(Cursor::new(&output)).unwrap();
+    assert!(form.is_signed());
+    assert!(form
+        .verify_signatures(&output)
+        .iter()
+        .all(|status| status.byte_range_valid));
+
+    // Rewriting the signature dictionary would change what it covers
+    let mut form = Form::load_from(Cursor::new(&original)).unwrap();
+    let signature = *form
+        .document
+        .objects
+        .iter()
+        .find(|(_, object)| object.as_dict().is_ok_and(|dict| dict.has(b"ByteRange")))
+        .unwrap()
+        .0;
+    form.document
+        .get_object_mut(signature)
+        .and_then(Object::as_dict_mut)
+        .unwrap()
+        .set("ByteRange", vec![0.into(), 10.into(), 20.into(), 30.into()]);
+    form.set_text(0, "filled".to_owned()).unwrap();
+    let mut output = Vec::new();
+    let error = form.save_incremental(&original, &mut output).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("signed by"));
+    assert!(output.is_empty());
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Document, Object};
+use pdf_forms2::{
+    FieldError, FieldState, FieldType, Form, FormLoader, LoadError, LoadOptions, ValueError,
+};
+
+use common::pdf_with_fields;
+
+#[test]
+fn malformed_fields_load_as_unknown() {
+    let pdf = pdf_with_fields(vec![
+        dictionary! { "FT" => "Tx", "T" => Object::string_literal("real_flags"), "Ff" => 1.5 },
+        dictionary! { "FT" => 3, "T" => Object::string_literal("numeric_type") },
+        dictionary! { "FT" => "Btn", "T" => Object::string_literal("string_flags"), "Ff" => Object::string_literal("x") },
+    ]);
+
+    for options in [LoadOptions::strict(), LoadOptions::lenient()] {
+        let form = FormLoader::with_options(options)
+            .load_from(Cursor::new(&pdf))
+            .unwrap();
+        assert_eq!(form.len(), 3);
+        assert_eq!(form.get_type(0), FieldType::Text);
+        assert_eq!(form.get_type(1), FieldType::Unknown);
+        assert_eq!(form.get_name(1).as_deref(), Some("numeric_type"));
+        assert_eq!(form.get_type(2), FieldType::Unknown);
+        for n in 0..form.len() {
+            let _ = form.get_state(n);
+        }
+    }
+}
+
+#[test]
+fn indirect_field_flags_are_followed() {
+    let pdf = pdf_with_fields(vec![
+        dictionary! { "FT" => "Btn", "T" => Object::string_literal("radio") },
+        dictionary! { "FT" => "Ch", "T" => Object::string_literal("combo"), "Ff" => 131072.0 },
+    ]);
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+    assert_eq!(form.get_type(1), FieldType::ComboBox);
+
+    // Radio flag, stored as an indirect object
+    let flags = form.document.add_object(Object::Integer(1 << 15));
+    let oid = form.get_object_id(0);
+    form.document
+        .get_object_mut(oid)
+        .and_then(Object::as_dict_mut)
+        .unwrap()
+        .set("Ff", flags);
+    form.reindex_fields();
+    assert_eq!(form.try_get_type(0).unwrap(), FieldType::Radio);
+    assert!(form.try_get_state(0).is_ok());
+    assert!(form.try_get_state(1).is_ok());
+}
+
+#[test]
+fn setters_fail_on_fields_that_are_not_dictionaries() {
+    let pdf = pdf_with_fields(vec![
+        dictionary! { "FT" => "Tx", "T" => Object::string_literal("text") },
+        dictionary! { "FT" => "Btn", "T" => Object::string_literal("check") },
+    ]);
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+    assert!(form.field_id(1).is_some());
+    assert_eq!(form.field_id(2), None);
+
+    // Replace both field dictionaries behind the form's back
+    for n in 0..form.len() {
+        let oid = form.get_object_id(n);
+        form.document.objects.insert(oid, Object::Integer(1));
+    }
+
+    let oid = form.get_object_id(0);
+    assert!(matches!(
+        form.set_text(0, "value".to_owned()),
+        Err(ValueError::MalformedField(FieldError::NotADictionary(id))) if id == oid
+    ));
+    assert!(matches!(
+        form.set_check_box(1, true),
+        Err(ValueError::MalformedField(_))
+    ));
+
+    let reader = form.reader();
+    assert_eq!(reader.len(), 2);
+    assert_eq!(reader.get(0).unwrap().state, FieldState::Unknown);
+}
+
+#[test]
+fn field_tree_loops_are_detected() {
+    let pdf = pdf_with_fields(vec![
+        dictionary! { "FT" => "Tx", "T" => Object::string_literal("parent") },
+    ]);
+
+    // Make the field its own kid
+    let mut doc = Document::load_mem(&pdf).unwrap();
+    let acroform = doc
+        .catalog()
+        .unwrap()
+        .get(b"AcroForm")
+        .unwrap()
+        .as_reference()
+        .unwrap();
+    let fields = doc
+        .get_dictionary(acroform)
+        .unwrap()
+        .get(b"Fields")
+        .unwrap();
+    let field = fields.as_array().unwrap()[0].as_reference().unwrap();
+    doc.get_object_mut(field)
+        .and_then(Object::as_dict_mut)
+        .unwrap()
+        .set("Kids", vec![field.into()]);
+    let mut pdf = Vec::new();
+    doc.save_to(&mut pdf).unwrap();
+
+    assert!(matches!(
+        FormLoader::with_options(LoadOptions::strict()).load_from(Cursor::new(&pdf)),
+        Err(LoadError::StructureError(_))
+    ));
+    let form = FormLoader::with_options(LoadOptions::lenient())
+        .load_from(Cursor::new(&pdf))
+        .unwrap();
+    assert_eq!(form.len(), 1);
+}
+
+#[test]
+fn getters_do_not_panic_on_missing_fields() {
+    let pdf = pdf_with_fields(vec![
+        dictionary! { "FT" => "Tx", "T" => Object::string_literal("text") },
+    ]);
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+    assert_eq!(form.get_type(1), FieldType::Unknown);
+    assert_eq!(form.get_name(1), None);
+    assert_eq!(form.get_state(1), FieldState::Unknown);
+
+    let oid = form.get_object_id(0);
+    form.document.objects.insert(oid, Object::Integer(1));
+    assert_eq!(form.get_state(0), FieldState::Unknown);
+    assert_eq!(form.get_all_names(), vec![Some("text".to_owned())]);
+    assert!(matches!(
+        form.get_state_by_name("text"),
+        Err(ValueError::MalformedField(FieldError::NotADictionary(id))) if id == oid
+    ));
+    assert!(matches!(
+        form.get_type_by_name("missing"),
+        Err(ValueError::NotFound)
+    ));
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Dictionary, Object};
+use pdf_forms2::{merge, Form, NameSuffix};
+
+use common::{text_field, PdfBuilder};
+
+/// Builds a one-page document whose AcroForm maps `/Helv` to the standard font `base_font`,
+/// with a text field inheriting the default appearance and one setting its own
+fn sample_pdf(base_font: &str) -> Vec<u8> {
+    let mut pdf = PdfBuilder::new();
+    let mut inherited = text_field("inherited", 700);
+    inherited.remove(b"DA");
+    let inherited_id = pdf.widget(inherited);
+    let mut own = text_field("own", 650);
+    own.set("DA", Object::string_literal("/Helv 10 Tf 0 g"));
+    let own_id = pdf.widget(own);
+
+    let font_id = pdf.doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => base_font,
+    });
+    let acroform = dictionary! {
+        "DA" => Object::string_literal("/Helv 0 Tf 0 g"),
+        "DR" => dictionary! { "Font" => dictionary! { "Helv" => font_id } },
+    };
+    pdf.finish(&[inherited_id, own_id], acroform, Dictionary::new())
+}
+
+fn load(base_font: &str) -> Form {
+    Form::load_from(Cursor::new(sample_pdf(base_font))).unwrap()
+}
+
+/// Gets the base font of each font of the default resources of the merged form
+fn default_fonts(form: &Form) -> Vec<(String, String)> {
+    let doc = &form.document;
+    let acroform = doc.catalog().unwrap().get(b"AcroForm").unwrap();
+    let acroform = doc.dereference(acroform).unwrap().1.as_dict().unwrap();
+    let dr = doc.dereference(acroform.get(b"DR").unwrap()).unwrap().1;
+    let fonts = dr.as_dict().unwrap().get(b"Font").unwrap();
+    let fonts = doc.dereference(fonts).unwrap().1.as_dict().unwrap();
+
+    fonts
+        .iter()
+        .map(|(name, font)| {
+            let font: &Dictionary = doc.dereference(font).unwrap().1.as_dict().unwrap();
+            let base_font = font.get(b"BaseFont").unwrap().as_name_str().unwrap();
+            (
+                String::from_utf8_lossy(name).into_owned(),
+                base_font.to_owned(),
+            )
+        })
+        .collect()
+}
+
+fn default_appearance(form: &Form, n: usize) -> Option<String> {
+    form.document
+        .get_dictionary(form.get_object_id(n))
+        .unwrap()
+        .get(b"DA")
+        .ok()
+        .map(|da| String::from_utf8_lossy(da.as_str().unwrap()).into_owned())
+}
+
+fn catalogs(form: &Form) -> usize {
+    form.document
+        .objects
+        .values()
+        .filter(|object| matches!(object.type_name(), Ok("Catalog")))
+        .count()
+}
+
+#[test]
+fn merge_renames_clashing_fonts() {
+    let merged = merge(
+        vec![load("Helvetica"), load("Courier")],
+        NameSuffix::Counter,
+    )
+    .unwrap();
+    assert_eq!(merged.len(), 4);
+    assert_eq!(
+        default_fonts(&merged),
+        vec![
+            ("Helv".to_owned(), "Helvetica".to_owned()),
+            ("Helv_2".to_owned(), "Courier".to_owned()),
+        ]
+    );
+
+    // The fields of the first form are untouched
+    assert_eq!(default_appearance(&merged, 0), None);
+    assert_eq!(
+        default_appearance(&merged, 1).as_deref(),
+        Some("/Helv 10 Tf 0 g")
+    );
+    // Those of the second use the new name, even when inherited from its AcroForm
+    assert_eq!(
+        default_appearance(&merged, 2).as_deref(),
+        Some("/Helv_2 0 Tf 0 g")
+    );
+    assert_eq!(
+        default_appearance(&merged, 3).as_deref(),
+        Some("/Helv_2 10 Tf 0 g")
+    );
+
+    assert_eq!(catalogs(&merged), 1);
+}
+
+#[test]
+fn merge_keeps_identical_fonts() {
+    let merged = merge(
+        vec![load("Helvetica"), load("Helvetica")],
+        NameSuffix::Counter,
+    )
+    .unwrap();
+    assert_eq!(
+        default_fonts(&merged),
+        vec![("Helv".to_owned(), "Helvetica".to_owned())]
+    );
+    assert_eq!(default_appearance(&merged, 2), None);
+    assert_eq!(
+        default_appearance(&merged, 3).as_deref(),
+        Some("/Helv 10 Tf 0 g")
+    );
+    assert_eq!(catalogs(&merged), 1);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::Object;
+use pdf_forms2::{measure_text, Form, StandardFont};
+
+use common::{appearance, operands, pdf_with_fields, text_field};
+
+fn assert_close(width: f32, expected: f32) {
+    assert!(
+        (width - expected).abs() < 0.001,
+        "{} != {}",
+        width,
+        expected
+    );
+}
+
+#[test]
+fn text_is_measured_with_the_font_metrics() {
+    // H, e, l, l and o are 722, 556, 222, 222 and 556 thousandths wide in Helvetica, and 722,
+    // 556, 278, 278 and 611 in Helvetica-Bold
+    assert_close(measure_text(StandardFont::Helvetica, 10.0, "Hello"), 22.78);
+    assert_close(
+        measure_text(StandardFont::HelveticaBold, 10.0, "Hello"),
+        24.45,
+    );
+    assert_close(measure_text(StandardFont::TimesRoman, 10.0, "W"), 9.44);
+    // Every glyph of Courier is 600 thousandths wide
+    assert_close(measure_text(StandardFont::CourierBold, 10.0, "iiWW"), 24.0);
+    // Accented letters are as wide as their base letter
+    assert_eq!(
+        measure_text(StandardFont::Helvetica, 12.0, "été"),
+        measure_text(StandardFont::Helvetica, 12.0, "ete")
+    );
+}
+
+#[test]
+fn base_fonts_map_to_standard_fonts() {
+    let cases = [
+        ("Helvetica", Some(StandardFont::Helvetica)),
+        ("ABCDEF+Arial-BoldMT", Some(StandardFont::HelveticaBold)),
+        ("Arial,BoldItalic", Some(StandardFont::HelveticaBoldOblique)),
+        ("Times-Italic", Some(StandardFont::TimesItalic)),
+        ("TimesNewRomanPSMT", Some(StandardFont::TimesRoman)),
+        ("CourierNew-Bold", Some(StandardFont::CourierBold)),
+        ("Symbol", None),
+    ];
+    for (base_font, font) in cases.iter() {
+        assert_eq!(
+            StandardFont::from_base_font(base_font),
+            *font,
+            "{}",
+            base_font
+        );
+    }
+}
+
+#[test]
+fn appearances_are_laid_out_with_the_font_metrics() {
+    let mut field = text_field("right", 700);
+    field.set("DA", Object::string_literal("/Cour 10 Tf 0 g"));
+    field.set("Q", 2);
+    let pdf = pdf_with_fields(vec![field]);
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+
+    // Right aligned in the 200 points wide widget, with 2 points of padding
+    form.set_text(0, "iiWW".to_owned()).unwrap();
+    let x = operands(&appearance(&form, 0), "Tm")[0][4];
+    assert!((x - (200.0 - 2.0 - 24.0)).abs() < 0.01);
+}
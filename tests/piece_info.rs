@@ -0,0 +1,70 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{Dictionary, Document, Object, StringFormat};
+use pdf_forms2::Form;
+
+use common::{text_field, utf16, PdfBuilder};
+
+/// Builds a one-page document with the text fields `person.名` and `city`
+fn sample_pdf() -> Vec<u8> {
+    let mut pdf = PdfBuilder::new();
+    let mut name = text_field("", 700);
+    name.set("T", Object::String(utf16("名"), StringFormat::Literal));
+    let name_id = pdf.widget(name);
+    let city_id = pdf.widget(text_field("city", 650));
+    let parent_id = pdf.parent("person", &[name_id]);
+    pdf.finish(&[parent_id, city_id], Dictionary::new(), Dictionary::new())
+}
+
+/// Gets the data dictionary written by the crate into the `/PieceInfo` of the catalog
+fn piece_info_data(pdf: &[u8]) -> Dictionary {
+    let doc = Document::load_mem(pdf).unwrap();
+    let piece_info = doc.catalog().unwrap().get(b"PieceInfo").unwrap();
+    let piece_info = doc.dereference(piece_info).unwrap().1.as_dict().unwrap();
+    piece_info
+        .get(b"pdf_forms2")
+        .and_then(Object::as_dict)
+        .unwrap()
+        .clone()
+}
+
+fn modified_fields(data: &Dictionary) -> Vec<Vec<u8>> {
+    data.get(b"Private")
+        .and_then(Object::as_dict)
+        .and_then(|private| private.get(b"ModifiedFields"))
+        .and_then(Object::as_array)
+        .unwrap()
+        .iter()
+        .map(|name| name.as_str().unwrap().to_vec())
+        .collect()
+}
+
+fn fill(pdf: &[u8], name: &str) -> Vec<u8> {
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+    let field = form.find_field(name).unwrap();
+    form.set_text(field, "value".to_owned()).unwrap();
+    assert_eq!(form.modified_fields(), vec![name.to_owned()]);
+
+    let mut output = Vec::new();
+    form.save_to(&mut output).unwrap();
+    output
+}
+
+#[test]
+fn piece_info_lists_qualified_names() {
+    let first = fill(&sample_pdf(), "person.名");
+    let data = piece_info_data(&first);
+    assert_eq!(modified_fields(&data), vec![utf16("person.名")]);
+    assert!(data.has(b"LastModified"));
+
+    // A later save keeps the fields recorded by the first one
+    let second = fill(&first, "city");
+    let data = piece_info_data(&second);
+    assert_eq!(
+        modified_fields(&data),
+        vec![b"city".to_vec(), utf16("person.名")]
+    );
+    assert!(data.has(b"LastModified"));
+}
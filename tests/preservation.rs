@@ -0,0 +1,156 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId};
+use pdf_forms2::{merge, Form, LoadError, NameSuffix, SaveOptions, ValueError};
+
+use common::{text_field, PdfBuilder};
+
+/// Builds a one-page document with a single text field carrying private entries
+fn sample_pdf() -> Vec<u8> {
+    let mut pdf = PdfBuilder::new();
+    let mut field = text_field("name", 700);
+    field.set("ACME_Private", Object::string_literal("keep me"));
+    field.set(
+        "PieceInfo",
+        dictionary! { "ACME" => dictionary! { "Private" => 1 } },
+    );
+    field.set("V", Object::string_literal("old"));
+    let field_id = pdf.widget(field);
+    let acroform = dictionary! { "ACME_FormData" => Object::string_literal("private") };
+    pdf.finish(&[field_id], acroform, Dictionary::new())
+}
+
+fn keys(dict: &Dictionary) -> Vec<Vec<u8>> {
+    dict.iter().map(|(key, _)| key.clone()).collect()
+}
+
+fn field_and_acroform(doc: &Document, field_id: ObjectId) -> (Dictionary, Dictionary) {
+    let acroform_id = doc
+        .catalog()
+        .unwrap()
+        .get(b"AcroForm")
+        .unwrap()
+        .as_reference()
+        .unwrap();
+    (
+        doc.get_dictionary(field_id).unwrap().clone(),
+        doc.get_dictionary(acroform_id).unwrap().clone(),
+    )
+}
+
+#[test]
+fn editing_keeps_private_entries_in_order() {
+    let original = Document::load_from(Cursor::new(sample_pdf())).unwrap();
+    let mut form = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    let field_id = form.get_object_id(0);
+    let (field_before, acroform_before) = field_and_acroform(&original, field_id);
+
+    form.set_text(0, "new".into()).unwrap();
+    let mut output = Vec::new();
+    form.save_to(&mut output).unwrap();
+
+    let saved = Document::load_from(Cursor::new(output)).unwrap();
+    let (field_after, acroform_after) = field_and_acroform(&saved, field_id);
+
+    // Existing entries keep their order, the generated appearance is appended
+    assert!(keys(&field_after).starts_with(&keys(&field_before)));
+    assert!(field_after.has(b"AP"));
+    assert_eq!(
+        field_after.get(b"ACME_Private").unwrap().as_str().unwrap(),
+        b"keep me"
+    );
+    assert!(field_after
+        .get(b"PieceInfo")
+        .unwrap()
+        .as_dict()
+        .unwrap()
+        .has(b"ACME"));
+    assert_eq!(keys(&acroform_before), keys(&acroform_after));
+}
+
+#[test]
+fn preservation_mode_refuses_destructive_operations() {
+    let mut form = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    assert_eq!(form.get_unknown_keys(0), vec!["ACME_Private", "PieceInfo"]);
+
+    form.set_preservation_mode(true);
+    assert!(matches!(
+        form.remove_field(0),
+        Err(ValueError::WouldDiscardData)
+    ));
+
+    form.set_preservation_mode(false);
+    assert!(form.remove_field(0).is_ok());
+}
+
+#[test]
+fn preservation_mode_checks_kid_widgets() {
+    let mut form = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    let field_id = form.get_object_id(0);
+
+    // Turn the field into a parent of a widget carrying the private entries
+    let mut field = form.document.get_dictionary(field_id).unwrap().clone();
+    let page_id = field.get(b"P").unwrap().clone();
+    for key in ["Type", "Subtype", "Rect", "P", "ACME_Private", "PieceInfo"] {
+        field.remove(key.as_bytes());
+    }
+    let widget_id = form.document.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "Parent" => field_id,
+        "Rect" => vec![100.into(), 700.into(), 300.into(), 720.into()],
+        "P" => page_id,
+        "ACME_Private" => Object::string_literal("keep me"),
+    });
+    field.set("Kids", vec![widget_id.into()]);
+    form.document
+        .objects
+        .insert(field_id, Object::Dictionary(field));
+    form.reindex_fields();
+    assert!(form.get_unknown_keys(0).is_empty());
+
+    form.set_preservation_mode(true);
+    assert!(matches!(
+        form.remove_field(0),
+        Err(ValueError::WouldDiscardData)
+    ));
+}
+
+#[test]
+fn preservation_mode_refuses_pruning_private_widgets() {
+    let mut form = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    form.document.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "ACME_Private" => Object::string_literal("orphan"),
+    });
+    let options = SaveOptions::new().prune(true);
+
+    form.set_preservation_mode(true);
+    assert!(form.save_with_to(&mut Vec::new(), &options).is_err());
+
+    form.set_preservation_mode(false);
+    assert!(form.save_with_to(&mut Vec::new(), &options).is_ok());
+}
+
+#[test]
+fn preservation_mode_refuses_merging_private_fields() {
+    let first = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    let mut second = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    second.set_preservation_mode(true);
+    assert!(matches!(
+        merge(vec![first, second], NameSuffix::Counter),
+        Err(LoadError::WouldDiscardData)
+    ));
+
+    let first = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    let second = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    assert_eq!(
+        merge(vec![first, second], NameSuffix::Counter)
+            .unwrap()
+            .len(),
+        2
+    );
+}
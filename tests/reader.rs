@@ -0,0 +1,58 @@
+mod common;
+
+use std::io::Cursor;
+use std::thread;
+
+use lopdf::Dictionary;
+use pdf_forms2::{FieldValue, Form};
+
+use common::{text_field, PdfBuilder};
+
+/// Builds a one-page document with the text fields `person.name`, `person.city` and `name`
+fn sample_pdf() -> Vec<u8> {
+    let mut pdf = PdfBuilder::new();
+    let name_id = pdf.widget(text_field("name", 700));
+    let city_id = pdf.widget(text_field("city", 650));
+    let top_id = pdf.widget(text_field("name", 600));
+    let person_id = pdf.parent("person", &[name_id, city_id]);
+    pdf.finish(&[person_id, top_id], Dictionary::new(), Dictionary::new())
+}
+
+#[test]
+fn reader_looks_up_names_like_the_form() {
+    let mut form = Form::load_from(Cursor::new(sample_pdf())).unwrap();
+    let reader = form.reader();
+
+    for name in [
+        "person.name",
+        "person.city",
+        "name",
+        "city",
+        "person",
+        "missing",
+    ] {
+        assert_eq!(
+            reader.get_index_by_name(name),
+            form.get_index_by_name(name),
+            "{}",
+            name
+        );
+    }
+    let n = reader.get_index_by_name("person.name").unwrap();
+    assert_eq!(reader.get(n).unwrap().name.as_deref(), Some("name"));
+    assert_eq!(
+        reader.get(n).unwrap().qualified_name.as_deref(),
+        Some("person.name")
+    );
+
+    // The snapshot can be read from another thread while the form is filled
+    let shared = reader.clone();
+    let handle = thread::spawn(move || shared.get_index_by_name("name"));
+    form.set_text_by_name("name", "value".to_owned()).unwrap();
+    assert_eq!(handle.join().unwrap(), form.get_index_by_name("name"));
+    let top = form.get_index_by_name("name").unwrap();
+    assert_eq!(
+        form.get_value(top),
+        Some(FieldValue::Text("value".to_owned()))
+    );
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::io::Cursor;
+
+use pdf_forms2::{
+    ChoiceFieldOptions, ChoiceOption, FieldLock, FieldState, FieldValue, Form, SignatureSlot,
+    ValueError,
+};
+
+use common::blank_pdf;
+
+fn is_signed(form: &Form, n: usize) -> bool {
+    match form.get_state(n) {
+        FieldState::Signature { signed, .. } => signed,
+        state => panic!("not a signature field: {:?}", state),
+    }
+}
+
+#[test]
+fn prepared_signature_is_not_signed_until_embedded() {
+    let mut form = Form::load_from(Cursor::new(blank_pdf())).unwrap();
+    let n = form
+        .add_signature_field(0, [100.0, 100.0, 300.0, 150.0], "signature")
+        .unwrap();
+    assert!(!is_signed(&form, n));
+
+    form.prepare_signature(n, 64).unwrap();
+    assert!(!is_signed(&form, n));
+    assert!(!form.is_signed());
+
+    let mut pdf = Vec::new();
+    form.save_to(&mut pdf).unwrap();
+    let reloaded = Form::load_from(Cursor::new(&pdf)).unwrap();
+    assert!(!is_signed(&reloaded, n));
+
+    let slot = SignatureSlot::locate(&mut pdf).unwrap();
+    slot.embed(&mut pdf, &[0x30, 0x82, 0x01, 0x00]).unwrap();
+    let signed = Form::load_from(Cursor::new(&pdf)).unwrap();
+    assert!(is_signed(&signed, n));
+    assert!(signed.is_signed());
+}
+
+#[test]
+fn locked_fields_cannot_be_cleared() {
+    let mut form = Form::load_from(Cursor::new(blank_pdf())).unwrap();
+    let options = ChoiceFieldOptions {
+        options: vec![ChoiceOption::new("a"), ChoiceOption::new("b")],
+        selected: vec!["a".to_owned()],
+        ..Default::default()
+    };
+    let combo = form
+        .add_combo_box(0, [100.0, 700.0, 300.0, 720.0], "combo", options)
+        .unwrap();
+    let radio = form
+        .add_radio_group(
+            "radio",
+            vec![(0, [100.0, 650.0, 120.0, 670.0], "on".to_owned())],
+        )
+        .unwrap();
+    form.set_radio(radio, "on".to_owned()).unwrap();
+    form.set_value(combo, FieldValue::Choice("b".to_owned()))
+        .unwrap();
+    let signature = form
+        .add_signature_field(0, [100.0, 100.0, 300.0, 150.0], "signature")
+        .unwrap();
+    form.set_signature_lock(signature, Some(FieldLock::All))
+        .unwrap();
+    form.prepare_signature(signature, 64).unwrap();
+
+    let mut pdf = Vec::new();
+    form.save_to(&mut pdf).unwrap();
+    let slot = SignatureSlot::locate(&mut pdf).unwrap();
+    slot.embed(&mut pdf, &[0x30, 0x82, 0x01, 0x00]).unwrap();
+    let mut signed = Form::load_from(Cursor::new(&pdf)).unwrap();
+    assert!(signed.is_locked(combo));
+    assert!(signed.is_locked(radio));
+
+    let is_readonly = |result| matches!(result, Err(ValueError::Readonly { .. }));
+    assert!(is_readonly(signed.reset_form()));
+    assert!(is_readonly(signed.clear_field(combo)));
+    assert!(is_readonly(signed.clear_field(radio)));
+    assert!(is_readonly(signed.clear_radio(radio)));
+    assert_eq!(
+        signed.get_value(combo),
+        Some(FieldValue::Choice("b".to_owned()))
+    );
+    assert_eq!(
+        signed.get_value(radio),
+        Some(FieldValue::Radio("on".to_owned()))
+    );
+}
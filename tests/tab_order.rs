@@ -0,0 +1,110 @@
+mod common;
+
+use std::io::Cursor;
+
+use pdf_forms2::{Form, TabPolicy, TextFieldOptions, ValueError};
+
+use common::{pdf_with_fields, text_field};
+
+/// Loads a form with the fields `a` and `c` in a row above the fields `b` and `d`, listed in
+/// the order `c`, `a`, `d`, `b` in the annotations of the page
+fn load() -> Form {
+    let field = |name, x: i64, y: i64| {
+        let mut field = text_field(name, y);
+        field.set(
+            "Rect",
+            vec![x.into(), y.into(), (x + 150).into(), (y + 20).into()],
+        );
+        field
+    };
+    let pdf = pdf_with_fields(vec![
+        field("c", 300, 700),
+        // Slightly higher, but still in the row of `c`
+        field("a", 100, 704),
+        field("d", 300, 650),
+        field("b", 100, 650),
+    ]);
+    Form::load_from(Cursor::new(pdf)).unwrap()
+}
+
+/// The names of the fields in the tab order of the first page
+fn tab_order(form: &Form) -> Vec<String> {
+    form.tab_order(0)
+        .into_iter()
+        .map(|n| form.get_name(n).unwrap())
+        .collect()
+}
+
+/// The `/Tabs` of the first page
+fn tabs(form: &Form) -> Option<String> {
+    let page = form.document.get_pages()[&1];
+    let tabs = form
+        .document
+        .get_dictionary(page)
+        .unwrap()
+        .get(b"Tabs")
+        .ok()?;
+    tabs.as_name_str().ok().map(str::to_owned)
+}
+
+#[test]
+fn fields_follow_the_tab_order_of_their_page() {
+    let mut form = load();
+    // Without `/Tabs`, in the order of the annotations
+    assert_eq!(tab_order(&form), ["c", "a", "d", "b"]);
+
+    form.set_tab_order(0, TabPolicy::Row).unwrap();
+    assert_eq!(tabs(&form).as_deref(), Some("R"));
+    assert_eq!(tab_order(&form), ["a", "c", "b", "d"]);
+
+    form.set_tab_order(0, TabPolicy::Column).unwrap();
+    assert_eq!(tab_order(&form), ["a", "b", "c", "d"]);
+
+    // The annotations were reordered, which the structure order falls back to
+    let mut pdf = Vec::new();
+    form.save_to(&mut pdf).unwrap();
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+    form.set_tab_order(0, TabPolicy::Structure).unwrap();
+    assert_eq!(tabs(&form).as_deref(), Some("S"));
+    assert_eq!(tab_order(&form), ["a", "b", "c", "d"]);
+
+    assert!(form.tab_order(1).is_empty());
+    assert!(matches!(
+        form.set_tab_order(1, TabPolicy::Row),
+        Err(ValueError::NotFound)
+    ));
+}
+
+/// The names of the fields in the order of their indices
+fn names(form: &Form) -> Vec<String> {
+    (0..form.len()).map(|n| form.get_name(n).unwrap()).collect()
+}
+
+#[test]
+fn fields_are_sorted_in_reading_order() {
+    let mut form = load();
+    assert_eq!(names(&form), ["c", "a", "d", "b"]);
+    let d = form.field_id(2).unwrap();
+
+    form.sort_fields_by_position();
+    assert_eq!(names(&form), ["a", "c", "b", "d"]);
+    assert_eq!(form.fields_on_page(0), [0, 1, 2, 3]);
+    assert_eq!(form.get_name(&d).as_deref(), Some("d"));
+
+    // New fields take their place in the order
+    form.add_text_field(
+        0,
+        [100.0, 750.0, 250.0, 770.0],
+        "top",
+        TextFieldOptions::default(),
+    )
+    .unwrap();
+    form.add_text_field(
+        0,
+        [300.0, 600.0, 450.0, 620.0],
+        "bottom",
+        TextFieldOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(names(&form), ["top", "a", "c", "b", "d", "bottom"]);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::io::Cursor;
+
+use pdf_forms2::{FieldValue, Form, FormTemplate, SaveOptions};
+
+use common::{pdf_with_fields, text_field};
+
+fn template() -> FormTemplate {
+    let pdf = pdf_with_fields(vec![text_field("first", 700), text_field("second", 650)]);
+    FormTemplate::load_from(Cursor::new(pdf)).unwrap()
+}
+
+fn text(value: &str) -> Option<FieldValue> {
+    Some(FieldValue::Text(value.to_owned()))
+}
+
+fn reload(pdf: &[u8]) -> Form {
+    Form::load_from(Cursor::new(pdf)).unwrap()
+}
+
+#[test]
+fn instances_only_copy_the_objects_they_modify() {
+    let template = template();
+    let blank = template.form().get_value(0);
+    let mut alice = template.instance();
+    let bob = template.instance();
+    assert!(alice.is_shared() && bob.is_shared());
+    assert!(alice.document.objects.is_empty());
+
+    alice.set_text(0, "Alice".to_owned()).unwrap();
+    assert_eq!(alice.get_value(0), text("Alice"));
+    assert!(!alice.is_shared());
+    // The field and its new appearance stream, the other objects are still the template's
+    assert!(alice.document.objects.len() <= 3);
+    assert!(alice.document.objects.contains_key(&alice.get_object_id(0)));
+    assert!(!alice.document.objects.contains_key(&alice.get_object_id(1)));
+
+    assert!(bob.is_shared());
+    assert_eq!(bob.get_value(0), blank);
+    assert_eq!(template.form().get_value(0), blank);
+}
+
+#[test]
+fn saved_instances_hold_the_whole_document() {
+    let template = template();
+    let mut alice = template.instance();
+    alice.set_text(0, "Alice".to_owned()).unwrap();
+    let mut bob = template.instance();
+    bob.set_text(1, "Bob".to_owned()).unwrap();
+
+    let mut pdf = Vec::new();
+    alice.save_to(&mut pdf).unwrap();
+    let form = reload(&pdf);
+    assert_eq!(form.len(), 2);
+    assert_eq!(form.get_value(0), text("Alice"));
+    assert_eq!(form.get_value(1), template.form().get_value(1));
+
+    let mut pdf = Vec::new();
+    bob.save_with_to(&mut pdf, &SaveOptions::new().prune(true))
+        .unwrap();
+    let form = reload(&pdf);
+    assert_eq!(form.get_value(0), template.form().get_value(0));
+    assert_eq!(form.get_value(1), text("Bob"));
+
+    // Saving an unmodified instance copies nothing
+    let mut untouched = template.instance();
+    let mut pdf = Vec::new();
+    untouched.save_to(&mut pdf).unwrap();
+    assert!(untouched.is_shared());
+    assert_eq!(reload(&pdf).len(), 2);
+}
+
+#[test]
+fn removed_objects_stay_in_the_template() {
+    let template = template();
+    let mut instance = template.instance();
+    instance.remove_field(0).unwrap();
+    assert_eq!(instance.len(), 1);
+    assert_eq!(template.form().len(), 2);
+    assert_eq!(template.instance().len(), 2);
+
+    let mut pdf = Vec::new();
+    instance.save_to(&mut pdf).unwrap();
+    assert_eq!(reload(&pdf).len(), 1);
+
+    let form = instance.into_form();
+    assert_eq!(form.len(), 1);
+    assert!(form.document.objects.len() > 3);
+    assert!(!form
+        .document
+        .objects
+        .contains_key(&template.form().get_object_id(0)));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId};
+use pdf_forms2::{FieldValue, Form, PdfVersion};
+
+use common::{utf16, PdfBuilder};
+
+/// Encodes a text string as UTF-8 with a byte order mark, as allowed by PDF 2.0
+fn utf8(s: &str) -> Object {
+    let mut bytes = vec![0xEF, 0xBB, 0xBF];
+    bytes.extend_from_slice(s.as_bytes());
+    Object::string_literal(bytes)
+}
+
+/// Builds a PDF 2.0 document with the list box `größe.wahl`, whose names, options and value are
+/// UTF-8 strings
+fn pdf20() -> (Vec<u8>, ObjectId, ObjectId) {
+    let mut pdf = PdfBuilder::new();
+    let list_id = pdf.widget(dictionary! {
+        "FT" => "Ch",
+        "T" => utf8("wahl"),
+        "Opt" => vec![
+            vec![utf8("ä"), utf8("Äpfel")].into(),
+            utf8("ö"),
+        ],
+        "V" => vec![utf8("ä")],
+    });
+    let parent_id = pdf.parent("", &[list_id]);
+    pdf.doc
+        .get_object_mut(parent_id)
+        .and_then(Object::as_dict_mut)
+        .unwrap()
+        .set("T", utf8("größe"));
+    pdf.doc.version = "2.0".to_owned();
+    (
+        pdf.finish(&[parent_id], Dictionary::new(), Dictionary::new()),
+        parent_id,
+        list_id,
+    )
+}
+
+fn strings(object: &Object) -> Vec<Vec<u8>> {
+    match object {
+        Object::String(bytes, _) => vec![bytes.clone()],
+        Object::Array(items) => items.iter().flat_map(strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[test]
+fn pdf17_target_rewrites_utf8_strings() {
+    let (pdf, parent_id, list_id) = pdf20();
+    let mut form = Form::load_from(Cursor::new(pdf)).unwrap();
+    assert_eq!(form.get_qualified_name(0).as_deref(), Some("größe.wahl"));
+    form.set_target_version(Some(PdfVersion::Pdf17));
+    let mut output = Vec::new();
+    form.save_to(&mut output).unwrap();
+
+    let doc = Document::load_mem(&output).unwrap();
+    assert_eq!(doc.version, "1.7");
+    let parent = doc.get_dictionary(parent_id).unwrap();
+    assert_eq!(strings(parent.get(b"T").unwrap()), vec![utf16("größe")]);
+    let list = doc.get_dictionary(list_id).unwrap();
+    assert_eq!(strings(list.get(b"T").unwrap()), vec![utf16("wahl")]);
+    assert_eq!(strings(list.get(b"V").unwrap()), vec![utf16("ä")]);
+    assert_eq!(
+        strings(list.get(b"Opt").unwrap()),
+        vec![utf16("ä"), utf16("Äpfel"), utf16("ö")]
+    );
+
+    let reloaded = Form::load_from(Cursor::new(&output)).unwrap();
+    assert_eq!(
+        reloaded.get_qualified_name(0).as_deref(),
+        Some("größe.wahl")
+    );
+    assert_eq!(
+        reloaded.get_value(0),
+        Some(FieldValue::Choices(vec!["ä".to_owned()]))
+    );
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::io::Cursor;
+
+use lopdf::{dictionary, Dictionary, Object, Stream};
+use pdf_forms2::Form;
+
+use common::{text_field, PdfBuilder};
+
+const DATASETS: &str = "<xfa:datasets xmlns:xfa=\"http://www.xfa.org/schema/xfa-data/1.0/\">\
+    <xfa:data><form1><person><name>old</name><city/></person></form1></xfa:data>\
+    </xfa:datasets>";
+
+/// Builds a hybrid form whose fields `form1[0].person[0].name[0]` and
+/// `form1[0].person[0].city[0]` have data in the XFA datasets, and `form1[0].missing[0]` has
+/// none
+fn hybrid_pdf() -> Vec<u8> {
+    let mut pdf = PdfBuilder::new();
+    let name_id = pdf.widget(text_field("name[0]", 700));
+    let city_id = pdf.widget(text_field("city[0]", 650));
+    let missing_id = pdf.widget(text_field("missing[0]", 600));
+    let person_id = pdf.parent("person[0]", &[name_id, city_id]);
+    let form1_id = pdf.parent("form1[0]", &[person_id, missing_id]);
+
+    let datasets_id = pdf
+        .doc
+        .add_object(Stream::new(dictionary! {}, DATASETS.as_bytes().to_vec()));
+    let acroform = dictionary! {
+        "XFA" => vec![Object::string_literal("datasets"), datasets_id.into()],
+    };
+    pdf.finish(&[form1_id], acroform, Dictionary::new())
+}
+
+fn load() -> Form {
+    let mut form = Form::load_from(Cursor::new(hybrid_pdf())).unwrap();
+    assert!(form.has_xfa());
+    form.set_xfa_sync(true);
+    form
+}
+
+fn set(form: &mut Form, name: &str, value: &str) {
+    let field = form.find_field(name).unwrap();
+    form.set_text(field, value.to_owned()).unwrap();
+}
+
+#[test]
+fn xfa_values_are_escaped() {
+    let mut form = load();
+    set(
+        &mut form,
+        "form1[0].person[0].name[0]",
+        "A & B <c> \"q\" 'a'",
+    );
+    assert!(form
+        .xfa_datasets()
+        .unwrap()
+        .contains("<name>A &amp; B &lt;c&gt; \"q\" 'a'</name>"));
+}
+
+#[test]
+fn xfa_values_follow_nested_subforms() {
+    let mut form = load();
+    set(&mut form, "form1[0].person[0].name[0]", "Alice");
+    set(&mut form, "form1[0].person[0].city[0]", "Paris");
+    assert!(form
+        .xfa_datasets()
+        .unwrap()
+        .contains("<form1><person><name>Alice</name><city>Paris</city></person></form1>"));
+}
+
+#[test]
+fn xfa_data_is_unchanged_without_a_matching_node() {
+    let mut form = load();
+    set(&mut form, "form1[0].missing[0]", "value");
+    assert_eq!(form.xfa_datasets().unwrap(), DATASETS);
+
+    // Without sync, the data is left alone as well
+    form.set_xfa_sync(false);
+    set(&mut form, "form1[0].person[0].name[0]", "Alice");
+    assert_eq!(form.xfa_datasets().unwrap(), DATASETS);
+}